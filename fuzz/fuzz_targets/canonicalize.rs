@@ -0,0 +1,20 @@
+#![no_main]
+
+use cborg::DecodeOptions;
+use libfuzzer_sys::fuzz_target;
+
+// canonicalize must agree with decoding and encoding canonically, and its output must pass the
+// deterministic-form check.
+fuzz_target!(|data: &[u8]| {
+	let expected = cborg::decode_exact(data).map(|v| v.encode_canonical());
+	match cborg::canonicalize(data) {
+		Ok(bytes) => {
+			assert_eq!(expected.unwrap(), bytes);
+			let opts = DecodeOptions::new().require_canonical(true);
+			assert!(cborg::decode_slice_with(&bytes, &opts).is_ok());
+		}
+		Err(_) => assert!(expected.is_err()),
+	}
+	let _ = cborg::decode_slice_with(data, &DecodeOptions::new().require_canonical(true));
+	let _ = cborg::decode_strict(data);
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Any input must give a value; whatever decode_slice rejects must come with an issue or error.
+fuzz_target!(|data: &[u8]| {
+	let (_, issues) = cborg::decode_lossy(data);
+	let (_, errors) = cborg::decode_lenient(data);
+	if cborg::decode_slice(data).is_err() {
+		assert!(!issues.is_empty());
+		assert!(!errors.is_empty());
+	}
+	let _ = cborg::decode_lossy_seq(data);
+});
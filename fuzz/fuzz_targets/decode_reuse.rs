@@ -0,0 +1,16 @@
+#![no_main]
+
+use cborg::Value;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding into an existing value must agree with decode_slice.
+fuzz_target!(|data: &[u8]| {
+	let mut v = Value::Array(vec![Value::Unsigned(0)]);
+	match cborg::decode_slice(data) {
+		Ok(expected) => {
+			cborg::decode_reuse(data, &mut v).unwrap();
+			assert_eq!(expected.encode(), v.encode());
+		}
+		Err(_) => assert!(cborg::decode_reuse(data, &mut v).is_err()),
+	}
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Any input must give a value or an error, and whatever decodes must encode to bytes that decode
+// to the same encoding.
+fuzz_target!(|data: &[u8]| {
+	if let Ok(v) = cborg::decode_slice(data) {
+		let bytes = v.encode();
+		assert_eq!(bytes, cborg::decode_slice(&bytes).unwrap().encode());
+		let _ = v.to_diagnostic();
+	}
+});
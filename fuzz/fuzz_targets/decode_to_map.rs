@@ -0,0 +1,10 @@
+#![no_main]
+
+use std::collections::HashMap;
+
+use cborg::Value;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let _ = cborg::decode_to::<HashMap<Value, Value>, _>(data);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Whatever expands must survive compressing and expanding again.
+fuzz_target!(|data: &[u8]| {
+	if let Ok(v) = cborg::decode_with_stringrefs(data) {
+		let bytes = cborg::encode_with_stringrefs(&v);
+		assert_eq!(v.encode(), cborg::decode_with_stringrefs(&bytes).unwrap().encode());
+	}
+});
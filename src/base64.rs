@@ -0,0 +1,96 @@
+// Base64 and base16 encoding (RFC 4648) for text renderings of byte strings.
+
+const URL_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const STANDARD_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+	let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+	for chunk in bytes.chunks(3) {
+		let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+		for i in 0..=chunk.len() {
+			out.push(alphabet[(n >> (18 - 6 * i) & 63) as usize] as char);
+		}
+		if pad {
+			for _ in chunk.len()..3 {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+// base64url without padding, as RFC 8949 section 6.1 uses when converting byte strings to JSON
+pub(crate) fn encode_url(bytes: &[u8]) -> String { encode(bytes, URL_ALPHABET, false) }
+
+// Classic base64 with padding, as tag 22 asks for
+pub(crate) fn encode_standard(bytes: &[u8]) -> String { encode(bytes, STANDARD_ALPHABET, true) }
+
+// Lowercase base16, as tag 23 asks for
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity(bytes.len() * 2);
+	for b in bytes {
+		out.push_str(&format!("{:02x}", b));
+	}
+	out
+}
+
+#[cfg(feature = "base64")]
+fn sextet(c: u8) -> Option<u32> {
+	let v = match c {
+		b'A'..=b'Z' => c - b'A',
+		b'a'..=b'z' => c - b'a' + 26,
+		b'0'..=b'9' => c - b'0' + 52,
+		b'+' | b'-' => 62,
+		b'/' | b'_' => 63,
+		_ => return None,
+	};
+	Some(u32::from(v))
+}
+
+// Decodes base64 in either alphabet, with or without padding. Errors describe the first problem.
+#[cfg(feature = "base64")]
+pub(crate) fn decode(text: &str) -> Result<Vec<u8>, String> {
+	let mut s = text.as_bytes();
+	if s.len().is_multiple_of(4) {
+		let pad = s.iter().rev().take(2).take_while(|c| **c == b'=').count();
+		s = &s[..s.len() - pad];
+	}
+	if s.len() % 4 == 1 {
+		return Err(format!("base64 text of {} characters is truncated", text.len()));
+	}
+	let mut out = Vec::with_capacity(s.len() * 3 / 4);
+	for (i, chunk) in s.chunks(4).enumerate() {
+		let mut n = 0u32;
+		for (j, c) in chunk.iter().enumerate() {
+			match sextet(*c) {
+				Some(x) => n |= x << (18 - 6 * j),
+				None => return Err(format!("invalid base64 character {:?} at {}", *c as char, i * 4 + j)),
+			}
+		}
+		let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+		let len = chunk.len() - 1;
+		if bytes[len..].iter().any(|b| *b != 0) && len < 3 {
+			return Err("base64 text has stray bits after the last byte".to_string());
+		}
+		out.extend_from_slice(&bytes[..len]);
+	}
+	Ok(out)
+}
+
+// Decodes base16 in either case
+#[cfg(feature = "base64")]
+pub(crate) fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+	let s = text.as_bytes();
+	if s.len() % 2 == 1 {
+		return Err(format!("hex text of {} characters has an odd length", s.len()));
+	}
+	let digit = |i: usize| match (s[i] as char).to_digit(16) {
+		Some(d) => Ok(d as u8),
+		None => Err(format!("invalid hex character {:?} at {}", s[i] as char, i)),
+	};
+	(0..s.len())
+		.step_by(2)
+		.map(|i| Ok(digit(i)? << 4 | digit(i + 1)?))
+		.collect()
+}
@@ -0,0 +1,561 @@
+// Deterministic encoding as described in RFC 8949 section 4.2.1: shortest argument encodings,
+// definite lengths only, floats in their shortest exact form and map entries ordered by the bytes
+// of their encoded keys.
+
+use core::cmp::Ordering;
+use std::io;
+
+use crate::header::read_header;
+use crate::header::write_header;
+use crate::header::Header;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::Value;
+
+/// How map keys are ordered in deterministic encodings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyOrder {
+	/// Bytewise lexicographic order of the encoded keys (RFC 8949 section 4.2.1)
+	#[default]
+	Rfc8949,
+	/// Shorter encoded keys first, then bytewise (RFC 7049 section 3.9)
+	Rfc7049LengthFirst,
+	/// Lower major type first, then shorter encoded keys, then bytewise (CTAP2 canonical CBOR)
+	Ctap2,
+}
+
+impl KeyOrder {
+	/// Compares two encoded keys.
+	pub fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+		match self {
+			KeyOrder::Rfc8949 => a.cmp(b),
+			KeyOrder::Rfc7049LengthFirst => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+			KeyOrder::Ctap2 => {
+				let major = |x: &[u8]| x.first().map(|b| b >> 5);
+				major(a)
+					.cmp(&major(b))
+					.then_with(|| a.len().cmp(&b.len()))
+					.then_with(|| a.cmp(b))
+			}
+		}
+	}
+}
+
+// The binary16 bits of `x` if the conversion is exact. NaN is handled by the caller.
+fn f16_bits(x: f32) -> Option<u16> {
+	let bits = x.to_bits();
+	let sign = ((bits >> 16) & 0x8000) as u16;
+	let exp = ((bits >> 23) & 0xFF) as i32;
+	let mant = bits & 0x7F_FFFF;
+	if exp == 0xFF {
+		return Some(sign | 0x7C00);
+	}
+	if exp == 0 {
+		// Zero, or an f32 subnormal which is far below the smallest f16
+		return if mant == 0 { Some(sign) } else { None };
+	}
+	let e = exp - 127;
+	if (-14..=15).contains(&e) {
+		if mant & 0x1FFF != 0 {
+			return None;
+		}
+		return Some(sign | (((e + 15) as u16) << 10) | (mant >> 13) as u16);
+	}
+	if (-24..-14).contains(&e) {
+		// Subnormal in f16: the value is m * 2^-24
+		let full = mant | 0x80_0000;
+		let shift = -e - 1;
+		if full & ((1 << shift) - 1) != 0 {
+			return None;
+		}
+		return Some(sign | (full >> shift) as u16);
+	}
+	None
+}
+
+pub(crate) fn write_float(out: &mut Vec<u8>, x: f64) {
+	if x.is_nan() {
+		out.extend_from_slice(&[0xF9, 0x7E, 0x00]);
+		return;
+	}
+	let single = x as f32;
+	if f64::from(single) != x {
+		out.push(0xFB);
+		out.extend_from_slice(&x.to_bits().to_be_bytes());
+		return;
+	}
+	match f16_bits(single) {
+		Some(half) => {
+			out.push(0xF9);
+			out.extend_from_slice(&half.to_be_bytes());
+		}
+		None => {
+			out.push(0xFA);
+			out.extend_from_slice(&single.to_bits().to_be_bytes());
+		}
+	}
+}
+
+// Emits a map whose entries have each been encoded already. The sort is stable, so duplicate keys
+// keep their original order.
+fn write_map<W: io::Write>(w: &mut W, mut entries: Vec<(Vec<u8>, Vec<u8>)>, order: KeyOrder) -> io::Result<()> {
+	entries.sort_by(|a, b| order.compare(&a.0, &b.0));
+	let mut header = Vec::with_capacity(9);
+	write_header(&mut header, 5, entries.len() as u64);
+	w.write_all(&header)?;
+	for (k, v) in entries {
+		w.write_all(&k)?;
+		w.write_all(&v)?;
+	}
+	Ok(())
+}
+
+pub(crate) fn encode_value(v: &Value, out: &mut Vec<u8>, order: KeyOrder) {
+	match v {
+		Value::Unsigned(x) => write_header(out, 0, *x),
+		Value::Negative(x) => write_header(out, 1, (-1 - x) as u64),
+		Value::ByteString(x) => {
+			write_header(out, 2, x.len() as u64);
+			out.extend_from_slice(x);
+		}
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(x) => {
+			write_header(out, 2, x.len() as u64);
+			out.extend_from_slice(x);
+		}
+		Value::Utf8String(x) => {
+			write_header(out, 3, x.len() as u64);
+			out.extend_from_slice(x.as_bytes());
+		}
+		Value::Array(x) => {
+			write_header(out, 4, x.len() as u64);
+			for item in x {
+				encode_value(item, out, order);
+			}
+		}
+		Value::Map(x) => {
+			let entries = x
+				.iter()
+				.map(|kv| {
+					let (mut k, mut v) = (Vec::new(), Vec::new());
+					encode_value(&kv.key, &mut k, order);
+					encode_value(&kv.val, &mut v, order);
+					(k, v)
+				})
+				.collect();
+			// Writing to a Vec cannot fail
+			let _ = write_map(out, entries, order);
+		}
+		Value::Tag(t, x) => {
+			write_header(out, 6, *t);
+			encode_value(x, out, order);
+		}
+		Value::Float(x) => write_float(out, *x),
+		Value::Simple(x) => out.extend_from_slice(&x.encode()),
+		// Raw bytes are re-encoded, as they need not be in canonical form themselves
+		Value::Raw(x) => encode_value(&x.decode(), out, order),
+	}
+}
+
+fn unexpected<T>(msg: &'static str) -> Result<T> { CborError::new_err(ErrorKind::UnexpectedValue, msg.into()) }
+
+struct Canonicalizer<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	order: KeyOrder,
+	// Arrays and maps enclosing the current item, and tags directly around it
+	depth: usize,
+	tags: usize,
+	// Whether the item is the first one, or inside self-described tags that are
+	leading: bool,
+}
+
+impl<'a> Canonicalizer<'a> {
+	fn header<W: io::Write>(w: &mut W, major: u8, arg: u64) -> Result<()> {
+		let mut header = Vec::with_capacity(9);
+		write_header(&mut header, major, arg);
+		w.write_all(&header)?;
+		Ok(())
+	}
+
+	fn take(&mut self, len: u64) -> Result<&'a [u8]> {
+		if len > (self.bytes.len() - self.pos) as u64 {
+			return CborError::new_err(ErrorKind::InsufficientBytes, "".into());
+		}
+		let start = self.pos;
+		self.pos += len as usize;
+		Ok(&self.bytes[start..self.pos])
+	}
+
+	// Consumes the break code if it is next
+	fn at_break(&mut self) -> bool {
+		if self.bytes.get(self.pos) == Some(&0xFF) {
+			self.pos += 1;
+			return true;
+		}
+		false
+	}
+
+	fn item<W: io::Write>(&mut self, w: &mut W) -> Result<()> {
+		let h = read_header(self.bytes, self.pos)?;
+		if h.is_break() {
+			return CborError::new_err(ErrorKind::InvalidBreak, "unexpected break".into());
+		}
+		let arg_bytes = &self.bytes[self.pos + 1..self.pos + h.len];
+		self.pos += h.len;
+		let leading = std::mem::replace(&mut self.leading, false);
+		let opts = crate::DecodeOptions::default();
+		if h.major == 6 {
+			if self.tags >= opts.max_tag_depth {
+				return crate::tags_too_deep(opts.max_tag_depth);
+			}
+			self.tags += 1;
+		} else {
+			self.tags = 0;
+		}
+		if (h.major == 4 || h.major == 5) && self.depth >= opts.max_depth {
+			return crate::too_deep(opts.max_depth);
+		}
+
+		match h.major {
+			0 => Self::header(w, 0, h.arg),
+			1 => {
+				if h.arg > i64::MAX as u64 {
+					return CborError::new_err(ErrorKind::LengthOverflow, "negative integer below i64::MIN".into());
+				}
+				Self::header(w, 1, h.arg)
+			}
+			2 | 3 => {
+				let mut chunks = Vec::<u8>::new();
+				let content: &[u8] = if h.is_indefinite() {
+					loop {
+						let chunk = read_header(self.bytes, self.pos)?;
+						self.pos += chunk.len;
+						if chunk.is_break() {
+							break;
+						}
+						if chunk.major != h.major || chunk.is_indefinite() {
+							return unexpected("invalid chunk in indefinite-length string");
+						}
+						let chunk = self.take(chunk.arg)?;
+						// As in decode, each chunk must be valid UTF-8 on its own
+						if h.major == 3 {
+							if let Err(e) = std::str::from_utf8(chunk) {
+								return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+							}
+						}
+						chunks.extend_from_slice(chunk);
+					}
+					&chunks
+				} else {
+					self.take(h.arg)?
+				};
+				if h.major == 3 {
+					if let Err(e) = std::str::from_utf8(content) {
+						return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+					}
+				}
+				Self::header(w, h.major, content.len() as u64)?;
+				w.write_all(content)?;
+				Ok(())
+			}
+			4 => {
+				self.depth += 1;
+				if h.is_indefinite() {
+					// The length must come first, so the items are held until the break
+					let mut items = Vec::<u8>::new();
+					let mut count = 0;
+					while !self.at_break() {
+						self.item(&mut items)?;
+						count += 1;
+					}
+					Self::header(w, 4, count)?;
+					w.write_all(&items)?;
+				} else {
+					Self::header(w, 4, h.arg)?;
+					for _ in 0..h.arg {
+						self.item(w)?;
+					}
+				}
+				self.depth -= 1;
+				Ok(())
+			}
+			5 => {
+				self.depth += 1;
+				let mut entries = Vec::new();
+				let mut i = 0;
+				while if h.is_indefinite() { !self.at_break() } else { i < h.arg } {
+					let (mut k, mut v) = (Vec::new(), Vec::new());
+					self.item(&mut k)?;
+					self.item(&mut v)?;
+					entries.push((k, v));
+					i += 1;
+				}
+				self.depth -= 1;
+				write_map(w, entries, self.order)?;
+				Ok(())
+			}
+			// A leading self-described tag is dropped, as decode drops it
+			6 if leading && h.arg == crate::tags::SELF_DESCRIBED => {
+				self.leading = true;
+				self.item(w)
+			}
+			6 => {
+				Self::header(w, 6, h.arg)?;
+				self.item(w)
+			}
+			_ => {
+				let mut out = Vec::with_capacity(9);
+				if h.minor <= 24 {
+					out.extend_from_slice(&crate::parse_simple(h.minor, &mut arg_bytes.iter())?.encode());
+				} else {
+					write_float(&mut out, crate::parse_float(h.minor, &mut arg_bytes.iter())?);
+				}
+				w.write_all(&out)?;
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Re-encodes a single CBOR item in the deterministic form of RFC 8949 section 4.2.1 without
+/// building a `Value` tree.
+///
+/// Strings and definite-length arrays are written through as they are read; only map entries (so
+/// they can be sorted) and the contents of indefinite-length items (so their length can be written
+/// first) are held in memory. The output is identical to `decode` followed by
+/// `Value::encode_canonical`: a leading self-described tag (55799) is dropped, and input that
+/// `decode` rejects, such as nesting past the default depth limits, is rejected. Bytes after the
+/// item are an error.
+///
+/// # Examples
+///
+/// ```
+/// // {"b": 1, "a": 1.5} with a 64-bit float
+/// let bytes = [0xA2, 0x61, 0x62, 0x01, 0x61, 0x61, 0xFB, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0];
+/// let canonical = cborg::canonicalize(&bytes).unwrap();
+/// assert_eq!(vec![0xA2, 0x61, 0x61, 0xF9, 0x3E, 0x00, 0x61, 0x62, 0x01], canonical);
+/// ```
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>> { canonicalize_with(bytes, KeyOrder::Rfc8949) }
+
+/// Like [`canonicalize`] with a choice of map key ordering.
+pub fn canonicalize_with(bytes: &[u8], order: KeyOrder) -> Result<Vec<u8>> {
+	let mut out = Vec::with_capacity(bytes.len());
+	canonicalize_item(bytes, &mut out, order)?;
+	Ok(out)
+}
+
+/// Like [`canonicalize`] but writes the output to `w`.
+pub fn canonicalize_to<W: io::Write>(bytes: &[u8], w: &mut W) -> Result<()> {
+	canonicalize_item(bytes, w, KeyOrder::Rfc8949)
+}
+
+fn canonicalize_item<W: io::Write>(bytes: &[u8], w: &mut W, order: KeyOrder) -> Result<()> {
+	let mut c = Canonicalizer {
+		bytes,
+		pos: 0,
+		order,
+		depth: 0,
+		tags: 0,
+		leading: true,
+	};
+	c.item(w)?;
+	if c.pos != bytes.len() {
+		return unexpected("trailing bytes after the item");
+	}
+	Ok(())
+}
+
+pub(crate) fn f16_to_f64(bits: u16) -> f64 {
+	let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+	let exp = i32::from((bits >> 10) & 0x1F);
+	let mant = f64::from(bits & 0x3FF);
+	match exp {
+		0 => sign * mant * 2f64.powi(-24),
+		31 if mant == 0.0 => sign * f64::INFINITY,
+		31 => f64::NAN,
+		_ => sign * (1.0 + mant / 1024.0) * 2f64.powi(exp - 15),
+	}
+}
+
+struct Checker<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	order: KeyOrder,
+	max_depth: usize,
+	max_tag_depth: usize,
+}
+
+fn not_canonical<T>(what: &str, offset: usize) -> Result<T> {
+	let msg = format!("{} at offset {}", what, offset);
+	CborError::new_err(ErrorKind::NotCanonical, msg.into())
+}
+
+impl<'a> Checker<'a> {
+	// Moves past the header `h` at `pos` if its argument is in its shortest form
+	fn shortest_header(&mut self, h: &Header) -> Result<()> {
+		let mut minimal = Vec::with_capacity(9);
+		write_header(&mut minimal, h.major, h.arg);
+		if minimal.len() != h.len {
+			let what = format!("argument {} of major type {} not in shortest form", h.arg, h.major);
+			return not_canonical(&what, self.pos);
+		}
+		self.pos += h.len;
+		Ok(())
+	}
+
+	// Moves past the next item if it is not an array or map, or past the header of one that is,
+	// and returns the header
+	fn header(&mut self) -> Result<Header> {
+		let mut h = read_header(self.bytes, self.pos)?;
+		// Tags directly inside each other are walked in a loop rather than by recursion
+		let mut tags = 0;
+		while h.major == 6 {
+			if tags >= self.max_tag_depth {
+				return crate::tags_too_deep(self.max_tag_depth);
+			}
+			tags += 1;
+			self.shortest_header(&h)?;
+			h = read_header(self.bytes, self.pos)?;
+		}
+		if h.is_break() {
+			let msg = format!("break code in place of an item at offset {}", self.pos);
+			return CborError::new_err(ErrorKind::InvalidBreak, msg.into());
+		}
+		if h.is_indefinite() {
+			return not_canonical("indefinite-length item", self.pos);
+		}
+		let start = self.pos;
+
+		if h.major == 7 && h.minor >= 25 {
+			self.pos += h.len;
+			let x = match h.minor {
+				25 => f16_to_f64(h.arg as u16),
+				26 => f64::from(f32::from_bits(h.arg as u32)),
+				_ => f64::from_bits(h.arg),
+			};
+			let mut shortest = Vec::with_capacity(9);
+			write_float(&mut shortest, x);
+			if shortest[..] != self.bytes[start..self.pos] {
+				return not_canonical(&format!("float {} not in its shortest form", x), start);
+			}
+			return Ok(h);
+		}
+		if h.major == 7 && h.minor == 24 && h.arg < 32 {
+			let msg = format!("simple value {} in two-byte form, which needs 32 or more", h.arg);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+		self.shortest_header(&h)?;
+
+		if h.major == 2 || h.major == 3 {
+			if h.arg > (self.bytes.len() - self.pos) as u64 {
+				return CborError::new_err(ErrorKind::InsufficientBytes, "".into());
+			}
+			let end = self.pos + h.arg as usize;
+			if h.major == 3 {
+				if let Err(e) = std::str::from_utf8(&self.bytes[self.pos..end]) {
+					return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+				}
+			}
+			self.pos = end;
+		}
+		Ok(h)
+	}
+
+	// Checks the item at `pos`. Arrays and maps are kept on a stack rather than checked by
+	// recursion, so a large `max_depth` cannot exhaust the call stack.
+	fn item(&mut self) -> Result<()> {
+		let mut stack = Vec::<Open>::new();
+		loop {
+			if let Some(top) = stack.last_mut() {
+				if top.is_map && !top.in_value {
+					top.key_start = self.pos;
+				}
+			}
+			let h = self.header()?;
+			let mut done = true;
+			if h.major == 4 || h.major == 5 {
+				if stack.len() >= self.max_depth {
+					return crate::too_deep(self.max_depth);
+				}
+				if h.arg > 0 {
+					stack.push(Open {
+						remaining: h.arg,
+						is_map: h.major == 5,
+						in_value: false,
+						key_start: 0,
+						prev_key: None,
+					});
+					done = false;
+				}
+			}
+
+			// Count the finished item in the containers it completes
+			while done {
+				let top = match stack.last_mut() {
+					None => return Ok(()),
+					Some(top) => top,
+				};
+				if top.is_map && !top.in_value {
+					let key = (top.key_start, self.pos);
+					if let Some(prev) = top.prev_key {
+						let (prev, next) = (&self.bytes[prev.0..prev.1], &self.bytes[key.0..key.1]);
+						match self.order.compare(prev, next) {
+							Ordering::Less => {}
+							Ordering::Equal => return not_canonical("duplicate map key", key.0),
+							Ordering::Greater => return not_canonical("map key out of order", key.0),
+						}
+					}
+					top.prev_key = Some(key);
+					top.in_value = true;
+					done = false;
+				} else {
+					top.in_value = false;
+					top.remaining -= 1;
+					if top.remaining == 0 {
+						stack.pop();
+					} else {
+						done = false;
+					}
+				}
+			}
+		}
+	}
+}
+
+// An array or map whose elements are still being checked
+struct Open {
+	// Elements, or for a map entries, still to come
+	remaining: u64,
+	is_map: bool,
+	// The key of the current entry has been checked
+	in_value: bool,
+	key_start: usize,
+	// Where the previous key is in the input
+	prev_key: Option<(usize, usize)>,
+}
+
+// Checks that the first item in `bytes` is in deterministic form and returns its length
+pub(crate) fn check_canonical(bytes: &[u8], order: KeyOrder, opts: &crate::DecodeOptions) -> Result<usize> {
+	let mut c = Checker {
+		bytes,
+		pos: 0,
+		order,
+		max_depth: opts.max_depth,
+		max_tag_depth: opts.max_tag_depth,
+	};
+	c.item()?;
+	Ok(c.pos)
+}
+
+/// Whether `bytes` is exactly one item in RFC 8949 deterministic form: shortest arguments, no
+/// indefinite lengths, floats in their shortest exact width, and map keys unique and in
+/// bytewise order. Tags are allowed.
+pub fn is_canonical(bytes: &[u8]) -> bool { is_canonical_with(bytes, KeyOrder::Rfc8949) }
+
+/// Like [`is_canonical`] with a choice of map key ordering.
+pub fn is_canonical_with(bytes: &[u8], order: KeyOrder) -> bool {
+	let opts = crate::DecodeOptions::default();
+	matches!(check_canonical(bytes, order, &opts), Ok(len) if len == bytes.len())
+}
@@ -0,0 +1,199 @@
+//! Claims sets for CBOR Web Tokens (RFC 8392).
+//!
+//! Only the claims map is handled here. Signing, MACing and encryption belong to a COSE layer.
+
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::CborError;
+use crate::ErrorKind;
+use crate::FromValue;
+use crate::KeyVal;
+use crate::Result;
+use crate::ToValue;
+use crate::Value;
+
+pub const ISS: u64 = 1;
+pub const SUB: u64 = 2;
+pub const AUD: u64 = 3;
+pub const EXP: u64 = 4;
+pub const NBF: u64 = 5;
+pub const IAT: u64 = 6;
+pub const CTI: u64 = 7;
+
+/// Seconds since the Unix epoch, as used by the exp, nbf and iat claims. It is the same quantity
+/// as the content of an epoch-based date/time (tag 1), carried without the tag as RFC 8392
+/// requires.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericDate {
+	Seconds(i64),
+	/// A date with a fractional part
+	Fractional(f64),
+}
+
+impl NumericDate {
+	pub fn from_system_time(t: SystemTime) -> NumericDate {
+		match t.duration_since(UNIX_EPOCH) {
+			Ok(d) if d.subsec_nanos() == 0 => NumericDate::Seconds(d.as_secs() as i64),
+			Ok(d) => NumericDate::Fractional(d.as_secs_f64()),
+			Err(e) => {
+				let d = e.duration();
+				if d.subsec_nanos() == 0 {
+					NumericDate::Seconds(-(d.as_secs() as i64))
+				} else {
+					NumericDate::Fractional(-d.as_secs_f64())
+				}
+			}
+		}
+	}
+
+	pub fn to_system_time(&self) -> SystemTime {
+		let secs = match *self {
+			NumericDate::Seconds(x) => x as f64,
+			NumericDate::Fractional(x) => x,
+		};
+		if secs >= 0.0 {
+			UNIX_EPOCH + Duration::from_secs_f64(secs)
+		} else {
+			UNIX_EPOCH - Duration::from_secs_f64(-secs)
+		}
+	}
+}
+
+impl ToValue for NumericDate {
+	fn to_value(&self) -> Value {
+		match *self {
+			NumericDate::Seconds(x) if x >= 0 => Value::Unsigned(x as u64),
+			NumericDate::Seconds(x) => Value::Negative(x),
+			NumericDate::Fractional(x) => Value::Float(x),
+		}
+	}
+}
+
+impl FromValue for NumericDate {
+	fn from_value(v: Value) -> Option<Self> { NumericDate::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Unsigned(x) if *x <= i64::MAX as u64 => Some(NumericDate::Seconds(*x as i64)),
+			Value::Negative(x) => Some(NumericDate::Seconds(*x)),
+			Value::Float(x) if x.is_finite() => Some(NumericDate::Fractional(*x)),
+			_ => None,
+		}
+	}
+}
+
+/// A CWT claims set. The registered claims have typed fields; any other claims are kept in
+/// `private` in their original order.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::cwt::{Claims, NumericDate};
+/// use cborg::ToValue;
+/// let claims = Claims {
+///     iss: Some("coap://as.example.com".to_string()),
+///     exp: Some(NumericDate::Seconds(1444064944)),
+///     ..Claims::default()
+/// };
+/// let bytes = claims.to_value().encode();
+/// assert_eq!(claims, Claims::decode(&bytes).unwrap());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Claims {
+	pub iss: Option<String>,
+	pub sub: Option<String>,
+	pub aud: Option<String>,
+	pub exp: Option<NumericDate>,
+	pub nbf: Option<NumericDate>,
+	pub iat: Option<NumericDate>,
+	pub cti: Option<Vec<u8>>,
+	pub private: Vec<KeyVal>,
+}
+
+fn invalid(name: &str, key: u64, expected: &str) -> CborError {
+	let msg = format!("claim {} ({}) must be {}", name, key, expected);
+	CborError::new(ErrorKind::UnexpectedValue, msg.into())
+}
+
+fn text(v: &Value, name: &str, key: u64) -> Result<String> {
+	v.get_string().ok_or_else(|| invalid(name, key, "a text string"))
+}
+
+fn date(v: &Value, name: &str, key: u64) -> Result<NumericDate> {
+	NumericDate::from_ref(v).ok_or_else(|| invalid(name, key, "a NumericDate"))
+}
+
+impl Claims {
+	/// Decodes a claims set from CBOR bytes. See [`Claims::from_value_checked`].
+	pub fn decode(bytes: &[u8]) -> Result<Claims> { Claims::from_value_checked(&crate::decode(bytes)?) }
+
+	/// Reads a claims set, failing with a message naming the claim if a registered claim has the
+	/// wrong type or appears twice.
+	pub fn from_value_checked(v: &Value) -> Result<Claims> {
+		let map = match v {
+			Value::Map(x) => x,
+			_ => return CborError::new_err(ErrorKind::UnexpectedValue, "CWT claims set must be a map".into()),
+		};
+		let mut claims = Claims::default();
+		for kv in map {
+			let key = match kv.key {
+				Value::Unsigned(x @ ISS..=CTI) => x,
+				_ => {
+					claims.private.push(kv.clone());
+					continue;
+				}
+			};
+			let v = &kv.val;
+			let duplicate = match key {
+				ISS => claims.iss.replace(text(v, "iss", key)?).is_some(),
+				SUB => claims.sub.replace(text(v, "sub", key)?).is_some(),
+				AUD => claims.aud.replace(text(v, "aud", key)?).is_some(),
+				EXP => claims.exp.replace(date(v, "exp", key)?).is_some(),
+				NBF => claims.nbf.replace(date(v, "nbf", key)?).is_some(),
+				IAT => claims.iat.replace(date(v, "iat", key)?).is_some(),
+				_ => claims
+					.cti
+					.replace(v.get_bytes().ok_or_else(|| invalid("cti", key, "a byte string"))?)
+					.is_some(),
+			};
+			if duplicate {
+				let msg = format!("claim {} appears more than once", key);
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
+		}
+		Ok(claims)
+	}
+}
+
+impl ToValue for Claims {
+	fn to_value(&self) -> Value {
+		let mut map = Vec::<KeyVal>::new();
+		let mut add = |key: u64, val: Option<Value>| {
+			if let Some(val) = val {
+				map.push(KeyVal {
+					key: Value::Unsigned(key),
+					val,
+				});
+			}
+		};
+		add(ISS, self.iss.clone().map(Value::Utf8String));
+		add(SUB, self.sub.clone().map(Value::Utf8String));
+		add(AUD, self.aud.clone().map(Value::Utf8String));
+		add(EXP, self.exp.map(|x| x.to_value()));
+		add(NBF, self.nbf.map(|x| x.to_value()));
+		add(IAT, self.iat.map(|x| x.to_value()));
+		add(CTI, self.cti.clone().map(Value::ByteString));
+		map.extend(self.private.iter().cloned());
+		Value::Map(map.into())
+	}
+}
+
+impl FromValue for Claims {
+	fn from_value(v: Value) -> Option<Self> { Claims::from_value_checked(&v).ok() }
+	fn from_ref(v: &Value) -> Option<Self> { Claims::from_value_checked(v).ok() }
+}
+
+impl From<Claims> for Value {
+	fn from(c: Claims) -> Value { c.to_value() }
+}
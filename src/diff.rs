@@ -0,0 +1,134 @@
+use core::fmt;
+
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::KeyVal;
+use crate::Value;
+
+/// A single point where two values disagree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+	pub path: Path,
+	pub kind: DiffKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffKind {
+	/// Both sides have a value here but they differ
+	Changed(Value, Value),
+	/// Only the left side has a value here
+	LeftOnly(Value),
+	/// Only the right side has a value here
+	RightOnly(Value),
+	/// Both maps have the same entries but in a different order. Only reported by [`diff`].
+	KeyOrder,
+}
+
+impl fmt::Display for Difference {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match &self.kind {
+			DiffKind::Changed(l, r) => write!(f, "{}: {} != {}", self.path, l.to_diagnostic(), r.to_diagnostic()),
+			DiffKind::LeftOnly(l) => write!(f, "{}: only in left: {}", self.path, l.to_diagnostic()),
+			DiffKind::RightOnly(r) => write!(f, "{}: only in right: {}", self.path, r.to_diagnostic()),
+			DiffKind::KeyOrder => write!(f, "{}: map keys are in a different order", self.path),
+		}
+	}
+}
+
+/// Lists every difference between `left` and `right`, with the path at which each occurs.
+///
+/// Map entries are matched up by key. Entries that are in a different order are reported as a
+/// single [`DiffKind::KeyOrder`] on the map, so an empty result means `left == right`.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{KeyVal, Map, Value};
+/// let a = Value::Map(Map::from(vec![KeyVal { key: Value::Utf8String("n".into()), val: Value::Unsigned(1) }]));
+/// let b = Value::Map(Map::from(vec![KeyVal { key: Value::Utf8String("n".into()), val: Value::Unsigned(2) }]));
+/// let diffs = cborg::diff(&a, &b);
+/// assert_eq!("$.n: 1 != 2", diffs[0].to_string());
+/// ```
+pub fn diff(left: &Value, right: &Value) -> Vec<Difference> {
+	let mut out = Vec::new();
+	diff_into(left, right, true, &mut Path::new(), &mut out);
+	out
+}
+
+/// Like [`diff`] but ignores the order of map entries.
+pub fn diff_unordered(left: &Value, right: &Value) -> Vec<Difference> {
+	let mut out = Vec::new();
+	diff_into(left, right, false, &mut Path::new(), &mut out);
+	out
+}
+
+fn same_leaf(left: &Value, right: &Value) -> bool {
+	match (left, right) {
+		(Value::Float(a), Value::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+		_ => left == right,
+	}
+}
+
+fn diff_into(left: &Value, right: &Value, ordered: bool, path: &mut Path, out: &mut Vec<Difference>) {
+	match (left, right) {
+		(Value::Array(l), Value::Array(r)) => {
+			for i in 0..l.len().max(r.len()) {
+				path.push(PathSegment::Index(i));
+				match (l.get(i), r.get(i)) {
+					(Some(a), Some(b)) => diff_into(a, b, ordered, path, out),
+					(Some(a), None) => out.push(Difference {
+						path: path.clone(),
+						kind: DiffKind::LeftOnly(a.clone()),
+					}),
+					(None, Some(b)) => out.push(Difference {
+						path: path.clone(),
+						kind: DiffKind::RightOnly(b.clone()),
+					}),
+					(None, None) => (),
+				}
+				path.pop();
+			}
+		}
+		(Value::Map(l), Value::Map(r)) => diff_maps(l, r, ordered, path, out),
+		(Value::Tag(a, l), Value::Tag(b, r)) if a == b => diff_into(l, r, ordered, path, out),
+		_ => {
+			if !same_leaf(left, right) {
+				out.push(Difference {
+					path: path.clone(),
+					kind: DiffKind::Changed(left.clone(), right.clone()),
+				});
+			}
+		}
+	}
+}
+
+fn diff_maps(l: &[KeyVal], r: &[KeyVal], ordered: bool, path: &mut Path, out: &mut Vec<Difference>) {
+	let before = out.len();
+	for kv in l {
+		path.push(PathSegment::Key(kv.key.clone()));
+		match r.iter().find(|x| x.key == kv.key) {
+			Some(other) => diff_into(&kv.val, &other.val, ordered, path, out),
+			None => out.push(Difference {
+				path: path.clone(),
+				kind: DiffKind::LeftOnly(kv.val.clone()),
+			}),
+		}
+		path.pop();
+	}
+	for kv in r {
+		if !l.iter().any(|x| x.key == kv.key) {
+			path.push(PathSegment::Key(kv.key.clone()));
+			out.push(Difference {
+				path: path.clone(),
+				kind: DiffKind::RightOnly(kv.val.clone()),
+			});
+			path.pop();
+		}
+	}
+	if ordered && out.len() == before && l.iter().zip(r).any(|(a, b)| a.key != b.key) {
+		out.push(Difference {
+			path: path.clone(),
+			kind: DiffKind::KeyOrder,
+		});
+	}
+}
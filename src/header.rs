@@ -0,0 +1,103 @@
+// Reading of initial bytes and their arguments directly from a slice, for code that walks encoded
+// data without building a Value.
+
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+pub(crate) struct Header {
+	pub(crate) major: u8,
+	pub(crate) minor: u8,
+	// The argument value; 0 for indefinite-length items and the break code.
+	pub(crate) arg: u64,
+	// Number of bytes taken by the initial byte and argument.
+	pub(crate) len: usize,
+}
+
+impl Header {
+	pub(crate) fn is_indefinite(&self) -> bool { self.minor == 31 && (2..=5).contains(&self.major) }
+
+	pub(crate) fn is_break(&self) -> bool { self.major == 7 && self.minor == 31 }
+}
+
+pub(crate) fn read_header(bytes: &[u8], pos: usize) -> Result<Header> {
+	let b = match bytes.get(pos) {
+		Some(x) => *x,
+		None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
+	};
+	let major = b >> 5;
+	let minor = b & 31;
+
+	let arg_len: usize = match minor {
+		0..=23 => 0,
+		24 => 1,
+		25 => 2,
+		26 => 4,
+		27 => 8,
+		31 if major >= 2 && major != 6 => 0,
+		28..=30 => return crate::reserved(b),
+		_ => {
+			let msg = format!("invalid additional information in initial byte 0x{:02X}", b);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+	};
+	let arg_bytes = match bytes.get(pos + 1..pos + 1 + arg_len) {
+		Some(x) => x,
+		None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
+	};
+
+	let arg = if arg_len == 0 {
+		if minor == 31 {
+			0
+		} else {
+			u64::from(minor)
+		}
+	} else {
+		arg_bytes.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b))
+	};
+
+	Ok(Header {
+		major,
+		minor,
+		arg,
+		len: 1 + arg_len,
+	})
+}
+
+// Appends the shortest initial byte and argument for `arg`.
+pub(crate) fn write_header(out: &mut Vec<u8>, major: u8, arg: u64) {
+	let (bytes, len) = header_bytes(major, arg);
+	out.extend_from_slice(&bytes[..len]);
+}
+
+// The shortest initial byte and argument for `arg` in the first `len` bytes of an array, for
+// writers that do not allocate.
+pub(crate) fn header_bytes(major: u8, arg: u64) -> ([u8; 9], usize) {
+	let major = major << 5;
+	let len = header_len(arg);
+	let mut bytes = [0; 9];
+	bytes[0] = match len {
+		1 => major | arg as u8,
+		2 => major | 24,
+		3 => major | 25,
+		5 => major | 26,
+		_ => major | 27,
+	};
+	bytes[1..len].copy_from_slice(&arg.to_be_bytes()[9 - len..]);
+	(bytes, len)
+}
+
+// The number of bytes write_header appends for `arg`.
+pub(crate) fn header_len(arg: u64) -> usize {
+	if arg <= 23 {
+		1
+	} else if arg <= 0xFF {
+		2
+	} else if arg <= 0xFFFF {
+		3
+	} else if arg <= 0xFFFF_FFFF {
+		5
+	} else {
+		9
+	}
+}
@@ -0,0 +1,188 @@
+// Canonical JSON (RFC 8785) rendering of Values.
+
+use std::collections::HashSet;
+
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::Simple;
+use crate::TextHint;
+use crate::Value;
+
+fn no_mapping<T>(msg: String) -> Result<T> { CborError::new_err(ErrorKind::UnexpectedValue, msg.into()) }
+
+// Integers beyond this lose precision as IEEE doubles, which is all JSON numbers can be relied on
+// to hold (RFC 7493 section 2.2)
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+fn write_string(out: &mut String, s: &str) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\u{8}' => out.push_str("\\b"),
+			'\t' => out.push_str("\\t"),
+			'\n' => out.push_str("\\n"),
+			'\u{c}' => out.push_str("\\f"),
+			'\r' => out.push_str("\\r"),
+			c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+// Formats a finite double the way ECMAScript's Number.prototype.toString does, as RFC 8785
+// section 3.2.2.3 requires
+fn write_number(out: &mut String, x: f64) {
+	if x == 0.0 {
+		out.push('0');
+		return;
+	}
+	if x < 0.0 {
+		out.push('-');
+	}
+	// Shortest round-tripping digits, as "d.ddde<exp>"
+	let sci = format!("{:e}", x.abs());
+	let (mantissa, exp) = sci.split_at(sci.find('e').unwrap_or(sci.len()));
+	let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+	let k = digits.len() as i32;
+	let n = exp[1..].parse::<i32>().unwrap_or(0) + 1;
+
+	if k <= n && n <= 21 {
+		out.push_str(&digits);
+		out.extend(std::iter::repeat_n('0', (n - k) as usize));
+	} else if 0 < n && n <= 21 {
+		out.push_str(&digits[..n as usize]);
+		out.push('.');
+		out.push_str(&digits[n as usize..]);
+	} else if -6 < n && n <= 0 {
+		out.push_str("0.");
+		out.extend(std::iter::repeat_n('0', (-n) as usize));
+		out.push_str(&digits);
+	} else {
+		out.push_str(&digits[..1]);
+		if k > 1 {
+			out.push('.');
+			out.push_str(&digits[1..]);
+		}
+		out.push_str(&format!("e{}{}", if n > 0 { "+" } else { "-" }, (n - 1).abs()));
+	}
+}
+
+// Byte strings are base64url encoded unless an enclosing tag 21, 22 or 23 asks for another
+// encoding (RFC 8949 section 6.1)
+fn bytes_string(b: &[u8], hint: Option<TextHint>) -> String { hint.unwrap_or(TextHint::Base64Url).encode(b) }
+
+// Map keys become JSON strings: text keys as they are, other keys as their JSON rendering, with
+// the quotes dropped where that rendering is itself a string (e.g. a base64url byte string)
+fn key_string(key: &Value, hint: Option<TextHint>) -> Result<String> {
+	match key {
+		Value::Utf8String(s) => Ok(s.clone()),
+		Value::ByteString(b) => Ok(bytes_string(b, hint)),
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(b) => Ok(bytes_string(b, hint)),
+		Value::Tag(t, x) => key_string(x, TextHint::from_tag(*t).or(hint)),
+		k => {
+			let mut s = String::new();
+			write_value(&mut s, k, hint)?;
+			Ok(s)
+		}
+	}
+}
+
+fn write_value(out: &mut String, v: &Value, hint: Option<TextHint>) -> Result<()> {
+	match v {
+		Value::Unsigned(x) => {
+			if *x > MAX_SAFE_INTEGER {
+				return no_mapping(format!("{} cannot be represented exactly as a JSON number", x));
+			}
+			out.push_str(&x.to_string());
+		}
+		Value::Negative(x) => {
+			if x.unsigned_abs() > MAX_SAFE_INTEGER {
+				return no_mapping(format!("{} cannot be represented exactly as a JSON number", x));
+			}
+			out.push_str(&x.to_string());
+		}
+		Value::ByteString(b) => write_string(out, &bytes_string(b, hint)),
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(b) => write_string(out, &bytes_string(b, hint)),
+		Value::Utf8String(s) => write_string(out, s),
+		Value::Array(x) => {
+			out.push('[');
+			for (i, item) in x.iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				write_value(out, item, hint)?;
+			}
+			out.push(']');
+		}
+		Value::Map(x) => {
+			let mut entries = Vec::with_capacity(x.len());
+			let mut seen = HashSet::with_capacity(x.len());
+			for kv in x {
+				let key = key_string(&kv.key, hint)?;
+				if !seen.insert(key.clone()) {
+					return no_mapping(format!("more than one map key becomes the JSON key {:?}", key));
+				}
+				entries.push((key.encode_utf16().collect::<Vec<u16>>(), key, &kv.val));
+			}
+			// Object members are ordered by the UTF-16 code units of their names
+			entries.sort_by(|a, b| a.0.cmp(&b.0));
+			out.push('{');
+			for (i, (_, key, val)) in entries.into_iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				write_string(out, &key);
+				out.push(':');
+				write_value(out, val, hint)?;
+			}
+			out.push('}');
+		}
+		Value::Tag(t, x) => write_value(out, x, TextHint::from_tag(*t).or(hint))?,
+		Value::Float(x) => {
+			if !x.is_finite() {
+				return no_mapping(format!("{} has no JSON representation", x));
+			}
+			write_number(out, *x);
+		}
+		Value::Simple(Simple::False) => out.push_str("false"),
+		Value::Simple(Simple::True) => out.push_str("true"),
+		Value::Simple(Simple::Null) => out.push_str("null"),
+		Value::Simple(x) => return no_mapping(format!("{} has no JSON representation", Value::Simple(x.clone()))),
+		Value::Raw(x) => return write_value(out, &x.decode(), hint),
+	}
+	Ok(())
+}
+
+impl Value {
+	/// Renders the value as canonical JSON (RFC 8785): no whitespace, object members sorted by
+	/// name and numbers formatted as ECMAScript does, so equal values always give identical text.
+	///
+	/// The mapping follows RFC 8949 section 6.1. Byte strings become base64url text without
+	/// padding, or base64 or base16 text inside tag 22 or 23, and tags are dropped in favour of
+	/// their content. Map keys that are not text are
+	/// converted the same way, using the text of a resulting string or the JSON of anything else,
+	/// so the key `1` becomes `"1"` and `h'01'` becomes `"AQ"`.
+	///
+	/// Fails rather than approximate for NaN and infinities, integers beyond ±(2^53 - 1),
+	/// `undefined` and unassigned simple values, and maps where two keys convert to the same
+	/// name.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let v = Value::map(vec![(Value::text("b"), Value::Float(1e21)), (Value::Unsigned(1), Value::bytes(vec![0xFF]))]);
+	/// assert_eq!(r#"{"1":"_w","b":1e+21}"#, v.to_canonical_json().unwrap());
+	/// ```
+	pub fn to_canonical_json(&self) -> Result<String> {
+		let mut out = String::new();
+		write_value(&mut out, self, None)?;
+		Ok(out)
+	}
+}
@@ -1,21 +1,161 @@
+mod base64;
+mod canonical;
+pub mod cwt;
+mod diff;
+mod header;
+mod json;
+mod lossy;
+mod options;
+pub mod path;
+mod profile;
+mod query;
+mod reuse;
+mod roundtrip;
+mod seq;
+mod seq_index;
+mod stream;
+mod stringref;
+pub mod tags;
+pub mod testing;
+mod trace;
+#[cfg(feature = "base64")]
+mod transport;
+mod validate;
 pub mod value;
+#[cfg(feature = "bytes")]
+mod zero_copy;
 
+use core::convert::TryFrom;
 use core::fmt;
 use core::iter::Iterator;
 use core::result;
 use std::error;
 
+pub use canonical::canonicalize;
+pub use canonical::canonicalize_to;
+pub use canonical::canonicalize_with;
+pub use canonical::is_canonical;
+pub use canonical::is_canonical_with;
+pub use canonical::KeyOrder;
+pub use diff::diff;
+pub use diff::diff_unordered;
+pub use diff::DiffKind;
+pub use diff::Difference;
+pub use lossy::decode_lenient;
+pub use lossy::decode_lossy;
+pub use lossy::decode_lossy_seq;
+pub use lossy::DecodeIssue;
+pub use options::DecodeOptions;
+pub use options::DuplicateKeyPolicy;
+pub use options::TagHandler;
+pub use options::TagPolicy;
+pub use path::Path;
+pub use path::PathSegment;
+pub use profile::profile;
+pub use profile::Profile;
+pub use query::Query;
+pub use reuse::decode_reuse;
+pub use roundtrip::roundtrip_check;
+pub use roundtrip::Divergence;
+pub use roundtrip::DivergenceKind;
+pub use roundtrip::RoundtripReport;
+pub use seq::decode_all;
+pub use seq::decode_seq;
+pub use seq::decode_seq_to;
+pub use seq::decode_seq_to_vec;
+pub use seq::encode_seq;
+pub use seq::SeqTo;
+pub use seq_index::index_seq;
+pub use seq_index::index_seq_sparse;
+pub use seq_index::SeqIndex;
+pub use stream::encode_array_from_iter;
+pub use stream::encode_map_from_iter;
+pub use stringref::decode_with_stringrefs;
+pub use stringref::encode_with_stringrefs;
+#[cfg(feature = "base64")]
+pub use transport::decode_base64;
+#[cfg(feature = "base64")]
+pub use transport::decode_hex;
+pub use validate::validate;
+pub use value::ct_eq;
+#[cfg(feature = "mime")]
+pub use value::mime_headers;
+pub use value::should_color;
+pub use value::Bigfloat;
+pub use value::Decimal;
+pub use value::Endian;
+pub use value::ExtendedTime;
 pub use value::FromValue;
+pub use value::IntoValue;
 pub use value::KeyVal;
+pub use value::Label;
+pub use value::LangString;
+pub use value::Map;
+pub use value::RawValue;
 pub use value::Simple;
+pub use value::SimpleNumber;
+pub use value::TextHint;
 pub use value::ToValue;
+pub use value::TypedArrayElement;
 pub use value::Value;
 pub use value::ValueInto;
+#[cfg(feature = "bytes")]
+pub use zero_copy::decode_bytes;
 
 pub type Result<T> = result::Result<T, CborError>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ErrorKind {
 	UnexpectedValue,
 	InsufficientBytes,
+	/// Reading or writing the underlying stream failed
+	Io,
+	/// A tag that is not known was decoded under `TagPolicy::Error`
+	UnknownTag(u64),
+	/// More tags were nested directly inside each other than `DecodeOptions::max_tag_depth` allows
+	TagNestingTooDeep,
+	/// Base64 or base16 text carrying CBOR is malformed
+	TextEncoding,
+	/// A text string, or a chunk of one, is not valid UTF-8. The error's source is the
+	/// `FromUtf8Error` or `Utf8Error`.
+	InvalidUtf8,
+	/// An initial byte has additional information 28, 29 or 30, which CBOR reserves
+	ReservedAdditionalInfo,
+	/// Arrays and maps were nested more deeply than `DecodeOptions::max_depth` allows
+	DepthLimitExceeded,
+	/// Bytes were left over after the one item expected
+	TrailingBytes {
+		remaining: usize,
+	},
+	/// A map has the same key more than once
+	DuplicateKey,
+	/// A length or integer argument does not fit the type it is decoded into
+	LengthOverflow,
+	/// A break code (0xFF) appears where no indefinite-length item can end
+	InvalidBreak,
+	/// Input decoded under `DecodeOptions::require_canonical` is not in deterministic form
+	NotCanonical,
+	/// An array has more elements than `DecodeOptions::max_array_len` allows. `len` is the length
+	/// its header declares, or for an indefinite-length array the count reached.
+	ArrayTooLong {
+		len: u64,
+	},
+	/// A map has more entries than `DecodeOptions::max_map_len` allows. `len` is as for
+	/// `ArrayTooLong`.
+	MapTooLong {
+		len: u64,
+	},
+	/// A byte or text string is longer than `DecodeOptions::max_string_len` allows. `len` is the
+	/// length its header declares, or for an indefinite-length string the length with the chunk
+	/// that goes past the limit.
+	StringTooLong {
+		len: u64,
+	},
+	/// The buffer given to `Value::encode_to_slice` is shorter than the `required` number of bytes
+	BufferTooSmall {
+		required: usize,
+	},
 }
 
 impl fmt::Debug for ErrorKind {
@@ -23,6 +163,29 @@ impl fmt::Debug for ErrorKind {
 		match self {
 			ErrorKind::UnexpectedValue => f.write_str("Unexpected value"),
 			ErrorKind::InsufficientBytes => f.write_str("Insufficient bytes"),
+			ErrorKind::Io => f.write_str("I/O error"),
+			ErrorKind::UnknownTag(tag) => write!(f, "Unknown tag {}", tag),
+			ErrorKind::TagNestingTooDeep => f.write_str("Tags nested too deeply"),
+			ErrorKind::TextEncoding => f.write_str("Invalid base64 or base16 text"),
+			ErrorKind::InvalidUtf8 => f.write_str("Invalid UTF-8"),
+			ErrorKind::ReservedAdditionalInfo => f.write_str("Reserved additional information"),
+			ErrorKind::DepthLimitExceeded => f.write_str("Depth limit exceeded"),
+			ErrorKind::DuplicateKey => f.write_str("Duplicate map key"),
+			ErrorKind::LengthOverflow => f.write_str("Length overflow"),
+			ErrorKind::InvalidBreak => f.write_str("Invalid break"),
+			ErrorKind::NotCanonical => f.write_str("Not canonical"),
+			ErrorKind::ArrayTooLong { len } => write!(f, "Array too long ({} elements)", len),
+			ErrorKind::MapTooLong { len } => write!(f, "Map too long ({} entries)", len),
+			ErrorKind::StringTooLong { len } => write!(f, "String too long ({} bytes)", len),
+			ErrorKind::BufferTooSmall { required } => write!(f, "Buffer too small ({} bytes needed)", required),
+			ErrorKind::TrailingBytes { remaining } => {
+				write!(
+					f,
+					"{} trailing byte{}",
+					remaining,
+					if *remaining == 1 { "" } else { "s" }
+				)
+			}
 		}
 	}
 }
@@ -32,26 +195,116 @@ impl fmt::Display for ErrorKind {
 		match self {
 			ErrorKind::UnexpectedValue => write!(fmt, "Unexpected value"),
 			ErrorKind::InsufficientBytes => write!(fmt, "Insufficient bytes"),
+			ErrorKind::Io => write!(fmt, "I/O error"),
+			ErrorKind::UnknownTag(tag) => write!(fmt, "Unknown tag {}", tag),
+			ErrorKind::TagNestingTooDeep => write!(fmt, "Tags nested too deeply"),
+			ErrorKind::TextEncoding => write!(fmt, "Invalid base64 or base16 text"),
+			ErrorKind::InvalidUtf8 => write!(fmt, "Invalid UTF-8"),
+			ErrorKind::ReservedAdditionalInfo => write!(fmt, "Reserved additional information"),
+			ErrorKind::DepthLimitExceeded => write!(fmt, "Depth limit exceeded"),
+			ErrorKind::DuplicateKey => write!(fmt, "Duplicate map key"),
+			ErrorKind::LengthOverflow => write!(fmt, "Length overflow"),
+			ErrorKind::InvalidBreak => write!(fmt, "Invalid break"),
+			ErrorKind::NotCanonical => write!(fmt, "Not canonical"),
+			ErrorKind::ArrayTooLong { len } => write!(fmt, "Array too long ({} elements)", len),
+			ErrorKind::MapTooLong { len } => write!(fmt, "Map too long ({} entries)", len),
+			ErrorKind::StringTooLong { len } => write!(fmt, "String too long ({} bytes)", len),
+			ErrorKind::BufferTooSmall { required } => write!(fmt, "Buffer too small ({} bytes needed)", required),
+			ErrorKind::TrailingBytes { remaining } => {
+				write!(
+					fmt,
+					"{} trailing byte{}",
+					remaining,
+					if *remaining == 1 { "" } else { "s" }
+				)
+			}
 		}
 	}
 }
 
+/// An error from decoding or encoding: what kind of failure it was, an inner error with the
+/// details and, for decoding errors inside arrays, maps and tags, where the decoder was. `Display`
+/// shows the kind followed by the inner error's message, if it has one, and the context.
 #[derive(Debug)]
 pub struct CborError {
 	kind: ErrorKind,
 	error: Box<dyn error::Error + Send + Sync>,
+	// The inner error's message, formatted once
+	message: String,
+	// Whether the inner error is an error in its own right rather than just a message
+	has_source: bool,
+	context: Option<String>,
 }
 
 impl CborError {
-	fn new(kind: ErrorKind, error: Box<dyn error::Error + Send + Sync>) -> CborError { CborError { kind, error } }
+	// An error whose details are only a message
+	fn new(kind: ErrorKind, error: Box<dyn error::Error + Send + Sync>) -> CborError {
+		CborError {
+			kind,
+			message: error.to_string(),
+			error,
+			has_source: false,
+			context: None,
+		}
+	}
+
+	// An error caused by `source`, such as a `Utf8Error`
+	fn wrap<E: error::Error + Send + Sync + 'static>(kind: ErrorKind, source: E) -> CborError {
+		CborError {
+			has_source: true,
+			..CborError::new(kind, Box::new(source))
+		}
+	}
+
+	fn wrap_err<T, E: error::Error + Send + Sync + 'static>(kind: ErrorKind, source: E) -> Result<T> {
+		Err(CborError::wrap(kind, source))
+	}
+
+	// Places the error inside `outer`, which encloses any context it already has
+	fn within(mut self, outer: String) -> CborError {
+		self.context = Some(match self.context.take() {
+			Some(inner) => format!("{}, {}", outer, inner),
+			None => outer,
+		});
+		self
+	}
 
 	fn new_err<T>(kind: ErrorKind, error: Box<dyn error::Error + Send + Sync>) -> Result<T> {
 		Err(CborError::new(kind, error))
 	}
+
+	pub fn kind(&self) -> &ErrorKind { &self.kind }
+
+	/// The inner error's message, which is empty when there are no details beyond the kind.
+	pub fn message(&self) -> &str { &self.message }
+
+	/// The inner error, such as the `FromUtf8Error` behind `ErrorKind::InvalidUtf8` or a message.
+	pub fn get_ref(&self) -> &(dyn error::Error + Send + Sync + 'static) { &*self.error }
+
+	pub fn into_inner(self) -> Box<dyn error::Error + Send + Sync> { self.error }
+
+	/// Where in the input the decoder was when it failed, outermost first, as in
+	/// `array index 3, value for key "name", content of tag 1`. None for errors outside any array,
+	/// map or tag.
+	pub fn context(&self) -> Option<&str> { self.context.as_deref() }
+}
+
+impl From<std::io::Error> for CborError {
+	fn from(e: std::io::Error) -> CborError { CborError::wrap(ErrorKind::Io, e) }
 }
 
 impl fmt::Display for CborError {
-	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { self.kind.fmt(fmt) }
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		if self.message.is_empty() {
+			fmt::Display::fmt(&self.kind, fmt)?;
+		} else {
+			write!(fmt, "{}: {}", self.kind, self.message)?;
+		}
+		match &self.context {
+			Some(context) => write!(fmt, " (at {})", context),
+			None => Ok(()),
+		}
+	}
 }
 
 impl error::Error for CborError {
@@ -59,9 +312,47 @@ impl error::Error for CborError {
 		match self.kind {
 			ErrorKind::UnexpectedValue => "Unexpected value",
 			ErrorKind::InsufficientBytes => "Insufficient bytes",
+			ErrorKind::Io => "I/O error",
+			ErrorKind::UnknownTag(_) => "Unknown tag",
+			ErrorKind::TagNestingTooDeep => "Tags nested too deeply",
+			ErrorKind::TextEncoding => "Invalid base64 or base16 text",
+			ErrorKind::InvalidUtf8 => "Invalid UTF-8",
+			ErrorKind::ReservedAdditionalInfo => "Reserved additional information",
+			ErrorKind::DepthLimitExceeded => "Depth limit exceeded",
+			ErrorKind::TrailingBytes { .. } => "Trailing bytes",
+			ErrorKind::DuplicateKey => "Duplicate map key",
+			ErrorKind::LengthOverflow => "Length overflow",
+			ErrorKind::InvalidBreak => "Invalid break",
+			ErrorKind::NotCanonical => "Not canonical",
+			ErrorKind::ArrayTooLong { .. } => "Array too long",
+			ErrorKind::MapTooLong { .. } => "Map too long",
+			ErrorKind::StringTooLong { .. } => "String too long",
+			ErrorKind::BufferTooSmall { .. } => "Buffer too small",
+		}
+	}
+	/// The inner error when it is an error of its own, such as the `FromUtf8Error` behind
+	/// `ErrorKind::InvalidUtf8` or the `io::Error` behind `ErrorKind::Io`. None when the details
+	/// are only a message, which `Display` already shows.
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		if self.has_source {
+			Some(&*self.error)
+		} else {
+			None
 		}
 	}
-	fn cause(&self) -> Option<&dyn error::Error> { None }
+}
+
+// An item of the decoder's input: a byte borrowed from a slice, or one the iterator owns
+pub(crate) trait Byte: Copy {
+	fn get(self) -> u8;
+}
+
+impl Byte for u8 {
+	fn get(self) -> u8 { self }
+}
+
+impl Byte for &u8 {
+	fn get(self) -> u8 { *self }
 }
 
 fn read_type(b: u8) -> (u8, u8) {
@@ -70,8 +361,41 @@ fn read_type(b: u8) -> (u8, u8) {
 	(major, minor)
 }
 
-fn parse_unsigned_int<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<u64> {
-	if minor < 1 || minor > 27 {
+// Arrays and maps are nested more than `max` deep
+pub(crate) fn too_deep<T>(max: usize) -> Result<T> {
+	let msg = format!("more than {} nested arrays and maps", max);
+	CborError::new_err(ErrorKind::DepthLimitExceeded, msg.into())
+}
+
+// More than `max` tags are nested directly inside each other
+pub(crate) fn tags_too_deep<T>(max: usize) -> Result<T> {
+	let msg = format!("more than {} nested tags", max);
+	CborError::new_err(ErrorKind::TagNestingTooDeep, msg.into())
+}
+
+// Additional information 28 to 30 is reserved in every major type
+pub(crate) fn reserved<T>(type_byte: u8) -> Result<T> {
+	let msg = format!(
+		"initial byte 0x{:02X} has reserved additional information {}",
+		type_byte,
+		type_byte & 31
+	);
+	CborError::new_err(ErrorKind::ReservedAdditionalInfo, msg.into())
+}
+
+pub(crate) fn check_reserved(type_byte: u8) -> Result<()> {
+	match type_byte & 31 {
+		28..=30 => reserved(type_byte),
+		_ => Ok(()),
+	}
+}
+
+fn parse_unsigned_int<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I) -> Result<u64> {
+	if (28..=30).contains(&minor) {
+		let msg = format!("additional information {} is reserved", minor);
+		return CborError::new_err(ErrorKind::ReservedAdditionalInfo, msg.into());
+	}
+	if minor > 27 {
 		return CborError::new_err(ErrorKind::UnexpectedValue, "".into());
 	}
 
@@ -87,7 +411,7 @@ fn parse_unsigned_int<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -
 	for _ in 0..int_size {
 		let next = iter.next();
 		let byte_val: u8 = match next {
-			Some(x) => *x,
+			Some(x) => x.get(),
 			None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 		};
 		value <<= 8;
@@ -96,167 +420,295 @@ fn parse_unsigned_int<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -
 	Ok(value)
 }
 
-fn parse_negative_int<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<i64> {
+fn parse_negative_int<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I) -> Result<i64> {
 	let val: u64 = parse_unsigned_int(minor, iter)?;
-	Ok(-1 - (val as i64))
+	if val > i64::MAX as u64 {
+		return CborError::new_err(ErrorKind::LengthOverflow, "negative integer below i64::MIN".into());
+	}
+	Ok(-1 - val as i64)
 }
 
-fn parse_byte_string<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<Vec<u8>> {
+fn parse_byte_string<B: Byte, I: Iterator<Item = B>>(
+	major: u8,
+	minor: u8,
+	iter: &mut I,
+	opts: &DecodeOptions,
+) -> Result<Vec<u8>> {
 	let mut binary_val = Vec::<u8>::new();
+	parse_byte_string_into(major, minor, iter, &mut binary_val, opts)?;
+	Ok(binary_val)
+}
+
+// Converts a length read from a header to `T`, which is `usize` outside of tests, failing if it
+// does not fit as on 32-bit targets
+pub(crate) fn checked_len<T: TryFrom<u64>>(len: u64) -> Result<T> {
+	match T::try_from(len) {
+		Ok(x) => Ok(x),
+		Err(_) => {
+			let msg = format!("length {} does not fit in {} bits", len, core::mem::size_of::<T>() * 8);
+			CborError::new_err(ErrorKind::LengthOverflow, msg.into())
+		}
+	}
+}
+
+// Checks a declared length of `count` items of at least `min_size` bytes each against what is
+// left of the input, so that a short input with a huge length header fails at once. Returns how
+// many items may be reserved up front, which `opts` caps for inputs of unknown length.
+pub(crate) fn prealloc<B: Byte, I: Iterator<Item = B>>(
+	count: u64,
+	min_size: u64,
+	iter: &I,
+	opts: &DecodeOptions,
+) -> Result<usize> {
+	checked_len::<usize>(count)?;
+	if let Some(remaining) = iter.size_hint().1 {
+		if count.saturating_mul(min_size) > remaining as u64 {
+			let msg = format!("length {} needs more than the {} bytes remaining", count, remaining);
+			return CborError::new_err(ErrorKind::InsufficientBytes, msg.into());
+		}
+	}
+	Ok(count.min(opts.max_prealloc as u64) as usize)
+}
+
+// Appends the content of a byte (major 2) or text (major 3) string to binary_val. Unless `opts`
+// allows split characters, each chunk of an indefinite-length text string must be valid UTF-8 on
+// its own.
+fn parse_byte_string_into<B: Byte, I: Iterator<Item = B>>(
+	major: u8,
+	minor: u8,
+	iter: &mut I,
+	binary_val: &mut Vec<u8>,
+	opts: &DecodeOptions,
+) -> Result<()> {
+	let utf8_chunks = major == 3 && !opts.allow_split_utf8;
+	let base = binary_val.len();
 	if minor == 31 {
 		// indefinite length
 		loop {
 			let val: u8 = match iter.next() {
-				Some(x) => *x,
+				Some(x) => x.get(),
 				None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 			};
 			if val == 0xFF {
 				break;
 			}
-			let (_, minor) = read_type(val);
+			check_reserved(val)?;
+			// Chunks must be definite-length strings of the same type (RFC 8949 section 3.2.3)
+			let (chunk_major, minor) = read_type(val);
+			if chunk_major != major || minor == 31 {
+				let msg = format!(
+					"chunk with initial byte 0x{:02X} in an indefinite-length {} string",
+					val,
+					if major == 2 { "byte" } else { "text" }
+				);
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
 			let length: u64 = parse_unsigned_int(minor, iter)?;
+			check_string_len(((binary_val.len() - base) as u64).saturating_add(length), opts)?;
+			binary_val.reserve(prealloc(length, 1, iter, opts)?);
+			let start = binary_val.len();
 			for _ in 0..length {
 				let val: u8 = match iter.next() {
-					Some(x) => *x,
+					Some(x) => x.get(),
 					None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 				};
 				binary_val.push(val);
 			}
+			if utf8_chunks {
+				if let Err(e) = std::str::from_utf8(&binary_val[start..]) {
+					return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+				}
+			}
 		}
 	} else {
 		// definite length
 		let length: u64 = parse_unsigned_int(minor, iter)?;
+		check_string_len(length, opts)?;
+		binary_val.reserve(prealloc(length, 1, iter, opts)?);
 		for _ in 0..length {
 			let val: u8 = match iter.next() {
-				Some(x) => *x,
+				Some(x) => x.get(),
 				None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 			};
 			binary_val.push(val);
 		}
 	}
-	Ok(binary_val)
+	Ok(())
 }
 
-fn parse_utf8_string<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<String> {
-	let binary_val = parse_byte_string(minor, iter)?;
-	let string_val = match String::from_utf8(binary_val) {
-		Ok(s) => s,
-		Err(e) => panic!("error parsing string from vec: {}", e),
-	};
-	Ok(string_val)
+fn check_string_len(len: u64, opts: &DecodeOptions) -> Result<()> {
+	if len > opts.max_string_len as u64 {
+		let msg = format!(
+			"string of {} bytes, more than the limit of {}",
+			len, opts.max_string_len
+		);
+		return CborError::new_err(ErrorKind::StringTooLong { len }, msg.into());
+	}
+	Ok(())
 }
 
-pub fn parse_array<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<Vec<Value>> {
-	let mut arr = Vec::<Value>::new();
-
-	if minor == 31 {
-		// indefinite length
-		while let Some(item) = decode_next(iter)? {
-			arr.push(item);
-		}
+// Fails if an array (major 4) or map (major 5) of `len` elements or entries is over its limit
+fn check_container_len(major: u8, len: u64, opts: &DecodeOptions) -> Result<()> {
+	let max = if major == 4 {
+		opts.max_array_len
 	} else {
-		// definite length
-		let length: u64 = parse_unsigned_int(minor, iter)?;
-		for _ in 0..length {
-			let item: Value = decode_element(iter)?;
-			arr.push(item);
-		}
+		opts.max_map_len
+	};
+	if len <= max as u64 {
+		return Ok(());
 	}
-
-	Ok(arr)
+	let (kind, msg) = if major == 4 {
+		(
+			ErrorKind::ArrayTooLong { len },
+			format!("array of {} elements, more than the limit of {}", len, max),
+		)
+	} else {
+		(
+			ErrorKind::MapTooLong { len },
+			format!("map of {} entries, more than the limit of {}", len, max),
+		)
+	};
+	CborError::new_err(kind, msg.into())
 }
 
-fn parse_map<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<Vec<KeyVal>> {
-	let mut map = Vec::<KeyVal>::new(); //HashMap::<Value, Value>::new();
-
-	if minor == 31 {
-		// indefinite length
-		while let Some(key) = decode_next(iter)? {
-			let val: Value = decode_element(iter)?;
-			map.push(KeyVal { key, val })
-		}
-	} else {
-		// definite length
-		let length: u64 = parse_unsigned_int(minor, iter)?;
-		for _ in 0..length {
-			let key: Value = decode_element(iter)?;
-			let val: Value = decode_element(iter)?;
-			map.push(KeyVal { key, val });
-		}
+fn parse_utf8_string<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I, opts: &DecodeOptions) -> Result<String> {
+	let binary_val = parse_byte_string(3, minor, iter, opts)?;
+	match String::from_utf8(binary_val) {
+		Ok(s) => Ok(s),
+		Err(e) => CborError::wrap_err(ErrorKind::InvalidUtf8, e),
 	}
-
-	Ok(map)
 }
 
-pub fn parse_float<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<f64> {
-	if minor < 25 || minor > 27 {
-		panic!("parse_float_double: minor: {} outside acceptable bounds 1-27", minor);
+pub fn parse_array<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<Vec<Value>> {
+	match parse_value(iter, 0x80 | minor, &DecodeOptions::default(), 0)? {
+		Value::Array(arr) => Ok(arr),
+		_ => CborError::new_err(ErrorKind::UnexpectedValue, "Internal error: Not an array".into()),
 	}
+}
+
+/// Reads the argument of a major type 7 float whose initial byte had additional information
+/// `minor`, which must be 25, 26 or 27 for a half, single or double precision float. Other values
+/// are an error.
+pub fn parse_float<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<f64> { read_float(minor, iter) }
 
+fn read_float<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I) -> Result<f64> {
 	let num_bytes: usize = match minor {
 		27 => 8,
 		26 => 4,
 		25 => 2,
-		_ => return CborError::new_err(ErrorKind::UnexpectedValue, "Invalid minor".into()),
+		_ => {
+			let msg = format!("additional information {} is not a float width (25 to 27)", minor);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
 	};
 
 	let mut value: u64 = 0;
 	for _ in 0..num_bytes {
 		let byte_val: u8 = match iter.next() {
-			Some(x) => *x,
+			Some(x) => x.get(),
 			None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 		};
 		value <<= 8;
 		value |= u64::from(byte_val);
 	}
 
-	let f = f64::from_bits(value);
+	let f = match num_bytes {
+		2 => canonical::f16_to_f64(value as u16),
+		4 => f64::from(f32::from_bits(value as u32)),
+		_ => f64::from_bits(value),
+	};
 	Ok(f)
 }
 
-fn parse_simple<'a, I: Iterator<Item = &'a u8>>(minor: u8, iter: &mut I) -> Result<Simple> {
-	let ret = match minor {
-		0..=19 => Simple::Unassigned(minor),
-		20 => Simple::False,
-		21 => Simple::True,
-		22 => Simple::Null,
-		23 => Simple::Undefined,
+// Reads a simple value of major type 7. Minors 25 to 27 are floats and are not accepted here.
+fn parse_simple<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I) -> Result<Simple> {
+	let number = match minor {
+		0..=23 => minor,
 		24 => match iter.next() {
-			Some(x) => match x {
-				32..=255 => Simple::Unassigned(*x),
-				_ => CborError::new_err(ErrorKind::UnexpectedValue, "".into())?,
-			},
-			None => CborError::new_err(ErrorKind::InsufficientBytes, "".into())?,
+			Some(x) if x.get() >= 32 => x.get(),
+			Some(x) => {
+				// RFC 8949 section 3.3: values below 32 only have the one-byte form
+				let msg = format!("simple value {} in two-byte form, which needs 32 or more", x.get());
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
+			None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
 		},
-		_ => CborError::new_err(ErrorKind::InsufficientBytes, "".into())?,
+		25..=27 => {
+			let msg = format!("additional information {} is a float, not a simple value", minor);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+		28..=30 => return reserved(0xE0 | minor),
+		_ => return CborError::new_err(ErrorKind::InvalidBreak, "break code in place of a simple value".into()),
 	};
+	// Only 24 to 31 are refused, and neither form above can hold them
+	Simple::try_from(number).or_else(|()| reserved(0xE0 | minor))
+}
+
+fn parse_tag_number<B: Byte, I: Iterator<Item = B>>(minor: u8, iter: &mut I, opts: &DecodeOptions) -> Result<u64> {
+	let tag = parse_unsigned_int(minor, iter)?;
+	if opts.unknown_tags == TagPolicy::Error && !opts.is_known(tag) {
+		let msg = format!("tag {} is not known", tag);
+		return CborError::new_err(ErrorKind::UnknownTag(tag), msg.into());
+	}
+	Ok(tag)
+}
 
-	Ok(ret)
+// Applies the handler or unknown tag policy for `tag` to its decoded content
+fn wrap_tag(tag: u64, content: Value, opts: &DecodeOptions) -> Result<Value> {
+	if let Some(handler) = opts.handler(tag) {
+		// The handler's error is kept whole, with its source, and only gains context
+		return handler(tag, content).map_err(|e| e.within(format!("handler for tag {}", tag)));
+	}
+	if opts.is_known(tag) || opts.unknown_tags == TagPolicy::Keep {
+		return Ok(Value::Tag(tag, Box::new(content)));
+	}
+	Ok(content)
 }
 
-fn parse_value<'a, I: Iterator<Item = &'a u8>>(iter: &mut I, type_byte: u8) -> Result<Value> {
-	let (major, minor) = read_type(type_byte);
+// Reads a tag and any tags directly inside it in a loop, so that a long chain of tags is limited
+// by `max_tag_depth`. Returns the tags, outermost first, and the initial byte of their content.
+fn read_tag_chain<B: Byte, I: Iterator<Item = B>>(
+	minor: u8,
+	iter: &mut I,
+	opts: &DecodeOptions,
+) -> Result<(Vec<u64>, u8)> {
+	let mut chain = vec![parse_tag_number(minor, iter, opts)?];
+	loop {
+		if chain.len() > opts.max_tag_depth {
+			return tags_too_deep(opts.max_tag_depth);
+		}
+		let type_byte = match iter.next() {
+			Some(x) => x.get(),
+			None => {
+				let msg = format!("input ended inside a chain of {} tags", chain.len());
+				return CborError::new_err(ErrorKind::InsufficientBytes, msg.into());
+			}
+		};
+		check_reserved(type_byte)?;
+		match read_type(type_byte) {
+			(6, minor) => chain.push(parse_tag_number(minor, iter, opts)?),
+			_ => return Ok((chain, type_byte)),
+		}
+	}
+}
 
+// Decodes an item of major type 0, 1, 2, 3 or 7
+fn parse_scalar<B: Byte, I: Iterator<Item = B>>(
+	major: u8,
+	minor: u8,
+	iter: &mut I,
+	opts: &DecodeOptions,
+) -> Result<Value> {
 	let item: Value = match major {
 		0 => Value::Unsigned(parse_unsigned_int(minor, iter)?),
 		1 => Value::Negative(parse_negative_int(minor, iter)?),
-		2 => Value::ByteString(parse_byte_string(minor, iter)?),
-		3 => Value::Utf8String(parse_utf8_string(minor, iter)?),
-		4 => Value::Array(parse_array(minor, iter)?),
-		5 => Value::Map(parse_map(minor, iter)?),
-		6 => {
-			// ToDo: let tag = parse_unsigned_int(minor, iter);
-			let type_byte: u8 = match iter.next() {
-				Some(x) => *x,
-				None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
-			};
-			return parse_value(iter, type_byte);
-		}
+		2 => Value::ByteString(parse_byte_string(2, minor, iter, opts)?),
+		3 => Value::Utf8String(parse_utf8_string(minor, iter, opts)?),
 		7 => {
 			if minor <= 24 {
 				Value::Simple(parse_simple(minor, iter)?)
 			} else {
-				Value::Float(parse_float(minor, iter)?)
+				Value::Float(read_float(minor, iter)?)
 			}
 		}
 		_ => {
@@ -267,46 +719,366 @@ fn parse_value<'a, I: Iterator<Item = &'a u8>>(iter: &mut I, type_byte: u8) -> R
 	Ok(item)
 }
 
-fn decode_next<'a, I: Iterator<Item = &'a u8>>(iter: &mut I) -> Result<Option<Value>> {
-	let type_byte: u8 = match iter.next() {
-		Some(x) => *x,
-		None => return Err(CborError::new(ErrorKind::InsufficientBytes, "".into())),
-	};
+// An item that parse_value has started but not finished
+enum Frame {
+	// `remaining` is None for indefinite length
+	Array {
+		items: Vec<Value>,
+		remaining: Option<u64>,
+	},
+	// `key` holds a key whose value is still to come
+	Map {
+		entries: Vec<KeyVal>,
+		key: Option<Value>,
+		remaining: Option<u64>,
+	},
+	// Tags, outermost first, around the item being decoded
+	Tags(Vec<u64>),
+}
+
+impl Frame {
+	// The finished container, if every element has been read
+	fn complete(&mut self) -> Option<Value> {
+		match self {
+			Frame::Array {
+				items,
+				remaining: Some(0),
+			} => Some(Value::Array(std::mem::take(items))),
+			Frame::Map {
+				entries,
+				key: None,
+				remaining: Some(0),
+			} => Some(Value::Map(std::mem::take(entries).into())),
+			_ => None,
+		}
+	}
+
+	// The container with the elements read so far, if a break code in place of the next item
+	// ends it
+	fn close(&mut self) -> Option<Value> {
+		match self {
+			Frame::Array { items, remaining: None } => Some(Value::Array(std::mem::take(items))),
+			Frame::Map {
+				entries,
+				key: None,
+				remaining: None,
+			} => Some(Value::Map(std::mem::take(entries).into())),
+			_ => None,
+		}
+	}
 
-	if type_byte == 0xFF {
-		return Ok(None);
+	// Fails if the next item would take an indefinite-length container past its limit. Definite
+	// lengths are checked at the header.
+	fn check_len(&self, opts: &DecodeOptions) -> Result<()> {
+		match self {
+			Frame::Array { items, remaining: None } => check_container_len(4, items.len() as u64 + 1, opts),
+			Frame::Map {
+				entries,
+				key: None,
+				remaining: None,
+			} => check_container_len(5, entries.len() as u64 + 1, opts),
+			_ => Ok(()),
+		}
 	}
 
-	match parse_value(iter, type_byte) {
-		Ok(x) => Ok(Some(x)),
-		Err(e) => Err(e),
+	// Adds where the next item goes in this frame, outermost first
+	fn describe(&self, context: &mut Vec<String>) {
+		match self {
+			Frame::Array { items, .. } => context.push(format!("array index {}", items.len())),
+			Frame::Map { key: None, .. } => context.push("map key".to_string()),
+			Frame::Map { key: Some(k), .. } => context.push(format!("value for key {}", k.to_diagnostic())),
+			Frame::Tags(chain) => context.extend(chain.iter().map(|tag| format!("content of tag {}", tag))),
+		}
+	}
+
+	// Where a break code read in place of the next item was found, as it ends nothing here
+	fn break_context(&self) -> &'static str {
+		match self {
+			Frame::Array { .. } => "inside a definite-length array",
+			Frame::Map { key: Some(_), .. } => "in place of a map value",
+			Frame::Map { .. } => "inside a definite-length map",
+			Frame::Tags(_) => "in place of a tag's content",
+		}
 	}
 }
 
-fn decode_element<'a, I: Iterator<Item = &'a u8>>(iter: &mut I) -> Result<Value> {
-	let type_byte: u8 = match iter.next() {
-		Some(x) => *x,
-		None => return Err(CborError::new(ErrorKind::InsufficientBytes, "sfsdf".into())),
-	};
+// Applies the duplicate key policy to a finished map
+fn check_keys(item: Value, opts: &DecodeOptions) -> Result<Value> {
+	match item {
+		Value::Map(mut map) => {
+			map.dedup_keys(opts.duplicate_keys)?;
+			Ok(Value::Map(map))
+		}
+		_ => Ok(item),
+	}
+}
 
-	parse_value(iter, type_byte)
+// Decodes the item starting with `type_byte`, which is inside `depth` arrays and maps. Containers
+// and tags are kept on a heap-allocated stack rather than decoded by recursion, so the depth of
+// the input is limited by `max_depth` and memory, not by the size of the call stack. An error is
+// given the position of the failed item, which is read off the stack only then.
+fn parse_value<B: Byte, I: Iterator<Item = B>>(
+	iter: &mut I,
+	type_byte: u8,
+	opts: &DecodeOptions,
+	depth: usize,
+) -> Result<Value> {
+	let mut stack = Vec::<Frame>::new();
+	match parse_items(iter, type_byte, opts, depth, &mut stack) {
+		Ok(v) => Ok(v),
+		Err(e) if stack.is_empty() => Err(e),
+		Err(e) => {
+			let mut context = Vec::new();
+			for frame in &stack {
+				frame.describe(&mut context);
+			}
+			Err(e.within(context.join(", ")))
+		}
+	}
 }
 
-pub fn decode_iter<'a, I: Iterator<Item = &'a u8>>(iter: &mut I) -> Result<Value> {
+fn parse_items<B: Byte, I: Iterator<Item = B>>(
+	iter: &mut I,
+	type_byte: u8,
+	opts: &DecodeOptions,
+	depth: usize,
+	stack: &mut Vec<Frame>,
+) -> Result<Value> {
+	// Arrays and maps on the stack
+	let mut containers = 0;
+	let mut type_byte = type_byte;
+	loop {
+		if type_byte == 0xFF {
+			let context = match stack.last() {
+				Some(frame) => frame.break_context(),
+				None => "outside any indefinite-length item",
+			};
+			let msg = format!("break code {}", context);
+			return CborError::new_err(ErrorKind::InvalidBreak, msg.into());
+		}
+		check_reserved(type_byte)?;
+		let (major, minor) = read_type(type_byte);
+		let mut done = match major {
+			4 | 5 => {
+				if depth + containers >= opts.max_depth {
+					return too_deep(opts.max_depth);
+				}
+				let remaining = if minor == 31 {
+					None
+				} else {
+					let length = parse_unsigned_int(minor, iter)?;
+					check_container_len(major, length, opts)?;
+					Some(length)
+				};
+				let reserve = match remaining {
+					Some(length) => prealloc(length, if major == 4 { 1 } else { 2 }, iter, opts)?,
+					None => 0,
+				};
+				stack.push(if major == 4 {
+					Frame::Array {
+						items: Vec::with_capacity(reserve),
+						remaining,
+					}
+				} else {
+					Frame::Map {
+						entries: Vec::with_capacity(reserve),
+						key: None,
+						remaining,
+					}
+				});
+				containers += 1;
+				None
+			}
+			6 => {
+				let (chain, content) = read_tag_chain(minor, iter, opts)?;
+				stack.push(Frame::Tags(chain));
+				type_byte = content;
+				continue;
+			}
+			_ => Some(parse_scalar(major, minor, iter, opts)?),
+		};
+
+		// Hand finished items to the frames waiting for them until one needs another item
+		loop {
+			if let Some(item) = done.take() {
+				match stack.last_mut() {
+					None => return Ok(item),
+					Some(Frame::Tags(_)) => {
+						let mut content = item;
+						if let Some(Frame::Tags(chain)) = stack.pop() {
+							for tag in chain.into_iter().rev() {
+								content = wrap_tag(tag, content, opts)?;
+							}
+						}
+						done = Some(content);
+						continue;
+					}
+					Some(Frame::Array { items, remaining }) => {
+						items.push(item);
+						if let Some(n) = remaining {
+							*n -= 1;
+						}
+					}
+					Some(Frame::Map {
+						entries,
+						key,
+						remaining,
+					}) => match key.take() {
+						None => *key = Some(item),
+						Some(k) => {
+							entries.push(KeyVal { key: k, val: item });
+							if let Some(n) = remaining {
+								*n -= 1;
+							}
+						}
+					},
+				}
+			}
+			// Only an array or map is left on top, as tags are removed with their content
+			let top = stack.last_mut().expect("a container waits for the next item");
+			if let Some(item) = top.complete() {
+				stack.pop();
+				containers -= 1;
+				done = Some(check_keys(item, opts)?);
+				continue;
+			}
+			let b = match iter.next() {
+				Some(x) => x.get(),
+				None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
+			};
+			if b == 0xFF {
+				if let Some(item) = top.close() {
+					stack.pop();
+					containers -= 1;
+					done = Some(check_keys(item, opts)?);
+					continue;
+				}
+			} else {
+				top.check_len(opts)?;
+			}
+			type_byte = b;
+			break;
+		}
+	}
+}
+
+fn decode_first<B: Byte, I: Iterator<Item = B>>(iter: &mut I, opts: &DecodeOptions) -> Result<Value> {
 	let type_byte: u8 = match iter.next() {
-		Some(x) => *x,
+		Some(x) => x.get(),
 		None => return Err(CborError::new(ErrorKind::InsufficientBytes, "".into())),
 	};
 
-	parse_value(iter, type_byte)
+	// A leading self-described CBOR tag only marks the data as CBOR
+	Ok(parse_value(iter, type_byte, opts, 0)?.strip_self_described())
+}
+
+pub fn decode_iter<'a, I: Iterator<Item = &'a u8>>(iter: &mut I) -> Result<Value> {
+	trace::decode(iter, &DecodeOptions::default())
 }
 
 pub fn decode<'a, I: IntoIterator<Item = &'a u8>>(stream: I) -> Result<Value> {
+	decode_with(stream, &DecodeOptions::default())
+}
+
+/// Like `decode` with the given [`DecodeOptions`]. Under `DecodeOptions::require_canonical` the rest of
+/// `stream` is read into memory first, as the checks need the encoded bytes.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{DecodeOptions, DuplicateKeyPolicy, ErrorKind};
+/// let opts = DecodeOptions::new().max_depth(2).duplicate_keys(DuplicateKeyPolicy::LastWins);
+/// // {1: 2, 1: [3]}
+/// let v = cborg::decode_with(&[0xA2, 0x01, 0x02, 0x01, 0x81, 0x03], &opts).unwrap();
+/// assert_eq!("{1: [3]}", v.to_diagnostic());
+/// // {1: [[3]]}
+/// let err = cborg::decode_with(&[0xA1, 0x01, 0x81, 0x81, 0x03], &opts).unwrap_err();
+/// assert_eq!(&ErrorKind::DepthLimitExceeded, err.kind());
+/// ```
+pub fn decode_with<'a, I: IntoIterator<Item = &'a u8>>(stream: I, opts: &DecodeOptions) -> Result<Value> {
 	let mut iter = stream.into_iter();
-	decode_iter(&mut iter)
+	if opts.require_canonical {
+		let bytes: Vec<u8> = iter.copied().collect();
+		return decode_slice_with(&bytes, opts);
+	}
+	trace::decode(&mut iter, opts)
+}
+
+/// Like `decode` but reads bytes the iterator owns, as from `Vec::into_iter`, `VecDeque::drain`
+/// or `io::Read::bytes` once its errors are dealt with, so they need not be collected first.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::VecDeque;
+/// let mut ring: VecDeque<u8> = vec![0x82, 0x01, 0x61, b'a', 0x00].into();
+/// let v = cborg::decode_owned(ring.drain(..4)).unwrap();
+/// assert_eq!(r#"[1, "a"]"#, v.to_diagnostic());
+/// assert_eq!(1, ring.len());
+/// ```
+pub fn decode_owned<I: IntoIterator<Item = u8>>(stream: I) -> Result<Value> {
+	trace::decode(&mut stream.into_iter(), &DecodeOptions::default())
 }
 
-pub fn decode_slice(bytes: &[u8]) -> Result<Value> { decode_iter(&mut bytes.iter()) }
+pub fn decode_slice(bytes: &[u8]) -> Result<Value> { decode_slice_with(bytes, &DecodeOptions::default()) }
+
+/// Like `decode_slice` with the given [`DecodeOptions`].
+pub fn decode_slice_with(bytes: &[u8], opts: &DecodeOptions) -> Result<Value> {
+	if opts.require_canonical {
+		canonical::check_canonical(bytes, KeyOrder::Rfc8949, opts)?;
+	}
+	trace::decode(&mut bytes.iter(), opts)
+}
+
+/// Like `decode_slice` but also fails if a tag the crate knows has content of the wrong type,
+/// such as a tag 36 MIME message over a byte string. See [`Value::check_tags`].
+pub fn decode_strict(bytes: &[u8]) -> Result<Value> {
+	let v = decode_slice(bytes)?;
+	v.check_tags()?;
+	Ok(v)
+}
+
+/// Like `decode_slice` but fails with `ErrorKind::TrailingBytes` unless `bytes` holds exactly one
+/// item. `decode_slice` ignores whatever follows the first item.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(cborg::Value::Unsigned(1), cborg::decode_exact(&[0x01]).unwrap());
+/// let err = cborg::decode_exact(&[0x01, 0x02]).unwrap_err();
+/// assert_eq!(&cborg::ErrorKind::TrailingBytes { remaining: 1 }, err.kind());
+/// ```
+pub fn decode_exact(bytes: &[u8]) -> Result<Value> {
+	let (v, len) = decode_partial(bytes)?;
+	let remaining = bytes.len() - len;
+	if remaining > 0 {
+		let msg = format!("{} bytes follow the item, which ends at offset {}", remaining, len);
+		return CborError::new_err(ErrorKind::TrailingBytes { remaining }, msg.into());
+	}
+	Ok(v)
+}
+
+/// Decodes the first item in `bytes` and returns it with the number of bytes it took up, so that
+/// items placed back to back can be read one after another.
+///
+/// # Examples
+///
+/// ```
+/// let bytes = [0x9F, 0x01, 0xFF, 0x62, b'h', b'i'];
+/// let (first, len) = cborg::decode_partial(&bytes).unwrap();
+/// assert_eq!("[1]", first.to_diagnostic());
+/// let (second, _) = cborg::decode_partial(&bytes[len..]).unwrap();
+/// assert_eq!(cborg::Value::text("hi"), second);
+/// ```
+pub fn decode_partial(bytes: &[u8]) -> Result<(Value, usize)> {
+	let mut iter = bytes.iter();
+	let v = decode_iter(&mut iter)?;
+	Ok((v, bytes.len() - iter.len()))
+}
+
+/// Like `decode_to` but fails with `ErrorKind::TrailingBytes` unless `bytes` holds exactly one
+/// item.
+pub fn decode_to_exact<T: FromValue>(bytes: &[u8]) -> Result<Option<T>> { Ok(T::from_value(decode_exact(bytes)?)) }
 
 /// Decode a given IntoIterator into a given object.
 ///
@@ -333,9 +1105,29 @@ pub fn decode_to<'a, T, I>(stream: I) -> Result<Option<T>>
 where
 	T: FromValue,
 	I: IntoIterator<Item = &'a u8>, {
-	let mut iter = stream.into_iter();
-	let v: Value = decode_iter(&mut iter)?;
-	Ok(T::from_value(v))
+	decode_to_with(stream, &DecodeOptions::default())
+}
+
+/// Like `decode_to` with the given [`DecodeOptions`].
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use cborg::{DecodeOptions, DuplicateKeyPolicy, TagPolicy};
+/// let opts = DecodeOptions::new()
+///     .unknown_tags(TagPolicy::Strip)
+///     .duplicate_keys(DuplicateKeyPolicy::FirstWins);
+/// // {"a": 100(1), "a": 2}
+/// let bytes = [0xA2, 0x61, 0x61, 0xD8, 0x64, 0x01, 0x61, 0x61, 0x02];
+/// let map: HashMap<String, u32> = cborg::decode_to_with(&bytes, &opts).unwrap().unwrap();
+/// assert_eq!(1, map["a"]);
+/// ```
+pub fn decode_to_with<'a, T, I>(stream: I, opts: &DecodeOptions) -> Result<Option<T>>
+where
+	T: FromValue,
+	I: IntoIterator<Item = &'a u8>, {
+	Ok(T::from_value(decode_with(stream, opts)?))
 }
 
 /// Encode a given object into CBOR.
@@ -359,6 +1151,28 @@ where
 	Value::from(v).encode()
 }
 
+/// Like `encode` but starts the output with the self-described CBOR tag (55799), so that tools
+/// can recognise it as CBOR. The decode functions skip the tag.
+///
+/// # Examples
+///
+/// ```
+/// let bytes = cborg::encode_self_described(7u32);
+/// assert_eq!(vec![0xD9, 0xD9, 0xF7, 0x07], bytes);
+/// assert_eq!(Some(7u32), cborg::decode_to(&bytes).unwrap());
+/// ```
+pub fn encode_self_described<V>(v: V) -> Vec<u8>
+where
+	Value: From<V>, {
+	let mut bytes = value::SELF_DESCRIBED_PREFIX.to_vec();
+	bytes.append(&mut Value::from(v).encode());
+	bytes
+}
+
+/// Like `encode` but moves owned strings, byte vectors and containers into the encoder's `Value`
+/// rather than cloning them. See [`IntoValue`].
+pub fn encode_owned<V: IntoValue>(v: V) -> Vec<u8> { v.into_value().encode() }
+
 /// Like `encode` but takes a reference.
 pub fn encode_ref<V>(v: &V) -> Vec<u8>
 where
@@ -366,5 +1180,50 @@ where
 	v.to_value().encode()
 }
 
+/// Like `encode_ref` but appends the bytes to `out`, as [`Value::encode_into`] does.
+pub fn encode_into<V>(v: &V, out: &mut Vec<u8>)
+where
+	V: ToValue + ?Sized, {
+	v.to_value().encode_into(out)
+}
+
+/// Like `encode_ref` but writes the bytes to `w` as they are produced, as
+/// [`Value::encode_to`] does.
+///
+/// # Examples
+///
+/// ```
+/// let mut out = Vec::new();
+/// cborg::encode_to_writer(&vec![1u32, 2, 3], &mut out).unwrap();
+/// assert_eq!(vec![0x83, 0x01, 0x02, 0x03], out);
+/// ```
+pub fn encode_to_writer<V, W>(v: &V, w: &mut W) -> std::io::Result<()>
+where
+	V: ToValue + ?Sized,
+	W: std::io::Write, {
+	v.to_value().encode_to(w)
+}
+
 /// Like `encode` but takes a dynamic trait object.
 pub fn encode_dyn(v: &dyn ToValue) -> Vec<u8> { v.to_value().encode() }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn checked_len_test() {
+		// u32 stands in for usize on 32-bit targets
+		assert_eq!(0, checked_len::<u32>(0).unwrap());
+		assert_eq!(u32::MAX, checked_len::<u32>(0xFFFF_FFFF).unwrap());
+		for &len in &[0x1_0000_0000, 0x1_0000_0005, u64::MAX] {
+			let err = checked_len::<u32>(len).unwrap_err();
+			assert_eq!(&ErrorKind::LengthOverflow, err.kind());
+			assert_eq!(format!("length {} does not fit in 32 bits", len), err.message());
+		}
+		assert_eq!(0xFFFF, checked_len::<u16>(0xFFFF).unwrap());
+		assert!(checked_len::<u16>(0x1_0000).is_err());
+		#[cfg(target_pointer_width = "64")]
+		assert_eq!(usize::MAX, checked_len::<usize>(u64::MAX).unwrap());
+	}
+}
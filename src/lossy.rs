@@ -0,0 +1,361 @@
+// Best-effort decoding that keeps whatever can be salvaged from damaged input.
+
+use crate::header::read_header;
+use crate::header::Header;
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::KeyVal;
+use crate::Simple;
+use crate::Value;
+
+/// A problem found by [`decode_lossy`] and the place it was found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeIssue {
+	/// Path of the item that could not be decoded. For a map key this is the path of the map.
+	pub path: Path,
+	/// Offset of the item's initial byte in the input
+	pub offset: usize,
+	pub kind: ErrorKind,
+	pub message: String,
+}
+
+struct Lossy<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	issues: Vec<DecodeIssue>,
+	// Set once an error leaves the position of the next item unknown; nothing more is read
+	stopped: bool,
+	// Arrays and maps enclosing the current item
+	depth: usize,
+	// Tags directly around the current item
+	tags: usize,
+	// For decode_lenient: items that fail are left out rather than replaced, truncated strings
+	// keep what was read, and problems are kept as errors instead of issues
+	lenient: bool,
+	errors: Vec<CborError>,
+}
+
+fn marker() -> Value { Value::Simple(Simple::Undefined) }
+
+// The context the decoder gives an error in the item at `path`
+fn context(path: &Path) -> String {
+	let steps: Vec<String> = path
+		.segments()
+		.iter()
+		.map(|segment| match segment {
+			PathSegment::Index(i) => format!("array index {}", i),
+			PathSegment::Key(k) => format!("value for key {}", k.to_diagnostic()),
+		})
+		.collect();
+	steps.join(", ")
+}
+
+impl<'a> Lossy<'a> {
+	fn new(bytes: &'a [u8], lenient: bool) -> Lossy<'a> {
+		Lossy {
+			bytes,
+			pos: 0,
+			issues: Vec::new(),
+			stopped: false,
+			depth: 0,
+			tags: 0,
+			lenient,
+			errors: Vec::new(),
+		}
+	}
+
+	fn record(&mut self, path: &Path, offset: usize, e: CborError) {
+		if self.lenient {
+			self
+				.errors
+				.push(if path.is_root() { e } else { e.within(context(path)) });
+			return;
+		}
+		let message = e.message().to_string();
+		self.issues.push(DecodeIssue {
+			path: path.clone(),
+			offset,
+			kind: e.kind,
+			message,
+		});
+	}
+
+	// What takes the place of an item that could not be decoded
+	fn failed(&self) -> Option<Value> {
+		if self.lenient {
+			None
+		} else {
+			Some(marker())
+		}
+	}
+
+	// An error inside an item whose extent is known: the item is replaced and decoding continues
+	fn skip(&mut self, path: &Path, offset: usize, e: CborError) -> Option<Value> {
+		self.record(path, offset, e);
+		self.failed()
+	}
+
+	// An error after which the next item cannot be found
+	fn stop(&mut self, path: &Path, offset: usize, e: CborError) -> Option<Value> {
+		self.record(path, offset, e);
+		self.stopped = true;
+		self.failed()
+	}
+
+	// None for an item left out by decode_lenient
+	fn item(&mut self, path: &mut Path) -> Option<Value> {
+		let start = self.pos;
+		let h = match read_header(self.bytes, start) {
+			Ok(h) => h,
+			Err(e) => return self.stop(path, start, e),
+		};
+		if h.is_break() {
+			let e = CborError::new(ErrorKind::InvalidBreak, "unexpected break".into());
+			return self.stop(path, start, e);
+		}
+		self.pos += h.len;
+		if h.major == 6 {
+			let max_tags = crate::DecodeOptions::default().max_tag_depth;
+			if self.tags >= max_tags {
+				return self.stop(path, start, crate::tags_too_deep::<()>(max_tags).unwrap_err());
+			}
+			self.tags += 1;
+		} else {
+			self.tags = 0;
+		}
+
+		match h.major {
+			0 => Some(Value::Unsigned(h.arg)),
+			1 => {
+				if h.arg > i64::MAX as u64 {
+					let e = CborError::new(ErrorKind::LengthOverflow, "negative integer below i64::MIN".into());
+					return self.skip(path, start, e);
+				}
+				Some(Value::Negative(-1 - h.arg as i64))
+			}
+			2 | 3 => self.string(&h, start, path),
+			4 | 5 => {
+				let max_depth = crate::DecodeOptions::default().max_depth;
+				if self.depth >= max_depth {
+					return self.stop(path, start, crate::too_deep::<()>(max_depth).unwrap_err());
+				}
+				self.depth += 1;
+				let v = if h.major == 4 {
+					self.array(&h, path)
+				} else {
+					self.map(&h, path)
+				};
+				self.depth -= 1;
+				Some(v)
+			}
+			6 => self.item(path).map(|v| Value::Tag(h.arg, Box::new(v))),
+			_ => {
+				let mut arg = self.bytes[start + 1..start + h.len].iter();
+				let result = if h.minor <= 24 {
+					crate::parse_simple(h.minor, &mut arg).map(Value::Simple)
+				} else {
+					crate::parse_float(h.minor, &mut arg).map(Value::Float)
+				};
+				match result {
+					Ok(v) => Some(v),
+					Err(e) => self.skip(path, start, e),
+				}
+			}
+		}
+	}
+
+	fn string(&mut self, h: &Header, start: usize, path: &Path) -> Option<Value> {
+		let mut content = Vec::<u8>::new();
+		// The first text chunk that is not valid UTF-8 on its own. The string is still read to its
+		// end so that decoding can carry on after it.
+		let mut bad_chunk = None;
+		if h.is_indefinite() {
+			loop {
+				let chunk = match read_header(self.bytes, self.pos) {
+					Ok(x) => x,
+					Err(e) => return self.stop(path, start, e),
+				};
+				self.pos += chunk.len;
+				if chunk.is_break() {
+					break;
+				}
+				if chunk.major != h.major || chunk.is_indefinite() {
+					let e = CborError::new(
+						ErrorKind::UnexpectedValue,
+						"invalid chunk in indefinite-length string".into(),
+					);
+					return self.stop(path, start, e);
+				}
+				let chunk_start = content.len();
+				if !self.take(chunk.arg, &mut content) {
+					return self.truncated(h.major, content, path, start);
+				}
+				if h.major == 3 && bad_chunk.is_none() {
+					bad_chunk = std::str::from_utf8(&content[chunk_start..]).err();
+				}
+			}
+		} else if !self.take(h.arg, &mut content) {
+			return self.truncated(h.major, content, path, start);
+		}
+
+		if h.major == 2 {
+			return Some(Value::ByteString(content));
+		}
+		if let Some(e) = bad_chunk {
+			return self.skip(path, start, CborError::wrap(ErrorKind::InvalidUtf8, e));
+		}
+		match String::from_utf8(content) {
+			Ok(s) => Some(Value::Utf8String(s)),
+			Err(e) => self.skip(path, start, CborError::wrap(ErrorKind::InvalidUtf8, e)),
+		}
+	}
+
+	// A string that runs past the end of the input. decode_lenient keeps the bytes that were there,
+	// up to the last whole character of a text string.
+	fn truncated(&mut self, major: u8, mut content: Vec<u8>, path: &Path, start: usize) -> Option<Value> {
+		let lenient = self.lenient;
+		let failed = self.stop(path, start, CborError::new(ErrorKind::InsufficientBytes, "".into()));
+		if !lenient {
+			return failed;
+		}
+		if major == 2 {
+			return Some(Value::ByteString(content));
+		}
+		if let Err(e) = std::str::from_utf8(&content) {
+			content.truncate(e.valid_up_to());
+		}
+		Some(Value::Utf8String(String::from_utf8(content).unwrap_or_default()))
+	}
+
+	// Appends `len` bytes to `out`, or under decode_lenient whatever is left if there are fewer
+	fn take(&mut self, len: u64, out: &mut Vec<u8>) -> bool {
+		let remaining = (self.bytes.len() - self.pos) as u64;
+		if len > remaining {
+			if self.lenient {
+				out.extend_from_slice(&self.bytes[self.pos..]);
+			}
+			self.pos = self.bytes.len();
+			return false;
+		}
+		let end = self.pos + len as usize;
+		out.extend_from_slice(&self.bytes[self.pos..end]);
+		self.pos = end;
+		true
+	}
+
+	// Consumes the break code if it is next
+	fn at_break(&mut self) -> bool {
+		if self.bytes.get(self.pos) == Some(&0xFF) {
+			self.pos += 1;
+			return true;
+		}
+		false
+	}
+
+	fn array(&mut self, h: &Header, path: &mut Path) -> Value {
+		let mut arr = Vec::<Value>::new();
+		let mut i = 0;
+		while !self.stopped
+			&& (if h.is_indefinite() {
+				!self.at_break()
+			} else {
+				(i as u64) < h.arg
+			}) {
+			path.push(PathSegment::Index(i));
+			if let Some(v) = self.item(path) {
+				arr.push(v);
+			}
+			path.pop();
+			i += 1;
+		}
+		Value::Array(arr)
+	}
+
+	fn map(&mut self, h: &Header, path: &mut Path) -> Value {
+		let mut map = Vec::<KeyVal>::new();
+		let mut i = 0;
+		while !self.stopped
+			&& (if h.is_indefinite() {
+				!self.at_break()
+			} else {
+				(i as u64) < h.arg
+			}) {
+			let key = self.item(path);
+			if self.stopped {
+				break;
+			}
+			// The value of a key that was left out is still read, to reach the next entry
+			path.push(PathSegment::Key(key.clone().unwrap_or_else(marker)));
+			let val = self.item(path);
+			path.pop();
+			if let (Some(key), Some(val)) = (key, val) {
+				map.push(KeyVal { key, val });
+			}
+			i += 1;
+		}
+		Value::Map(map.into())
+	}
+}
+
+/// Decodes the first item in `bytes`, salvaging as much as possible from malformed or truncated
+/// input instead of failing.
+///
+/// Items that cannot be decoded are replaced by `Value::Simple(Simple::Undefined)` and reported as
+/// issues. When the damage is contained in one item, such as a text string that is not valid
+/// UTF-8, decoding carries on with its siblings. When it is not, such as truncated input or an
+/// unreadable header, the containers decoded so far are closed and returned as they are.
+///
+/// # Examples
+///
+/// ```
+/// // [1, 2, 3] cut short after the second element
+/// let (value, issues) = cborg::decode_lossy(&[0x83, 0x01, 0x02]);
+/// assert_eq!("[1, 2, undefined]", value.to_diagnostic());
+/// assert_eq!("$[2]", issues[0].path.to_string());
+/// ```
+pub fn decode_lossy(bytes: &[u8]) -> (Value, Vec<DecodeIssue>) {
+	let mut lossy = Lossy::new(bytes, false);
+	let value = lossy
+		.item(&mut Path::new())
+		.unwrap_or_else(marker)
+		.strip_self_described();
+	(value, lossy.issues)
+}
+
+/// Like [`decode_lossy`] but decodes every item of a CBOR sequence. Items after an error that
+/// stops decoding are lost.
+pub fn decode_lossy_seq(bytes: &[u8]) -> (Vec<Value>, Vec<DecodeIssue>) {
+	let mut lossy = Lossy::new(bytes, false);
+	let mut values = Vec::new();
+	while !lossy.stopped && lossy.pos < bytes.len() {
+		values.push(lossy.item(&mut Path::new()).unwrap_or_else(marker));
+	}
+	(values, lossy.issues)
+}
+
+/// Decodes the first item in `bytes`, keeping the readable part of damaged input.
+///
+/// Unlike [`decode_lossy`], items that cannot be decoded are left out of their array or map rather
+/// than replaced, and a string cut short by the end of the input keeps the bytes that were there,
+/// up to the last whole character of a text string. When an item fails but its extent is known,
+/// decoding carries on with the next item in its container. When the next item cannot be found,
+/// as after an unreadable header, every open container is closed with what it holds and the parse
+/// ends. The value is None if the first item itself could not be decoded. Each error has its
+/// [`context`](CborError::context) set.
+///
+/// # Examples
+///
+/// ```
+/// // {"a": 1, "b": "hello"} cut off inside "hello"
+/// let bytes = [0xA2, 0x61, b'a', 0x01, 0x61, b'b', 0x65, b'h', b'e', b'l'];
+/// let (value, errors) = cborg::decode_lenient(&bytes);
+/// assert_eq!(r#"{"a": 1, "b": "hel"}"#, value.unwrap().to_diagnostic());
+/// assert_eq!(Some(r#"value for key "b""#), errors[0].context());
+/// ```
+pub fn decode_lenient(bytes: &[u8]) -> (Option<Value>, Vec<CborError>) {
+	let mut lenient = Lossy::new(bytes, true);
+	let value = lenient.item(&mut Path::new()).map(Value::strip_self_described);
+	(value, lenient.errors)
+}
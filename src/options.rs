@@ -0,0 +1,223 @@
+use crate::tags;
+use crate::Result;
+use crate::Value;
+
+/// Turns the content of a tag into the value the decoder returns in place of the tagged item.
+/// Called with the tag number and the decoded content.
+pub type TagHandler = fn(u64, Value) -> Result<Value>;
+
+/// What the decoder does with a tag that is neither in the [`tags`] module nor registered with
+/// [`DecodeOptions::known_tag`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TagPolicy {
+	/// Keep the `Value::Tag` wrapper around the content
+	#[default]
+	Keep,
+	/// Drop the tag and keep only its content
+	Strip,
+	/// Fail with `ErrorKind::UnknownTag`
+	Error,
+}
+
+/// What the decoder does with a map that has the same key more than once. `Map` lookups see the
+/// first entry for a key, while converting to a `HashMap` or `BTreeMap` keeps the last, so leaving
+/// duplicates in place lets different readers of one message disagree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+	/// Keep every entry
+	#[default]
+	Allow,
+	/// Keep the first entry for each key
+	FirstWins,
+	/// Keep the last entry for each key
+	LastWins,
+	/// Fail with `ErrorKind::DuplicateKey`
+	Error,
+}
+
+/// Settings for [`decode_with`](crate::decode_with), [`decode_slice_with`](crate::decode_slice_with)
+/// and [`decode_to_with`](crate::decode_to_with). The default decodes like
+/// [`decode`](crate::decode) and the other functions without options.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{DecodeOptions, TagPolicy, Value};
+/// let bytes = [0x82, 0xD8, 0x64, 0x01, 0xC1, 0x02]; // [100(1), 1(2)]
+/// let opts = DecodeOptions::new().unknown_tags(TagPolicy::Strip);
+/// assert_eq!("[1, 1(2)]", cborg::decode_slice_with(&bytes, &opts).unwrap().to_diagnostic());
+/// ```
+#[derive(Clone, Debug)]
+pub struct DecodeOptions {
+	pub(crate) unknown_tags: TagPolicy,
+	pub(crate) known_tags: Vec<u64>,
+	pub(crate) handlers: Vec<(u64, TagHandler)>,
+	pub(crate) max_tag_depth: usize,
+	pub(crate) max_depth: usize,
+	pub(crate) allow_split_utf8: bool,
+	pub(crate) max_prealloc: usize,
+	pub(crate) duplicate_keys: DuplicateKeyPolicy,
+	pub(crate) require_canonical: bool,
+	pub(crate) max_array_len: usize,
+	pub(crate) max_map_len: usize,
+	pub(crate) max_string_len: usize,
+}
+
+impl Default for DecodeOptions {
+	fn default() -> DecodeOptions {
+		DecodeOptions {
+			unknown_tags: TagPolicy::Keep,
+			known_tags: Vec::new(),
+			handlers: Vec::new(),
+			max_tag_depth: 16,
+			max_depth: 128,
+			allow_split_utf8: false,
+			max_prealloc: 4096,
+			duplicate_keys: DuplicateKeyPolicy::Allow,
+			require_canonical: false,
+			max_array_len: usize::MAX,
+			max_map_len: usize::MAX,
+			max_string_len: usize::MAX,
+		}
+	}
+}
+
+impl DecodeOptions {
+	pub fn new() -> DecodeOptions { DecodeOptions::default() }
+
+	/// Sets what happens to tags the decoder does not know, at any depth. Defaults to `Keep`.
+	pub fn unknown_tags(mut self, policy: TagPolicy) -> DecodeOptions {
+		self.unknown_tags = policy;
+		self
+	}
+
+	/// Treats `tag` as known, so that it is kept whatever the unknown tag policy.
+	pub fn known_tag(mut self, tag: u64) -> DecodeOptions {
+		self.known_tags.push(tag);
+		self
+	}
+
+	/// Sets how many tags may be nested directly inside each other, as in `1(2(3(4)))`, before
+	/// decoding fails with `ErrorKind::TagNestingTooDeep`. Defaults to 16.
+	pub fn max_tag_depth(mut self, depth: usize) -> DecodeOptions {
+		self.max_tag_depth = depth;
+		self
+	}
+
+	/// Sets what happens to maps with repeated keys, at any depth. Defaults to `Allow`.
+	pub fn duplicate_keys(mut self, policy: DuplicateKeyPolicy) -> DecodeOptions {
+		self.duplicate_keys = policy;
+		self
+	}
+
+	/// Sets how many arrays and maps may be nested inside each other before decoding fails with
+	/// `ErrorKind::DepthLimitExceeded`. Decoding itself does not recurse, but dropping, comparing or
+	/// printing a `Value` does, so this keeps hostile input from building values that exhaust the
+	/// stack later. Defaults to 128.
+	pub fn max_depth(mut self, depth: usize) -> DecodeOptions {
+		self.max_depth = depth;
+		self
+	}
+
+	/// Accepts indefinite-length text strings whose chunks split a multi-byte character, checking
+	/// only the joined text. RFC 8949 requires each chunk to be valid UTF-8 on its own, which is
+	/// the default.
+	pub fn allow_split_utf8(mut self, allow: bool) -> DecodeOptions {
+		self.allow_split_utf8 = allow;
+		self
+	}
+
+	/// Sets how many elements or bytes a length header may reserve before they are read. Inputs
+	/// whose length is known fail early when a header declares more than is left; this cap is what
+	/// bounds memory for the rest. Defaults to 4096.
+	pub fn max_prealloc(mut self, n: usize) -> DecodeOptions {
+		self.max_prealloc = n;
+		self
+	}
+
+	/// Sets how many elements any one array may have before decoding fails with
+	/// `ErrorKind::ArrayTooLong`. Definite-length arrays fail at their header, indefinite-length ones
+	/// when an element past the limit starts. Defaults to no limit.
+	pub fn max_array_len(mut self, len: usize) -> DecodeOptions {
+		self.max_array_len = len;
+		self
+	}
+
+	/// Sets how many key-value pairs any one map may have before decoding fails with
+	/// `ErrorKind::MapTooLong`, in the same way as `max_array_len`. Defaults to no limit.
+	pub fn max_map_len(mut self, len: usize) -> DecodeOptions {
+		self.max_map_len = len;
+		self
+	}
+
+	/// Sets how many bytes the content of any one byte or text string may have before decoding
+	/// fails with `ErrorKind::StringTooLong`. The chunks of an indefinite-length string count
+	/// together, and fail as soon as the one that goes past the limit is read. Defaults to no limit.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{DecodeOptions, ErrorKind};
+	/// let opts = DecodeOptions::new().max_string_len(4);
+	/// // ["abcd", "abcde"]
+	/// let bytes = [0x82, 0x64, b'a', b'b', b'c', b'd', 0x65, b'a', b'b', b'c', b'd', b'e'];
+	/// let err = cborg::decode_slice_with(&bytes, &opts).unwrap_err();
+	/// assert_eq!(&ErrorKind::StringTooLong { len: 5 }, err.kind());
+	/// ```
+	pub fn max_string_len(mut self, len: usize) -> DecodeOptions {
+		self.max_string_len = len;
+		self
+	}
+
+	/// Rejects input that is not in the deterministic form of RFC 8949 section 4.2.1: arguments
+	/// and lengths not in their shortest form, indefinite-length items, floats that fit a shorter
+	/// width, and map keys that repeat or are not in bytewise order. The error has kind
+	/// `ErrorKind::NotCanonical` and names the violation and its byte offset. Only the item decoded
+	/// is checked, not bytes after it. Tag contents are not checked; [`decode_strict`] and
+	/// [`Value::check_tags`] do that. Defaults to `false`.
+	///
+	/// [`decode_strict`]: crate::decode_strict
+	/// [`Value::check_tags`]: crate::Value::check_tags
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{DecodeOptions, ErrorKind};
+	/// let bytes = [0x18, 0x05]; // 5 with a one-byte argument
+	/// assert!(cborg::decode_slice_with(&bytes, &DecodeOptions::new()).is_ok());
+	/// let err = cborg::decode_slice_with(&bytes, &DecodeOptions::new().require_canonical(true)).unwrap_err();
+	/// assert_eq!(&ErrorKind::NotCanonical, err.kind());
+	/// ```
+	pub fn require_canonical(mut self, require: bool) -> DecodeOptions {
+		self.require_canonical = require;
+		self
+	}
+
+	/// Registers `handler` for `tag`, replacing any earlier one. The content is decoded first, so
+	/// handlers for tags nested inside it have already run. An error from the handler fails the
+	/// decode unchanged apart from its [`context`](crate::CborError::context), which names the tag.
+	/// Handled tags count as known.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{DecodeOptions, Value};
+	/// fn celsius(_tag: u64, v: Value) -> cborg::Result<Value> { Ok(Value::map(vec![("celsius", v)])) }
+	/// let opts = DecodeOptions::new().tag_handler(1001, celsius);
+	/// let v = cborg::decode_slice_with(&[0xD9, 0x03, 0xE9, 0x15], &opts).unwrap();
+	/// assert_eq!(r#"{"celsius": 21}"#, v.to_diagnostic());
+	/// ```
+	pub fn tag_handler(mut self, tag: u64, handler: TagHandler) -> DecodeOptions {
+		self.handlers.retain(|(t, _)| *t != tag);
+		self.handlers.push((tag, handler));
+		self
+	}
+
+	pub(crate) fn handler(&self, tag: u64) -> Option<TagHandler> {
+		self.handlers.iter().find(|(t, _)| *t == tag).map(|(_, h)| *h)
+	}
+
+	pub(crate) fn is_known(&self, tag: u64) -> bool {
+		tags::is_known(tag) || self.known_tags.contains(&tag) || self.handler(tag).is_some()
+	}
+}
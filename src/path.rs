@@ -0,0 +1,68 @@
+use core::fmt;
+
+use crate::Value;
+
+/// One step from a container to one of its children.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+	/// Position in an array
+	Index(usize),
+	/// Key of a map entry
+	Key(Value),
+}
+
+/// The location of a value inside a document, as a list of steps from the root.
+///
+/// Paths display as `$` followed by `[n]` for array indices and `.key` for map keys. Text keys that
+/// look like identifiers are written bare; all other keys are written in diagnostic notation, so
+/// `$.555.name[2]` is the third element of the array under "name" in the map under key 555.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path(pub Vec<PathSegment>);
+
+impl Path {
+	pub fn new() -> Path { Path(Vec::new()) }
+
+	pub fn segments(&self) -> &[PathSegment] { &self.0 }
+
+	pub fn is_root(&self) -> bool { self.0.is_empty() }
+
+	pub fn push(&mut self, segment: PathSegment) { self.0.push(segment) }
+
+	pub fn pop(&mut self) -> Option<PathSegment> { self.0.pop() }
+
+	/// Returns a copy of this path extended by one segment.
+	pub fn join(&self, segment: PathSegment) -> Path {
+		let mut path = self.clone();
+		path.push(segment);
+		path
+	}
+}
+
+fn is_identifier(s: &str) -> bool {
+	let mut chars = s.chars();
+	match chars.next() {
+		Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+		_ => return false,
+	}
+	chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl fmt::Display for PathSegment {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PathSegment::Index(i) => write!(f, "[{}]", i),
+			PathSegment::Key(Value::Utf8String(s)) if is_identifier(s) => write!(f, ".{}", s),
+			PathSegment::Key(k) => write!(f, ".{}", k.to_diagnostic()),
+		}
+	}
+}
+
+impl fmt::Display for Path {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("$")?;
+		for segment in &self.0 {
+			segment.fmt(f)?;
+		}
+		Ok(())
+	}
+}
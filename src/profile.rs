@@ -0,0 +1,201 @@
+use crate::header::read_header;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+/// Statistics gathered by walking encoded CBOR without decoding it into Values.
+///
+/// Every byte of the input is counted either as payload (the contents of byte and text strings)
+/// or as header overhead (initial bytes, arguments, break codes, and the bodies of integers,
+/// floats and simple values), so `header_bytes + payload_bytes.iter().sum()` is the input length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+	/// Number of data items of each major type. String chunks are not counted separately.
+	pub items: [u64; 8],
+	/// String content bytes, indexed by major type. Only 2 and 3 are ever non-zero.
+	pub payload_bytes: [u64; 8],
+	pub header_bytes: u64,
+	/// Deepest nesting of arrays and maps. A document with no containers has depth 0.
+	pub max_depth: usize,
+	pub max_array_len: u64,
+	/// Largest number of entries (key/value pairs) in a single map.
+	pub max_map_len: u64,
+	/// Number of indefinite-length strings, arrays and maps.
+	pub indefinite_items: u64,
+}
+
+impl Profile {
+	/// Adds the counts from `other` into `self`, keeping the larger of each maximum.
+	pub fn merge(&mut self, other: &Profile) {
+		for i in 0..8 {
+			self.items[i] += other.items[i];
+			self.payload_bytes[i] += other.payload_bytes[i];
+		}
+		self.header_bytes += other.header_bytes;
+		self.max_depth = self.max_depth.max(other.max_depth);
+		self.max_array_len = self.max_array_len.max(other.max_array_len);
+		self.max_map_len = self.max_map_len.max(other.max_map_len);
+		self.indefinite_items += other.indefinite_items;
+	}
+
+	pub fn total_items(&self) -> u64 { self.items.iter().sum() }
+}
+
+struct Frame {
+	// Items still expected, or None for indefinite length
+	remaining: Option<u64>,
+	is_map: bool,
+	count: u64,
+}
+
+impl Frame {
+	fn close(&self, profile: &mut Profile) {
+		if self.is_map {
+			profile.max_map_len = profile.max_map_len.max(self.count / 2);
+		} else {
+			profile.max_array_len = profile.max_array_len.max(self.count);
+		}
+	}
+}
+
+fn insufficient<T>() -> Result<T> { CborError::new_err(ErrorKind::InsufficientBytes, "".into()) }
+
+fn unexpected<T>(msg: &str) -> Result<T> { CborError::new_err(ErrorKind::UnexpectedValue, msg.to_string().into()) }
+
+// Walks one top-level item starting at pos, leaving pos just past it.
+fn profile_item(bytes: &[u8], pos: &mut usize, profile: &mut Profile) -> Result<()> {
+	let mut stack = Vec::<Frame>::new();
+
+	loop {
+		while let Some(top) = stack.last() {
+			if top.remaining != Some(0) {
+				break;
+			}
+			top.close(profile);
+			stack.pop();
+			if stack.is_empty() {
+				return Ok(());
+			}
+		}
+
+		let h = read_header(bytes, *pos)?;
+		*pos += h.len;
+		profile.header_bytes += h.len as u64;
+
+		if h.is_break() {
+			match stack.pop() {
+				Some(ref top) if top.remaining.is_none() => {
+					if top.is_map && top.count % 2 != 0 {
+						return CborError::new_err(ErrorKind::InvalidBreak, "break inside a map entry".into());
+					}
+					top.close(profile);
+				}
+				_ => return CborError::new_err(ErrorKind::InvalidBreak, "unexpected break".into()),
+			}
+			if stack.is_empty() {
+				return Ok(());
+			}
+			continue;
+		}
+
+		let major = usize::from(h.major);
+		profile.items[major] += 1;
+		if h.major == 6 {
+			// The tagged item follows and takes the tag's place in its container
+			continue;
+		}
+		if let Some(top) = stack.last_mut() {
+			top.count += 1;
+			if let Some(remaining) = top.remaining.as_mut() {
+				*remaining -= 1;
+			}
+		}
+		if h.is_indefinite() {
+			profile.indefinite_items += 1;
+		}
+
+		match h.major {
+			2 | 3 => {
+				if h.is_indefinite() {
+					loop {
+						let chunk = read_header(bytes, *pos)?;
+						*pos += chunk.len;
+						profile.header_bytes += chunk.len as u64;
+						if chunk.is_break() {
+							break;
+						}
+						if chunk.major != h.major || chunk.is_indefinite() {
+							return unexpected("invalid chunk in indefinite-length string");
+						}
+						skip_payload(bytes, pos, chunk.arg)?;
+						profile.payload_bytes[major] += chunk.arg;
+					}
+				} else {
+					skip_payload(bytes, pos, h.arg)?;
+					profile.payload_bytes[major] += h.arg;
+				}
+			}
+			4 | 5 => {
+				let is_map = h.major == 5;
+				let remaining = if h.is_indefinite() {
+					None
+				} else if is_map {
+					Some(
+						h.arg
+							.checked_mul(2)
+							.ok_or_else(|| CborError::new(ErrorKind::InsufficientBytes, "".into()))?,
+					)
+				} else {
+					Some(h.arg)
+				};
+				stack.push(Frame {
+					remaining,
+					is_map,
+					count: 0,
+				});
+				profile.max_depth = profile.max_depth.max(stack.len());
+				continue;
+			}
+			_ => (),
+		}
+
+		if stack.is_empty() {
+			return Ok(());
+		}
+	}
+}
+
+fn skip_payload(bytes: &[u8], pos: &mut usize, len: u64) -> Result<()> {
+	let remaining = (bytes.len() - *pos) as u64;
+	if len > remaining {
+		return insufficient();
+	}
+	*pos += len as usize;
+	Ok(())
+}
+
+/// Profiles encoded CBOR without decoding it. `bytes` may hold a single item or a CBOR sequence of
+/// several, in which case the profile covers all of them.
+///
+/// # Examples
+///
+/// ```
+/// // ["a", [1, 2]]
+/// let profile = cborg::profile(&[0x82, 0x61, 0x61, 0x82, 0x01, 0x02]).unwrap();
+/// assert_eq!(5, profile.total_items());
+/// assert_eq!(2, profile.max_depth);
+/// assert_eq!(1, profile.payload_bytes[3]);
+/// ```
+pub fn profile(bytes: &[u8]) -> Result<Profile> {
+	let mut profile = Profile::default();
+	let mut pos = 0;
+	while pos < bytes.len() {
+		profile_item(bytes, &mut pos, &mut profile)?;
+	}
+	Ok(profile)
+}
+
+// Moves pos past the item starting there without recording anything.
+pub(crate) fn skip_item(bytes: &[u8], pos: &mut usize) -> Result<()> {
+	profile_item(bytes, pos, &mut Profile::default())
+}
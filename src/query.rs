@@ -0,0 +1,302 @@
+// Selection of values by path patterns with wildcards and recursive descent.
+
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Selector {
+	Key(Value),
+	Index(usize),
+	// Every array element or map value
+	Wildcard,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Step {
+	// Apply the selector to the current values and all of their descendants
+	recursive: bool,
+	selector: Selector,
+}
+
+/// A parsed query selecting values inside a document.
+///
+/// Queries use the notation that [`Path`] displays, optionally starting with `$`:
+///
+/// - `.name` selects the entry with text key "name"; `."any key"` quotes other text keys and
+///   `.555` or `.-1` select integer keys
+/// - `[2]` selects an array element, and `["name"]` is the same as `.name`
+/// - `[*]` or `.*` selects every array element or map value
+/// - `..` before a selector applies it at any depth, so `$..id` finds every "id" entry
+///
+/// Tags are looked through: a selector applies to the tagged content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+	steps: Vec<Step>,
+}
+
+struct Parser<'a> {
+	s: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error<T>(&self, msg: &str) -> Result<T> {
+		let msg = format!("invalid query {:?} at offset {}: {}", self.s, self.pos, msg);
+		CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+	}
+
+	fn peek(&self) -> Option<char> { self.s[self.pos..].chars().next() }
+
+	fn eat(&mut self, c: char) -> bool {
+		if self.peek() == Some(c) {
+			self.pos += c.len_utf8();
+			true
+		} else {
+			false
+		}
+	}
+
+	fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+		let start = self.pos;
+		while let Some(c) = self.peek() {
+			if !f(c) {
+				break;
+			}
+			self.pos += c.len_utf8();
+		}
+		&self.s[start..self.pos]
+	}
+
+	fn quoted(&mut self) -> Result<Value> {
+		let mut text = String::new();
+		loop {
+			match self.peek() {
+				None => return self.error("unterminated string"),
+				Some('"') => {
+					self.pos += 1;
+					return Ok(Value::Utf8String(text));
+				}
+				Some('\\') => {
+					self.pos += 1;
+					match self.peek() {
+						Some(c) => {
+							text.push(c);
+							self.pos += c.len_utf8();
+						}
+						None => return self.error("unterminated string"),
+					}
+				}
+				Some(c) => {
+					text.push(c);
+					self.pos += c.len_utf8();
+				}
+			}
+		}
+	}
+
+	fn integer_key(&mut self) -> Result<Value> {
+		let negative = self.eat('-');
+		let digits = self.take_while(|c| c.is_ascii_digit());
+		let n: u64 = match digits.parse() {
+			Ok(x) => x,
+			Err(_) => return self.error("expected an integer key"),
+		};
+		if !negative {
+			return Ok(Value::Unsigned(n));
+		}
+		match Value::integer(-i128::from(n)) {
+			Ok(x) => Ok(x),
+			Err(_) => self.error("integer key out of range"),
+		}
+	}
+
+	// The selector after a '.'
+	fn dotted(&mut self) -> Result<Selector> {
+		match self.peek() {
+			Some('*') => {
+				self.pos += 1;
+				Ok(Selector::Wildcard)
+			}
+			Some('"') => {
+				self.pos += 1;
+				Ok(Selector::Key(self.quoted()?))
+			}
+			Some(c) if c == '-' || c.is_ascii_digit() => Ok(Selector::Key(self.integer_key()?)),
+			Some(c) if c.is_alphabetic() || c == '_' => {
+				let name = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-');
+				Ok(Selector::Key(Value::Utf8String(name.to_string())))
+			}
+			_ => self.error("expected a key or '*'"),
+		}
+	}
+
+	// The selector inside '[...]', with the '[' already consumed
+	fn bracketed(&mut self) -> Result<Selector> {
+		let selector = match self.peek() {
+			Some('*') => {
+				self.pos += 1;
+				Selector::Wildcard
+			}
+			Some('"') => {
+				self.pos += 1;
+				Selector::Key(self.quoted()?)
+			}
+			Some(c) if c.is_ascii_digit() => {
+				let digits = self.take_while(|c| c.is_ascii_digit());
+				match digits.parse() {
+					Ok(x) => Selector::Index(x),
+					Err(_) => return self.error("index out of range"),
+				}
+			}
+			_ => return self.error("expected an index, a quoted key or '*'"),
+		};
+		if !self.eat(']') {
+			return self.error("expected ']'");
+		}
+		Ok(selector)
+	}
+
+	fn step(&mut self) -> Result<Step> {
+		if self.eat('[') {
+			return Ok(Step {
+				recursive: false,
+				selector: self.bracketed()?,
+			});
+		}
+		if !self.eat('.') {
+			return self.error("expected '.' or '['");
+		}
+		if self.eat('.') {
+			let selector = if self.eat('[') {
+				self.bracketed()?
+			} else {
+				self.dotted()?
+			};
+			return Ok(Step {
+				recursive: true,
+				selector,
+			});
+		}
+		Ok(Step {
+			recursive: false,
+			selector: self.dotted()?,
+		})
+	}
+}
+
+fn untagged(mut v: &Value) -> &Value {
+	while let Value::Tag(_, x) = v {
+		v = x;
+	}
+	v
+}
+
+fn select<'a>(selector: &Selector, path: &Path, v: &'a Value, out: &mut Vec<(Path, &'a Value)>) {
+	match (selector, untagged(v)) {
+		(Selector::Index(i), Value::Array(x)) => {
+			if let Some(item) = x.get(*i) {
+				out.push((path.join(PathSegment::Index(*i)), item));
+			}
+		}
+		(Selector::Key(k), Value::Map(x)) => {
+			for kv in x.iter().filter(|kv| kv.key == *k) {
+				out.push((path.join(PathSegment::Key(kv.key.clone())), &kv.val));
+			}
+		}
+		(Selector::Wildcard, Value::Array(x)) => {
+			for (i, item) in x.iter().enumerate() {
+				out.push((path.join(PathSegment::Index(i)), item));
+			}
+		}
+		(Selector::Wildcard, Value::Map(x)) => {
+			for kv in x {
+				out.push((path.join(PathSegment::Key(kv.key.clone())), &kv.val));
+			}
+		}
+		_ => (),
+	}
+}
+
+// Applies the selector to v and, depth first, to everything beneath it
+fn select_recursive<'a>(selector: &Selector, path: &mut Path, v: &'a Value, out: &mut Vec<(Path, &'a Value)>) {
+	select(selector, path, v, out);
+	match untagged(v) {
+		Value::Array(x) => {
+			for (i, item) in x.iter().enumerate() {
+				path.push(PathSegment::Index(i));
+				select_recursive(selector, path, item, out);
+				path.pop();
+			}
+		}
+		Value::Map(x) => {
+			for kv in x {
+				path.push(PathSegment::Key(kv.key.clone()));
+				select_recursive(selector, path, &kv.val, out);
+				path.pop();
+			}
+		}
+		_ => (),
+	}
+}
+
+impl Query {
+	/// Parses a query such as `$.items[*].id` or `..signature`.
+	pub fn parse(s: &str) -> Result<Query> {
+		let mut p = Parser { s, pos: 0 };
+		p.eat('$');
+		let mut steps = Vec::new();
+		while p.pos < s.len() {
+			steps.push(p.step()?);
+		}
+		Ok(Query { steps })
+	}
+
+	// The path this query names, if it selects at most one value
+	pub(crate) fn to_path(&self) -> Option<Path> {
+		let mut path = Path::new();
+		for step in &self.steps {
+			match &step.selector {
+				_ if step.recursive => return None,
+				Selector::Key(k) => path.push(PathSegment::Key(k.clone())),
+				Selector::Index(i) => path.push(PathSegment::Index(*i)),
+				Selector::Wildcard => return None,
+			}
+		}
+		Some(path)
+	}
+
+	/// Finds the values the query selects in `v`, with their paths, in document order for each
+	/// step. A query with no steps selects `v` itself.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{Query, Value};
+	/// let v = Value::map(vec![("items", Value::array(vec![
+	///     Value::map(vec![("id", 1u32)]),
+	///     Value::map(vec![("id", 2u32)]),
+	/// ]))]);
+	/// let found = Query::parse("$.items[*].id").unwrap().find(&v);
+	/// assert_eq!("$.items[1].id", found[1].0.to_string());
+	/// assert_eq!(&Value::Unsigned(2), found[1].1);
+	/// ```
+	pub fn find<'a>(&self, v: &'a Value) -> Vec<(Path, &'a Value)> {
+		let mut current = vec![(Path::new(), v)];
+		for step in &self.steps {
+			let mut next = Vec::new();
+			for (mut path, v) in current {
+				if step.recursive {
+					select_recursive(&step.selector, &mut path, v, &mut next);
+				} else {
+					select(&step.selector, &path, v, &mut next);
+				}
+			}
+			current = next;
+		}
+		current
+	}
+}
@@ -0,0 +1,179 @@
+// Decoding into an existing Value, keeping the allocations of the parts whose shape matches.
+
+use crate::CborError;
+use crate::ErrorKind;
+use crate::KeyVal;
+use crate::Map;
+use crate::Result;
+use crate::Simple;
+use crate::Value;
+
+fn next_byte<'a, I: Iterator<Item = &'a u8>>(iter: &mut I) -> Result<u8> {
+	match iter.next() {
+		Some(x) => Ok(*x),
+		None => CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
+	}
+}
+
+fn placeholder() -> Value { Value::Simple(Simple::Null) }
+
+// `depth` is the number of arrays and maps enclosing the item and `tags` the number of tags
+// directly around it
+fn value_into<'a, I: Iterator<Item = &'a u8>>(
+	iter: &mut I,
+	type_byte: u8,
+	out: &mut Value,
+	depth: usize,
+	tags: usize,
+) -> Result<()> {
+	if type_byte == 0xFF {
+		return CborError::new_err(ErrorKind::InvalidBreak, "break code where an item was expected".into());
+	}
+	crate::check_reserved(type_byte)?;
+	let (major, minor) = crate::read_type(type_byte);
+	let max_depth = crate::DecodeOptions::default().max_depth;
+	if (major == 4 || major == 5) && depth >= max_depth {
+		return crate::too_deep(max_depth);
+	}
+	match major {
+		2 => {
+			if !matches!(out, Value::ByteString(_)) {
+				*out = Value::ByteString(Vec::new());
+			}
+			if let Value::ByteString(b) = out {
+				b.clear();
+				crate::parse_byte_string_into(2, minor, iter, b, &crate::DecodeOptions::default())?;
+			}
+		}
+		3 => {
+			let mut buf = match out {
+				Value::Utf8String(s) => std::mem::take(s).into_bytes(),
+				_ => Vec::new(),
+			};
+			buf.clear();
+			crate::parse_byte_string_into(3, minor, iter, &mut buf, &crate::DecodeOptions::default())?;
+			match String::from_utf8(buf) {
+				Ok(s) => *out = Value::Utf8String(s),
+				Err(e) => return CborError::wrap_err(ErrorKind::InvalidUtf8, e),
+			}
+		}
+		4 => {
+			if !matches!(out, Value::Array(_)) {
+				*out = Value::Array(Vec::new());
+			}
+			if let Value::Array(arr) = out {
+				let mut len = 0;
+				if minor == 31 {
+					loop {
+						let b = next_byte(iter)?;
+						if b == 0xFF {
+							break;
+						}
+						element_into(iter, b, arr, len, depth + 1)?;
+						len += 1;
+					}
+				} else {
+					let count = crate::parse_unsigned_int(minor, iter)?;
+					crate::prealloc(count, 1, iter, &crate::DecodeOptions::default())?;
+					for _ in 0..count {
+						let b = next_byte(iter)?;
+						element_into(iter, b, arr, len, depth + 1)?;
+						len += 1;
+					}
+				}
+				arr.truncate(len);
+			}
+		}
+		5 => {
+			if !matches!(out, Value::Map(_)) {
+				*out = Value::Map(Map::new());
+			}
+			if let Value::Map(map) = out {
+				let mut len = 0;
+				let count = if minor == 31 {
+					None
+				} else {
+					let count = crate::parse_unsigned_int(minor, iter)?;
+					crate::prealloc(count, 2, iter, &crate::DecodeOptions::default())?;
+					Some(count)
+				};
+				loop {
+					if count == Some(len as u64) {
+						break;
+					}
+					let b = next_byte(iter)?;
+					if count.is_none() && b == 0xFF {
+						break;
+					}
+					if len == map.len() {
+						map.push(KeyVal {
+							key: placeholder(),
+							val: placeholder(),
+						});
+					}
+					value_into(iter, b, &mut map[len].key, depth + 1, 0)?;
+					let b = next_byte(iter)?;
+					value_into(iter, b, &mut map[len].val, depth + 1, 0)?;
+					len += 1;
+				}
+				map.truncate(len);
+			}
+		}
+		6 => {
+			let max_tags = crate::DecodeOptions::default().max_tag_depth;
+			if tags >= max_tags {
+				return crate::tags_too_deep(max_tags);
+			}
+			let tag = crate::parse_unsigned_int(minor, iter)?;
+			if !matches!(out, Value::Tag(..)) {
+				*out = Value::Tag(tag, Box::new(placeholder()));
+			}
+			if let Value::Tag(t, inner) = out {
+				*t = tag;
+				let b = next_byte(iter)?;
+				value_into(iter, b, inner, depth, tags + 1)?;
+			}
+		}
+		_ => *out = crate::parse_value(iter, type_byte, &crate::DecodeOptions::default(), depth)?,
+	}
+	Ok(())
+}
+
+fn element_into<'a, I: Iterator<Item = &'a u8>>(
+	iter: &mut I,
+	b: u8,
+	arr: &mut Vec<Value>,
+	i: usize,
+	depth: usize,
+) -> Result<()> {
+	if i == arr.len() {
+		arr.push(placeholder());
+	}
+	value_into(iter, b, &mut arr[i], depth, 0)
+}
+
+/// Decodes `bytes` into `out`, reusing the memory of what `out` held before.
+///
+/// Strings, arrays and maps in `out` that sit where the new data has the same kind of item are
+/// cleared and refilled rather than dropped, so decoding messages of a similar shape in a loop
+/// allocates little once `out` has grown to fit them. The result is the same as `decode`. If an
+/// error is returned, `out` holds partially decoded data.
+///
+/// # Examples
+///
+/// ```
+/// let mut v = cborg::Value::Simple(cborg::Simple::Null);
+/// for msg in &[[0x82, 0x61, b'a', 0x01], [0x82, 0x61, b'b', 0x02]] {
+///     cborg::decode_reuse(msg, &mut v).unwrap();
+/// }
+/// assert_eq!(cborg::decode(&[0x82, 0x61, b'b', 0x02]).unwrap(), v);
+/// ```
+pub fn decode_reuse(bytes: &[u8], out: &mut Value) -> Result<()> {
+	let mut iter = bytes.iter();
+	let b = next_byte(&mut iter)?;
+	value_into(&mut iter, b, out, 0, 0)?;
+	if out.is_self_described() {
+		*out = out.take().strip_self_described();
+	}
+	Ok(())
+}
@@ -0,0 +1,156 @@
+use crate::header::read_header;
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::Result;
+
+/// Why re-encoding changed the bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DivergenceKind {
+	/// An indefinite-length string, array or map was re-encoded with a definite length
+	IndefiniteCollapsed,
+	/// A half or single precision float was re-encoded at a different width
+	FloatWidthChanged,
+	/// An integer, length or tag number was re-encoded with a shorter argument
+	IntegerMinimized,
+	/// Data after the first item, which decoding ignores
+	TrailingData,
+	/// Any other change
+	Other,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+	/// Offset of the first byte that differs between the input and its re-encoding
+	pub offset: usize,
+	/// Path of the item whose encoding differs. Map keys are identified by their decoded value.
+	pub path: Path,
+	pub kind: DivergenceKind,
+}
+
+/// The result of [`roundtrip_check`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundtripReport {
+	/// What `decode` followed by `encode` produced
+	pub encoded: Vec<u8>,
+	/// The first difference, or `None` if the round trip is exact
+	pub divergence: Option<Divergence>,
+}
+
+impl RoundtripReport {
+	pub fn is_faithful(&self) -> bool { self.divergence.is_none() }
+}
+
+struct Lockstep<'a> {
+	orig: &'a [u8],
+	enc: &'a [u8],
+	o: usize,
+	e: usize,
+}
+
+impl<'a> Lockstep<'a> {
+	// Walks one item in both encodings, returning the kind of the first difference found
+	fn item(&mut self, path: &mut Path) -> Option<DivergenceKind> {
+		let (ho, he) = match (read_header(self.orig, self.o), read_header(self.enc, self.e)) {
+			(Ok(ho), Ok(he)) => (ho, he),
+			_ => return Some(DivergenceKind::Other),
+		};
+		if self.orig[self.o..self.o + ho.len] != self.enc[self.e..self.e + he.len] {
+			return Some(if ho.is_indefinite() {
+				DivergenceKind::IndefiniteCollapsed
+			} else if ho.major == 7 && ho.minor >= 25 && he.minor >= 25 {
+				DivergenceKind::FloatWidthChanged
+			} else if ho.major == he.major && ho.arg == he.arg && ho.len > he.len {
+				DivergenceKind::IntegerMinimized
+			} else {
+				DivergenceKind::Other
+			});
+		}
+		self.o += ho.len;
+		self.e += he.len;
+
+		match ho.major {
+			2 | 3 => {
+				let len = ho.arg as usize;
+				if self.orig.get(self.o..self.o + len) != self.enc.get(self.e..self.e + len) {
+					return Some(DivergenceKind::Other);
+				}
+				self.o += len;
+				self.e += len;
+				None
+			}
+			4 => {
+				for i in 0..ho.arg as usize {
+					// On a difference the path is left pointing at it
+					path.push(PathSegment::Index(i));
+					let d = self.item(path);
+					if d.is_some() {
+						return d;
+					}
+					path.pop();
+				}
+				None
+			}
+			5 => {
+				for _ in 0..ho.arg {
+					let key_start = self.o;
+					let d = self.item(path);
+					if d.is_some() {
+						return d;
+					}
+					let key = crate::decode_slice(&self.orig[key_start..self.o]).ok()?;
+					path.push(PathSegment::Key(key));
+					let d = self.item(path);
+					if d.is_some() {
+						return d;
+					}
+					path.pop();
+				}
+				None
+			}
+			6 => self.item(path),
+			_ => None,
+		}
+	}
+}
+
+/// Decodes `bytes`, re-encodes the result and reports whether that reproduced the input exactly.
+/// If not, the report gives the first differing offset and classifies the cause by where it falls
+/// in the structure of the data.
+///
+/// `encode` keeps map entries in their decoded order, so key order never causes a difference.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::DivergenceKind;
+/// // 5 with a needless one-byte argument
+/// let report = cborg::roundtrip_check(&[0x18, 0x05]).unwrap();
+/// assert_eq!(vec![0x05], report.encoded);
+/// assert_eq!(DivergenceKind::IntegerMinimized, report.divergence.unwrap().kind);
+/// ```
+pub fn roundtrip_check(bytes: &[u8]) -> Result<RoundtripReport> {
+	let encoded = crate::decode(bytes)?.encode();
+	let offset = bytes.iter().zip(&encoded).position(|(a, b)| a != b);
+
+	let mut walk = Lockstep {
+		orig: bytes,
+		enc: &encoded,
+		o: 0,
+		e: 0,
+	};
+	let mut path = Path::new();
+	let divergence = match walk.item(&mut path) {
+		Some(kind) => Some(Divergence {
+			offset: offset.unwrap_or(walk.o),
+			path,
+			kind,
+		}),
+		None if walk.o < bytes.len() => Some(Divergence {
+			offset: walk.o,
+			path: Path::new(),
+			kind: DivergenceKind::TrailingData,
+		}),
+		None => None,
+	};
+	Ok(RoundtripReport { encoded, divergence })
+}
@@ -0,0 +1,109 @@
+// Decoding of CBOR sequences (RFC 8742): top-level items placed back to back.
+
+use core::marker::PhantomData;
+
+use crate::CborError;
+use crate::FromValue;
+use crate::Result;
+use crate::ToValue;
+use crate::Value;
+
+/// Iterator returned by [`decode_seq_to`].
+pub struct SeqTo<'a, T> {
+	bytes: &'a [u8],
+	pos: usize,
+	index: usize,
+	done: bool,
+	_marker: PhantomData<T>,
+}
+
+impl<'a, T: FromValue> Iterator for SeqTo<'a, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.done || self.pos >= self.bytes.len() {
+			return None;
+		}
+		let (index, offset) = (self.index, self.pos);
+		self.index += 1;
+
+		let mut iter = self.bytes[offset..].iter();
+		let v = match crate::decode_iter(&mut iter) {
+			Ok(v) => v,
+			Err(e) => {
+				// The end of a malformed item is unknown, so nothing after it can be read
+				self.done = true;
+				return Some(Err(e.within(format!("sequence item {} at offset {}", index, offset))));
+			}
+		};
+		self.pos = self.bytes.len() - iter.as_slice().len();
+
+		Some(match T::from_value(v) {
+			Some(x) => Ok(x),
+			None => {
+				let msg = format!("could not convert sequence item {} at offset {}", index, offset);
+				CborError::new_err(crate::ErrorKind::UnexpectedValue, msg.into())
+			}
+		})
+	}
+}
+
+/// Decodes each top-level item of the CBOR sequence in `bytes` and converts it to `T`.
+///
+/// Items that decode but do not convert are yielded as errors naming their index and offset, and
+/// iteration carries on with the next item. A malformed or truncated item ends the iteration after
+/// its error. Empty input is an empty sequence.
+///
+/// # Examples
+///
+/// ```
+/// // 1, 2, "three"
+/// let items: Vec<_> = cborg::decode_seq_to::<u64>(&[0x01, 0x02, 0x65, b't', b'h', b'r', b'e', b'e']).collect();
+/// assert_eq!(2, *items[1].as_ref().unwrap());
+/// assert!(items[2].is_err());
+/// ```
+pub fn decode_seq_to<T: FromValue>(bytes: &[u8]) -> SeqTo<'_, T> {
+	SeqTo {
+		bytes,
+		pos: 0,
+		index: 0,
+		done: false,
+		_marker: PhantomData,
+	}
+}
+
+/// Like [`decode_seq_to`] but collects the items, stopping at the first error.
+pub fn decode_seq_to_vec<T: FromValue>(bytes: &[u8]) -> Result<Vec<T>> { decode_seq_to(bytes).collect() }
+
+/// Decodes each top-level item of the CBOR sequence in `bytes`. This is [`decode_seq_to`] for
+/// `Value`, so every item that is well-formed is yielded.
+///
+/// # Examples
+///
+/// ```
+/// let items: Vec<_> = cborg::decode_seq(&[0x01, 0x61, b'a', 0x82]).collect();
+/// assert_eq!(3, items.len());
+/// assert_eq!(cborg::Value::text("a"), *items[1].as_ref().unwrap());
+/// assert_eq!(&cborg::ErrorKind::InsufficientBytes, items[2].as_ref().unwrap_err().kind());
+/// ```
+pub fn decode_seq(bytes: &[u8]) -> SeqTo<'_, Value> { decode_seq_to(bytes) }
+
+/// Like [`decode_seq`] but collects the items, stopping at the first error.
+pub fn decode_all(bytes: &[u8]) -> Result<Vec<Value>> { decode_seq(bytes).collect() }
+
+/// Encodes each item and places the results back to back as a CBOR sequence.
+///
+/// # Examples
+///
+/// ```
+/// let bytes = cborg::encode_seq(&[1u32, 2, 3]);
+/// assert_eq!(vec![0x01, 0x02, 0x03], bytes);
+/// assert_eq!(3, cborg::decode_all(&bytes).unwrap().len());
+/// ```
+pub fn encode_seq<T: ToValue, I: IntoIterator<Item = T>>(items: I) -> Vec<u8> {
+	let mut out = Vec::new();
+	for item in items {
+		out.extend_from_slice(&item.to_value().encode());
+	}
+	out
+}
@@ -0,0 +1,152 @@
+// Random access into CBOR sequences by recording where each top-level item starts.
+
+use crate::header::read_header;
+use crate::header::write_header;
+use crate::profile::skip_item;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::Value;
+
+/// Byte offsets of the top-level items in a CBOR sequence, from [`index_seq`] or
+/// [`index_seq_sparse`].
+///
+/// A sparse index records only every Nth item and finds the others by skipping forward from the
+/// nearest recorded one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeqIndex<'a> {
+	data: &'a [u8],
+	every: u64,
+	count: u64,
+	offsets: Vec<u64>,
+}
+
+fn mismatch<T>(msg: &str) -> Result<T> { CborError::new_err(ErrorKind::UnexpectedValue, msg.to_string().into()) }
+
+fn read_uint(bytes: &[u8], pos: &mut usize, major: u8) -> Result<u64> {
+	let h = read_header(bytes, *pos)?;
+	if h.major != major || h.minor == 31 {
+		return mismatch("malformed sequence index");
+	}
+	*pos += h.len;
+	Ok(h.arg)
+}
+
+impl<'a> SeqIndex<'a> {
+	/// Number of items in the sequence
+	pub fn len(&self) -> usize { self.count as usize }
+
+	pub fn is_empty(&self) -> bool { self.count == 0 }
+
+	/// The encoded bytes of item `n`, or `None` if the sequence has no such item.
+	pub fn get(&self, n: usize) -> Option<&'a [u8]> {
+		let n = n as u64;
+		if n >= self.count {
+			return None;
+		}
+		let mut pos = self.offsets[(n / self.every) as usize] as usize;
+		for _ in 0..n % self.every {
+			skip_item(self.data, &mut pos).ok()?;
+		}
+		let start = pos;
+		skip_item(self.data, &mut pos).ok()?;
+		Some(&self.data[start..pos])
+	}
+
+	/// Decodes item `n`, giving `Ok(None)` if the sequence has no such item.
+	pub fn decode_nth(&self, n: usize) -> Result<Option<Value>> {
+		match self.get(n) {
+			Some(x) => crate::decode_slice(x).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Serializes the index so it can be stored next to the data and loaded with
+	/// [`SeqIndex::from_bytes`]. The result is itself CBOR: an array of the sampling interval,
+	/// the item count, the data length and a byte string of big-endian 64-bit offsets.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(32 + self.offsets.len() * 8);
+		write_header(&mut out, 4, 4);
+		write_header(&mut out, 0, self.every);
+		write_header(&mut out, 0, self.count);
+		write_header(&mut out, 0, self.data.len() as u64);
+		write_header(&mut out, 2, self.offsets.len() as u64 * 8);
+		for x in &self.offsets {
+			out.extend_from_slice(&x.to_be_bytes());
+		}
+		out
+	}
+
+	/// Loads an index written by [`SeqIndex::to_bytes`] for use with `data`. Fails if the index is
+	/// malformed or was built over data of a different length; the items themselves are not
+	/// re-checked.
+	pub fn from_bytes(index: &[u8], data: &'a [u8]) -> Result<SeqIndex<'a>> {
+		let mut pos = 0;
+		if read_uint(index, &mut pos, 4)? != 4 {
+			return mismatch("malformed sequence index");
+		}
+		let every = read_uint(index, &mut pos, 0)?;
+		let count = read_uint(index, &mut pos, 0)?;
+		let data_len = read_uint(index, &mut pos, 0)?;
+		let offsets_len = read_uint(index, &mut pos, 2)?;
+		if data_len != data.len() as u64 {
+			return mismatch("sequence index was built over data of a different length");
+		}
+		if every == 0 || offsets_len != count.div_ceil(every) * 8 || (index.len() - pos) as u64 != offsets_len {
+			return mismatch("malformed sequence index");
+		}
+		let offsets: Vec<u64> = index[pos..]
+			.chunks(8)
+			.map(|x| x.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+			.collect();
+		if offsets.windows(2).any(|x| x[0] >= x[1]) || offsets.last().is_some_and(|x| *x >= data_len) {
+			return mismatch("malformed sequence index");
+		}
+		Ok(SeqIndex {
+			data,
+			every,
+			count,
+			offsets,
+		})
+	}
+}
+
+/// Indexes every top-level item of the CBOR sequence in `bytes`. Fails if any item is malformed
+/// or truncated.
+///
+/// # Examples
+///
+/// ```
+/// // 1, "a", [2]
+/// let index = cborg::index_seq(&[0x01, 0x61, 0x61, 0x81, 0x02]).unwrap();
+/// assert_eq!(3, index.len());
+/// assert_eq!(Some(&[0x61, 0x61][..]), index.get(1));
+/// assert_eq!(Some(cborg::Value::Array(vec![cborg::Value::Unsigned(2)])), index.decode_nth(2).unwrap());
+/// ```
+pub fn index_seq(bytes: &[u8]) -> Result<SeqIndex<'_>> { index_seq_sparse(bytes, 1) }
+
+/// Like [`index_seq`], but records only every `every`th item to bound the size of the index.
+/// Looking up other items skips forward over at most `every - 1` items.
+///
+/// # Panics
+///
+/// Panics if `every` is 0.
+pub fn index_seq_sparse(bytes: &[u8], every: usize) -> Result<SeqIndex<'_>> {
+	assert!(every > 0, "index_seq_sparse interval must be at least 1");
+	let mut offsets = Vec::new();
+	let mut count = 0u64;
+	let mut pos = 0;
+	while pos < bytes.len() {
+		if count.is_multiple_of(every as u64) {
+			offsets.push(pos as u64);
+		}
+		skip_item(bytes, &mut pos)?;
+		count += 1;
+	}
+	Ok(SeqIndex {
+		data: bytes,
+		every: every as u64,
+		count,
+		offsets,
+	})
+}
@@ -0,0 +1,112 @@
+// Encoding of arrays and maps whose items are produced one at a time.
+
+use std::io;
+
+use crate::header::write_header;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::ToValue;
+
+const BREAK: u8 = 0xFF;
+
+struct Framed<'w, W> {
+	w: &'w mut W,
+	written: u64,
+	len_hint: Option<u64>,
+	count: u64,
+}
+
+impl<'w, W: io::Write> Framed<'w, W> {
+	fn begin(w: &'w mut W, major: u8, len_hint: Option<u64>) -> Result<Self> {
+		let mut header = Vec::with_capacity(9);
+		match len_hint {
+			Some(len) => write_header(&mut header, major, len),
+			None => header.push(major << 5 | 31),
+		}
+		let mut framed = Framed {
+			w,
+			written: 0,
+			len_hint,
+			count: 0,
+		};
+		framed.write(&header)?;
+		Ok(framed)
+	}
+
+	fn write(&mut self, bytes: &[u8]) -> Result<()> {
+		self.w.write_all(bytes)?;
+		self.written += bytes.len() as u64;
+		Ok(())
+	}
+
+	// Called before each item is written
+	fn next_item(&mut self) -> Result<()> {
+		if let Some(len) = self.len_hint {
+			if self.count == len {
+				let msg = format!("iterator produced more than the {} items in the length hint", len);
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
+		}
+		self.count += 1;
+		Ok(())
+	}
+
+	fn end(mut self) -> Result<u64> {
+		match self.len_hint {
+			Some(len) if len != self.count => {
+				let msg = format!("iterator produced {} items but the length hint was {}", self.count, len);
+				CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+			}
+			Some(_) => Ok(self.written),
+			None => {
+				self.write(&[BREAK])?;
+				Ok(self.written)
+			}
+		}
+	}
+}
+
+/// Writes the items of `iter` to `w` as one CBOR array, encoding and writing each item as it is
+/// produced so the whole sequence is never held in memory. Returns the number of bytes written.
+///
+/// With `len_hint` the array has a definite length and it is an error for the iterator to produce
+/// a different number of items. Without it the array is written with indefinite-length framing.
+/// On error, whatever was written before the failure is left in `w`.
+///
+/// # Examples
+///
+/// ```
+/// let mut out = Vec::new();
+/// cborg::encode_array_from_iter(&mut out, (1..4).map(|x: u32| x * 10), Some(3)).unwrap();
+/// assert_eq!(vec![0x83, 0x0A, 0x14, 0x18, 0x1E], out);
+/// ```
+pub fn encode_array_from_iter<W, T, I>(w: &mut W, iter: I, len_hint: Option<u64>) -> Result<u64>
+where
+	W: io::Write,
+	T: ToValue,
+	I: IntoIterator<Item = T>, {
+	let mut framed = Framed::begin(w, 4, len_hint)?;
+	for item in iter {
+		framed.next_item()?;
+		framed.write(&item.to_value().encode())?;
+	}
+	framed.end()
+}
+
+/// Like [`encode_array_from_iter`] but writes a map from key-value pairs. `len_hint` counts
+/// entries, not keys and values separately.
+pub fn encode_map_from_iter<W, K, V, I>(w: &mut W, iter: I, len_hint: Option<u64>) -> Result<u64>
+where
+	W: io::Write,
+	K: ToValue,
+	V: ToValue,
+	I: IntoIterator<Item = (K, V)>, {
+	let mut framed = Framed::begin(w, 5, len_hint)?;
+	for (k, v) in iter {
+		framed.next_item()?;
+		framed.write(&k.to_value().encode())?;
+		framed.write(&v.to_value().encode())?;
+	}
+	framed.end()
+}
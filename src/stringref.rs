@@ -0,0 +1,301 @@
+// The stringref extension (tags 25 and 256, http://cbor.schmorp.de/stringref): strings that
+// recur within a namespace are replaced by references to their first occurrence.
+
+use core::convert::TryFrom;
+use std::collections::HashMap;
+
+use crate::header::read_header;
+use crate::header::write_header;
+use crate::header::Header;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::KeyVal;
+use crate::Map;
+use crate::Result;
+use crate::Value;
+
+// Strings shorter than this are never entered into a table of `n` strings, as a reference to
+// them would be no shorter than the string itself
+fn min_len(n: usize) -> usize {
+	match n as u64 {
+		0..=23 => 3,
+		24..=255 => 4,
+		256..=65_535 => 5,
+		65_536..=0xFFFF_FFFF => 7,
+		_ => 11,
+	}
+}
+
+fn unexpected<T>(msg: &str) -> Result<T> { CborError::new_err(ErrorKind::UnexpectedValue, msg.to_string().into()) }
+
+fn insufficient<T>() -> Result<T> { CborError::new_err(ErrorKind::InsufficientBytes, "".into()) }
+
+struct Expander<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	// One table per open namespace, innermost last
+	tables: Vec<Vec<Value>>,
+	// Arrays and maps enclosing the current item
+	depth: usize,
+	// Tags directly around the current item
+	tags: usize,
+}
+
+impl<'a> Expander<'a> {
+	fn take(&mut self, len: u64) -> Result<&'a [u8]> {
+		if len > (self.bytes.len() - self.pos) as u64 {
+			return insufficient();
+		}
+		let start = self.pos;
+		self.pos += len as usize;
+		Ok(&self.bytes[start..self.pos])
+	}
+
+	fn string(&mut self, major: u8, indefinite: bool, len: u64) -> Result<Value> {
+		let mut content = Vec::new();
+		if indefinite {
+			loop {
+				let chunk = read_header(self.bytes, self.pos)?;
+				self.pos += chunk.len;
+				if chunk.is_break() {
+					break;
+				}
+				if chunk.major != major || chunk.is_indefinite() {
+					return unexpected("invalid chunk in indefinite-length string");
+				}
+				let chunk = self.take(chunk.arg)?;
+				if major == 3 {
+					if let Err(e) = std::str::from_utf8(chunk) {
+						return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+					}
+				}
+				content.extend_from_slice(chunk);
+			}
+		} else {
+			content.extend_from_slice(self.take(len)?);
+		}
+		let v = if major == 2 {
+			Value::ByteString(content)
+		} else {
+			match String::from_utf8(content) {
+				Ok(s) => Value::Utf8String(s),
+				Err(e) => return CborError::wrap_err(ErrorKind::InvalidUtf8, e),
+			}
+		};
+		// Only definite-length strings are entered into the table
+		if let Some(table) = self.tables.last_mut() {
+			if !indefinite && len as usize >= min_len(table.len()) {
+				table.push(v.clone());
+			}
+		}
+		Ok(v)
+	}
+
+	fn container(&mut self, h: &Header) -> Result<Value> {
+		if h.major == 4 {
+			let mut arr = Vec::new();
+			loop {
+				if h.is_indefinite() {
+					if read_header(self.bytes, self.pos)?.is_break() {
+						self.pos += 1;
+						break;
+					}
+				} else if arr.len() as u64 == h.arg {
+					break;
+				}
+				arr.push(self.item()?);
+			}
+			return Ok(Value::Array(arr));
+		}
+		let mut map = Map::new();
+		loop {
+			if h.is_indefinite() {
+				if read_header(self.bytes, self.pos)?.is_break() {
+					self.pos += 1;
+					break;
+				}
+			} else if map.len() as u64 == h.arg {
+				break;
+			}
+			let key = self.item()?;
+			let val = self.item()?;
+			map.push(KeyVal { key, val });
+		}
+		Ok(Value::Map(map))
+	}
+
+	fn item(&mut self) -> Result<Value> {
+		let start = self.pos;
+		let h = read_header(self.bytes, start)?;
+		if h.is_break() {
+			return CborError::new_err(ErrorKind::InvalidBreak, "unexpected break".into());
+		}
+		self.pos += h.len;
+		if h.major == 6 {
+			let max_tags = crate::DecodeOptions::default().max_tag_depth;
+			if self.tags >= max_tags {
+				return crate::tags_too_deep(max_tags);
+			}
+			self.tags += 1;
+		} else {
+			self.tags = 0;
+		}
+		if h.major == 4 || h.major == 5 {
+			let max_depth = crate::DecodeOptions::default().max_depth;
+			if self.depth >= max_depth {
+				return crate::too_deep(max_depth);
+			}
+			self.depth += 1;
+			let v = self.container(&h);
+			self.depth -= 1;
+			return v;
+		}
+
+		Ok(match h.major {
+			0 => Value::Unsigned(h.arg),
+			1 => {
+				if h.arg > i64::MAX as u64 {
+					return CborError::new_err(ErrorKind::LengthOverflow, "negative integer below i64::MIN".into());
+				}
+				Value::Negative(-1 - h.arg as i64)
+			}
+			2 | 3 => self.string(h.major, h.is_indefinite(), h.arg)?,
+			6 if h.arg == tags::STRINGREF_NAMESPACE => {
+				self.tables.push(Vec::new());
+				let v = self.item()?;
+				self.tables.pop();
+				v
+			}
+			6 if h.arg == tags::STRINGREF => {
+				let index = match self.item()? {
+					Value::Unsigned(x) => x,
+					_ => return unexpected("stringref index must be an unsigned integer"),
+				};
+				let table = match self.tables.last() {
+					Some(x) => x,
+					None => return unexpected("stringref outside of a stringref namespace"),
+				};
+				match usize::try_from(index).ok().and_then(|i| table.get(i)) {
+					Some(x) => x.clone(),
+					None => {
+						let msg = format!(
+							"stringref {} is out of range; the table has {} strings",
+							index,
+							table.len()
+						);
+						return unexpected(&msg);
+					}
+				}
+			}
+			6 => Value::Tag(h.arg, Box::new(self.item()?)),
+			_ => {
+				let mut arg = self.bytes[start + 1..start + h.len].iter();
+				if h.minor <= 24 {
+					Value::Simple(crate::parse_simple(h.minor, &mut arg)?)
+				} else {
+					Value::Float(crate::parse_float(h.minor, &mut arg)?)
+				}
+			}
+		})
+	}
+}
+
+/// Decodes one item, expanding stringref references (tag 25) from the tables built up inside each
+/// stringref namespace (tag 256). The namespace tags are removed, so the result is what decoding
+/// the uncompressed data would give. Plain `decode` leaves both tags in place.
+///
+/// Fails if a reference is out of range for its table or appears outside any namespace.
+///
+/// # Examples
+///
+/// ```
+/// // 256(["aaa", 25(0)])
+/// let bytes = [0xD9, 0x01, 0x00, 0x82, 0x63, b'a', b'a', b'a', 0xD8, 0x19, 0x00];
+/// let v = cborg::decode_with_stringrefs(&bytes).unwrap();
+/// assert_eq!(r#"["aaa", "aaa"]"#, v.to_diagnostic());
+/// ```
+pub fn decode_with_stringrefs(bytes: &[u8]) -> Result<Value> {
+	let mut e = Expander {
+		bytes,
+		pos: 0,
+		tables: Vec::new(),
+		depth: 0,
+		tags: 0,
+	};
+	e.item()
+}
+
+struct Compressor {
+	out: Vec<u8>,
+	// String contents by major type, mapped to their table index
+	table: HashMap<(u8, Vec<u8>), usize>,
+}
+
+impl Compressor {
+	fn string(&mut self, major: u8, content: &[u8]) {
+		let key = (major, content.to_vec());
+		if let Some(index) = self.table.get(&key) {
+			write_header(&mut self.out, 6, tags::STRINGREF);
+			write_header(&mut self.out, 0, *index as u64);
+			return;
+		}
+		write_header(&mut self.out, major, content.len() as u64);
+		self.out.extend_from_slice(content);
+		if content.len() >= min_len(self.table.len()) {
+			let index = self.table.len();
+			self.table.insert(key, index);
+		}
+	}
+
+	fn item(&mut self, v: &Value) {
+		match v {
+			Value::Unsigned(x) => write_header(&mut self.out, 0, *x),
+			Value::Negative(x) => write_header(&mut self.out, 1, (-1 - x) as u64),
+			Value::ByteString(x) => self.string(2, x),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => self.string(2, x),
+			Value::Utf8String(x) => self.string(3, x.as_bytes()),
+			Value::Array(x) => {
+				write_header(&mut self.out, 4, x.len() as u64);
+				for item in x {
+					self.item(item);
+				}
+			}
+			Value::Map(x) => {
+				write_header(&mut self.out, 5, x.len() as u64);
+				for kv in x {
+					self.item(&kv.key);
+					self.item(&kv.val);
+				}
+			}
+			Value::Tag(tags::STRINGREF_NAMESPACE, x) => {
+				// A nested namespace starts its own table
+				write_header(&mut self.out, 6, tags::STRINGREF_NAMESPACE);
+				let outer = std::mem::take(&mut self.table);
+				self.item(x);
+				self.table = outer;
+			}
+			Value::Tag(t, x) => {
+				write_header(&mut self.out, 6, *t);
+				self.item(x);
+			}
+			Value::Float(_) | Value::Simple(_) => self.out.extend_from_slice(&v.encode_compact()),
+			// Decoded so that its strings can join the table
+			Value::Raw(x) => self.item(&x.decode()),
+		}
+	}
+}
+
+/// Encodes `v` inside a stringref namespace (tag 256), replacing each repeat of a string that
+/// was long enough to enter the table with a reference (tag 25) to its first occurrence. Decode
+/// the result with [`decode_with_stringrefs`].
+pub fn encode_with_stringrefs(v: &Value) -> Vec<u8> {
+	let mut c = Compressor {
+		out: Vec::new(),
+		table: HashMap::new(),
+	};
+	write_header(&mut c.out, 6, tags::STRINGREF_NAMESPACE);
+	c.item(v);
+	c.out
+}
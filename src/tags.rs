@@ -0,0 +1,59 @@
+//! Numbers of the CBOR tags this crate knows, from the
+//! [IANA registry](https://www.iana.org/assignments/cbor-tags/cbor-tags.xhtml).
+
+use core::ops::RangeInclusive;
+
+/// Date/time as an RFC 3339 text string
+pub const DATETIME_STRING: u64 = 0;
+/// Date/time as seconds since the Unix epoch
+pub const EPOCH: u64 = 1;
+/// Unsigned bignum: a big-endian byte string
+pub const POS_BIGNUM: u64 = 2;
+/// Negative bignum: -1 minus a big-endian byte string
+pub const NEG_BIGNUM: u64 = 3;
+/// Decimal fraction: an [exponent, mantissa] array in base 10
+pub const DECIMAL: u64 = 4;
+/// Bigfloat: an [exponent, mantissa] array in base 2
+pub const BIGFLOAT: u64 = 5;
+/// Hint that a byte string is best shown as base64url
+pub const BASE64URL: u64 = 21;
+/// Hint that a byte string is best shown as base64
+pub const BASE64: u64 = 22;
+/// Hint that a byte string is best shown as base16
+pub const BASE16: u64 = 23;
+/// Encoded CBOR data item in a byte string
+pub const EMBEDDED: u64 = 24;
+/// Reference to an earlier string in a stringref namespace
+pub const STRINGREF: u64 = 25;
+/// URI text string
+pub const URI: u64 = 32;
+/// Regular expression text string
+pub const REGEX: u64 = 35;
+/// MIME message text string
+pub const MIME: u64 = 36;
+/// Binary UUID
+pub const UUID: u64 = 37;
+/// Text string with a language tag: a [language, text] array
+pub const LANG_STRING: u64 = 38;
+/// Typed arrays of packed numbers (RFC 8746)
+pub const TYPED_ARRAYS: RangeInclusive<u64> = 64..=87;
+/// Namespace for stringrefs
+pub const STRINGREF_NAMESPACE: u64 = 256;
+/// Extended time: a map of time fields
+pub const EXTENDED_TIME: u64 = 1001;
+/// Self-described CBOR marker
+pub const SELF_DESCRIBED: u64 = 55799;
+
+/// Whether `tag` is one of the tags above.
+pub fn is_known(tag: u64) -> bool {
+	matches!(
+		tag,
+		DATETIME_STRING..=BIGFLOAT
+			| BASE64URL..=STRINGREF
+			| URI
+			| REGEX..=LANG_STRING
+			| STRINGREF_NAMESPACE
+			| EXTENDED_TIME
+			| SELF_DESCRIBED
+	) || TYPED_ARRAYS.contains(&tag)
+}
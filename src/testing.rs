@@ -0,0 +1,93 @@
+//! Support for comparing CBOR in tests. See [`assert_cbor_eq!`](crate::assert_cbor_eq).
+
+use crate::diff::Difference;
+use crate::Value;
+
+/// Something `assert_cbor_eq!` can compare: a [`Value`], or encoded bytes which are decoded first.
+pub trait CborOperand {
+	fn cbor_operand(&self) -> Value;
+}
+
+impl CborOperand for Value {
+	fn cbor_operand(&self) -> Value { self.clone() }
+}
+
+impl CborOperand for [u8] {
+	fn cbor_operand(&self) -> Value {
+		match crate::decode(self) {
+			Ok(v) => v,
+			Err(e) => panic!("assert_cbor_eq: could not decode operand: {}", e),
+		}
+	}
+}
+
+impl CborOperand for Vec<u8> {
+	fn cbor_operand(&self) -> Value { self.as_slice().cbor_operand() }
+}
+
+impl<const N: usize> CborOperand for [u8; N] {
+	fn cbor_operand(&self) -> Value { self[..].cbor_operand() }
+}
+
+impl<T: CborOperand + ?Sized> CborOperand for &T {
+	fn cbor_operand(&self) -> Value { (**self).cbor_operand() }
+}
+
+/// Builds the panic message for a failed comparison, or None if there are no differences.
+pub fn mismatch_message(left: &Value, right: &Value, diffs: &[Difference]) -> Option<String> {
+	if diffs.is_empty() {
+		return None;
+	}
+	let mut msg = String::from("assertion failed: CBOR values differ\n");
+	msg.push_str(&format!(
+		" left: {}\nright: {}\ndifferences:\n",
+		left.to_diagnostic(),
+		right.to_diagnostic()
+	));
+	for d in diffs {
+		msg.push_str(&format!("  {}\n", d));
+	}
+	Some(msg)
+}
+
+#[doc(hidden)]
+pub fn assert_eq_impl(left: &dyn CborOperand, right: &dyn CborOperand, ordered: bool) {
+	let (left, right) = (left.cbor_operand(), right.cbor_operand());
+	let diffs = if ordered {
+		crate::diff(&left, &right)
+	} else {
+		crate::diff_unordered(&left, &right)
+	};
+	if let Some(msg) = mismatch_message(&left, &right, &diffs) {
+		panic!("{}", msg);
+	}
+}
+
+/// Asserts that two CBOR documents are equal, where each side is a [`Value`] or encoded bytes.
+///
+/// Byte operands are decoded and compared as values, so the same data with different integer or
+/// length encodings compares equal. On mismatch it panics with both sides in diagnostic notation
+/// and the path of every difference.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::Value;
+/// cborg::assert_cbor_eq!(&[0x82, 0x01, 0x02], Value::Array(vec![Value::Unsigned(1), Value::Unsigned(2)]));
+/// // The same array with a two-byte length
+/// cborg::assert_cbor_eq!(vec![0x98, 0x02, 0x01, 0x02], &[0x82, 0x01, 0x02]);
+/// ```
+#[macro_export]
+macro_rules! assert_cbor_eq {
+	($left:expr, $right:expr $(,)?) => {
+		$crate::testing::assert_eq_impl(&$left, &$right, true)
+	};
+}
+
+/// Like [`assert_cbor_eq!`] but ignores the order of map entries.
+#[macro_export]
+macro_rules! assert_cbor_eq_unordered {
+	($left:expr, $right:expr $(,)?) => {
+		$crate::testing::assert_eq_impl(&$left, &$right, false)
+	};
+}
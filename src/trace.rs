@@ -0,0 +1,152 @@
+// Instrumentation for the top-level decode and encode calls. With the `tracing` feature disabled
+// these are plain forwarding functions.
+
+#[cfg(not(feature = "tracing"))]
+mod imp {
+	use crate::Byte;
+	use crate::DecodeOptions;
+	use crate::Result;
+	use crate::Value;
+
+	#[inline]
+	pub(crate) fn decode<B: Byte, I: Iterator<Item = B>>(iter: &mut I, opts: &DecodeOptions) -> Result<Value> {
+		crate::decode_first(iter, opts)
+	}
+
+	#[inline]
+	pub(crate) fn encode(v: &Value, out: &mut Vec<u8>) { v.encode_compact_into(out) }
+}
+
+#[cfg(feature = "tracing")]
+mod imp {
+	use std::time::Instant;
+
+	use tracing::field::Empty;
+
+	use crate::Byte;
+	use crate::DecodeOptions;
+	use crate::ErrorKind;
+	use crate::Result;
+	use crate::Value;
+
+	struct Counted<'i, I> {
+		inner: &'i mut I,
+		count: usize,
+	}
+
+	impl<'i, B: Byte, I: Iterator<Item = B>> Iterator for Counted<'i, I> {
+		type Item = B;
+		fn next(&mut self) -> Option<B> {
+			let next = self.inner.next();
+			if next.is_some() {
+				self.count += 1;
+			}
+			next
+		}
+		fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+	}
+
+	// Returns the number of data items in v (including v) and the deepest container nesting. Walks
+	// the tree with its own stack, as decoded values may be nested deeper than the call stack allows.
+	fn stats(v: &Value) -> (u64, u64) {
+		let mut items = 0;
+		let mut max_depth = 0;
+		// Each item with the number of containers it is in
+		let mut pending = vec![(v, 0)];
+		while let Some((v, depth)) = pending.pop() {
+			items += 1;
+			match v {
+				Value::Array(x) => {
+					max_depth = max_depth.max(depth + 1);
+					pending.extend(x.iter().map(|item| (item, depth + 1)));
+				}
+				Value::Map(x) => {
+					max_depth = max_depth.max(depth + 1);
+					for kv in x.iter() {
+						pending.push((&kv.key, depth + 1));
+						pending.push((&kv.val, depth + 1));
+					}
+				}
+				Value::Tag(_, x) => pending.push((x, depth)),
+				_ => {}
+			}
+		}
+		(items, max_depth)
+	}
+
+	pub(crate) fn decode<B: Byte, I: Iterator<Item = B>>(iter: &mut I, opts: &DecodeOptions) -> Result<Value> {
+		let span = tracing::debug_span!(
+			"decode",
+			input_len = Empty,
+			items = Empty,
+			max_depth = Empty,
+			elapsed_us = Empty
+		);
+		let _enter = span.enter();
+		let start = Instant::now();
+
+		let mut counted = Counted { inner: iter, count: 0 };
+		let result = crate::decode_first(&mut counted, opts);
+
+		let offset = counted.count as u64;
+		span.record("input_len", offset);
+		match &result {
+			// Walking the value is only worth it when something records the span
+			Ok(v) if !span.is_disabled() => {
+				let (items, max_depth) = stats(v);
+				span.record("items", items);
+				span.record("max_depth", max_depth);
+			}
+			Ok(_) => {}
+			Err(e) if *e.kind() == ErrorKind::DepthLimitExceeded => {
+				let limit = opts.max_depth as u64;
+				tracing::debug!(offset, limit, error = %e, "depth limit exceeded")
+			}
+			Err(e) if *e.kind() == ErrorKind::TagNestingTooDeep => {
+				let limit = opts.max_tag_depth as u64;
+				tracing::debug!(offset, limit, error = %e, "tag nesting limit exceeded")
+			}
+			Err(e) if matches!(e.kind(), ErrorKind::ArrayTooLong { .. }) => {
+				let limit = opts.max_array_len as u64;
+				tracing::debug!(offset, limit, error = %e, "array length limit exceeded")
+			}
+			Err(e) if matches!(e.kind(), ErrorKind::MapTooLong { .. }) => {
+				let limit = opts.max_map_len as u64;
+				tracing::debug!(offset, limit, error = %e, "map length limit exceeded")
+			}
+			Err(e) if matches!(e.kind(), ErrorKind::StringTooLong { .. }) => {
+				let limit = opts.max_string_len as u64;
+				tracing::debug!(offset, limit, error = %e, "string length limit exceeded")
+			}
+			Err(e) => tracing::debug!(offset, error = %e, "decode failed"),
+		}
+		span.record("elapsed_us", start.elapsed().as_micros() as u64);
+		result
+	}
+
+	// Appends to `out`; output_len counts only the bytes added
+	pub(crate) fn encode(v: &Value, out: &mut Vec<u8>) {
+		let span = tracing::debug_span!(
+			"encode",
+			output_len = Empty,
+			items = Empty,
+			max_depth = Empty,
+			elapsed_us = Empty
+		);
+		if !span.is_disabled() {
+			let (items, max_depth) = stats(v);
+			span.record("items", items);
+			span.record("max_depth", max_depth);
+		}
+		let _enter = span.enter();
+		let start = Instant::now();
+		let before = out.len();
+
+		v.encode_compact_into(out);
+
+		span.record("output_len", (out.len() - before) as u64);
+		span.record("elapsed_us", start.elapsed().as_micros() as u64);
+	}
+}
+
+pub(crate) use imp::*;
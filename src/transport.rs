@@ -0,0 +1,37 @@
+// CBOR carried as base64 or base16 text, as in web tokens
+
+use crate::base64;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+use crate::Value;
+
+fn decode_text(bytes: std::result::Result<Vec<u8>, String>) -> Result<Value> {
+	match bytes {
+		Ok(b) => crate::decode_slice(&b),
+		Err(msg) => CborError::new_err(ErrorKind::TextEncoding, msg.into()),
+	}
+}
+
+/// Decodes CBOR from base64 text. Both the standard and URL-safe alphabets are accepted, with or
+/// without padding.
+///
+/// # Examples
+///
+/// ```
+/// let v = cborg::decode_base64("gwECAw").unwrap();
+/// assert_eq!("[1, 2, 3]", v.to_diagnostic());
+/// assert_eq!(v, cborg::decode_base64("gwECAw==").unwrap());
+/// ```
+pub fn decode_base64(text: &str) -> Result<Value> { decode_text(base64::decode(text)) }
+
+/// Decodes CBOR from base16 text in either case.
+pub fn decode_hex(text: &str) -> Result<Value> { decode_text(base64::decode_hex(text)) }
+
+impl Value {
+	/// The value's encoding as base64url text without padding.
+	pub fn encode_base64url(&self) -> String { base64::encode_url(&self.encode()) }
+
+	/// The value's encoding as lowercase base16 text.
+	pub fn encode_hex(&self) -> String { base64::encode_hex(&self.encode()) }
+}
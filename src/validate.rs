@@ -0,0 +1,179 @@
+// Checking that a CBOR item is well formed without decoding it into a Value.
+
+use crate::header::read_header;
+use crate::header::Header;
+use crate::CborError;
+use crate::DecodeOptions;
+use crate::ErrorKind;
+use crate::Result;
+
+// An array or map whose elements are still being read
+struct Frame {
+	// None for indefinite length
+	remaining: Option<u64>,
+	is_map: bool,
+	// A key has been read and its value is still to come
+	in_entry: bool,
+}
+
+impl Frame {
+	// Counts an element that has been read in full
+	fn add(&mut self) {
+		if self.is_map {
+			self.in_entry = !self.in_entry;
+			if self.in_entry {
+				return;
+			}
+		}
+		if let Some(n) = self.remaining.as_mut() {
+			*n -= 1;
+		}
+	}
+
+	// Where a break code read in place of the next element was found, if it does not end this
+	fn break_context(&self) -> Option<&'static str> {
+		match self {
+			Frame { in_entry: true, .. } => Some("in place of a map value"),
+			Frame { remaining: None, .. } => None,
+			Frame { is_map: true, .. } => Some("inside a definite-length map"),
+			Frame { .. } => Some("inside a definite-length array"),
+		}
+	}
+}
+
+// Checks the content of the string whose header `h` is at `pos` and returns where it ends
+fn string(bytes: &[u8], pos: usize, h: &Header, opts: &DecodeOptions) -> Result<usize> {
+	let start = pos + h.len;
+	crate::prealloc(h.arg, 1, &bytes[start..].iter(), opts)?;
+	let end = start + h.arg as usize;
+	if h.major == 3 {
+		if let Err(e) = std::str::from_utf8(&bytes[start..end]) {
+			return CborError::wrap_err(ErrorKind::InvalidUtf8, e);
+		}
+	}
+	Ok(end)
+}
+
+/// Checks that `bytes` starts with a well-formed CBOR item that `decode_slice` accepts, without
+/// building a `Value`, and returns the number of bytes the item takes. Bytes after it are not
+/// looked at.
+///
+/// Headers, lengths, UTF-8, break codes and the nesting limits of the default [`DecodeOptions`] are
+/// checked as the decoder checks them, and failures have the same `ErrorKind`. Nothing is allocated
+/// apart from a stack with an entry per enclosing array or map.
+///
+/// # Examples
+///
+/// ```
+/// // [1, "a"] followed by another item
+/// assert_eq!(4, cborg::validate(&[0x82, 0x01, 0x61, 0x61, 0x00]).unwrap());
+/// // An array of two with one element
+/// assert!(cborg::validate(&[0x82, 0x01]).is_err());
+/// ```
+pub fn validate(bytes: &[u8]) -> Result<usize> {
+	let opts = DecodeOptions::default();
+	let mut stack = Vec::<Frame>::new();
+	let mut pos = 0;
+	loop {
+		// Tags directly in front of the item
+		let mut tags = 0;
+		let mut h = read_header(bytes, pos)?;
+		while h.major == 6 {
+			tags += 1;
+			if tags > opts.max_tag_depth {
+				return crate::tags_too_deep(opts.max_tag_depth);
+			}
+			pos += h.len;
+			h = read_header(bytes, pos)?;
+		}
+
+		let mut done = true;
+		if h.is_break() {
+			let context = match stack.last() {
+				_ if tags > 0 => Some("in place of a tag's content"),
+				None => Some("outside any indefinite-length item"),
+				Some(top) => top.break_context(),
+			};
+			if let Some(context) = context {
+				let msg = format!("break code {}", context);
+				return CborError::new_err(ErrorKind::InvalidBreak, msg.into());
+			}
+			pos += h.len;
+			stack.pop();
+		} else {
+			match h.major {
+				1 if h.arg > i64::MAX as u64 => {
+					return CborError::new_err(ErrorKind::LengthOverflow, "negative integer below i64::MIN".into());
+				}
+				2 | 3 if h.is_indefinite() => {
+					pos += h.len;
+					loop {
+						let b = match bytes.get(pos) {
+							Some(x) => *x,
+							None => return CborError::new_err(ErrorKind::InsufficientBytes, "".into()),
+						};
+						if b == 0xFF {
+							pos += 1;
+							break;
+						}
+						crate::check_reserved(b)?;
+						// Chunks must be definite-length strings of the same type
+						if b >> 5 != h.major || b & 31 == 31 {
+							let msg = format!(
+								"chunk with initial byte 0x{:02X} in an indefinite-length {} string",
+								b,
+								if h.major == 2 { "byte" } else { "text" }
+							);
+							return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+						}
+						pos = string(bytes, pos, &read_header(bytes, pos)?, &opts)?;
+					}
+				}
+				2 | 3 => pos = string(bytes, pos, &h, &opts)?,
+				4 | 5 => {
+					if stack.len() >= opts.max_depth {
+						return crate::too_deep(opts.max_depth);
+					}
+					pos += h.len;
+					let is_map = h.major == 5;
+					let remaining = if h.is_indefinite() {
+						None
+					} else {
+						let min_size = if is_map { 2 } else { 1 };
+						crate::prealloc(h.arg, min_size, &bytes[pos..].iter(), &opts)?;
+						Some(h.arg)
+					};
+					stack.push(Frame {
+						remaining,
+						is_map,
+						in_entry: false,
+					});
+					done = false;
+				}
+				7 if h.minor == 24 && h.arg < 32 => {
+					// RFC 8949 section 3.3: values below 32 only have the one-byte form
+					let msg = format!("simple value {} in two-byte form, which needs 32 or more", h.arg);
+					return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+				}
+				_ => pos += h.len,
+			}
+		}
+
+		// Count the finished item in the containers it completes
+		loop {
+			if done {
+				match stack.last_mut() {
+					None => return Ok(pos),
+					Some(top) => top.add(),
+				}
+			}
+			match stack.last() {
+				Some(top) if top.remaining == Some(0) => {
+					stack.pop();
+					done = true;
+				}
+				_ => break,
+			}
+		}
+	}
+}
@@ -0,0 +1,67 @@
+use super::Value;
+
+fn close(a: f64, b: f64, rel_tol: f64, abs_tol: f64) -> bool {
+	if a.is_nan() || b.is_nan() {
+		return a.is_nan() && b.is_nan();
+	}
+	if a == b {
+		return true;
+	}
+	// No tolerance brings an infinity any closer to something else
+	if a.is_infinite() || b.is_infinite() {
+		return false;
+	}
+	(a - b).abs() <= (rel_tol * a.abs().max(b.abs())).max(abs_tol)
+}
+
+impl Value {
+	/// Like `==`, but floats compare equal when within `rel_tol` of the larger magnitude or
+	/// within `abs_tol`, whichever is looser, and an integer compares equal to a float that close to
+	/// it. NaN equals NaN. Maps compare without regard to entry order; their keys must match
+	/// exactly. Everything else is compared exactly.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let sent = Value::Float(33.3);
+	/// let received = Value::Float(33.3f32 as f64);
+	/// assert!(sent != received);
+	/// assert!(sent.approx_eq(&received, 1e-6, 0.0));
+	/// ```
+	pub fn approx_eq(&self, other: &Value, rel_tol: f64, abs_tol: f64) -> bool {
+		let eq = |a: &Value, b: &Value| a.approx_eq(b, rel_tol, abs_tol);
+		match (self, other) {
+			(Value::Float(a), Value::Float(b)) => close(*a, *b, rel_tol, abs_tol),
+			(Value::Float(a), Value::Unsigned(b)) | (Value::Unsigned(b), Value::Float(a)) => {
+				close(*a, *b as f64, rel_tol, abs_tol)
+			}
+			(Value::Float(a), Value::Negative(b)) | (Value::Negative(b), Value::Float(a)) => {
+				close(*a, *b as f64, rel_tol, abs_tol)
+			}
+			(Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| eq(x, y)),
+			(Value::Map(a), Value::Map(b)) => {
+				if a.len() != b.len() {
+					return false;
+				}
+				// Each entry on the left claims a distinct matching entry on the right
+				let mut used = vec![false; b.len()];
+				a.iter().all(|x| {
+					let found = b
+						.iter()
+						.enumerate()
+						.position(|(i, y)| !used[i] && x.key == y.key && eq(&x.val, &y.val));
+					match found {
+						Some(i) => {
+							used[i] = true;
+							true
+						}
+						None => false,
+					}
+				})
+			}
+			(Value::Tag(t, a), Value::Tag(u, b)) => t == u && eq(a, b),
+			(a, b) => a == b,
+		}
+	}
+}
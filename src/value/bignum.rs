@@ -0,0 +1,88 @@
+use core::convert::TryFrom;
+
+use super::FromValue;
+use super::ToValue;
+use super::Value;
+use crate::tags;
+
+// The big-endian magnitude of a bignum, if it fits in 128 bits. Leading zero bytes are allowed.
+fn magnitude(bytes: &[u8]) -> Option<u128> {
+	let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+	let bytes = &bytes[start..];
+	if bytes.len() > 16 {
+		return None;
+	}
+	Some(bytes.iter().fold(0, |acc, b| acc << 8 | u128::from(*b)))
+}
+
+// A bignum with the minimal number of bytes: no leading zeros
+fn bignum(tag: u64, n: u128) -> Value {
+	let bytes = n.to_be_bytes();
+	let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+	Value::Tag(tag, Box::new(Value::ByteString(bytes[start..].to_vec())))
+}
+
+/// An unsigned integer if the value fits in 64 bits, otherwise a positive bignum (tag 2).
+impl ToValue for u128 {
+	fn to_value(&self) -> Value {
+		match u64::try_from(*self) {
+			Ok(x) => Value::Unsigned(x),
+			Err(_) => bignum(tags::POS_BIGNUM, *self),
+		}
+	}
+}
+
+/// An integer if the value fits in the `Unsigned` or `Negative` variants, otherwise a positive or
+/// negative bignum (tag 2 or 3).
+impl ToValue for i128 {
+	fn to_value(&self) -> Value {
+		match (u64::try_from(*self), i64::try_from(*self)) {
+			(Ok(x), _) => Value::Unsigned(x),
+			(_, Ok(x)) if x < 0 => Value::Negative(x),
+			_ if *self > 0 => bignum(tags::POS_BIGNUM, *self as u128),
+			// -1 - x cannot overflow for negative x
+			_ => bignum(tags::NEG_BIGNUM, (-1 - *self) as u128),
+		}
+	}
+}
+
+impl From<u128> for Value {
+	fn from(n: u128) -> Value { n.to_value() }
+}
+
+impl From<i128> for Value {
+	fn from(n: i128) -> Value { n.to_value() }
+}
+
+/// Accepts integers and positive bignums (tag 2).
+impl FromValue for u128 {
+	fn from_value(v: Value) -> Option<Self> { u128::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Unsigned(x) => Some(u128::from(*x)),
+			Value::Negative(_) => None,
+			Value::Tag(tags::POS_BIGNUM, x) => magnitude(x.as_byte_slice()?),
+			Value::Tag(tags::NEG_BIGNUM, _) => None,
+			Value::Tag(_, x) => u128::from_ref(x),
+			_ => None,
+		}
+	}
+}
+
+/// Accepts integers and positive and negative bignums (tags 2 and 3).
+impl FromValue for i128 {
+	fn from_value(v: Value) -> Option<Self> { i128::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Unsigned(x) => Some(i128::from(*x)),
+			Value::Negative(x) => Some(i128::from(*x)),
+			Value::Tag(tag @ tags::POS_BIGNUM, x) | Value::Tag(tag @ tags::NEG_BIGNUM, x) => {
+				let n = i128::try_from(magnitude(x.as_byte_slice()?)?).ok()?;
+				// A negative bignum n stands for -1 - n
+				Some(if *tag == tags::POS_BIGNUM { n } else { -1 - n })
+			}
+			Value::Tag(_, x) => i128::from_ref(x),
+			_ => None,
+		}
+	}
+}
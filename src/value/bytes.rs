@@ -0,0 +1,39 @@
+use bytes::Bytes;
+
+use super::untagged;
+use super::untagged_ref;
+use super::FromValue;
+use super::IntoValue;
+use super::ToValue;
+use super::Value;
+
+/// Takes over a `ByteString`'s buffer or shares a `SharedBytes` one, so neither copies the content.
+impl FromValue for Bytes {
+	fn from_value(v: Value) -> Option<Self> {
+		match untagged(v) {
+			Value::ByteString(b) => Some(Bytes::from(b)),
+			Value::SharedBytes(b) => Some(b),
+			_ => None,
+		}
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		match untagged_ref(v) {
+			Value::ByteString(b) => Some(Bytes::copy_from_slice(b)),
+			Value::SharedBytes(b) => Some(b.clone()),
+			_ => None,
+		}
+	}
+}
+
+impl ToValue for Bytes {
+	fn to_value(&self) -> Value { Value::SharedBytes(self.clone()) }
+}
+
+impl IntoValue for Bytes {
+	fn into_value(self) -> Value { Value::SharedBytes(self) }
+}
+
+impl From<Bytes> for Value {
+	fn from(b: Bytes) -> Value { Value::SharedBytes(b) }
+}
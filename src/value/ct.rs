@@ -0,0 +1,41 @@
+use std::hint::black_box;
+
+use super::Value;
+
+/// Compares `actual` against `expected` in time that depends only on their lengths: every byte of
+/// `expected` is visited and differences are accumulated rather than returned early. `black_box` keeps the
+/// optimiser from turning the loop back into an early-exit comparison.
+fn ct_eq_slices(actual: &[u8], expected: &[u8]) -> bool {
+	let mut acc = (actual.len() != expected.len()) as u8;
+	for (i, e) in expected.iter().enumerate() {
+		// Reading past the end of a shorter `actual` substitutes a zero; the length mismatch has
+		// already been recorded in `acc`.
+		let a = if i < actual.len() { actual[i] } else { 0 };
+		acc |= black_box(a ^ e);
+	}
+	black_box(acc) == 0
+}
+
+impl Value {
+	/// Constant-time comparison of a byte string value against `other`, for MACs, tokens and
+	/// similar secrets. Any value other than a byte string gives `false`.
+	pub fn ct_eq_bytes(&self, other: &[u8]) -> bool {
+		match self {
+			Value::ByteString(x) => ct_eq_slices(x, other),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => ct_eq_slices(x, other),
+			_ => false,
+		}
+	}
+}
+
+/// Constant-time comparison of two byte string values, running over the length of `expected`.
+/// Returns `false` unless both are byte strings with equal contents.
+pub fn ct_eq(actual: &Value, expected: &Value) -> bool {
+	match expected {
+		Value::ByteString(x) => actual.ct_eq_bytes(x),
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(x) => actual.ct_eq_bytes(x),
+		_ => false,
+	}
+}
@@ -0,0 +1,174 @@
+use super::FromValue;
+use super::ToValue;
+use super::Value;
+use crate::tags;
+use core::convert::TryFrom;
+
+// The [exponent, mantissa] array shared by decimal fractions and bigfloats
+fn parts(v: &Value) -> Option<(i64, i128)> {
+	let arr = match v {
+		Value::Array(x) if x.len() == 2 => x,
+		_ => return None,
+	};
+	let exponent = match arr[0] {
+		Value::Unsigned(x) => i64::try_from(x).ok()?,
+		Value::Negative(x) => x,
+		_ => return None,
+	};
+	let mantissa = match &arr[1] {
+		Value::Unsigned(_) | Value::Negative(_) | Value::Tag(tags::POS_BIGNUM, _) | Value::Tag(tags::NEG_BIGNUM, _) => {
+			i128::from_ref(&arr[1])?
+		}
+		_ => return None,
+	};
+	Some((exponent, mantissa))
+}
+
+// Whether `v` has the shape of an [exponent, mantissa] array, whatever the size of its numbers
+pub(crate) fn is_fraction_content(v: &Value) -> bool {
+	let arr = match v {
+		Value::Array(x) if x.len() == 2 => x,
+		_ => return false,
+	};
+	let int = |v: &Value| matches!(v, Value::Unsigned(_) | Value::Negative(_));
+	let bignum = |v: &Value| match v {
+		Value::Tag(tags::POS_BIGNUM, x) | Value::Tag(tags::NEG_BIGNUM, x) => x.as_byte_slice().is_some(),
+		_ => false,
+	};
+	int(&arr[0]) && (int(&arr[1]) || bignum(&arr[1]))
+}
+
+/// A decimal fraction (tag 4): `mantissa * 10^exponent`, exactly.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{Decimal, Value};
+/// let price = Decimal { exponent: -2, mantissa: 27315 };
+/// let v = Value::from(price);
+/// assert_eq!("4([-2, 27315])", v.to_diagnostic());
+/// assert_eq!(Some(price), v.get_decimal());
+/// assert_eq!(273.15, price.to_f64());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+	pub exponent: i64,
+	pub mantissa: i128,
+}
+
+impl Decimal {
+	pub(crate) fn from_content(v: &Value) -> Option<Decimal> {
+		parts(v).map(|(exponent, mantissa)| Decimal { exponent, mantissa })
+	}
+
+	/// The nearest `f64`. Values too large or too small for an `f64` give infinity or zero.
+	pub fn to_f64(&self) -> f64 {
+		// Parsing rounds correctly, which scaling by powers of ten would not
+		format!("{}e{}", self.mantissa, self.exponent)
+			.parse()
+			.unwrap_or(f64::NAN)
+	}
+}
+
+/// The mantissa is written as an integer, or as a bignum if it does not fit in 64 bits.
+impl ToValue for Decimal {
+	fn to_value(&self) -> Value {
+		let content = Value::Array(vec![self.exponent.to_value(), self.mantissa.to_value()]);
+		Value::tagged(tags::DECIMAL, content)
+	}
+}
+
+impl From<Decimal> for Value {
+	fn from(d: Decimal) -> Value { d.to_value() }
+}
+
+impl FromValue for Decimal {
+	fn from_value(v: Value) -> Option<Self> { Decimal::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Tag(tags::DECIMAL, x) => Decimal::from_content(x),
+			_ => None,
+		}
+	}
+}
+
+impl Value {
+	/// The decimal fraction (tag 4) this value holds. `None` if it is not one, or if the content
+	/// is not an array of an integer exponent and an integer or bignum mantissa that fit `Decimal`.
+	pub fn get_decimal(&self) -> Option<Decimal> { Decimal::from_ref(self) }
+}
+
+/// A bigfloat (tag 5): `mantissa * 2^exponent`, exactly.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{Bigfloat, Value};
+/// let v = Value::from(Bigfloat { exponent: -1, mantissa: 3 });
+/// assert_eq!("5([-1, 3])", v.to_diagnostic());
+/// assert_eq!(Some(1.5), v.get_bigfloat().map(|x| x.to_f64()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bigfloat {
+	pub exponent: i64,
+	pub mantissa: i128,
+}
+
+// x * 2^e, stepping by the largest normal powers of two so that only the result can overflow or
+// underflow
+fn scale(mut x: f64, mut e: i64) -> f64 {
+	let max = f64::from_bits(0x7FE0_0000_0000_0000); // 2^1023
+	let min = f64::from_bits(0x0010_0000_0000_0000); // 2^-1022
+	while e > 1023 {
+		if x == 0.0 || x.is_infinite() {
+			return x;
+		}
+		x *= max;
+		e -= 1023;
+	}
+	while e < -1022 {
+		if x == 0.0 {
+			return x;
+		}
+		x *= min;
+		e += 1022;
+	}
+	x * f64::from_bits(((e + 1023) as u64) << 52)
+}
+
+impl Bigfloat {
+	pub(crate) fn from_content(v: &Value) -> Option<Bigfloat> {
+		parts(v).map(|(exponent, mantissa)| Bigfloat { exponent, mantissa })
+	}
+
+	/// The nearest `f64`, or infinity or zero when the value is out of the `f64` range. Results
+	/// in the subnormal range may be off by one unit in the last place.
+	pub fn to_f64(&self) -> f64 { scale(self.mantissa as f64, self.exponent) }
+}
+
+/// The mantissa is written as an integer, or as a bignum if it does not fit in 64 bits.
+impl ToValue for Bigfloat {
+	fn to_value(&self) -> Value {
+		let content = Value::Array(vec![self.exponent.to_value(), self.mantissa.to_value()]);
+		Value::tagged(tags::BIGFLOAT, content)
+	}
+}
+
+impl From<Bigfloat> for Value {
+	fn from(b: Bigfloat) -> Value { b.to_value() }
+}
+
+impl FromValue for Bigfloat {
+	fn from_value(v: Value) -> Option<Self> { Bigfloat::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Tag(tags::BIGFLOAT, x) => Bigfloat::from_content(x),
+			_ => None,
+		}
+	}
+}
+
+impl Value {
+	/// The bigfloat (tag 5) this value holds, with the same conditions as [`Value::get_decimal`].
+	pub fn get_bigfloat(&self) -> Option<Bigfloat> { Bigfloat::from_ref(self) }
+}
@@ -0,0 +1,56 @@
+use core::convert::TryFrom;
+use std::time::Duration;
+
+use super::untagged_ref;
+use super::FromValue;
+use super::ToValue;
+use super::Value;
+
+/// A float number of seconds. Durations longer than about 104 days lose nanoseconds to the
+/// float's precision; [`Value::duration_exact`] keeps them.
+impl ToValue for Duration {
+	fn to_value(&self) -> Value { Value::Float(self.as_secs_f64()) }
+}
+
+impl From<Duration> for Value {
+	fn from(d: Duration) -> Value { d.to_value() }
+}
+
+/// Accepts an unsigned integer or float number of seconds, or a `[seconds, nanoseconds]` array.
+/// Negative, non-finite and out-of-range values give `None`.
+impl FromValue for Duration {
+	fn from_value(v: Value) -> Option<Self> { Duration::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match untagged_ref(v) {
+			Value::Unsigned(x) => Some(Duration::from_secs(*x)),
+			Value::Float(x) => Duration::try_from_secs_f64(*x).ok(),
+			Value::Array(arr) => match arr.as_slice() {
+				[Value::Unsigned(secs), Value::Unsigned(nanos)] if *nanos < 1_000_000_000 => {
+					Some(Duration::new(*secs, u32::try_from(*nanos).ok()?))
+				}
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+impl Value {
+	/// A duration as a `[seconds, nanoseconds]` array, which round-trips exactly where the float
+	/// form written by `ToValue` may not.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// use std::time::Duration;
+	/// let v = Value::duration_exact(Duration::new(90, 5));
+	/// assert_eq!("[90, 5]", v.to_diagnostic());
+	/// ```
+	pub fn duration_exact(d: Duration) -> Value {
+		Value::Array(vec![
+			Value::Unsigned(d.as_secs()),
+			Value::Unsigned(u64::from(d.subsec_nanos())),
+		])
+	}
+}
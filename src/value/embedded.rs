@@ -0,0 +1,44 @@
+use super::Value;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+impl Value {
+	/// An embedded data item (tag 24): `v` encoded and wrapped in a byte string.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let payload = Value::map(vec![("amount", 10u32)]);
+	/// let wrapped = Value::encode_embedded(&payload);
+	/// assert_eq!(r#"24(h'a166616d6f756e740a')"#, wrapped.to_diagnostic());
+	/// assert_eq!(payload, wrapped.decode_embedded(false).unwrap());
+	/// ```
+	pub fn encode_embedded(v: &Value) -> Value { Value::tagged(tags::EMBEDDED, Value::ByteString(v.encode())) }
+
+	/// Decodes the data item embedded in a tag 24 byte string. With `allow_untagged`, a byte string
+	/// without the tag is decoded as well. The bytes must hold exactly one data item; errors
+	/// say that they come from an embedded item.
+	pub fn decode_embedded(&self, allow_untagged: bool) -> Result<Value> {
+		let bytes = match self {
+			Value::Tag(tags::EMBEDDED, x) => match x.as_byte_slice() {
+				Some(b) => b,
+				None => return CborError::new_err(ErrorKind::UnexpectedValue, "tag 24 must contain a byte string".into()),
+			},
+			x => match x.as_byte_slice() {
+				Some(b) if allow_untagged => b,
+				_ => return CborError::new_err(ErrorKind::UnexpectedValue, "not a tag 24 embedded data item".into()),
+			},
+		};
+		let mut iter = bytes.iter();
+		let v = crate::decode_iter(&mut iter).map_err(|e| e.within("embedded data item (tag 24)".to_string()))?;
+		let trailing = iter.len();
+		if trailing > 0 {
+			let msg = format!("embedded data item (tag 24) is followed by {} more bytes", trailing);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+		Ok(v)
+	}
+}
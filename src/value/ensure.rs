@@ -0,0 +1,121 @@
+use super::KeyVal;
+use super::Map;
+use super::Value;
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Query;
+use crate::Result;
+
+fn kind(v: &Value) -> &'static str {
+	match v {
+		Value::Unsigned(_) | Value::Negative(_) => "an integer",
+		Value::ByteString(_) => "a byte string",
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(_) => "a byte string",
+		Value::Utf8String(_) => "a text string",
+		Value::Array(_) => "an array",
+		Value::Map(_) => "a map",
+		Value::Tag(..) => "a tag",
+		Value::Simple(_) => "a simple value",
+		Value::Raw(_) => "a raw value",
+		Value::Float(_) => "a float",
+	}
+}
+
+// The node created for a missing segment: an array if the next segment indexes into it, otherwise a map.
+fn empty_for(next: Option<&PathSegment>) -> Value {
+	match next {
+		Some(PathSegment::Index(_)) => Value::Array(Vec::new()),
+		_ => Value::Map(Map::new()),
+	}
+}
+
+fn conflict<T>(path: &Path, segment: &PathSegment, expected: &str, found: &Value) -> Result<T> {
+	let msg = format!(
+		"cannot follow {} at {}: expected {} but found {}",
+		segment,
+		path,
+		expected,
+		kind(found)
+	);
+	CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+}
+
+fn ensure<'a>(mut v: &'a mut Value, segments: &[PathSegment], path: &mut Path) -> Result<&'a mut Value> {
+	let (segment, rest) = match segments.split_first() {
+		Some(x) => x,
+		None => return Ok(v),
+	};
+	while let Value::Tag(_, inner) = v {
+		v = inner;
+	}
+	let child = match (segment, v) {
+		(PathSegment::Key(key), Value::Map(map)) => {
+			let i = match map.iter().position(|kv| kv.key == *key) {
+				Some(i) => i,
+				None => {
+					map.push(KeyVal {
+						key: key.clone(),
+						val: empty_for(rest.first()),
+					});
+					map.len() - 1
+				}
+			};
+			&mut map[i].val
+		}
+		(PathSegment::Index(i), Value::Array(arr)) => {
+			if *i == arr.len() {
+				arr.push(empty_for(rest.first()));
+			} else if *i > arr.len() {
+				let msg = format!(
+					"cannot follow {} at {}: the array has only {} elements",
+					segment,
+					path,
+					arr.len()
+				);
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
+			&mut arr[*i]
+		}
+		(PathSegment::Key(_), v) => return conflict(path, segment, "a map", v),
+		(PathSegment::Index(_), v) => return conflict(path, segment, "an array", v),
+	};
+	path.push(segment.clone());
+	ensure(child, rest, path)
+}
+
+impl Value {
+	/// Walks `path` from `self` and returns the node at its end, creating any missing nodes on the
+	/// way. A missing map entry is created, as is an array element whose index equals the array's
+	/// length. Created nodes are empty arrays when the following segment is an index and empty maps
+	/// otherwise, including the final node. Tags are looked through.
+	///
+	/// Fails, naming the segment, if an existing node is not a map where a key is needed or not an
+	/// array where an index is needed, or if an index is past the end of an array.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::path::PathSegment;
+	/// use cborg::{Map, Value};
+	/// let mut doc = Value::Map(Map::new());
+	/// let path = [PathSegment::Key("server".into()), PathSegment::Key("ports".into()), PathSegment::Index(0)];
+	/// *doc.ensure_path(&path).unwrap() = Value::Unsigned(8080);
+	/// assert_eq!(r#"{"server": {"ports": [8080]}}"#, doc.to_diagnostic());
+	/// ```
+	pub fn ensure_path(&mut self, path: &[PathSegment]) -> Result<&mut Value> { ensure(self, path, &mut Path::new()) }
+
+	/// Like [`Value::ensure_path`], with the path written in the notation [`Path`] displays, such
+	/// as `$.server.ports[0]`. Wildcards and recursive steps are rejected.
+	pub fn ensure_pointer(&mut self, pointer: &str) -> Result<&mut Value> {
+		match Query::parse(pointer)?.to_path() {
+			Some(path) => self.ensure_path(path.segments()),
+			None => {
+				let msg = format!("path {:?} contains a wildcard or recursive step", pointer);
+				CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+			}
+		}
+	}
+}
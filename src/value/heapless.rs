@@ -0,0 +1,159 @@
+use core::hash::Hash;
+
+use heapless::FnvIndexMap;
+use heapless::IndexMap;
+use heapless::String;
+use heapless::Vec;
+
+use super::untagged;
+use super::untagged_ref;
+use super::FromValue;
+use super::KeyVal;
+use super::ToValue;
+use super::Value;
+
+// Each conversion returns None if the decoded data does not fit in the container's capacity.
+
+impl<T, const N: usize> FromValue for Vec<T, N>
+where
+	T: FromValue,
+{
+	fn from_value(v: Value) -> Option<Self> {
+		let value_arr: std::vec::Vec<Value> = match untagged(v) {
+			Value::Array(x) => x,
+			_ => return None,
+		};
+
+		let mut arr = Vec::<T, N>::new();
+
+		for item in value_arr {
+			if let Some(x) = T::from_value(item) {
+				arr.push(x).ok()?;
+			}
+		}
+
+		Some(arr)
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		let value_arr: &std::vec::Vec<Value> = match untagged_ref(v) {
+			Value::Array(x) => x,
+			_ => return None,
+		};
+
+		let mut arr = Vec::<T, N>::new();
+
+		for item in value_arr {
+			if let Some(x) = T::from_ref(item) {
+				arr.push(x).ok()?;
+			}
+		}
+
+		Some(arr)
+	}
+}
+
+impl<const N: usize> FromValue for Vec<u8, N> {
+	fn from_value(v: Value) -> Option<Self> { Self::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		// Same inputs as the unbounded `Vec<u8>`: byte strings, arrays of bytes and typed arrays
+		Vec::from_slice(&std::vec::Vec::<u8>::from_ref(v)?).ok()
+	}
+}
+
+impl<const N: usize> FromValue for String<N> {
+	fn from_value(v: Value) -> Option<Self> { Self::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match untagged_ref(v) {
+			Value::Utf8String(s) => {
+				let mut string = String::<N>::new();
+				string.push_str(s).ok()?;
+				Some(string)
+			}
+			_ => None,
+		}
+	}
+}
+
+impl<K, V, const N: usize> FromValue for FnvIndexMap<K, V, N>
+where
+	K: FromValue + Eq + Hash,
+	V: FromValue,
+{
+	fn from_value(v: Value) -> Option<Self> {
+		let cmap: super::Map = match untagged(v) {
+			Value::Map(x) => x,
+			_ => return None,
+		};
+
+		let mut m = FnvIndexMap::<K, V, N>::new();
+
+		for kv in cmap {
+			if let Some(k) = K::from_value(kv.key) {
+				if let Some(v) = V::from_value(kv.val) {
+					m.insert(k, v).ok()?;
+				}
+			}
+		}
+
+		Some(m)
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		let cmap: &std::vec::Vec<KeyVal> = match untagged_ref(v) {
+			Value::Map(x) => x,
+			_ => return None,
+		};
+
+		let mut m = FnvIndexMap::<K, V, N>::new();
+
+		for kv in cmap {
+			if let Some(k) = K::from_ref(&kv.key) {
+				if let Some(v) = V::from_ref(&kv.val) {
+					m.insert(k, v).ok()?;
+				}
+			}
+		}
+
+		Some(m)
+	}
+}
+
+impl<T, const N: usize> ToValue for Vec<T, N>
+where
+	T: ToValue,
+{
+	fn to_value(&self) -> Value {
+		let mut arr = std::vec::Vec::<Value>::with_capacity(self.len());
+		for e in self {
+			arr.push(e.to_value());
+		}
+		Value::Array(arr)
+	}
+}
+
+impl<const N: usize> ToValue for Vec<u8, N> {
+	fn to_value(&self) -> Value { Value::ByteString(self.to_vec()) }
+}
+
+impl<const N: usize> ToValue for String<N> {
+	fn to_value(&self) -> Value { Value::Utf8String(std::string::String::from(self.as_str())) }
+}
+
+impl<K, V, S, const N: usize> ToValue for IndexMap<K, V, S, N>
+where
+	K: ToValue,
+	V: ToValue,
+{
+	fn to_value(&self) -> Value {
+		let mut v = std::vec::Vec::<KeyVal>::with_capacity(self.len());
+		for entry in self {
+			let kv = KeyVal {
+				key: entry.0.to_value(),
+				val: entry.1.to_value(),
+			};
+			v.push(kv);
+		}
+		Value::Map(v.into())
+	}
+}
@@ -0,0 +1,64 @@
+use super::Value;
+use crate::base64;
+use crate::tags;
+
+/// The text encoding that tags 21, 22 and 23 (RFC 8949 section 3.4.5.2) ask for when the byte
+/// strings inside them are converted to text, as in JSON or diagnostic output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextHint {
+	/// Tag 21: base64url without padding
+	Base64Url,
+	/// Tag 22: base64 with padding
+	Base64,
+	/// Tag 23: lowercase hexadecimal
+	Base16,
+}
+
+impl TextHint {
+	pub fn from_tag(tag: u64) -> Option<TextHint> {
+		match tag {
+			tags::BASE64URL => Some(TextHint::Base64Url),
+			tags::BASE64 => Some(TextHint::Base64),
+			tags::BASE16 => Some(TextHint::Base16),
+			_ => None,
+		}
+	}
+
+	pub fn tag(self) -> u64 {
+		match self {
+			TextHint::Base64Url => tags::BASE64URL,
+			TextHint::Base64 => tags::BASE64,
+			TextHint::Base16 => tags::BASE16,
+		}
+	}
+
+	/// Encodes `bytes` as this hint asks.
+	pub fn encode(self, bytes: &[u8]) -> String {
+		match self {
+			TextHint::Base64Url => base64::encode_url(bytes),
+			TextHint::Base64 => base64::encode_standard(bytes),
+			TextHint::Base16 => base64::encode_hex(bytes),
+		}
+	}
+}
+
+impl Value {
+	/// The text encoding that byte strings in this value should be rendered with: `Some` for tags
+	/// 21, 22 and 23, which apply to every byte string nested inside them until another of these
+	/// tags, and `None` for anything else.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{TextHint, Value};
+	/// let v = Value::Tag(22, Box::new(Value::bytes(b"hi".to_vec())));
+	/// assert_eq!(Some(TextHint::Base64), v.to_text_hint());
+	/// assert_eq!("22(b64'aGk=')", v.to_diagnostic());
+	/// ```
+	pub fn to_text_hint(&self) -> Option<TextHint> {
+		match self {
+			Value::Tag(t, _) => TextHint::from_tag(*t),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,83 @@
+use core::convert::TryFrom;
+use core::fmt;
+
+use super::untagged;
+use super::untagged_ref;
+use super::FromValue;
+use super::IntoValue;
+use super::ToValue;
+use super::Value;
+
+/// A map key that is either an integer or a text string, as used by COSE headers, CWT claims and
+/// similar protocols.
+///
+/// Integers sort before text, integers numerically and text lexicographically, so a
+/// `BTreeMap<Label, _>` lists the registered integer labels first.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Label {
+	Int(i64),
+	Text(String),
+}
+
+impl fmt::Display for Label {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Label::Int(x) => write!(f, "{}", x),
+			Label::Text(x) => f.write_str(x),
+		}
+	}
+}
+
+impl From<i64> for Label {
+	fn from(x: i64) -> Label { Label::Int(x) }
+}
+
+impl From<&str> for Label {
+	fn from(x: &str) -> Label { Label::Text(x.to_string()) }
+}
+
+impl From<String> for Label {
+	fn from(x: String) -> Label { Label::Text(x) }
+}
+
+impl ToValue for Label {
+	fn to_value(&self) -> Value {
+		match self {
+			Label::Int(x) => x.to_value(),
+			Label::Text(x) => Value::Utf8String(x.clone()),
+		}
+	}
+}
+
+impl IntoValue for Label {
+	fn into_value(self) -> Value {
+		match self {
+			Label::Int(x) => x.to_value(),
+			Label::Text(x) => Value::Utf8String(x),
+		}
+	}
+}
+
+impl From<Label> for Value {
+	fn from(l: Label) -> Value { l.into_value() }
+}
+
+/// Only integers that fit in an `i64` and text strings convert, looking through any tags; any
+/// other key gives `None`.
+impl FromValue for Label {
+	fn from_value(v: Value) -> Option<Self> {
+		match untagged(v) {
+			Value::Utf8String(x) => Some(Label::Text(x)),
+			v => Label::from_ref(&v),
+		}
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		match untagged_ref(v) {
+			Value::Unsigned(x) => i64::try_from(*x).ok().map(Label::Int),
+			Value::Negative(x) => Some(Label::Int(*x)),
+			Value::Utf8String(x) => Some(Label::Text(x.clone())),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,81 @@
+use super::FromValue;
+use super::ToValue;
+use super::Value;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+/// Text with a language tag, carried as tag 38 over `[language, text]`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LangString {
+	pub lang: String,
+	pub text: String,
+}
+
+/// Checks the shape of a BCP 47 language tag: hyphen-separated subtags of 1 to 8 ASCII letters or
+/// digits, the first being letters only. The subtags themselves are not checked against the
+/// registry.
+pub fn is_plausible_language_tag(lang: &str) -> bool {
+	let mut subtags = lang.split('-');
+	let first = subtags.next().unwrap_or("");
+	let valid = |s: &str| (1..=8).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric());
+	valid(first) && first.bytes().all(|b| b.is_ascii_alphabetic()) && subtags.all(valid)
+}
+
+impl LangString {
+	/// Fails if `lang` is not a plausible language tag. See [`is_plausible_language_tag`].
+	pub fn new(lang: impl Into<String>, text: impl Into<String>) -> Result<LangString> {
+		let lang = lang.into();
+		if !is_plausible_language_tag(&lang) {
+			let msg = format!("{:?} is not a valid language tag", lang);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+		Ok(LangString {
+			lang,
+			text: text.into(),
+		})
+	}
+}
+
+impl ToValue for LangString {
+	fn to_value(&self) -> Value {
+		let pair = vec![
+			Value::Utf8String(self.lang.clone()),
+			Value::Utf8String(self.text.clone()),
+		];
+		Value::tagged(tags::LANG_STRING, Value::Array(pair))
+	}
+}
+
+impl From<LangString> for Value {
+	fn from(s: LangString) -> Value {
+		Value::Tag(
+			tags::LANG_STRING,
+			Box::new(Value::Array(vec![Value::Utf8String(s.lang), Value::Utf8String(s.text)])),
+		)
+	}
+}
+
+impl FromValue for LangString {
+	fn from_value(v: Value) -> Option<Self> { v.as_lang_string() }
+	fn from_ref(v: &Value) -> Option<Self> { v.as_lang_string() }
+}
+
+impl Value {
+	/// Reads a tag 38 language-tagged string. Returns `None` for anything else, including a tag 38
+	/// whose content is not an array of two text strings or whose language tag is not plausible.
+	pub fn as_lang_string(&self) -> Option<LangString> {
+		let pair = match self {
+			Value::Tag(tags::LANG_STRING, x) => match x.as_ref() {
+				Value::Array(pair) if pair.len() == 2 => pair,
+				_ => return None,
+			},
+			_ => return None,
+		};
+		match (&pair[0], &pair[1]) {
+			(Value::Utf8String(lang), Value::Utf8String(text)) => LangString::new(lang.as_str(), text.as_str()).ok(),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,154 @@
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::slice;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::vec;
+
+use super::KeyVal;
+use super::Value;
+use crate::CborError;
+use crate::DuplicateKeyPolicy;
+use crate::ErrorKind;
+use crate::KeyOrder;
+use crate::Result;
+
+/// The entries of a CBOR map, kept in the order they were decoded or inserted.
+///
+/// Lookups compare keys with `==` and scan the entries, which is fast for the small maps CBOR
+/// usually carries. Decoded data may hold duplicate keys; lookups see the first of them.
+/// `Map` dereferences to the underlying `Vec<KeyVal>` for anything not covered here.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map(Vec<KeyVal>);
+
+impl Map {
+	pub fn new() -> Map { Map(Vec::new()) }
+
+	pub fn with_capacity(capacity: usize) -> Map { Map(Vec::with_capacity(capacity)) }
+
+	pub fn len(&self) -> usize { self.0.len() }
+
+	pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+	pub fn get(&self, key: &Value) -> Option<&Value> { self.0.iter().find(|kv| kv.key == *key).map(|kv| &kv.val) }
+
+	pub fn get_mut(&mut self, key: &Value) -> Option<&mut Value> {
+		self.0.iter_mut().find(|kv| kv.key == *key).map(|kv| &mut kv.val)
+	}
+
+	pub fn contains_key(&self, key: &Value) -> bool { self.0.iter().any(|kv| kv.key == *key) }
+
+	/// Sets the value for `key`, returning the previous value. An existing entry keeps its
+	/// position; a new one is appended.
+	pub fn insert(&mut self, key: Value, val: Value) -> Option<Value> {
+		match self.get_mut(&key) {
+			Some(x) => Some(std::mem::replace(x, val)),
+			None => {
+				self.0.push(KeyVal { key, val });
+				None
+			}
+		}
+	}
+
+	/// Removes the first entry with `key` and returns its value, keeping the order of the rest.
+	pub fn remove(&mut self, key: &Value) -> Option<Value> {
+		let pos = self.0.iter().position(|kv| kv.key == *key)?;
+		Some(self.0.remove(pos).val)
+	}
+
+	pub fn entries(&self) -> &[KeyVal] { &self.0 }
+
+	pub fn into_entries(self) -> Vec<KeyVal> { self.0 }
+
+	pub fn iter(&self) -> slice::Iter<'_, KeyVal> { self.0.iter() }
+
+	pub fn iter_mut(&mut self) -> slice::IterMut<'_, KeyVal> { self.0.iter_mut() }
+
+	/// Removes entries whose key appeared earlier or later, as `policy` says, keeping the order of
+	/// the rest. With `DuplicateKeyPolicy::Error` a repeated key is an error and the map is left
+	/// as it was.
+	pub fn dedup_keys(&mut self, policy: DuplicateKeyPolicy) -> Result<()> {
+		if policy == DuplicateKeyPolicy::Allow || self.0.len() < 2 {
+			return Ok(());
+		}
+		let mut keep = vec![true; self.0.len()];
+		// Index of the entry currently kept for each key
+		let mut kept = HashMap::<&Value, usize>::with_capacity(self.0.len());
+		for (i, kv) in self.0.iter().enumerate() {
+			match kept.entry(&kv.key) {
+				Entry::Vacant(e) => {
+					e.insert(i);
+				}
+				Entry::Occupied(mut e) => match policy {
+					DuplicateKeyPolicy::Error => {
+						let msg = format!("duplicate map key {}", kv.key.to_diagnostic());
+						return CborError::new_err(ErrorKind::DuplicateKey, msg.into());
+					}
+					DuplicateKeyPolicy::LastWins => keep[e.insert(i)] = false,
+					_ => keep[i] = false,
+				},
+			}
+		}
+		let mut keep = keep.into_iter();
+		self.0.retain(|_| keep.next() == Some(true));
+		Ok(())
+	}
+
+	/// Sorts the entries into the RFC 8949 deterministic order (bytewise by encoded key).
+	pub fn sort_keys(&mut self) { self.sort_keys_with(KeyOrder::Rfc8949) }
+
+	/// Sorts the entries by their encoded keys in the given order. The sort is stable, so
+	/// duplicate keys keep their relative order.
+	pub fn sort_keys_with(&mut self, order: KeyOrder) {
+		let mut keyed: Vec<(Vec<u8>, KeyVal)> = self
+			.0
+			.drain(..)
+			.map(|kv| (kv.key.encode_canonical_with(order), kv))
+			.collect();
+		keyed.sort_by(|a, b| order.compare(&a.0, &b.0));
+		self.0.extend(keyed.into_iter().map(|x| x.1));
+	}
+}
+
+impl Deref for Map {
+	type Target = Vec<KeyVal>;
+	fn deref(&self) -> &Vec<KeyVal> { &self.0 }
+}
+
+impl DerefMut for Map {
+	fn deref_mut(&mut self) -> &mut Vec<KeyVal> { &mut self.0 }
+}
+
+impl From<Vec<KeyVal>> for Map {
+	fn from(v: Vec<KeyVal>) -> Map { Map(v) }
+}
+
+impl From<Map> for Vec<KeyVal> {
+	fn from(m: Map) -> Vec<KeyVal> { m.0 }
+}
+
+impl core::iter::FromIterator<KeyVal> for Map {
+	fn from_iter<I: IntoIterator<Item = KeyVal>>(iter: I) -> Map { Map(iter.into_iter().collect()) }
+}
+
+impl Extend<KeyVal> for Map {
+	fn extend<I: IntoIterator<Item = KeyVal>>(&mut self, iter: I) { self.0.extend(iter) }
+}
+
+impl IntoIterator for Map {
+	type Item = KeyVal;
+	type IntoIter = vec::IntoIter<KeyVal>;
+	fn into_iter(self) -> Self::IntoIter { self.0.into_iter() }
+}
+
+impl<'a> IntoIterator for &'a Map {
+	type Item = &'a KeyVal;
+	type IntoIter = slice::Iter<'a, KeyVal>;
+	fn into_iter(self) -> Self::IntoIter { self.0.iter() }
+}
+
+impl<'a> IntoIterator for &'a mut Map {
+	type Item = &'a mut KeyVal;
+	type IntoIter = slice::IterMut<'a, KeyVal>;
+	fn into_iter(self) -> Self::IntoIter { self.0.iter_mut() }
+}
@@ -0,0 +1,73 @@
+use super::Value;
+use crate::tags;
+
+impl Value {
+	/// A MIME message (RFC 2045) as a text string under tag 36.
+	pub fn mime(text: impl Into<String>) -> Value { Value::tagged(tags::MIME, Value::Utf8String(text.into())) }
+
+	/// The text of a tag 36 MIME message. Untagged strings and tag 36 over anything but a text
+	/// string give `None`.
+	pub fn as_mime_str(&self) -> Option<&str> {
+		match self {
+			Value::Tag(tags::MIME, x) => match x.as_ref() {
+				Value::Utf8String(s) => Some(s),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+#[cfg(feature = "mime")]
+mod headers {
+	use super::Value;
+
+	/// Splits the header section of a MIME message into name/value pairs in their original order.
+	/// Headers end at the first blank line. Folded lines are joined to the header they continue,
+	/// values are trimmed, and lines without a colon are skipped. Nothing is decoded, so encoded
+	/// words and parameters are left as written.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let msg = "Subject: monthly\r\n report\r\nTo: a@example.com\r\n\r\nBody: not a header\r\n";
+	/// let headers = cborg::mime_headers(msg);
+	/// assert_eq!(vec![("Subject".to_string(), "monthly report".to_string()),
+	///                 ("To".to_string(), "a@example.com".to_string())], headers);
+	/// ```
+	pub fn mime_headers(message: &str) -> Vec<(String, String)> {
+		let mut headers = Vec::<(String, String)>::new();
+		// Whether the previous line was a header that a folded line may continue
+		let mut open = false;
+		for line in message.lines() {
+			if line.is_empty() {
+				break;
+			}
+			if line.starts_with(' ') || line.starts_with('\t') {
+				if let (true, Some(last)) = (open, headers.last_mut()) {
+					if !last.1.is_empty() {
+						last.1.push(' ');
+					}
+					last.1.push_str(line.trim());
+				}
+				continue;
+			}
+			open = match line.find(':') {
+				Some(i) => {
+					headers.push((line[..i].trim_end().to_string(), line[i + 1..].trim().to_string()));
+					true
+				}
+				None => false,
+			};
+		}
+		headers
+	}
+
+	impl Value {
+		/// The headers of a tag 36 MIME message; see [`mime_headers`]. `None` if `self` is not one.
+		pub fn mime_headers(&self) -> Option<Vec<(String, String)>> { self.as_mime_str().map(mime_headers) }
+	}
+}
+
+#[cfg(feature = "mime")]
+pub use headers::mime_headers;
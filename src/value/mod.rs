@@ -1,25 +1,66 @@
+mod approx;
+mod bignum;
+#[cfg(feature = "bytes")]
+mod bytes;
+mod ct;
+mod decimal;
+mod duration;
+mod embedded;
+mod ensure;
+#[cfg(feature = "heapless")]
+mod heapless;
+mod hint;
+mod label;
+mod lang_string;
+mod map;
+mod mime;
+#[cfg(feature = "unicode-normalization")]
+mod nfc;
+mod raw;
+mod regex;
+mod self_described;
+#[cfg(feature = "smallvec")]
+mod smallvec;
+mod tag_check;
+mod time;
+mod typed_array;
 pub mod types;
+mod uri;
+mod uuid;
 
 use core::convert::TryFrom;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 
+pub use ct::ct_eq;
+pub use decimal::Bigfloat;
+pub use decimal::Decimal;
+pub use hint::TextHint;
+pub use label::Label;
+pub use lang_string::is_plausible_language_tag;
+pub use lang_string::LangString;
+pub use map::Map;
+#[cfg(feature = "mime")]
+pub use mime::mime_headers;
+pub use raw::RawValue;
+pub(crate) use self_described::SELF_DESCRIBED_PREFIX;
+pub use time::ExtendedTime;
+pub use typed_array::Endian;
+pub use typed_array::TypedArrayElement;
+pub use types::should_color;
+pub use types::Colored;
 pub use types::KeyVal;
 pub use types::Simple;
+pub use types::SimpleNumber;
 pub use types::Value;
 
 impl TryFrom<Value> for u8 {
 	type Error = ();
 	fn try_from(value: Value) -> Result<u8, ()> {
 		match value {
-			Value::Unsigned(x) => match u8::try_from(x) {
-				Ok(x) => Ok(x),
-				Err(_) => Err(()),
-			},
-			Value::Negative(x) => match u8::try_from(x) {
-				Ok(x) => Ok(x),
-				Err(_) => Err(()),
-			},
+			Value::Unsigned(x) => u8::try_from(x).map_err(|_| ()),
+			Value::Negative(x) => u8::try_from(x).map_err(|_| ()),
 			_ => Err(()),
 		}
 	}
@@ -29,19 +70,28 @@ impl TryFrom<&Value> for u8 {
 	type Error = ();
 	fn try_from(value: &Value) -> Result<u8, ()> {
 		match value {
-			Value::Unsigned(x) => match u8::try_from(*x) {
-				Ok(x) => Ok(x),
-				Err(_) => Err(()),
-			},
-			Value::Negative(x) => match u8::try_from(*x) {
-				Ok(x) => Ok(x),
-				Err(_) => Err(()),
-			},
+			Value::Unsigned(x) => u8::try_from(*x).map_err(|_| ()),
+			Value::Negative(x) => u8::try_from(*x).map_err(|_| ()),
 			_ => Err(()),
 		}
 	}
 }
 
+// The conversions below look through tags so that tagged data converts like its content. Types
+// whose meaning comes from a tag, such as `ExtendedTime`, match on the tag themselves.
+fn untagged(mut v: Value) -> Value {
+	while let Value::Tag(_, x) = v {
+		v = *x;
+	}
+	v
+}
+fn untagged_ref(mut v: &Value) -> &Value {
+	while let Value::Tag(_, x) = v {
+		v = x;
+	}
+	v
+}
+
 pub trait FromValue {
 	fn from_value(v: Value) -> Option<Self>
 	where
@@ -58,22 +108,18 @@ impl FromValue for Value {
 }
 impl FromValue for u64 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
 			Value::Unsigned(x) => Some(x),
-			Value::Negative(x) => match u64::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Negative(x) => u64::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
 			Value::Unsigned(x) => Some(*x),
-			Value::Negative(x) => match u64::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Negative(x) => u64::try_from(*x).ok(),
 			_ => None,
 		}
 	}
@@ -81,28 +127,37 @@ impl FromValue for u64 {
 
 impl FromValue for u32 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match u32::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match u32::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => u32::try_from(x).ok(),
+			Value::Negative(x) => u32::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match u32::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match u32::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => u32::try_from(*x).ok(),
+			Value::Negative(x) => u32::try_from(*x).ok(),
+			_ => None,
+		}
+	}
+}
+
+impl FromValue for u16 {
+	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
+		match v {
+			Value::Unsigned(x) => u16::try_from(x).ok(),
+			Value::Negative(x) => u16::try_from(x).ok(),
+			_ => None,
+		}
+	}
+	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
+		match v {
+			Value::Unsigned(x) => u16::try_from(*x).ok(),
+			Value::Negative(x) => u16::try_from(*x).ok(),
 			_ => None,
 		}
 	}
@@ -110,28 +165,18 @@ impl FromValue for u32 {
 
 impl FromValue for usize {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match usize::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match usize::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => usize::try_from(x).ok(),
+			Value::Negative(x) => usize::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match usize::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match usize::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => usize::try_from(*x).ok(),
+			Value::Negative(x) => usize::try_from(*x).ok(),
 			_ => None,
 		}
 	}
@@ -139,21 +184,17 @@ impl FromValue for usize {
 
 impl FromValue for i64 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match i64::try_from(x) {
-				Ok(x) => Some(x as i64),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i64::try_from(x).ok(),
 			Value::Negative(x) => Some(x),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match i64::try_from(*x) {
-				Ok(x) => Some(x as i64),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i64::try_from(*x).ok(),
 			Value::Negative(x) => Some(*x),
 			_ => None,
 		}
@@ -162,28 +203,37 @@ impl FromValue for i64 {
 
 impl FromValue for i32 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match i32::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match i32::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i32::try_from(x).ok(),
+			Value::Negative(x) => i32::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match i32::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match i32::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i32::try_from(*x).ok(),
+			Value::Negative(x) => i32::try_from(*x).ok(),
+			_ => None,
+		}
+	}
+}
+
+impl FromValue for i16 {
+	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
+		match v {
+			Value::Unsigned(x) => i16::try_from(x).ok(),
+			Value::Negative(x) => i16::try_from(x).ok(),
+			_ => None,
+		}
+	}
+	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
+		match v {
+			Value::Unsigned(x) => i16::try_from(*x).ok(),
+			Value::Negative(x) => i16::try_from(*x).ok(),
 			_ => None,
 		}
 	}
@@ -191,28 +241,18 @@ impl FromValue for i32 {
 
 impl FromValue for i8 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match i8::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match i8::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i8::try_from(x).ok(),
+			Value::Negative(x) => i8::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match i8::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match i8::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => i8::try_from(*x).ok(),
+			Value::Negative(x) => i8::try_from(*x).ok(),
 			_ => None,
 		}
 	}
@@ -220,32 +260,24 @@ impl FromValue for i8 {
 
 impl FromValue for isize {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
-			Value::Unsigned(x) => match isize::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match isize::try_from(x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => isize::try_from(x).ok(),
+			Value::Negative(x) => isize::try_from(x).ok(),
 			_ => None,
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
-			Value::Unsigned(x) => match isize::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
-			Value::Negative(x) => match isize::try_from(*x) {
-				Ok(x) => Some(x),
-				Err(_) => None,
-			},
+			Value::Unsigned(x) => isize::try_from(*x).ok(),
+			Value::Negative(x) => isize::try_from(*x).ok(),
 			_ => None,
 		}
 	}
 }
+/// When a key repeats, the last entry wins. Decode with
+/// [`DecodeOptions::duplicate_keys`](crate::DecodeOptions::duplicate_keys) to choose otherwise.
 impl<K, V, S> FromValue for HashMap<K, V, S>
 where
 	K: FromValue + Eq + std::hash::Hash,
@@ -253,7 +285,8 @@ where
 	S: std::hash::BuildHasher + Default,
 {
 	fn from_value(v: Value) -> Option<Self> {
-		let cmap: Vec<KeyVal> = match v {
+		let v = untagged(v);
+		let cmap: Map = match v {
 			Value::Map(x) => x,
 			_ => return None,
 		};
@@ -272,6 +305,7 @@ where
 	}
 
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		let cmap: &Vec<KeyVal> = match v {
 			Value::Map(x) => x,
 			_ => return None,
@@ -291,13 +325,15 @@ where
 	}
 }
 
+/// When a key repeats, the last entry wins, as for `HashMap`.
 impl<K, V> FromValue for BTreeMap<K, V>
 where
 	K: FromValue + std::cmp::Ord,
 	V: FromValue,
 {
 	fn from_value(v: Value) -> Option<Self> {
-		let cmap: Vec<KeyVal> = match v {
+		let v = untagged(v);
+		let cmap: Map = match v {
 			Value::Map(x) => x,
 			_ => return None,
 		};
@@ -315,6 +351,7 @@ where
 		Some(m)
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		let cmap: &Vec<KeyVal> = match v {
 			Value::Map(x) => x,
 			_ => return None,
@@ -334,6 +371,46 @@ where
 	}
 }
 
+impl<T> FromValue for BTreeSet<T>
+where
+	T: FromValue + std::cmp::Ord,
+{
+	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
+		let value_arr: Vec<Value> = match v {
+			Value::Array(x) => x,
+			_ => return None,
+		};
+
+		let mut set = BTreeSet::<T>::new();
+
+		for item in value_arr {
+			if let Some(x) = T::from_value(item) {
+				set.insert(x);
+			}
+		}
+
+		Some(set)
+	}
+	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
+		let value_arr: &Vec<Value> = match v {
+			Value::Array(x) => x,
+			_ => return None,
+		};
+
+		let mut set = BTreeSet::<T>::new();
+
+		for item in value_arr {
+			if let Some(x) = T::from_ref(item) {
+				set.insert(x);
+			}
+		}
+
+		Some(set)
+	}
+}
+
 // Needs specialization feature in Stable
 // impl FromValue for u8 {
 // 	fn from_value(v: Value) -> Option<Self> {
@@ -356,12 +433,16 @@ where
 	T: FromValue,
 {
 	fn from_value(v: Value) -> Option<Self> {
+		if let Some(items) = typed_array::elements(&v) {
+			return Some(items.into_iter().filter_map(T::from_value).collect());
+		}
+		let v = untagged(v);
 		let value_arr: Vec<Value> = match v {
 			Value::Array(x) => x,
 			Value::Map(m) => {
 				let mut arr = Vec::<T>::new();
 				for kv in m {
-					if let Some(x) = T::from_value(Value::Map(vec![kv.clone()])) {
+					if let Some(x) = T::from_value(Value::Map(vec![kv.clone()].into())) {
 						arr.push(x);
 					}
 				}
@@ -382,12 +463,16 @@ where
 	}
 
 	fn from_ref(v: &Value) -> Option<Self> {
+		if let Some(items) = typed_array::elements(v) {
+			return Some(items.into_iter().filter_map(T::from_value).collect());
+		}
+		let v = untagged_ref(v);
 		let value_arr: &Vec<Value> = match v {
 			Value::Array(x) => x,
 			Value::Map(m) => {
 				let mut arr = Vec::<T>::new();
 				for kv in m {
-					if let Some(x) = T::from_value(Value::Map(vec![kv.clone()])) {
+					if let Some(x) = T::from_value(Value::Map(vec![kv.clone()].into())) {
 						arr.push(x);
 					}
 				}
@@ -409,49 +494,84 @@ where
 }
 impl FromValue for Vec<u8> {
 	fn from_value(v: Value) -> Option<Self> {
+		if let Some(items) = typed_array::elements(&v) {
+			return items.into_iter().map(|x| u8::try_from(x).ok()).collect();
+		}
+		let v = untagged(v);
 		let value_arr: Vec<Value> = match v {
 			Value::ByteString(bs) => return Some(bs),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(bs) => return Some(bs.to_vec()),
 			Value::Array(x) => x,
 			_ => return None,
 		};
 
-		let mut arr = Vec::<u8>::new();
+		// Every element must be a byte; dropping the ones that are not would silently corrupt data
+		let mut arr = Vec::<u8>::with_capacity(value_arr.len());
 
 		for item in value_arr {
-			if let Ok(x) = u8::try_from(item) {
-				arr.push(x);
-			}
+			arr.push(u8::try_from(item).ok()?);
 		}
 
 		Some(arr)
 	}
 
 	fn from_ref(v: &Value) -> Option<Self> {
+		if let Some(items) = typed_array::elements(v) {
+			return items.into_iter().map(|x| u8::try_from(x).ok()).collect();
+		}
+		let v = untagged_ref(v);
 		let value_arr: &Vec<Value> = match v {
 			Value::ByteString(bs) => return Some(bs.clone()),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(bs) => return Some(bs.to_vec()),
 			Value::Array(x) => x,
 			_ => return None,
 		};
 
-		let mut arr = Vec::<u8>::new();
+		// Every element must be a byte; dropping the ones that are not would silently corrupt data
+		let mut arr = Vec::<u8>::with_capacity(value_arr.len());
 
 		for item in value_arr {
-			if let Ok(x) = u8::try_from(item) {
-				arr.push(x);
-			}
+			arr.push(u8::try_from(item).ok()?);
 		}
 
 		Some(arr)
 	}
 }
 
+/// Fixed-size binary fields such as hashes and keys: only a byte string of exactly `N` bytes
+/// converts.
+impl<const N: usize> FromValue for [u8; N] {
+	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
+		match v {
+			Value::ByteString(bs) => <[u8; N]>::try_from(bs).ok(),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(bs) => <[u8; N]>::try_from(&bs[..]).ok(),
+			_ => None,
+		}
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
+		match v {
+			Value::ByteString(bs) => <[u8; N]>::try_from(bs.as_slice()).ok(),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(bs) => <[u8; N]>::try_from(&bs[..]).ok(),
+			_ => None,
+		}
+	}
+}
+
 impl<K, V> FromValue for (K, V)
 where
 	K: FromValue,
 	V: FromValue,
 {
 	fn from_value(v: Value) -> Option<Self> {
-		let pair: Vec<KeyVal> = match v {
+		let v = untagged(v);
+		let pair: Map = match v {
 			Value::Map(m) => m,
 			_ => return None,
 		};
@@ -472,6 +592,7 @@ where
 	}
 
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		let pair: &Vec<KeyVal> = match v {
 			Value::Map(m) => m,
 			_ => return None,
@@ -493,11 +614,12 @@ where
 	}
 }
 impl FromValue for String {
-	fn from_value(v: Value) -> Option<Self> { v.get_string() }
-	fn from_ref(v: &Value) -> Option<Self> { v.get_string() }
+	fn from_value(v: Value) -> Option<Self> { untagged(v).get_string() }
+	fn from_ref(v: &Value) -> Option<Self> { untagged_ref(v).get_string() }
 }
 impl FromValue for f64 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
 			Value::Unsigned(x) => Some(x as f64),
 			Value::Negative(x) => Some(x as f64),
@@ -506,6 +628,7 @@ impl FromValue for f64 {
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
 			Value::Unsigned(x) => Some(*x as f64),
 			Value::Negative(x) => Some(*x as f64),
@@ -515,8 +638,10 @@ impl FromValue for f64 {
 	}
 }
 
+// Lossy: rounds to the nearest f32. See Value::as_f32_exact() for a checked conversion.
 impl FromValue for f32 {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
 			Value::Unsigned(x) => Some(x as f32),
 			Value::Negative(x) => Some(x as f32),
@@ -525,6 +650,7 @@ impl FromValue for f32 {
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
 			Value::Unsigned(x) => Some(*x as f32),
 			Value::Negative(x) => Some(*x as f32),
@@ -535,6 +661,7 @@ impl FromValue for f32 {
 }
 impl FromValue for bool {
 	fn from_value(v: Value) -> Option<Self> {
+		let v = untagged(v);
 		match v {
 			Value::Simple(x) => match x {
 				Simple::True => Some(true),
@@ -545,6 +672,7 @@ impl FromValue for bool {
 		}
 	}
 	fn from_ref(v: &Value) -> Option<Self> {
+		let v = untagged_ref(v);
 		match v {
 			Value::Simple(x) => match x {
 				Simple::True => Some(true),
@@ -585,6 +713,9 @@ impl ToValue for Value {
 // 		Value::Unsigned(u64::from(*self))
 // 	}
 // }
+impl ToValue for u16 {
+	fn to_value(&self) -> Value { Value::Unsigned(u64::from(*self)) }
+}
 impl ToValue for u32 {
 	fn to_value(&self) -> Value { Value::Unsigned(u64::from(*self)) }
 }
@@ -600,6 +731,15 @@ impl ToValue for i8 {
 		}
 	}
 }
+impl ToValue for i16 {
+	fn to_value(&self) -> Value {
+		if *self < 0 {
+			Value::Negative(i64::from(*self))
+		} else {
+			Value::Unsigned(*self as u64)
+		}
+	}
+}
 impl ToValue for i32 {
 	fn to_value(&self) -> Value {
 		if *self < 0 {
@@ -621,14 +761,20 @@ impl ToValue for i64 {
 impl ToValue for Vec<u8> {
 	fn to_value(&self) -> Value { Value::ByteString(self.clone()) }
 }
+impl<const N: usize> ToValue for [u8; N] {
+	fn to_value(&self) -> Value { Value::ByteString(self.to_vec()) }
+}
 impl ToValue for String {
 	fn to_value(&self) -> Value { Value::Utf8String(self.clone()) }
 }
 impl ToValue for str {
 	fn to_value(&self) -> Value { Value::Utf8String(String::from(self)) }
 }
-impl ToValue for &str {
-	fn to_value(&self) -> Value { Value::Utf8String(String::from(*self)) }
+impl<T: ToValue + ?Sized> ToValue for &T {
+	fn to_value(&self) -> Value { (**self).to_value() }
+}
+impl<T: ToValue + ?Sized> ToValue for Box<T> {
+	fn to_value(&self) -> Value { (**self).to_value() }
 }
 impl<T> ToValue for Vec<T>
 where
@@ -642,6 +788,12 @@ where
 		Value::Array(arr)
 	}
 }
+impl<T> ToValue for [T]
+where
+	T: ToValue,
+{
+	fn to_value(&self) -> Value { Value::Array(self.iter().map(ToValue::to_value).collect()) }
+}
 impl<K, V, S> ToValue for HashMap<K, V, S>
 where
 	K: ToValue,
@@ -656,7 +808,7 @@ where
 			};
 			v.push(kv);
 		}
-		Value::Map(v)
+		Value::Map(v.into())
 	}
 }
 
@@ -674,7 +826,19 @@ where
 			};
 			v.push(kv);
 		}
-		Value::Map(v)
+		Value::Map(v.into())
+	}
+}
+impl<T> ToValue for BTreeSet<T>
+where
+	T: ToValue,
+{
+	fn to_value(&self) -> Value {
+		let mut arr = Vec::<Value>::with_capacity(self.len());
+		for e in self {
+			arr.push(e.to_value());
+		}
+		Value::Array(arr)
 	}
 }
 impl ToValue for f32 {
@@ -693,11 +857,93 @@ impl ToValue for bool {
 	}
 }
 // -----------------------------------------------------------------------------
+/// Consuming conversion into a `Value`. Owned strings, byte vectors and containers move their
+/// storage into the result instead of cloning it as `ToValue` must. References to anything that
+/// implements `ToValue` fall back to `to_value`.
+pub trait IntoValue {
+	fn into_value(self) -> Value;
+}
+impl<T: ToValue + ?Sized> IntoValue for &T {
+	fn into_value(self) -> Value { self.to_value() }
+}
+macro_rules! into_value_by_copy {
+	($($t:ty),*) => {
+		$(impl IntoValue for $t {
+			fn into_value(self) -> Value { self.to_value() }
+		})*
+	};
+}
+into_value_by_copy!(
+	u16,
+	u32,
+	u64,
+	u128,
+	i8,
+	i16,
+	i32,
+	i64,
+	i128,
+	f32,
+	f64,
+	bool,
+	std::time::Duration
+);
+impl IntoValue for Value {
+	fn into_value(self) -> Value { self }
+}
+impl IntoValue for Map {
+	fn into_value(self) -> Value { Value::Map(self) }
+}
+impl IntoValue for String {
+	fn into_value(self) -> Value { Value::Utf8String(self) }
+}
+impl IntoValue for Vec<u8> {
+	fn into_value(self) -> Value { Value::ByteString(self) }
+}
+impl<const N: usize> IntoValue for [u8; N] {
+	fn into_value(self) -> Value { Value::ByteString(self.to_vec()) }
+}
+impl<T: IntoValue> IntoValue for Vec<T> {
+	fn into_value(self) -> Value { Value::Array(self.into_iter().map(IntoValue::into_value).collect()) }
+}
+impl<T: IntoValue> IntoValue for BTreeSet<T> {
+	fn into_value(self) -> Value { Value::Array(self.into_iter().map(IntoValue::into_value).collect()) }
+}
+impl<K: IntoValue, V: IntoValue, S> IntoValue for HashMap<K, V, S> {
+	fn into_value(self) -> Value {
+		Value::Map(
+			self
+				.into_iter()
+				.map(|(k, v)| KeyVal {
+					key: k.into_value(),
+					val: v.into_value(),
+				})
+				.collect(),
+		)
+	}
+}
+impl<K: IntoValue, V: IntoValue> IntoValue for BTreeMap<K, V> {
+	fn into_value(self) -> Value {
+		Value::Map(
+			self
+				.into_iter()
+				.map(|(k, v)| KeyVal {
+					key: k.into_value(),
+					val: v.into_value(),
+				})
+				.collect(),
+		)
+	}
+}
+// -----------------------------------------------------------------------------
 // impl From<u8> for Value {
 // 	fn from(i: u8) -> Value {
 // 		Value::Unsigned(u64::from(i))
 // 	}
 // }
+impl From<u16> for Value {
+	fn from(i: u16) -> Value { Value::Unsigned(u64::from(i)) }
+}
 impl From<u32> for Value {
 	fn from(i: u32) -> Value { Value::Unsigned(u64::from(i)) }
 }
@@ -713,6 +959,15 @@ impl From<i8> for Value {
 		}
 	}
 }
+impl From<i16> for Value {
+	fn from(i: i16) -> Value {
+		if i < 0 {
+			Value::Negative(i64::from(i))
+		} else {
+			Value::Unsigned(i as u64)
+		}
+	}
+}
 impl From<i32> for Value {
 	fn from(i: i32) -> Value {
 		if i < 0 {
@@ -734,6 +989,9 @@ impl From<i64> for Value {
 impl From<Vec<u8>> for Value {
 	fn from(v: Vec<u8>) -> Self { Value::ByteString(v) }
 }
+impl<const N: usize> From<[u8; N]> for Value {
+	fn from(v: [u8; N]) -> Self { Value::ByteString(v.to_vec()) }
+}
 impl From<String> for Value {
 	fn from(s: String) -> Self { Value::Utf8String(s) }
 }
@@ -767,7 +1025,7 @@ where
 			};
 			v.push(kv);
 		}
-		Value::Map(v)
+		Value::Map(v.into())
 	}
 }
 
@@ -785,7 +1043,19 @@ where
 			};
 			v.push(kv);
 		}
-		Value::Map(v)
+		Value::Map(v.into())
+	}
+}
+impl<T> From<BTreeSet<T>> for Value
+where
+	Value: From<T>,
+{
+	fn from(set: BTreeSet<T>) -> Self {
+		let mut arr = Vec::<Value>::with_capacity(set.len());
+		for e in set {
+			arr.push(Value::from(e));
+		}
+		Value::Array(arr)
 	}
 }
 impl From<f32> for Value {
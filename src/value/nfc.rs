@@ -0,0 +1,68 @@
+use unicode_normalization::is_nfc;
+
+use super::Value;
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+fn walk(v: &Value, path: &mut Path, out: &mut Vec<Path>) {
+	match v {
+		Value::Utf8String(s) if !is_nfc(s) => out.push(path.clone()),
+		Value::Array(x) => {
+			for (i, item) in x.iter().enumerate() {
+				path.push(PathSegment::Index(i));
+				walk(item, path, out);
+				path.pop();
+			}
+		}
+		Value::Map(x) => {
+			for kv in x {
+				path.push(PathSegment::Key(kv.key.clone()));
+				// A key is reported at the path of its entry
+				if let Value::Utf8String(s) = &kv.key {
+					if !is_nfc(s) {
+						out.push(path.clone());
+					}
+				}
+				walk(&kv.val, path, out);
+				path.pop();
+			}
+		}
+		Value::Tag(_, x) => walk(x, path, out),
+		_ => (),
+	}
+}
+
+impl Value {
+	/// Paths of the text strings that are not in Unicode Normalization Form C, in document order.
+	/// A map key that is not in NFC is reported at the path of its entry, and again for its value
+	/// if that is not in NFC either.
+	pub fn nfc_violations(&self) -> Vec<Path> {
+		let mut out = Vec::new();
+		walk(self, &mut Path::new(), &mut out);
+		out
+	}
+
+	/// Fails with the path of the first text string that is not in Unicode Normalization Form C.
+	/// Strings that look identical but are composed differently can otherwise be used to spoof
+	/// identifiers.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// assert!(Value::text("caf\u{e9}").check_nfc().is_ok());
+	/// assert!(Value::text("cafe\u{301}").check_nfc().is_err());
+	/// ```
+	pub fn check_nfc(&self) -> Result<()> {
+		match self.nfc_violations().first() {
+			Some(path) => {
+				let msg = format!("text at {} is not in Unicode Normalization Form C", path);
+				CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+			}
+			None => Ok(()),
+		}
+	}
+}
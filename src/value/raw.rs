@@ -0,0 +1,52 @@
+use super::ToValue;
+use super::Value;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+/// The encoding of a single CBOR data item, to be embedded in a larger value without decoding and
+/// re-encoding it. `Value::encode` and the other encoders write the bytes verbatim.
+///
+/// # Examples
+///
+/// ```
+/// use cborg::{RawValue, Value};
+/// let body = RawValue::new(vec![0x82, 0x01, 0x02]).unwrap();
+/// let envelope = Value::map(vec![("body", Value::Raw(body))]);
+/// assert_eq!(vec![0xA1, 0x64, b'b', b'o', b'd', b'y', 0x82, 0x01, 0x02], envelope.encode());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RawValue(Vec<u8>);
+
+impl RawValue {
+	/// Fails unless `bytes` is exactly one well-formed data item.
+	pub fn new(bytes: Vec<u8>) -> Result<RawValue> {
+		let mut iter = bytes.iter();
+		crate::decode_iter(&mut iter)?;
+		let rest = iter.len();
+		if rest > 0 {
+			let msg = format!("raw value is followed by {} more bytes", rest);
+			return CborError::new_err(ErrorKind::TrailingBytes { remaining: rest }, msg.into());
+		}
+		Ok(RawValue(bytes))
+	}
+
+	pub fn as_bytes(&self) -> &[u8] { &self.0 }
+
+	pub fn into_bytes(self) -> Vec<u8> { self.0 }
+
+	/// Decodes the item.
+	pub fn decode(&self) -> Value { crate::decode_slice(&self.0).expect("RawValue holds a well-formed data item") }
+}
+
+impl std::fmt::Debug for RawValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "RawValue({:02x?})", self.0) }
+}
+
+impl ToValue for RawValue {
+	fn to_value(&self) -> Value { Value::Raw(self.clone()) }
+}
+
+impl From<RawValue> for Value {
+	fn from(r: RawValue) -> Value { Value::Raw(r) }
+}
@@ -0,0 +1,56 @@
+use super::Value;
+use crate::tags;
+
+impl Value {
+	/// A regular expression: the pattern as a text string under tag 35.
+	pub fn regex(pattern: &str) -> Value { Value::tagged(tags::REGEX, Value::Utf8String(pattern.to_string())) }
+
+	/// The pattern of a tag 35 regular expression. Untagged strings and tag 35 over anything but
+	/// a text string give `None`.
+	pub fn as_regex_str(&self) -> Option<&str> {
+		match self {
+			Value::Tag(tags::REGEX, x) => match x.as_ref() {
+				Value::Utf8String(s) => Some(s),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+#[cfg(feature = "regex")]
+mod compiled {
+	use regex::Regex;
+
+	use super::super::FromValue;
+	use super::super::ToValue;
+	use super::Value;
+	use crate::CborError;
+	use crate::ErrorKind;
+	use crate::Result;
+
+	impl Value {
+		/// Compiles a tag 35 regular expression, reporting why if the pattern is invalid.
+		pub fn to_regex(&self) -> Result<Regex> {
+			let pattern = match self.as_regex_str() {
+				Some(x) => x,
+				None => return CborError::new_err(ErrorKind::UnexpectedValue, "not a tag 35 regular expression".into()),
+			};
+			Regex::new(pattern).map_err(|e| CborError::wrap(ErrorKind::UnexpectedValue, e))
+		}
+	}
+
+	impl ToValue for Regex {
+		fn to_value(&self) -> Value { Value::regex(self.as_str()) }
+	}
+
+	impl From<Regex> for Value {
+		fn from(r: Regex) -> Value { Value::regex(r.as_str()) }
+	}
+
+	/// Fails if the pattern does not compile. Use [`Value::to_regex`] to see the compile error.
+	impl FromValue for Regex {
+		fn from_value(v: Value) -> Option<Self> { v.to_regex().ok() }
+		fn from_ref(v: &Value) -> Option<Self> { v.to_regex().ok() }
+	}
+}
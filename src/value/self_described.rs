@@ -0,0 +1,27 @@
+use super::Value;
+use crate::tags;
+
+/// The encoding of tag 55799, which marks data as CBOR without changing its meaning
+pub(crate) const SELF_DESCRIBED_PREFIX: [u8; 3] = [0xD9, 0xD9, 0xF7];
+
+impl Value {
+	/// Whether the value is wrapped in the self-described CBOR tag (55799).
+	pub fn is_self_described(&self) -> bool { matches!(self, Value::Tag(tags::SELF_DESCRIBED, _)) }
+
+	/// Removes any self-described CBOR tags (55799) wrapping the value. Occurrences further in are
+	/// kept.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let v = Value::Tag(55799, Box::new(Value::array(vec![Value::Tag(55799, Box::new(Value::Unsigned(1)))])));
+	/// assert_eq!("[55799(1)]", v.strip_self_described().to_diagnostic());
+	/// ```
+	pub fn strip_self_described(mut self) -> Value {
+		while let Value::Tag(tags::SELF_DESCRIBED, x) = self {
+			self = *x;
+		}
+		self
+	}
+}
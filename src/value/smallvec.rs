@@ -0,0 +1,94 @@
+use smallvec::Array;
+use smallvec::SmallVec;
+
+use super::untagged;
+use super::untagged_ref;
+use super::FromValue;
+use super::ToValue;
+use super::Value;
+
+impl<A> FromValue for SmallVec<A>
+where
+	A: Array,
+	A::Item: FromValue,
+{
+	fn from_value(v: Value) -> Option<Self> {
+		let value_arr: Vec<Value> = match untagged(v) {
+			Value::Array(x) => x,
+			Value::Map(m) => {
+				let mut arr = SmallVec::<A>::new();
+				for kv in m {
+					if let Some(x) = A::Item::from_value(Value::Map(vec![kv.clone()].into())) {
+						arr.push(x);
+					}
+				}
+				return Some(arr);
+			}
+			_ => return None,
+		};
+
+		let mut arr = SmallVec::<A>::with_capacity(value_arr.len());
+
+		for item in value_arr {
+			if let Some(x) = A::Item::from_value(item) {
+				arr.push(x);
+			}
+		}
+
+		Some(arr)
+	}
+
+	fn from_ref(v: &Value) -> Option<Self> {
+		let value_arr: &Vec<Value> = match untagged_ref(v) {
+			Value::Array(x) => x,
+			Value::Map(m) => {
+				let mut arr = SmallVec::<A>::new();
+				for kv in m {
+					if let Some(x) = A::Item::from_value(Value::Map(vec![kv.clone()].into())) {
+						arr.push(x);
+					}
+				}
+				return Some(arr);
+			}
+			_ => return None,
+		};
+
+		let mut arr = SmallVec::<A>::with_capacity(value_arr.len());
+
+		for item in value_arr {
+			if let Some(x) = A::Item::from_ref(item) {
+				arr.push(x);
+			}
+		}
+
+		Some(arr)
+	}
+}
+
+impl<A> ToValue for SmallVec<A>
+where
+	A: Array,
+	A::Item: ToValue,
+{
+	fn to_value(&self) -> Value {
+		let mut arr = Vec::<Value>::with_capacity(self.len());
+		for e in self {
+			arr.push(e.to_value());
+		}
+		Value::Array(arr)
+	}
+}
+
+impl<A> From<SmallVec<A>> for Value
+where
+	A: Array,
+	Value: From<A::Item>,
+{
+	fn from(v: SmallVec<A>) -> Self {
+		let mut arr = Vec::<Value>::with_capacity(v.len());
+		for e in v {
+			arr.push(Value::from(e));
+		}
+		Value::Array(arr)
+	}
+}
@@ -0,0 +1,67 @@
+use super::decimal::is_fraction_content;
+use super::typed_array::is_typed_array_content;
+use super::typed_array::is_typed_array_tag;
+use super::uri::is_uri;
+use super::uuid::is_uuid_content;
+use super::ExtendedTime;
+use super::Value;
+use crate::path::Path;
+use crate::path::PathSegment;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+// What the content of a tag the crate knows must be, if it is not that
+fn misfit(tag: u64, content: &Value) -> Option<&'static str> {
+	match (tag, content) {
+		(tags::DATETIME_STRING, Value::Utf8String(s)) if ExtendedTime::from_rfc3339(s).is_ok() => None,
+		(tags::DATETIME_STRING, _) => Some("an RFC 3339 date/time string"),
+		(tags::POS_BIGNUM, x) | (tags::NEG_BIGNUM, x) | (tags::EMBEDDED, x) if x.as_byte_slice().is_some() => None,
+		(tags::POS_BIGNUM, _) | (tags::NEG_BIGNUM, _) | (tags::EMBEDDED, _) => Some("a byte string"),
+		(tags::DECIMAL, x) | (tags::BIGFLOAT, x) if !is_fraction_content(x) => Some("an [exponent, mantissa] array"),
+		(tags::URI, Value::Utf8String(s)) if is_uri(s) => None,
+		(tags::URI, _) => Some("a URI"),
+		(tags::UUID, x) if !is_uuid_content(x) => Some("a 16-byte byte string"),
+		(tags::REGEX, Value::Utf8String(_)) | (tags::MIME, Value::Utf8String(_)) => None,
+		(tags::REGEX, _) | (tags::MIME, _) => Some("a text string"),
+		(t, x) if is_typed_array_tag(t) && !is_typed_array_content(t, x) => Some("a byte string of whole elements"),
+		_ => None,
+	}
+}
+
+fn check(v: &Value, path: &mut Path) -> Result<()> {
+	match v {
+		Value::Tag(tag, content) => {
+			if let Some(expected) = misfit(*tag, content) {
+				let msg = format!("tag {} at {} must contain {}", tag, path, expected);
+				return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+			}
+			check(content, path)
+		}
+		Value::Array(arr) => {
+			for (i, x) in arr.iter().enumerate() {
+				path.push(PathSegment::Index(i));
+				check(x, path)?;
+				path.pop();
+			}
+			Ok(())
+		}
+		Value::Map(map) => {
+			for kv in map {
+				path.push(PathSegment::Key(kv.key.clone()));
+				check(&kv.key, path)?;
+				check(&kv.val, path)?;
+				path.pop();
+			}
+			Ok(())
+		}
+		_ => Ok(()),
+	}
+}
+
+impl Value {
+	/// Checks that every tag the crate knows, at any depth, has content of the type its
+	/// definition requires. Unknown tags are not checked.
+	pub fn check_tags(&self) -> Result<()> { check(self, &mut Path::new()) }
+}
@@ -0,0 +1,440 @@
+use core::convert::TryFrom;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::FromValue;
+use super::KeyVal;
+use super::Map;
+use super::ToValue;
+use super::Value;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+const KEY_SECONDS: i64 = 1;
+const KEY_MILLIS: i64 = -3;
+const KEY_MICROS: i64 = -6;
+const KEY_NANOS: i64 = -9;
+
+/// A point in time with nanosecond precision, carried as an extended time (tag 1001, RFC 9581):
+/// a map of integer seconds since the Unix epoch under key 1 and, if needed, the fraction of a
+/// second under -3 (milliseconds), -6 (microseconds) or -9 (nanoseconds).
+///
+/// Times before 1970 have negative `seconds`; `nanos` always counts forward from there, so
+/// 1.5 seconds before the epoch is `seconds: -2, nanos: 500_000_000`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExtendedTime {
+	pub seconds: i64,
+	/// Always below 1,000,000,000
+	pub nanos: u32,
+}
+
+impl ExtendedTime {
+	/// Returns `None` if `nanos` is a whole second or more.
+	pub fn new(seconds: i64, nanos: u32) -> Option<ExtendedTime> {
+		if nanos < 1_000_000_000 {
+			Some(ExtendedTime { seconds, nanos })
+		} else {
+			None
+		}
+	}
+
+	pub fn from_system_time(t: SystemTime) -> ExtendedTime {
+		match t.duration_since(UNIX_EPOCH) {
+			Ok(d) => ExtendedTime {
+				seconds: d.as_secs() as i64,
+				nanos: d.subsec_nanos(),
+			},
+			Err(e) => {
+				let d = e.duration();
+				if d.subsec_nanos() == 0 {
+					ExtendedTime {
+						seconds: -(d.as_secs() as i64),
+						nanos: 0,
+					}
+				} else {
+					ExtendedTime {
+						seconds: -(d.as_secs() as i64) - 1,
+						nanos: 1_000_000_000 - d.subsec_nanos(),
+					}
+				}
+			}
+		}
+	}
+
+	pub fn to_system_time(&self) -> SystemTime {
+		let nanos = Duration::from_nanos(u64::from(self.nanos));
+		if self.seconds >= 0 {
+			UNIX_EPOCH + Duration::from_secs(self.seconds as u64) + nanos
+		} else {
+			UNIX_EPOCH - Duration::from_secs(self.seconds.unsigned_abs()) + nanos
+		}
+	}
+
+	// Like to_system_time, but `None` where the platform's SystemTime cannot hold the time
+	fn checked_system_time(&self) -> Option<SystemTime> {
+		let t = if self.seconds >= 0 {
+			UNIX_EPOCH.checked_add(Duration::from_secs(self.seconds as u64))?
+		} else {
+			UNIX_EPOCH.checked_sub(Duration::from_secs(self.seconds.unsigned_abs()))?
+		};
+		t.checked_add(Duration::from_nanos(u64::from(self.nanos)))
+	}
+
+	/// The same time as an epoch-based date/time (tag 1): an integer when there is no fraction of
+	/// a second, otherwise a float, which may lose precision.
+	pub fn to_epoch_value(&self) -> Value {
+		let content = if self.nanos == 0 {
+			self.seconds.to_value()
+		} else {
+			Value::Float(self.seconds as f64 + f64::from(self.nanos) / 1e9)
+		};
+		Value::tagged(tags::EPOCH, content)
+	}
+
+	fn from_epoch_content(v: &Value) -> Option<ExtendedTime> {
+		match v {
+			Value::Unsigned(x) => Some(ExtendedTime {
+				seconds: i64::try_from(*x).ok()?,
+				nanos: 0,
+			}),
+			Value::Negative(x) => Some(ExtendedTime { seconds: *x, nanos: 0 }),
+			Value::Float(x) if x.is_finite() && x.abs() < 9.2e18 => {
+				let seconds = x.floor();
+				let nanos = ((x - seconds) * 1e9).round() as u32;
+				// Rounding can carry into the next second
+				let (seconds, nanos) = if nanos >= 1_000_000_000 {
+					(seconds + 1.0, 0)
+				} else {
+					(seconds, nanos)
+				};
+				Some(ExtendedTime {
+					seconds: seconds as i64,
+					nanos,
+				})
+			}
+			_ => None,
+		}
+	}
+
+	fn from_extended_content(v: &Value) -> Option<ExtendedTime> {
+		let map = match v {
+			Value::Map(x) => x,
+			_ => return None,
+		};
+		let mut seconds = None;
+		let mut nanos = None;
+		for kv in map {
+			let key = match kv.key {
+				Value::Unsigned(x) => i64::try_from(x).ok()?,
+				Value::Negative(x) => x,
+				_ => continue,
+			};
+			let (scale, limit) = match key {
+				KEY_SECONDS => {
+					let s = match kv.val {
+						Value::Unsigned(x) => i64::try_from(x).ok()?,
+						Value::Negative(x) => x,
+						_ => return None,
+					};
+					if seconds.is_some() && seconds != Some(s) {
+						return None;
+					}
+					seconds = Some(s);
+					continue;
+				}
+				KEY_MILLIS => (1_000_000, 1_000),
+				KEY_MICROS => (1_000, 1_000_000),
+				KEY_NANOS => (1, 1_000_000_000),
+				// Other negative keys can change the meaning of the time, so cannot be ignored
+				k if k < 0 => return None,
+				_ => continue,
+			};
+			let n = match kv.val {
+				Value::Unsigned(x) if x < limit => x as u32 * scale,
+				_ => return None,
+			};
+			// Several subsecond keys must agree
+			if nanos.is_some() && nanos != Some(n) {
+				return None;
+			}
+			nanos = Some(n);
+		}
+		Some(ExtendedTime {
+			seconds: seconds?,
+			nanos: nanos.unwrap_or(0),
+		})
+	}
+}
+
+/// Writes the fraction of a second with the coarsest key that holds it exactly, and leaves it out
+/// when it is zero.
+impl ToValue for ExtendedTime {
+	fn to_value(&self) -> Value {
+		let mut map = Map::with_capacity(2);
+		map.push(KeyVal {
+			key: KEY_SECONDS.to_value(),
+			val: self.seconds.to_value(),
+		});
+		let n = u64::from(self.nanos);
+		let fraction = if n == 0 {
+			None
+		} else if n % 1_000_000 == 0 {
+			Some((KEY_MILLIS, n / 1_000_000))
+		} else if n % 1_000 == 0 {
+			Some((KEY_MICROS, n / 1_000))
+		} else {
+			Some((KEY_NANOS, n))
+		};
+		if let Some((key, val)) = fraction {
+			map.push(KeyVal {
+				key: key.to_value(),
+				val: Value::Unsigned(val),
+			});
+		}
+		Value::tagged(tags::EXTENDED_TIME, Value::Map(map))
+	}
+}
+
+impl From<ExtendedTime> for Value {
+	fn from(t: ExtendedTime) -> Value { t.to_value() }
+}
+
+/// Accepts an extended time (tag 1001) and also an epoch-based date/time (tag 1) or a date/time
+/// string (tag 0).
+impl FromValue for ExtendedTime {
+	fn from_value(v: Value) -> Option<Self> { ExtendedTime::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> {
+		match v {
+			Value::Tag(tags::EXTENDED_TIME, x) => ExtendedTime::from_extended_content(x),
+			Value::Tag(tags::EPOCH, x) => ExtendedTime::from_epoch_content(x),
+			Value::Tag(tags::DATETIME_STRING, _) => v.get_datetime().ok(),
+			_ => None,
+		}
+	}
+}
+
+/// An epoch-based date/time (tag 1) holding whole seconds. The fraction of a second is dropped,
+/// rounding towards the past; use [`ExtendedTime`] to keep it.
+impl ToValue for SystemTime {
+	fn to_value(&self) -> Value {
+		let seconds = ExtendedTime::from_system_time(*self).seconds;
+		Value::tagged(tags::EPOCH, seconds.to_value())
+	}
+}
+
+impl From<SystemTime> for Value {
+	fn from(t: SystemTime) -> Value { t.to_value() }
+}
+
+/// Accepts the same forms as [`ExtendedTime`], including tag 1 with an integer or float number of
+/// seconds. Fails if the time is out of range for `SystemTime` on this platform.
+impl FromValue for SystemTime {
+	fn from_value(v: Value) -> Option<Self> { SystemTime::from_ref(&v) }
+	fn from_ref(v: &Value) -> Option<Self> { ExtendedTime::from_ref(v)?.checked_system_time() }
+}
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+	let y = if month <= 2 { year - 1 } else { year };
+	let era = y.div_euclid(400);
+	let yoe = y.rem_euclid(400);
+	let mp = i64::from((month + 9) % 12);
+	let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+	match month {
+		2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
+	}
+}
+
+struct Cursor<'a> {
+	s: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+	fn digits(&mut self, n: usize) -> Option<u32> {
+		let digits = self.s.get(self.pos..self.pos + n)?;
+		let mut x = 0;
+		for d in digits {
+			if !d.is_ascii_digit() {
+				return None;
+			}
+			x = x * 10 + u32::from(d - b'0');
+		}
+		self.pos += n;
+		Some(x)
+	}
+
+	fn expect(&mut self, allowed: &[u8]) -> Option<u8> {
+		let c = *self.s.get(self.pos)?;
+		if allowed.contains(&c) {
+			self.pos += 1;
+			Some(c)
+		} else {
+			None
+		}
+	}
+}
+
+fn parse_rfc3339(s: &str) -> Option<ExtendedTime> {
+	let mut c = Cursor {
+		s: s.as_bytes(),
+		pos: 0,
+	};
+	let year = c.digits(4)?;
+	c.expect(b"-")?;
+	let month = c.digits(2)?;
+	c.expect(b"-")?;
+	let day = c.digits(2)?;
+	c.expect(b"Tt ")?;
+	let hour = c.digits(2)?;
+	c.expect(b":")?;
+	let minute = c.digits(2)?;
+	c.expect(b":")?;
+	// 60 is a leap second, counted here as the first second of the next minute
+	let second = c.digits(2)?;
+	if !(1..=12).contains(&month) || day < 1 || day > days_in_month(i64::from(year), month) {
+		return None;
+	}
+	if hour > 23 || minute > 59 || second > 60 {
+		return None;
+	}
+	let mut nanos = 0;
+	if c.expect(b".").is_some() {
+		let start = c.pos;
+		while let Some(d) = c.s.get(c.pos).filter(|d| d.is_ascii_digit()) {
+			// Digits past nanoseconds are dropped
+			if c.pos - start < 9 {
+				nanos = nanos * 10 + u32::from(d - b'0');
+			}
+			c.pos += 1;
+		}
+		let n = c.pos - start;
+		if n == 0 {
+			return None;
+		}
+		for _ in n..9 {
+			nanos *= 10;
+		}
+	}
+	let offset = match c.expect(b"Zz+-")? {
+		b'Z' | b'z' => 0,
+		sign => {
+			let h = c.digits(2)?;
+			c.expect(b":")?;
+			let m = c.digits(2)?;
+			if h > 23 || m > 59 {
+				return None;
+			}
+			let offset = i64::from(h * 60 + m) * 60;
+			if sign == b'-' {
+				-offset
+			} else {
+				offset
+			}
+		}
+	};
+	if c.pos != c.s.len() {
+		return None;
+	}
+	let days = days_from_civil(i64::from(year), month, day);
+	let seconds = days * 86_400 + i64::from(hour * 3600 + minute * 60 + second) - offset;
+	Some(ExtendedTime { seconds, nanos })
+}
+
+impl ExtendedTime {
+	/// Parses an RFC 3339 date/time such as `2013-03-21T20:04:00+01:00` or
+	/// `2013-03-21T20:04:00.5Z`. The offset is applied, so the result is the same instant in UTC.
+	/// Fractions finer than a nanosecond are truncated.
+	pub fn from_rfc3339(s: &str) -> Result<ExtendedTime> {
+		match parse_rfc3339(s) {
+			Some(x) => Ok(x),
+			None => {
+				let msg = format!("{:?} is not an RFC 3339 date/time", s);
+				CborError::new_err(ErrorKind::UnexpectedValue, msg.into())
+			}
+		}
+	}
+
+	/// Formats the time in UTC as RFC 3339, with as many fractional digits as needed.
+	/// Returns `None` for years outside 0 to 9999, which RFC 3339 cannot represent.
+	pub fn to_rfc3339(&self) -> Option<String> {
+		let (year, month, day) = civil_from_days(self.seconds.div_euclid(86_400));
+		if !(0..=9999).contains(&year) {
+			return None;
+		}
+		let secs = self.seconds.rem_euclid(86_400);
+		let mut s = format!(
+			"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+			year,
+			month,
+			day,
+			secs / 3600,
+			secs / 60 % 60,
+			secs % 60
+		);
+		if self.nanos != 0 {
+			let fraction = format!("{:09}", self.nanos);
+			s.push('.');
+			s.push_str(fraction.trim_end_matches('0'));
+		}
+		s.push('Z');
+		Some(s)
+	}
+}
+
+impl Value {
+	/// A date/time string (tag 0). Fails if `s` is not a valid RFC 3339 date/time.
+	pub fn from_rfc3339(s: &str) -> Result<Value> {
+		ExtendedTime::from_rfc3339(s)?;
+		Ok(Value::Tag(
+			tags::DATETIME_STRING,
+			Box::new(Value::Utf8String(s.to_string())),
+		))
+	}
+
+	/// Parses a date/time string (tag 0). Fails if `self` is not one or the string is not a valid
+	/// RFC 3339 date/time.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let v = Value::from_rfc3339("2013-03-21T22:04:00.25+02:00").unwrap();
+	/// let t = v.get_datetime().unwrap();
+	/// assert_eq!((1363896240, 250_000_000), (t.seconds, t.nanos));
+	/// assert_eq!("2013-03-21T20:04:00.25Z", t.to_rfc3339().unwrap());
+	/// ```
+	pub fn get_datetime(&self) -> Result<ExtendedTime> {
+		match self {
+			Value::Tag(tags::DATETIME_STRING, x) => match x.as_ref() {
+				Value::Utf8String(s) => ExtendedTime::from_rfc3339(s),
+				_ => CborError::new_err(ErrorKind::UnexpectedValue, "tag 0 must contain a text string".into()),
+			},
+			_ => CborError::new_err(ErrorKind::UnexpectedValue, "not a tag 0 date/time string".into()),
+		}
+	}
+}
@@ -0,0 +1,185 @@
+use super::Value;
+use crate::tags;
+use crate::CborError;
+use crate::ErrorKind;
+use crate::Result;
+
+// How the elements of a typed array (RFC 8746 section 2.1) are packed. Its tag is 0b010_f_s_e_ll:
+// float, signed, little-endian (clamped for uint8) and the log2 of the element size.
+struct Layout {
+	float: bool,
+	signed: bool,
+	little_endian: bool,
+	size: usize,
+}
+
+fn layout(tag: u64) -> Option<Layout> {
+	if !tags::TYPED_ARRAYS.contains(&tag) {
+		return None;
+	}
+	let float = tag & 0x10 != 0;
+	let signed = tag & 0x08 != 0;
+	let e = tag & 0x04 != 0;
+	let ll = (tag & 0x03) as u32;
+	if float {
+		return Some(Layout {
+			float,
+			signed: true,
+			little_endian: e,
+			size: 2 << ll,
+		});
+	}
+	// With 8-bit elements the e bit marks uint8 as clamped, and is reserved for sint8 (tag 76)
+	if ll == 0 && e && signed {
+		return None;
+	}
+	Some(Layout {
+		float,
+		signed,
+		little_endian: e && ll != 0,
+		size: 1 << ll,
+	})
+}
+
+pub(crate) fn is_typed_array_tag(tag: u64) -> bool { layout(tag).is_some() }
+
+// Whether `v` is the content that typed array tag `tag` requires
+pub(crate) fn is_typed_array_content(tag: u64, v: &Value) -> bool {
+	match (layout(tag), v.as_byte_slice()) {
+		(Some(l), Some(b)) => b.len() % l.size == 0,
+		_ => false,
+	}
+}
+
+fn element(l: &Layout, chunk: &[u8]) -> Value {
+	let mut buf = [0u8; 8];
+	if l.little_endian {
+		buf[..l.size].copy_from_slice(chunk);
+	} else {
+		for (i, b) in chunk.iter().rev().enumerate() {
+			buf[i] = *b;
+		}
+	}
+	let bits = u64::from_le_bytes(buf);
+	if l.float {
+		return Value::Float(match l.size {
+			2 => crate::canonical::f16_to_f64(bits as u16),
+			4 => f64::from(f32::from_bits(bits as u32)),
+			_ => f64::from_bits(bits),
+		});
+	}
+	let shift = 64 - 8 * l.size as u32;
+	if l.signed && bits << shift >> 63 == 1 {
+		Value::Negative(((bits << shift) as i64) >> shift)
+	} else {
+		Value::Unsigned(bits)
+	}
+}
+
+fn expand(tag: u64, content: &Value) -> Result<Vec<Value>> {
+	let l = match layout(tag) {
+		Some(x) => x,
+		None => return CborError::new_err(ErrorKind::UnexpectedValue, "not a typed array (tags 64 to 87)".into()),
+	};
+	let bytes = match content.as_byte_slice() {
+		Some(x) => x,
+		None => {
+			let msg = format!("typed array (tag {}) must contain a byte string", tag);
+			return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+		}
+	};
+	if l.float && l.size == 16 {
+		let msg = format!("128-bit floats in typed array (tag {}) are not supported", tag);
+		return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+	}
+	if bytes.len() % l.size != 0 {
+		let msg = format!(
+			"typed array (tag {}) of {} bytes is not a whole number of {}-byte elements",
+			tag,
+			bytes.len(),
+			l.size
+		);
+		return CborError::new_err(ErrorKind::UnexpectedValue, msg.into());
+	}
+	Ok(bytes.chunks(l.size).map(|c| element(&l, c)).collect())
+}
+
+// The elements of a typed array, looking through any other tags around it. `None` if `v` is not
+// a valid typed array.
+pub(crate) fn elements(mut v: &Value) -> Option<Vec<Value>> {
+	loop {
+		match v {
+			Value::Tag(t, x) if is_typed_array_tag(*t) => return expand(*t, x).ok(),
+			Value::Tag(_, x) => v = x,
+			_ => return None,
+		}
+	}
+}
+
+/// Byte order of the elements of a typed array.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+	Big,
+	Little,
+}
+
+/// Numeric types that can be packed into a typed array with [`Value::typed_array`].
+pub trait TypedArrayElement: Copy {
+	/// The tag of a big-endian typed array of this type
+	const TAG: u64;
+
+	/// Appends the element's bytes in the given order.
+	fn put(self, endian: Endian, out: &mut Vec<u8>);
+}
+
+macro_rules! typed_array_element {
+	($($t:ty => $tag:expr),*) => {
+		$(impl TypedArrayElement for $t {
+			const TAG: u64 = $tag;
+			fn put(self, endian: Endian, out: &mut Vec<u8>) {
+				match endian {
+					Endian::Big => out.extend_from_slice(&self.to_be_bytes()),
+					Endian::Little => out.extend_from_slice(&self.to_le_bytes()),
+				}
+			}
+		})*
+	};
+}
+
+typed_array_element!(
+	u8 => 64, u16 => 65, u32 => 66, u64 => 67, i8 => 72, i16 => 73, i32 => 74, i64 => 75, f32 => 81, f64 => 82
+);
+
+impl Value {
+	/// Packs `items` into a typed array (RFC 8746): a byte string under the tag for the element
+	/// type and byte order. This is more compact than an array, but only readers that know typed
+	/// arrays can use it. Single-byte elements have no byte order.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{Endian, Value};
+	/// let v = Value::typed_array(&[1u32, 2], Endian::Little);
+	/// assert_eq!("70(h'0100000002000000')", v.to_diagnostic());
+	/// assert_eq!("[1, 2]", v.expand_typed_array().unwrap().to_diagnostic());
+	/// ```
+	pub fn typed_array<T: TypedArrayElement>(items: &[T], endian: Endian) -> Value {
+		let mut bytes = Vec::with_capacity(std::mem::size_of_val(items));
+		for x in items {
+			x.put(endian, &mut bytes);
+		}
+		let little = endian == Endian::Little && std::mem::size_of::<T>() > 1;
+		Value::tagged(T::TAG + if little { 4 } else { 0 }, Value::ByteString(bytes))
+	}
+
+	/// Unpacks a typed array (tags 64 to 87) into an array of its elements. Fails if `self` is not
+	/// a typed array, if its length is not a whole number of elements, or for 128-bit floats.
+	///
+	/// Conversions to `Vec` accept typed arrays without this step.
+	pub fn expand_typed_array(&self) -> Result<Value> {
+		match self {
+			Value::Tag(t, x) => expand(*t, x).map(Value::Array),
+			_ => CborError::new_err(ErrorKind::UnexpectedValue, "not a typed array (tags 64 to 87)".into()),
+		}
+	}
+}
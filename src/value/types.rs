@@ -1,34 +1,71 @@
+use core::cmp::Ordering;
+use core::convert::TryFrom;
 use core::fmt;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io;
 
+use super::Map;
+use super::RawValue;
+use super::TextHint;
+use super::ToValue;
+
 #[derive(Clone, PartialEq, Hash)]
 pub enum Simple {
 	False,
 	True,
 	Null,
 	Undefined,
-	Unassigned(u8),
+	/// Simple values 0 to 19 and 32 to 255. Build one from a number with `Simple::try_from`.
+	Unassigned(SimpleNumber),
+}
+
+/// The number of a simple value without a name of its own: 0 to 19 or 32 to 255. The only way to
+/// make one is `Simple::try_from`, which gives the named variants for 20 to 23 and rejects 24 to
+/// 31, so every `Simple` has exactly one form and one valid encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SimpleNumber(u8);
+
+impl SimpleNumber {
+	pub fn get(self) -> u8 { self.0 }
+}
+
+impl fmt::Display for SimpleNumber {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.0.fmt(f) }
 }
 
 impl Simple {
+	/// Encodes 0 to 23 in the initial byte and 32 to 255 in a second byte.
 	pub fn encode(&self) -> Vec<u8> {
+		let (bytes, len) = self.encode_array();
+		bytes[..len].to_vec()
+	}
+
+	// The encoding in the first `len` bytes of an array, for writers that do not allocate
+	fn encode_array(&self) -> ([u8; 2], usize) {
 		let major = 7 << 5;
-		let mut v = Vec::<u8>::new();
 		match self {
-			Simple::False => v.push(major | 20),
-			Simple::True => v.push(major | 21),
-			Simple::Null => v.push(major | 22),
-			Simple::Undefined => v.push(major | 23),
-			Simple::Unassigned(x) => {
-				if *x < 20 {
-					v.push(major | *x);
-				} else if *x > 31 {
-					v.push(major | 24);
-				}
-			}
+			Simple::False => ([major | 20, 0], 1),
+			Simple::True => ([major | 21, 0], 1),
+			Simple::Null => ([major | 22, 0], 1),
+			Simple::Undefined => ([major | 23, 0], 1),
+			Simple::Unassigned(SimpleNumber(x)) if *x < 24 => ([major | *x, 0], 1),
+			Simple::Unassigned(SimpleNumber(x)) => ([major | 24, *x], 2),
+		}
+	}
+}
+
+impl TryFrom<u8> for Simple {
+	type Error = ();
+	fn try_from(x: u8) -> Result<Simple, ()> {
+		match x {
+			20 => Ok(Simple::False),
+			21 => Ok(Simple::True),
+			22 => Ok(Simple::Null),
+			23 => Ok(Simple::Undefined),
+			24..=31 => Err(()),
+			_ => Ok(Simple::Unassigned(SimpleNumber(x))),
 		}
-		v
 	}
 }
 impl std::fmt::Display for Simple {
@@ -55,18 +92,66 @@ pub enum Value {
 	ByteString(Vec<u8>),
 	Utf8String(String),
 	Array(Vec<Value>),
-	Map(Vec<KeyVal>), // Vec used in place of map to preserve ordering of original data
+	Map(Map), // Entries are kept in the order of the original data
+	Tag(u64, Box<Value>),
 	Float(f64),
 	Simple(Simple),
+	/// A data item that is already encoded, written out as is. It compares equal only to a `Raw`
+	/// holding the same bytes, and accessors and conversions do not look inside it. Use
+	/// [`RawValue::decode`] to compare it with other values.
+	Raw(RawValue),
+	/// A byte string that shares the buffer it was decoded from, made by
+	/// [`decode_bytes`](crate::decode_bytes). It compares equal to the `ByteString` with the same
+	/// content and is encoded the same way.
+	#[cfg(feature = "bytes")]
+	SharedBytes(bytes::Bytes),
 }
 
-#[derive(Clone, PartialEq)]
+// How many bytes Value::encode_to gathers before writing them
+const SINK_BUF: usize = 8192;
+
+// The writer for Value::encode_to, with a buffer so that every header is not a separate write
+struct Sink<'w, W> {
+	w: &'w mut W,
+	buf: Vec<u8>,
+}
+
+impl<'w, W: io::Write> Sink<'w, W> {
+	fn header(&mut self, major: u8, arg: u64) -> io::Result<()> {
+		crate::header::write_header(&mut self.buf, major, arg);
+		if self.buf.len() >= SINK_BUF {
+			self.flush()?;
+		}
+		Ok(())
+	}
+
+	fn bytes(&mut self, x: &[u8]) -> io::Result<()> {
+		if self.buf.len() + x.len() > SINK_BUF {
+			self.flush()?;
+			if x.len() >= SINK_BUF {
+				return self.w.write_all(x);
+			}
+		}
+		self.buf.extend_from_slice(x);
+		Ok(())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.write_all(&self.buf)?;
+		self.buf.clear();
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct KeyVal {
 	pub key: Value,
 	pub val: Value,
 }
 
 impl Eq for Value {}
+/// Floats are equal when their bits are, so NaN equals a NaN with the same payload and `0.0` does
+/// not equal `-0.0`. This keeps `Eq`, `Hash` and `Ord` in agreement for values used as map keys.
 impl PartialEq for Value {
 	fn eq(&self, rhs: &Self) -> bool {
 		use Value::*;
@@ -80,8 +165,14 @@ impl PartialEq for Value {
 			(Utf8String(a), Utf8String(b)) => a == b,
 			(Array(a), Array(b)) => a == b,
 			(Map(a), Map(b)) => a == b,
-			(Float(a), Float(b)) => a == b,
+			(Tag(a, x), Tag(b, y)) => a == b && x == y,
+			(Float(a), Float(b)) => a.to_bits() == b.to_bits(),
 			(Simple(a), Simple(b)) => a == b,
+			(Raw(a), Raw(b)) => a == b,
+			#[cfg(feature = "bytes")]
+			(SharedBytes(a), SharedBytes(b)) => a == b,
+			#[cfg(feature = "bytes")]
+			(ByteString(a), SharedBytes(b)) | (SharedBytes(b), ByteString(a)) => a[..] == b[..],
 			(Float(_), Simple(_)) => false,
 			(Simple(_), Float(_)) => false,
 			(_, _) => false,
@@ -89,6 +180,44 @@ impl PartialEq for Value {
 	}
 }
 
+/// Values are ordered by comparing their encoded bytes lexicographically, which is the map key
+/// ordering RFC 8949 uses for deterministic encoding. Integers sort before byte strings, which sort
+/// before text strings, and so on by major type; within a type, shorter encodings sort first. A
+/// `Value::Raw` sorts by its bytes as they are, just after a value with the same encoding.
+///
+/// Values are compared as they stand rather than encoded first. Encodings never run into each
+/// other, so comparing headers, then contents, then elements one by one gives the same order.
+impl Ord for Value {
+	fn cmp(&self, rhs: &Self) -> Ordering {
+		use Value::*;
+		match (self, rhs) {
+			(Raw(a), Raw(b)) => return a.as_bytes().cmp(b.as_bytes()),
+			// A raw value never equals anything else, so it follows a value with the same encoding
+			(Raw(a), x) => return a.as_bytes().cmp(&x.encode_compact()).then(Ordering::Greater),
+			(x, Raw(b)) => return x.encode_compact()[..].cmp(b.as_bytes()).then(Ordering::Less),
+			_ => {}
+		}
+		let ((a, a_len), (b, b_len)) = (self.head(), rhs.head());
+		let heads = a[..a_len].cmp(&b[..b_len]);
+		if heads != Ordering::Equal {
+			return heads;
+		}
+		// The heads match, so both sides have the same major type and length
+		match (self, rhs) {
+			(Array(a), Array(b)) => a.iter().cmp(b.iter()),
+			(Map(a), Map(b)) => a
+				.iter()
+				.map(|kv| (&kv.key, &kv.val))
+				.cmp(b.iter().map(|kv| (&kv.key, &kv.val))),
+			(Tag(_, x), Tag(_, y)) => x.cmp(y),
+			_ => self.content().cmp(rhs.content()),
+		}
+	}
+}
+impl PartialOrd for Value {
+	fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> { Some(self.cmp(rhs)) }
+}
+
 impl std::hash::Hash for Value {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
 		match self {
@@ -103,11 +232,15 @@ impl std::hash::Hash for Value {
 					kv.val.hash(state);
 				}
 			}
-			Value::Float(x) => {
-				let y: u64 = unsafe { std::mem::transmute::<f64, u64>(*x) };
-				y.hash(state);
+			Value::Tag(t, x) => {
+				t.hash(state);
+				x.hash(state);
 			}
+			Value::Float(x) => x.to_bits().hash(state),
 			Value::Simple(x) => x.hash(state),
+			Value::Raw(x) => x.hash(state),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => x[..].hash(state),
 		}
 	}
 }
@@ -115,7 +248,7 @@ impl std::hash::Hash for Value {
 impl std::fmt::Debug for Value {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		let mut output = Vec::<u8>::new();
-		match print_cbor(&self, &mut output) {
+		match print_cbor(self, &mut output) {
 			Ok(x) => x,
 			Err(_) => return Err(fmt::Error),
 		};
@@ -130,7 +263,7 @@ impl std::fmt::Debug for Value {
 impl std::fmt::Display for Value {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		let mut output = Vec::<u8>::new();
-		match print_cbor(&self, &mut output) {
+		match print_cbor(self, &mut output) {
 			Ok(x) => x,
 			Err(_) => return Err(fmt::Error),
 		};
@@ -151,9 +284,140 @@ impl Value {
 			Self::Utf8String(_) => 3,
 			Self::Array(_) => 4,
 			Self::Map(_) => 5,
-			// Self::Tag(_) => 6,
+			Self::Tag(..) => 6,
 			Self::Float(_) => 7,
 			Self::Simple(_) => 7,
+			Self::Raw(x) => x.as_bytes()[0] >> 5,
+			#[cfg(feature = "bytes")]
+			Self::SharedBytes(_) => 2,
+		}
+	}
+
+	// The encoded initial byte and argument in the first `len` bytes of an array, or for a float or
+	// simple value the whole encoding. Not for `Raw`, which has no header of its own.
+	fn head(&self) -> ([u8; 9], usize) {
+		use crate::header::header_bytes;
+		match self {
+			Value::Unsigned(x) => header_bytes(0, *x),
+			Value::Negative(x) => header_bytes(1, (-1 - x) as u64),
+			Value::ByteString(x) => header_bytes(2, x.len() as u64),
+			Value::Utf8String(x) => header_bytes(3, x.len() as u64),
+			Value::Array(x) => header_bytes(4, x.len() as u64),
+			Value::Map(x) => header_bytes(5, x.len() as u64),
+			Value::Tag(t, _) => header_bytes(6, *t),
+			Value::Float(x) => {
+				let mut bytes = [7 << 5 | 27; 9];
+				bytes[1..].copy_from_slice(&x.to_bits().to_be_bytes());
+				(bytes, 9)
+			}
+			Value::Simple(x) => {
+				let (simple, len) = x.encode_array();
+				let mut bytes = [0; 9];
+				bytes[..len].copy_from_slice(&simple[..len]);
+				(bytes, len)
+			}
+			Value::Raw(_) => ([0; 9], 0),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => header_bytes(2, x.len() as u64),
+		}
+	}
+
+	// The bytes that follow the header of a string, empty for anything else
+	fn content(&self) -> &[u8] {
+		match self {
+			Value::ByteString(x) => x,
+			Value::Utf8String(x) => x.as_bytes(),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => x,
+			_ => &[],
+		}
+	}
+
+	pub fn text(s: impl Into<String>) -> Value { Value::Utf8String(s.into()) }
+
+	pub fn bytes(b: impl Into<Vec<u8>>) -> Value { Value::ByteString(b.into()) }
+
+	pub fn array<T: Into<Value>>(items: impl IntoIterator<Item = T>) -> Value {
+		Value::Array(items.into_iter().map(Into::into).collect())
+	}
+
+	/// Builds an array from values of mixed types, converting each with `ToValue`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let row = Value::array_dyn(&[&1u64, &"two", &3.5]);
+	/// assert_eq!(r#"[1, "two", 3.5]"#, row.to_diagnostic());
+	/// ```
+	pub fn array_dyn(items: &[&dyn ToValue]) -> Value { items.to_value() }
+
+	/// Builds a map from key-value pairs, keeping them in iteration order.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// let v = Value::map(vec![("a", Value::from(1u32)), ("b", Value::array(vec!["x", "y"]))]);
+	/// assert_eq!(r#"{"a": 1, "b": ["x", "y"]}"#, v.to_diagnostic());
+	/// ```
+	pub fn map<K: Into<Value>, V: Into<Value>>(entries: impl IntoIterator<Item = (K, V)>) -> Value {
+		Value::Map(
+			entries
+				.into_iter()
+				.map(|(k, v)| KeyVal {
+					key: k.into(),
+					val: v.into(),
+				})
+				.collect(),
+		)
+	}
+
+	/// Wraps `value` in `tag`. The [`tags`](crate::tags) module names the tags this crate knows.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{tags, Value};
+	/// let v = Value::tagged(tags::URI, "https://example.com");
+	/// assert_eq!(r#"32("https://example.com")"#, v.to_diagnostic());
+	/// ```
+	pub fn tagged(tag: u64, value: impl Into<Value>) -> Value { Value::Tag(tag, Box::new(value.into())) }
+
+	/// Builds an integer value, choosing `Unsigned` or `Negative` by sign.
+	///
+	/// CBOR integers span -2^64 to 2^64-1, but `Negative` holds the value itself as an `i64`, so
+	/// numbers below `i64::MIN` are rejected along with those outside CBOR's range.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::Value;
+	/// assert_eq!(Value::Negative(-5), Value::integer(-5).unwrap());
+	/// assert_eq!(Value::Unsigned(5), Value::integer(5).unwrap());
+	/// assert!(Value::integer(1 << 64).is_err());
+	/// ```
+	pub fn integer(i: i128) -> crate::Result<Value> {
+		if let Ok(x) = u64::try_from(i) {
+			return Ok(Value::Unsigned(x));
+		}
+		if let Ok(x) = i64::try_from(i) {
+			return Ok(Value::Negative(x));
+		}
+		let msg = if !(-(1i128 << 64)..=0).contains(&i) {
+			format!("{} is outside the range of a CBOR integer", i)
+		} else {
+			format!("{} is below i64::MIN and cannot be held by Value::Negative", i)
+		};
+		crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, msg.into())
+	}
+
+	/// Returns the value of either integer variant.
+	pub fn as_integer(&self) -> Option<i128> {
+		match self {
+			Value::Unsigned(x) => Some(i128::from(*x)),
+			Value::Negative(x) => Some(i128::from(*x)),
+			_ => None,
 		}
 	}
 
@@ -178,9 +442,54 @@ impl Value {
 		}
 	}
 
+	/// Returns the value as an `f32` only if the conversion is exact, i.e. widening the result
+	/// back reproduces the original. NaN converts to NaN. `FromValue for f32` is the lossy
+	/// alternative.
+	pub fn as_f32_exact(&self) -> Option<f32> {
+		match self {
+			Value::Float(x) => {
+				let f = *x as f32;
+				if f64::from(f) == *x || x.is_nan() {
+					Some(f)
+				} else {
+					None
+				}
+			}
+			Value::Unsigned(x) => {
+				let f = *x as f32;
+				if f as u128 == u128::from(*x) {
+					Some(f)
+				} else {
+					None
+				}
+			}
+			Value::Negative(x) => {
+				let f = *x as f32;
+				if f as i128 == i128::from(*x) {
+					Some(f)
+				} else {
+					None
+				}
+			}
+			_ => None,
+		}
+	}
+
+	/// Borrows the content of a byte string, whether a `ByteString` or a `SharedBytes`.
+	pub fn as_byte_slice(&self) -> Option<&[u8]> {
+		match self {
+			Value::ByteString(x) => Some(x),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => Some(x),
+			_ => None,
+		}
+	}
+
 	pub fn get_bytes(&self) -> Option<Vec<u8>> {
 		match self {
 			Value::ByteString(x) => Some(x.clone()),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => Some(x.to_vec()),
 			_ => None,
 		}
 	}
@@ -201,13 +510,74 @@ impl Value {
 
 	pub fn get_map(&self) -> Option<Vec<KeyVal>> {
 		match self {
-			Value::Map(x) => Some(x.clone()),
+			Value::Map(x) => Some(x.to_vec()),
+			_ => None,
+		}
+	}
+
+	/// The tag number and content of a tagged value.
+	pub fn tag(&self) -> Option<(u64, &Value)> {
+		match self {
+			Value::Tag(t, x) => Some((*t, x)),
 			_ => None,
 		}
 	}
 
+	/// The content of a tagged value with one tag removed, or the value itself if it is not tagged.
+	pub fn untagged(&self) -> &Value {
+		match self {
+			Value::Tag(_, x) => x,
+			x => x,
+		}
+	}
+
+	/// A copy of the value with every tag removed, at any depth, including inside map keys.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{tags, Value};
+	/// let v = Value::array(vec![Value::tagged(tags::URI, Value::tagged(tags::SELF_DESCRIBED, "a:b"))]);
+	/// assert_eq!(r#"["a:b"]"#, v.strip_tags().to_diagnostic());
+	/// ```
+	pub fn strip_tags(&self) -> Value {
+		let mut v = self;
+		while let Value::Tag(_, x) = v {
+			v = x;
+		}
+		match v {
+			Value::Array(arr) => Value::Array(arr.iter().map(Value::strip_tags).collect()),
+			Value::Map(map) => Value::Map(
+				map.iter()
+					.map(|kv| KeyVal {
+						key: kv.key.strip_tags(),
+						val: kv.val.strip_tags(),
+					})
+					.collect(),
+			),
+			x => x.clone(),
+		}
+	}
+
+	/// Like [`Value::strip_tags`] but in place, reusing the existing strings and containers.
+	pub fn strip_tags_mut(&mut self) {
+		while let Value::Tag(_, x) = self {
+			*self = x.take();
+		}
+		match self {
+			Value::Array(arr) => arr.iter_mut().for_each(Value::strip_tags_mut),
+			Value::Map(map) => {
+				for kv in map.iter_mut() {
+					kv.key.strip_tags_mut();
+					kv.val.strip_tags_mut();
+				}
+			}
+			_ => {}
+		}
+	}
+
 	pub fn get_hash_map(&self) -> Option<HashMap<Value, Value>> {
-		let v: &Vec<KeyVal> = match self {
+		let v: &Map = match self {
 			Value::Map(x) => x,
 			_ => {
 				return None;
@@ -223,125 +593,478 @@ impl Value {
 		Some(map)
 	}
 
-	fn encode_compact_uint(bytes: &mut Vec<u8>, x: u64, major: u8) {
-		let mut b: u8 = major << 5;
-		let byte_len;
-		if x <= 23 {
-			b |= x as u8;
-			byte_len = 0;
-		} else if x < 0xFF {
-			b |= 24;
-			byte_len = 1;
-		} else if x < 0xFFFF {
-			b |= 25;
-			byte_len = 2;
-		} else if x < 0xFFFF_FFFF {
-			b |= 26;
-			byte_len = 4;
-		} else {
-			b |= 27;
-			byte_len = 8;
+	/// Converts each element of an array with `f`, skipping elements for which it returns `None`.
+	/// Returns `None` if `self` is not an array.
+	pub fn convert_array_with<T>(&self, f: impl FnMut(&Value) -> Option<T>) -> Option<Vec<T>> {
+		match self {
+			Value::Array(x) => Some(x.iter().filter_map(f).collect()),
+			_ => None,
 		}
-		bytes.push(b);
-		for i in 0..byte_len {
-			bytes.push((x >> (8 * ((byte_len - 1) - i))) as u8);
+	}
+
+	/// Like `convert_array_with` but fails on the first element `f` rejects, reporting its index.
+	pub fn try_convert_array_with<T>(&self, mut f: impl FnMut(&Value) -> Option<T>) -> crate::Result<Vec<T>> {
+		let arr: &Vec<Value> = match self {
+			Value::Array(x) => x,
+			_ => return crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, "not an array".into()),
+		};
+		let mut out = Vec::<T>::with_capacity(arr.len());
+		for (i, item) in arr.iter().enumerate() {
+			match f(item) {
+				Some(x) => out.push(x),
+				None => {
+					let msg = format!("could not convert array element at index {}", i);
+					return crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, msg.into());
+				}
+			}
 		}
+		Ok(out)
 	}
 
-	fn push_major_and_len(bytes: &mut Vec<u8>, len: usize, item_code: u8) {
-		let length_code: u8;
-		match len {
-			0..=23 => {
-				length_code = len as u8;
-				let b: u8 = (item_code << 5) | length_code;
-				bytes.push(b);
-			}
-			24..=0xFF => {
-				length_code = 24;
-				let b: u8 = (item_code << 5) | length_code;
-				bytes.push(b);
-				bytes.push(len as u8);
-			}
-			0x100..=0xFFFF => {
-				length_code = 25;
-				let b: u8 = (item_code << 5) | length_code;
-				bytes.push(b);
-				bytes.push((len >> 8) as u8);
-				bytes.push(len as u8);
-			}
-			0x1_0000..=0xFFFF_FFFF => {
-				length_code = 26;
-				let b: u8 = (item_code << 5) | length_code;
-				bytes.push(b);
-				bytes.push((len >> 16) as u8);
-				bytes.push((len >> 8) as u8);
-				bytes.push(len as u8);
+	/// Converts each entry of a map with `f`, skipping entries for which it returns `None`.
+	/// Entries are returned in their original order. Returns `None` if `self` is not a map.
+	pub fn convert_map_with<K, V>(&self, mut f: impl FnMut(&Value, &Value) -> Option<(K, V)>) -> Option<Vec<(K, V)>> {
+		match self {
+			Value::Map(x) => Some(x.iter().filter_map(|kv| f(&kv.key, &kv.val)).collect()),
+			_ => None,
+		}
+	}
+
+	/// Like `convert_map_with` but fails on the first entry `f` rejects, reporting its key.
+	pub fn try_convert_map_with<K, V>(
+		&self,
+		mut f: impl FnMut(&Value, &Value) -> Option<(K, V)>,
+	) -> crate::Result<Vec<(K, V)>> {
+		let map: &Map = match self {
+			Value::Map(x) => x,
+			_ => return crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, "not a map".into()),
+		};
+		let mut out = Vec::<(K, V)>::with_capacity(map.len());
+		for kv in map {
+			match f(&kv.key, &kv.val) {
+				Some(x) => out.push(x),
+				None => {
+					let msg = format!("could not convert map entry with key {}", kv.key);
+					return crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, msg.into());
+				}
 			}
-			_ => {
-				length_code = 27;
-				let b: u8 = (item_code << 5) | length_code;
-				bytes.push(b);
-				bytes.push((len >> 24) as u8);
-				bytes.push((len >> 16) as u8);
-				bytes.push((len >> 8) as u8);
-				bytes.push(len as u8);
+		}
+		Ok(out)
+	}
+
+	/// Moves the value out, leaving `Simple::Null` in its place.
+	pub fn take(&mut self) -> Value { std::mem::replace(self, Value::Simple(Simple::Null)) }
+
+	/// Replaces the value with `new`, returning the old one.
+	pub fn replace(&mut self, new: Value) -> Value { std::mem::replace(self, new) }
+
+	/// Removes the first entry with the given key from a map and returns its value.
+	/// Returns `None` if `self` is not a map or the key is absent.
+	pub fn take_entry(&mut self, key: &Value) -> Option<Value> {
+		match self {
+			Value::Map(x) => x.remove(key),
+			_ => None,
+		}
+	}
+
+	/// Keeps the map entries for which `f(key, value)` returns true, in their original order.
+	/// Does nothing if `self` is not a map.
+	pub fn retain_entries(&mut self, f: impl FnMut(&Value, &Value) -> bool) { let _ = self.try_retain_entries(f); }
+
+	/// Like `retain_entries` but fails if `self` is not a map.
+	pub fn try_retain_entries(&mut self, mut f: impl FnMut(&Value, &Value) -> bool) -> crate::Result<()> {
+		match self {
+			Value::Map(x) => {
+				x.retain(|kv| f(&kv.key, &kv.val));
+				Ok(())
 			}
-		};
+			_ => crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, "not a map".into()),
+		}
+	}
+
+	/// Keeps the array elements for which `f` returns true, in their original order. Does nothing
+	/// if `self` is not an array.
+	pub fn retain_elements(&mut self, f: impl FnMut(&Value) -> bool) { let _ = self.try_retain_elements(f); }
+
+	/// Like `retain_elements` but fails if `self` is not an array.
+	pub fn try_retain_elements(&mut self, f: impl FnMut(&Value) -> bool) -> crate::Result<()> {
+		match self {
+			Value::Array(x) => {
+				x.retain(f);
+				Ok(())
+			}
+			_ => crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, "not an array".into()),
+		}
+	}
+
+	/// Shortens an array to its first `len` elements or a map to its first `len` entries. Does
+	/// nothing if `self` is neither, or already that short.
+	pub fn truncate(&mut self, len: usize) { let _ = self.try_truncate(len); }
+
+	/// Like `truncate` but fails if `self` is not an array or a map.
+	pub fn try_truncate(&mut self, len: usize) -> crate::Result<()> {
+		match self {
+			Value::Array(x) => x.truncate(len),
+			Value::Map(x) => x.truncate(len),
+			_ => return crate::CborError::new_err(crate::ErrorKind::UnexpectedValue, "not an array or map".into()),
+		}
+		Ok(())
+	}
+
+	fn encode_compact_uint(bytes: &mut Vec<u8>, x: u64, major: u8) { crate::header::write_header(bytes, major, x) }
+
+	// Lengths over u32::MAX take the full 8-byte argument
+	fn push_major_and_len(bytes: &mut Vec<u8>, len: usize, item_code: u8) {
+		crate::header::write_header(bytes, item_code, len as u64)
 	}
 
 	fn add_bytes(bytes: &mut Vec<u8>, x: &[u8], item_code: u8) {
 		Value::push_major_and_len(bytes, x.len(), item_code);
-		for item in x {
-			bytes.push(*item);
-		}
+		bytes.extend_from_slice(x);
 	}
 
 	pub fn encode_compact(&self) -> Vec<u8> {
 		let mut bytes = Vec::<u8>::new();
+		self.encode_compact_into(&mut bytes);
+		bytes
+	}
+
+	pub(crate) fn encode_compact_into(&self, bytes: &mut Vec<u8>) {
 		match self {
-			Value::Unsigned(x) => Value::encode_compact_uint(&mut bytes, *x, 0),
+			Value::Unsigned(x) => Value::encode_compact_uint(bytes, *x, 0),
 			Value::Negative(x) => {
+				debug_assert!(*x < 0, "Value::Negative({}) is not negative", x);
 				let x: u64 = (-1 - x) as u64;
-				Value::encode_compact_uint(&mut bytes, x, 1);
+				Value::encode_compact_uint(bytes, x, 1);
 			}
 
 			Value::ByteString(ref x) => {
-				Value::add_bytes(&mut bytes, x.as_slice(), 2);
+				Value::add_bytes(bytes, x.as_slice(), 2);
 			}
 			Value::Utf8String(ref x) => {
-				Value::add_bytes(&mut bytes, x.as_bytes(), 3);
+				Value::add_bytes(bytes, x.as_bytes(), 3);
 			}
 			Value::Array(ref x) => {
-				Value::push_major_and_len(&mut bytes, x.len(), 4);
+				Value::push_major_and_len(bytes, x.len(), 4);
 				for item in x {
-					bytes.append(&mut item.encode_compact());
+					item.encode_compact_into(bytes);
 				}
 			}
 			Value::Map(ref x) => {
-				Value::push_major_and_len(&mut bytes, x.len(), 5);
+				Value::push_major_and_len(bytes, x.len(), 5);
 				for kv in x {
-					bytes.append(&mut kv.key.encode_compact());
-					bytes.append(&mut kv.val.encode_compact());
+					kv.key.encode_compact_into(bytes);
+					kv.val.encode_compact_into(bytes);
 				}
 			}
+			Value::Tag(t, ref x) => {
+				Value::encode_compact_uint(bytes, *t, 6);
+				x.encode_compact_into(bytes);
+			}
 			Value::Float(x) => {
-				let b: u8 = 7 << 5 | 27;
-				bytes.push(b);
-				let x: u64 = x.to_bits(); // unsafe { *(x as *const f64 as *const u64) };
-				for i in 0..8 {
-					bytes.push((x >> (8 * (7 - i))) as u8);
+				bytes.push(7 << 5 | 27);
+				bytes.extend_from_slice(&x.to_bits().to_be_bytes());
+			}
+			Value::Simple(x) => bytes.extend_from_slice(&x.encode()),
+			Value::Raw(x) => bytes.extend_from_slice(x.as_bytes()),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => Value::add_bytes(bytes, x, 2),
+		}
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(self.encoded_size());
+		self.encode_into(&mut out);
+		out
+	}
+
+	/// The number of bytes `encode` produces, worked out without encoding or allocating.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let v = cborg::Value::map(vec![("a", 1000)]);
+	/// assert_eq!(6, v.encoded_size()); // A1 61 61 19 03 E8
+	/// assert_eq!(v.encode().len(), v.encoded_size());
+	/// ```
+	pub fn encoded_size(&self) -> usize {
+		use crate::header::header_len;
+		match self {
+			Value::Unsigned(x) => header_len(*x),
+			Value::Negative(x) => header_len((-1 - x) as u64),
+			Value::ByteString(x) => header_len(x.len() as u64) + x.len(),
+			Value::Utf8String(x) => header_len(x.len() as u64) + x.len(),
+			Value::Array(x) => header_len(x.len() as u64) + x.iter().map(Value::encoded_size).sum::<usize>(),
+			Value::Map(x) => {
+				let entries: usize = x.iter().map(|kv| kv.key.encoded_size() + kv.val.encoded_size()).sum();
+				header_len(x.len() as u64) + entries
+			}
+			Value::Tag(t, x) => header_len(*t) + x.encoded_size(),
+			Value::Float(_) => 9,
+			Value::Simple(Simple::Unassigned(x)) if x.get() >= 24 => 2,
+			Value::Simple(_) => 1,
+			Value::Raw(x) => x.as_bytes().len(),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => header_len(x.len() as u64) + x.len(),
+		}
+	}
+
+	/// Appends the bytes of `encode` to `out`, leaving what it already holds, so that one buffer
+	/// can be reused across messages or hold a frame header in front of the item.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut frame = vec![0, 0]; // room for a length prefix
+	/// cborg::Value::from("hi").encode_into(&mut frame);
+	/// let len = (frame.len() - 2) as u16;
+	/// frame[..2].copy_from_slice(&len.to_be_bytes());
+	/// assert_eq!(vec![0, 3, 0x62, b'h', b'i'], frame);
+	/// ```
+	pub fn encode_into(&self, out: &mut Vec<u8>) { crate::trace::encode(self, out) }
+
+	/// Writes the same bytes as `encode` to `w` without building them all in memory first. Small
+	/// items are gathered in a buffer of a few kilobytes between writes, and long strings are
+	/// written straight from the value. Stops at the first error from `w`, leaving what was already
+	/// written. `w` is not flushed.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let v = cborg::Value::map(vec![("a", 1)]);
+	/// let mut out = Vec::new();
+	/// v.encode_to(&mut out).unwrap();
+	/// assert_eq!(v.encode(), out);
+	/// ```
+	pub fn encode_to<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+		let mut sink = Sink {
+			w,
+			buf: Vec::with_capacity(SINK_BUF),
+		};
+		self.write_item(&mut sink)?;
+		sink.flush()
+	}
+
+	/// Writes the bytes of `encode` to the start of `buf` and returns how many there are. Fails
+	/// with `ErrorKind::BufferTooSmall`, leaving `buf` as it was, if they do not fit. Nothing is
+	/// allocated unless it fails.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut buf = [0; 16];
+	/// let v = cborg::Value::Array(vec![cborg::Value::Unsigned(1), cborg::Value::Float(0.5)]);
+	/// let len = v.encode_to_slice(&mut buf).unwrap();
+	/// assert_eq!(v.encode(), buf[..len].to_vec());
+	/// let err = v.encode_to_slice(&mut buf[..4]).unwrap_err();
+	/// assert_eq!(&cborg::ErrorKind::BufferTooSmall { required: 11 }, err.kind());
+	/// ```
+	pub fn encode_to_slice(&self, buf: &mut [u8]) -> crate::Result<usize> {
+		let required = self.encoded_size();
+		if required > buf.len() {
+			let msg = format!("encoding takes {} bytes but the buffer holds {}", required, buf.len());
+			return crate::CborError::new_err(crate::ErrorKind::BufferTooSmall { required }, msg.into());
+		}
+		let mut pos = 0;
+		self.write_slice(buf, &mut pos);
+		debug_assert_eq!(required, pos);
+		Ok(pos)
+	}
+
+	// Writes at `pos` of a buffer that encoded_size has shown to be long enough
+	fn write_slice(&self, buf: &mut [u8], pos: &mut usize) {
+		fn put(buf: &mut [u8], pos: &mut usize, x: &[u8]) {
+			buf[*pos..*pos + x.len()].copy_from_slice(x);
+			*pos += x.len();
+		}
+		fn header(buf: &mut [u8], pos: &mut usize, major: u8, arg: u64) {
+			let (bytes, len) = crate::header::header_bytes(major, arg);
+			put(buf, pos, &bytes[..len]);
+		}
+		match self {
+			Value::Unsigned(x) => header(buf, pos, 0, *x),
+			Value::Negative(x) => header(buf, pos, 1, (-1 - x) as u64),
+			Value::ByteString(x) => {
+				header(buf, pos, 2, x.len() as u64);
+				put(buf, pos, x);
+			}
+			Value::Utf8String(x) => {
+				header(buf, pos, 3, x.len() as u64);
+				put(buf, pos, x.as_bytes());
+			}
+			Value::Array(x) => {
+				header(buf, pos, 4, x.len() as u64);
+				for item in x {
+					item.write_slice(buf, pos);
+				}
+			}
+			Value::Map(x) => {
+				header(buf, pos, 5, x.len() as u64);
+				for kv in x {
+					kv.key.write_slice(buf, pos);
+					kv.val.write_slice(buf, pos);
 				}
 			}
+			Value::Tag(t, x) => {
+				header(buf, pos, 6, *t);
+				x.write_slice(buf, pos);
+			}
+			Value::Float(x) => {
+				put(buf, pos, &[7 << 5 | 27]);
+				put(buf, pos, &x.to_bits().to_be_bytes());
+			}
 			Value::Simple(x) => {
-				for b in x.encode() {
-					bytes.push(b);
+				let (bytes, len) = x.encode_array();
+				put(buf, pos, &bytes[..len]);
+			}
+			Value::Raw(x) => put(buf, pos, x.as_bytes()),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => {
+				header(buf, pos, 2, x.len() as u64);
+				put(buf, pos, x);
+			}
+		}
+	}
+
+	fn write_item<W: io::Write>(&self, sink: &mut Sink<W>) -> io::Result<()> {
+		match self {
+			Value::Unsigned(x) => sink.header(0, *x),
+			Value::Negative(x) => sink.header(1, (-1 - x) as u64),
+			Value::ByteString(x) => {
+				sink.header(2, x.len() as u64)?;
+				sink.bytes(x)
+			}
+			Value::Utf8String(x) => {
+				sink.header(3, x.len() as u64)?;
+				sink.bytes(x.as_bytes())
+			}
+			Value::Array(x) => {
+				sink.header(4, x.len() as u64)?;
+				for item in x {
+					item.write_item(sink)?;
 				}
+				Ok(())
+			}
+			Value::Map(x) => {
+				sink.header(5, x.len() as u64)?;
+				for kv in x {
+					kv.key.write_item(sink)?;
+					kv.val.write_item(sink)?;
+				}
+				Ok(())
+			}
+			Value::Tag(t, x) => {
+				sink.header(6, *t)?;
+				x.write_item(sink)
+			}
+			Value::Float(x) => {
+				sink.bytes(&[7 << 5 | 27])?;
+				sink.bytes(&x.to_bits().to_be_bytes())
+			}
+			Value::Simple(x) => sink.bytes(&x.encode()),
+			Value::Raw(x) => sink.bytes(x.as_bytes()),
+			#[cfg(feature = "bytes")]
+			Value::SharedBytes(x) => {
+				sink.header(2, x.len() as u64)?;
+				sink.bytes(x)
 			}
 		}
+	}
+
+	/// Encodes the value deterministically, following RFC 8949 section 4.2.1: integers and lengths
+	/// use their shortest form, floats use the shortest of half, single or double precision that
+	/// represents them exactly, and map entries are sorted by the bytes of their encoded keys.
+	pub fn encode_canonical(&self) -> Vec<u8> { self.encode_canonical_with(crate::KeyOrder::Rfc8949) }
+
+	/// Like `encode_canonical` with a choice of map key ordering.
+	pub fn encode_canonical_with(&self, order: crate::KeyOrder) -> Vec<u8> {
+		let mut out = Vec::new();
+		crate::canonical::encode_value(self, &mut out, order);
+		out
+	}
+
+	/// Encodes the value with the entries of every map, at any depth, sorted by `cmp` applied to
+	/// their keys. The value itself is left untouched.
+	///
+	/// The sort is stable: keys that `cmp` considers equal, including duplicate keys, keep their
+	/// original relative order. See [`Value::try_encode_sorted_by`] to reject duplicates instead.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cborg::{KeyVal, Map, Value};
+	/// let map = Value::Map(Map::from(vec![
+	///     KeyVal { key: Value::Unsigned(2), val: Value::Unsigned(0) },
+	///     KeyVal { key: Value::Unsigned(1), val: Value::Unsigned(0) },
+	/// ]));
+	/// assert_eq!(vec![0xA2, 0x01, 0x00, 0x02, 0x00], map.encode_sorted_by(|a, b| a.cmp(b)));
+	/// ```
+	pub fn encode_sorted_by<F: Fn(&Value, &Value) -> Ordering>(&self, cmp: F) -> Vec<u8> {
+		let mut bytes = Vec::<u8>::new();
+		// Cannot fail without the duplicate check
+		let _ = self.encode_sorted_into(&mut bytes, &cmp, false);
 		bytes
 	}
 
-	pub fn encode(&self) -> Vec<u8> { self.encode_compact() }
+	/// Like [`Value::encode_sorted_by`], but fails with `ErrorKind::DuplicateKey` if any map
+	/// contains the same key more than once.
+	pub fn try_encode_sorted_by<F: Fn(&Value, &Value) -> Ordering>(&self, cmp: F) -> crate::Result<Vec<u8>> {
+		let mut bytes = Vec::<u8>::new();
+		self.encode_sorted_into(&mut bytes, &cmp, true)?;
+		Ok(bytes)
+	}
+
+	fn encode_sorted_into(
+		&self,
+		bytes: &mut Vec<u8>,
+		cmp: &dyn Fn(&Value, &Value) -> Ordering,
+		reject_duplicates: bool,
+	) -> crate::Result<()> {
+		match self {
+			Value::Array(ref x) => {
+				Value::push_major_and_len(bytes, x.len(), 4);
+				for item in x {
+					item.encode_sorted_into(bytes, cmp, reject_duplicates)?;
+				}
+			}
+			Value::Map(ref x) => {
+				if reject_duplicates {
+					let mut seen = HashSet::with_capacity(x.len());
+					if let Some(kv) = x.iter().find(|kv| !seen.insert(&kv.key)) {
+						let msg = format!("duplicate map key {}", kv.key.to_diagnostic());
+						return crate::CborError::new_err(crate::ErrorKind::DuplicateKey, msg.into());
+					}
+				}
+				let mut sorted: Vec<&KeyVal> = x.iter().collect();
+				sorted.sort_by(|a, b| cmp(&a.key, &b.key));
+				Value::push_major_and_len(bytes, x.len(), 5);
+				for kv in sorted {
+					kv.key.encode_sorted_into(bytes, cmp, reject_duplicates)?;
+					kv.val.encode_sorted_into(bytes, cmp, reject_duplicates)?;
+				}
+			}
+			Value::Tag(t, ref x) => {
+				Value::encode_compact_uint(bytes, *t, 6);
+				x.encode_sorted_into(bytes, cmp, reject_duplicates)?;
+			}
+			_ => self.encode_compact_into(bytes),
+		}
+		Ok(())
+	}
+
+	/// Renders the value on one line in the diagnostic notation of RFC 8949 section 8, e.g.
+	/// `{1: h'0102', "a": [2.5, null]}`.
+	pub fn to_diagnostic(&self) -> String {
+		let mut output = Vec::<u8>::new();
+		// Writing to a Vec cannot fail
+		let _ = print_diagnostic(self, &mut output);
+		String::from_utf8(output).unwrap_or_default()
+	}
+
+	/// Renders the value like `Display`, with ANSI colors for a terminal: map keys blue, text
+	/// strings green, numbers yellow, byte strings magenta, simple values cyan and punctuation
+	/// dimmed. Use [`should_color`] to decide whether to use it.
+	pub fn display_colored(&self) -> Colored<'_> { Colored(self) }
 
 	// Possible future extension
 	// pub fn encode_preserving_types(&self) -> Vec<u8> {
@@ -351,63 +1074,215 @@ impl Value {
 }
 
 pub fn print_cbor<W: io::Write>(val: &Value, w: &mut W) -> io::Result<()> {
-	print_cbor_padded(val, 0, w)?;
+	print_cbor_padded(val, 0, Colors::Off, None, w)?;
 	Ok(())
 }
 
-fn print_cbor_padded<W: io::Write>(val: &Value, indent: usize, w: &mut W) -> io::Result<()> {
+// ANSI escape sequences used by Value::display_colored
+const ANSI_KEY: &str = "\x1b[34m";
+const ANSI_STRING: &str = "\x1b[32m";
+const ANSI_NUMBER: &str = "\x1b[33m";
+const ANSI_BYTES: &str = "\x1b[35m";
+const ANSI_SIMPLE: &str = "\x1b[36m";
+const ANSI_PUNCT: &str = "\x1b[2m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Colors {
+	Off,
+	On,
+	// Inside a map key: every scalar takes the key color
+	Key,
+}
+
+impl Colors {
+	fn paint<W: io::Write>(self, w: &mut W, ansi: &str, args: fmt::Arguments) -> io::Result<()> {
+		match self {
+			Colors::Off => w.write_fmt(args),
+			Colors::On => write!(w, "{}{}{}", ansi, args, ANSI_RESET),
+			Colors::Key => write!(w, "{}{}{}", ANSI_KEY, args, ANSI_RESET),
+		}
+	}
+
+	fn punct<W: io::Write>(self, w: &mut W, s: &str) -> io::Result<()> {
+		match self {
+			Colors::Off => w.write_all(s.as_bytes()),
+			_ => write!(w, "{}{}{}", ANSI_PUNCT, s, ANSI_RESET),
+		}
+	}
+}
+
+// `hint` is the text encoding asked for by an enclosing tag 21, 22 or 23
+fn print_cbor_padded<W: io::Write>(
+	val: &Value,
+	indent: usize,
+	colors: Colors,
+	hint: Option<TextHint>,
+	w: &mut W,
+) -> io::Result<()> {
 	match val {
-		Value::Unsigned(x) => write!(w, "{}", x),
-		Value::Negative(x) => write!(w, "{}", x),
+		Value::Unsigned(x) => colors.paint(w, ANSI_NUMBER, format_args!("{}", x)),
+		Value::Negative(x) => colors.paint(w, ANSI_NUMBER, format_args!("{}", x)),
 		Value::ByteString(ref x) => {
-			if x.is_empty() {
-				w.write_all(b"[]")?;
+			let s = if let Some(h) = hint {
+				h.encode(x)
+			} else if x.is_empty() {
+				"[]".to_string()
 			} else if x.len() == 1 {
-				write!(w, "[ {} ]", x[0])?;
+				format!("[ {} ]", x[0])
 			} else {
-				w.write_all(b"[")?;
-				write!(w, "{}", x[0])?;
+				let mut s = format!("[{}", x[0]);
 				for y in x.iter().skip(1) {
-					write!(w, ", {}", y)?;
+					s += &format!(", {}", y);
 				}
-				w.write_all(b"]")?;
-			}
-			Ok(())
+				s + "]"
+			};
+			colors.paint(w, ANSI_BYTES, format_args!("{}", s))
 		}
-		Value::Utf8String(ref x) => write!(w, r#""{}""#, x),
+		Value::Utf8String(ref x) => colors.paint(w, ANSI_STRING, format_args!(r#""{}""#, x)),
 		Value::Array(ref x) => {
-			w.write_all(b"[\n")?;
+			colors.punct(w, "[")?;
+			w.write_all(b"\n")?;
 			for y in x {
 				for _ in 0..=indent {
 					w.write_all(b"   ")?;
 				}
-				print_cbor_padded(&y, indent, w)?;
-				w.write_all(b",\n")?;
+				print_cbor_padded(y, indent, colors, hint, w)?;
+				colors.punct(w, ",")?;
+				w.write_all(b"\n")?;
 			}
 			for _ in 0..indent {
 				w.write_all(b"   ")?;
 			}
-			w.write_all(b"]")?;
-			Ok(())
+			colors.punct(w, "]")
 		}
 		Value::Map(ref x) => {
-			w.write_all(b"{\n")?;
+			let key_colors = if colors == Colors::Off {
+				Colors::Off
+			} else {
+				Colors::Key
+			};
+			colors.punct(w, "{")?;
+			w.write_all(b"\n")?;
 			for kv in x {
 				for _ in 0..=indent {
 					w.write_all(b"   ")?;
 				}
-				print_cbor_padded(&kv.key, indent + 1, w)?;
-				w.write_all(b": ")?;
-				print_cbor_padded(&kv.val, indent + 1, w)?;
-				w.write_all(b",\n")?;
+				print_cbor_padded(&kv.key, indent + 1, key_colors, hint, w)?;
+				colors.punct(w, ":")?;
+				w.write_all(b" ")?;
+				print_cbor_padded(&kv.val, indent + 1, colors, hint, w)?;
+				colors.punct(w, ",")?;
+				w.write_all(b"\n")?;
 			}
 			for _ in 0..indent {
 				w.write_all(b"   ")?;
 			}
-			w.write_all(b"}")?;
-			Ok(())
+			colors.punct(w, "}")
+		}
+		Value::Tag(t, ref x) => {
+			colors.punct(w, &format!("{}(", t))?;
+			print_cbor_padded(x, indent, colors, TextHint::from_tag(*t).or(hint), w)?;
+			colors.punct(w, ")")
+		}
+		Value::Float(x) => colors.paint(w, ANSI_NUMBER, format_args!("{}", x)),
+		Value::Simple(x) => colors.paint(w, ANSI_SIMPLE, format_args!("{}", x)),
+		Value::Raw(x) => print_cbor_padded(&x.decode(), indent, colors, hint, w),
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(x) => print_cbor_padded(&Value::ByteString(x.to_vec()), indent, colors, hint, w),
+	}
+}
+
+/// Display adapter returned by [`Value::display_colored`].
+pub struct Colored<'a>(&'a Value);
+
+impl fmt::Display for Colored<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut output = Vec::<u8>::new();
+		print_cbor_padded(self.0, 0, Colors::On, None, &mut output).map_err(|_| fmt::Error)?;
+		f.write_str(std::str::from_utf8(&output).map_err(|_| fmt::Error)?)
+	}
+}
+
+/// Whether standard output is a terminal that colored output should be sent to: `false` when it
+/// is redirected or the `NO_COLOR` environment variable is set.
+pub fn should_color() -> bool {
+	use std::io::IsTerminal;
+	std::env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal()
+}
+
+/// Writes `val` in RFC 8949 diagnostic notation. See [`Value::to_diagnostic`].
+pub fn print_diagnostic<W: io::Write>(val: &Value, w: &mut W) -> io::Result<()> {
+	print_diagnostic_hinted(val, None, w)
+}
+
+// Byte strings inside tag 21 or 22 are written as b64'' with the alphabet the tag asks for
+fn print_diagnostic_hinted<W: io::Write>(val: &Value, hint: Option<TextHint>, w: &mut W) -> io::Result<()> {
+	match val {
+		Value::Unsigned(x) => write!(w, "{}", x),
+		Value::Negative(x) => write!(w, "{}", x),
+		Value::ByteString(ref x) => match hint {
+			Some(h @ TextHint::Base64Url) | Some(h @ TextHint::Base64) => write!(w, "b64'{}'", h.encode(x)),
+			_ => {
+				w.write_all(b"h'")?;
+				for b in x {
+					write!(w, "{:02x}", b)?;
+				}
+				w.write_all(b"'")
+			}
+		},
+		Value::Utf8String(ref x) => {
+			w.write_all(b"\"")?;
+			for c in x.chars() {
+				match c {
+					'"' => w.write_all(b"\\\"")?,
+					'\\' => w.write_all(b"\\\\")?,
+					c if c.is_control() => write!(w, "\\u{:04x}", c as u32)?,
+					c => write!(w, "{}", c)?,
+				}
+			}
+			w.write_all(b"\"")
+		}
+		Value::Array(ref x) => {
+			w.write_all(b"[")?;
+			for (i, y) in x.iter().enumerate() {
+				if i > 0 {
+					w.write_all(b", ")?;
+				}
+				print_diagnostic_hinted(y, hint, w)?;
+			}
+			w.write_all(b"]")
+		}
+		Value::Map(ref x) => {
+			w.write_all(b"{")?;
+			for (i, kv) in x.iter().enumerate() {
+				if i > 0 {
+					w.write_all(b", ")?;
+				}
+				print_diagnostic_hinted(&kv.key, hint, w)?;
+				w.write_all(b": ")?;
+				print_diagnostic_hinted(&kv.val, hint, w)?;
+			}
+			w.write_all(b"}")
+		}
+		Value::Tag(t, ref x) => {
+			write!(w, "{}(", t)?;
+			print_diagnostic_hinted(x, TextHint::from_tag(*t).or(hint), w)?;
+			w.write_all(b")")
+		}
+		Value::Float(x) => {
+			if x.is_nan() {
+				w.write_all(b"NaN")
+			} else if x.is_infinite() {
+				w.write_all(if *x > 0.0 { b"Infinity" } else { b"-Infinity" })
+			} else {
+				write!(w, "{:?}", x)
+			}
 		}
-		Value::Float(x) => write!(w, "{}", x),
+		Value::Simple(Simple::Unassigned(x)) => write!(w, "simple({})", x),
 		Value::Simple(x) => write!(w, "{}", x),
+		Value::Raw(x) => print_diagnostic_hinted(&x.decode(), hint, w),
+		#[cfg(feature = "bytes")]
+		Value::SharedBytes(x) => print_diagnostic_hinted(&Value::ByteString(x.to_vec()), hint, w),
 	}
 }
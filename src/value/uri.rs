@@ -0,0 +1,95 @@
+use super::Value;
+use crate::tags;
+
+// Whether `s` is a URI by the syntax of RFC 3986 section 3: a scheme, a colon, and only
+// characters a URI may hold, with well-formed percent-encodings and at most one fragment.
+// Relative references are not URIs.
+pub(crate) fn is_uri(s: &str) -> bool {
+	let colon = match s.find(':') {
+		Some(x) => x,
+		None => return false,
+	};
+	let mut scheme = s[..colon].chars();
+	match scheme.next() {
+		Some(c) if c.is_ascii_alphabetic() => (),
+		_ => return false,
+	}
+	if !scheme.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+		return false;
+	}
+	let rest = &s.as_bytes()[colon + 1..];
+	let mut fragments = 0;
+	let mut i = 0;
+	while i < rest.len() {
+		match rest[i] {
+			b'%' => {
+				if !rest
+					.get(i + 1..i + 3)
+					.is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit))
+				{
+					return false;
+				}
+				i += 2;
+			}
+			b'#' => fragments += 1,
+			c if c.is_ascii_alphanumeric() || b"-._~:/?[]@!$&'()*+,;=".contains(&c) => (),
+			_ => return false,
+		}
+		i += 1;
+	}
+	fragments <= 1
+}
+
+impl Value {
+	/// A URI: the text as a text string under tag 32. The text is not checked.
+	pub fn uri(uri: &str) -> Value { Value::tagged(tags::URI, Value::Utf8String(uri.to_string())) }
+
+	/// The text of a tag 32 URI. Untagged strings and tag 32 over anything but a text string give
+	/// `None`. The text is not checked; [`crate::decode_strict`] rejects text that is not a URI.
+	pub fn get_uri(&self) -> Option<&str> {
+		match self {
+			Value::Tag(tags::URI, x) => match x.as_ref() {
+				Value::Utf8String(s) => Some(s),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+#[cfg(feature = "url")]
+mod parsed {
+	use url::Url;
+
+	use super::super::FromValue;
+	use super::super::ToValue;
+	use super::Value;
+	use crate::CborError;
+	use crate::ErrorKind;
+	use crate::Result;
+
+	impl Value {
+		/// Parses a tag 32 URI, reporting why if it is invalid.
+		pub fn to_url(&self) -> Result<Url> {
+			let uri = match self.get_uri() {
+				Some(x) => x,
+				None => return CborError::new_err(ErrorKind::UnexpectedValue, "not a tag 32 URI".into()),
+			};
+			Url::parse(uri).map_err(|e| CborError::wrap(ErrorKind::UnexpectedValue, e))
+		}
+	}
+
+	impl ToValue for Url {
+		fn to_value(&self) -> Value { Value::uri(self.as_str()) }
+	}
+
+	impl From<Url> for Value {
+		fn from(u: Url) -> Value { Value::uri(u.as_str()) }
+	}
+
+	/// Fails if the URI does not parse. Use [`Value::to_url`] to see the parse error.
+	impl FromValue for Url {
+		fn from_value(v: Value) -> Option<Self> { v.to_url().ok() }
+		fn from_ref(v: &Value) -> Option<Self> { v.to_url().ok() }
+	}
+}
@@ -0,0 +1,38 @@
+use super::Value;
+
+// Whether `v` is the content tag 37 requires: a 16-byte byte string
+pub(crate) fn is_uuid_content(v: &Value) -> bool { matches!(v.as_byte_slice(), Some(b) if b.len() == 16) }
+
+#[cfg(feature = "uuid")]
+mod parsed {
+	use uuid::Uuid;
+
+	use super::super::FromValue;
+	use super::super::ToValue;
+	use super::Value;
+	use crate::tags;
+
+	fn from_bytes(b: &[u8]) -> Option<Uuid> { Uuid::from_slice(b).ok() }
+
+	/// Tag 37 over the 16 bytes of the UUID.
+	impl ToValue for Uuid {
+		fn to_value(&self) -> Value { Value::tagged(tags::UUID, Value::ByteString(self.as_bytes().to_vec())) }
+	}
+
+	impl From<Uuid> for Value {
+		fn from(u: Uuid) -> Value { u.to_value() }
+	}
+
+	/// Accepts tag 37 over 16 bytes and, without the tag, a 16-byte byte string or the 36-character
+	/// hyphenated text form.
+	impl FromValue for Uuid {
+		fn from_value(v: Value) -> Option<Self> { Uuid::from_ref(&v) }
+		fn from_ref(v: &Value) -> Option<Self> {
+			match v {
+				Value::Tag(tags::UUID, x) => from_bytes(x.as_byte_slice()?),
+				Value::Utf8String(s) if s.len() == 36 => Uuid::try_parse(s).ok(),
+				x => from_bytes(x.as_byte_slice()?),
+			}
+		}
+	}
+}
@@ -0,0 +1,115 @@
+// Decoding from a `bytes::Bytes` buffer with byte strings that share it.
+
+use bytes::Bytes;
+
+use crate::header::read_header;
+use crate::header::Header;
+use crate::KeyVal;
+use crate::Result;
+use crate::Value;
+
+/// Decodes the first item in `buf` like [`decode_slice`](crate::decode_slice), except that
+/// definite-length byte strings become `Value::SharedBytes` views into `buf` instead of copies.
+/// Indefinite-length byte strings are joined into a new buffer, as their chunks are not contiguous.
+///
+/// The input is checked with [`validate`](crate::validate) first, so errors are those
+/// `decode_slice` gives, without their context.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::Bytes;
+/// use cborg::Value;
+/// let buf = Bytes::from(vec![0x82, 0x43, 1, 2, 3, 0x41, 4]); // [h'010203', h'04']
+/// let v = cborg::decode_bytes(buf.clone()).unwrap();
+/// assert_eq!("[h'010203', h'04']", v.to_diagnostic());
+/// let first = match v {
+///     Value::Array(mut items) => items.remove(0),
+///     _ => unreachable!(),
+/// };
+/// match first {
+///     Value::SharedBytes(b) => assert_eq!(buf[2..].as_ptr(), b.as_ptr()),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn decode_bytes(buf: Bytes) -> Result<Value> {
+	crate::validate(&buf)?;
+	let mut pos = 0;
+	Ok(item(&buf, &mut pos).strip_self_described())
+}
+
+// Reads the item at `pos` of input that has been validated, so nothing here can fail. Nesting is
+// bounded by the default depth limits validate enforces.
+fn item(buf: &Bytes, pos: &mut usize) -> Value {
+	let start = *pos;
+	let h = read_header(buf, start).expect("validated");
+	*pos += h.len;
+	match h.major {
+		0 => Value::Unsigned(h.arg),
+		1 => Value::Negative(-1 - h.arg as i64),
+		2 if !h.is_indefinite() => {
+			let end = *pos + h.arg as usize;
+			let v = Value::SharedBytes(buf.slice(*pos..end));
+			*pos = end;
+			v
+		}
+		2 | 3 => {
+			let mut content = Vec::new();
+			if h.is_indefinite() {
+				while buf[*pos] != 0xFF {
+					let chunk = read_header(buf, *pos).expect("validated");
+					*pos += chunk.len;
+					content.extend_from_slice(&buf[*pos..*pos + chunk.arg as usize]);
+					*pos += chunk.arg as usize;
+				}
+				*pos += 1;
+			} else {
+				content.extend_from_slice(&buf[*pos..*pos + h.arg as usize]);
+				*pos += h.arg as usize;
+			}
+			if h.major == 2 {
+				Value::SharedBytes(Bytes::from(content))
+			} else {
+				Value::Utf8String(String::from_utf8(content).expect("validated"))
+			}
+		}
+		4 => {
+			let mut arr = Vec::new();
+			while more(buf, pos, &h, arr.len()) {
+				arr.push(item(buf, pos));
+			}
+			Value::Array(arr)
+		}
+		5 => {
+			let mut map = Vec::new();
+			while more(buf, pos, &h, map.len()) {
+				let key = item(buf, pos);
+				let val = item(buf, pos);
+				map.push(KeyVal { key, val });
+			}
+			Value::Map(map.into())
+		}
+		6 => Value::Tag(h.arg, Box::new(item(buf, pos))),
+		_ => {
+			let mut arg = buf[start + 1..start + h.len].iter();
+			if h.minor <= 24 {
+				Value::Simple(crate::parse_simple(h.minor, &mut arg).expect("validated"))
+			} else {
+				Value::Float(crate::parse_float(h.minor, &mut arg).expect("validated"))
+			}
+		}
+	}
+}
+
+// Whether the array or map with header `h` has more elements after the `n` read so far, consuming
+// the break code that ends an indefinite-length one
+fn more(buf: &Bytes, pos: &mut usize, h: &Header, n: usize) -> bool {
+	if !h.is_indefinite() {
+		return (n as u64) < h.arg;
+	}
+	if buf[*pos] == 0xFF {
+		*pos += 1;
+		return false;
+	}
+	true
+}
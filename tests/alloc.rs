@@ -0,0 +1,149 @@
+// Tests that measure heap usage. They live in their own binary so the counting allocator only
+// sees this file's tests, and the tests take a lock so they never overlap.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Mutex;
+
+use cborg::Value;
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static LOCK: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let now = CURRENT.fetch_add(layout.size(), SeqCst) + layout.size();
+		PEAK.fetch_max(now, SeqCst);
+		ALLOCS.fetch_add(1, SeqCst);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		CURRENT.fetch_sub(layout.size(), SeqCst);
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// Peak heap growth while running f
+fn peak_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+	let base = CURRENT.load(SeqCst);
+	PEAK.store(base, SeqCst);
+	let result = f();
+	(result, PEAK.load(SeqCst) - base)
+}
+
+// Number of allocations made while running f
+fn allocs_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+	let before = ALLOCS.load(SeqCst);
+	let result = f();
+	(result, ALLOCS.load(SeqCst) - before)
+}
+
+#[test]
+fn canonicalize_peak_memory() {
+	let _guard = LOCK.lock().unwrap();
+
+	let records = (1..5000u32).map(|i| {
+		Value::map(vec![
+			("name", Value::text(format!("record {}", i))),
+			("id", Value::from(i)),
+			("tags", Value::array(vec!["a", "b", "c"])),
+			("score", Value::from(f64::from(i) / 8.0)),
+		])
+	});
+	let doc = Value::array(records).encode();
+
+	let (streamed, streamed_peak) = peak_during(|| cborg::canonicalize(&doc).unwrap());
+	let (via_value, tree_peak) = peak_during(|| cborg::decode(&doc).unwrap().encode_canonical());
+	assert_eq!(via_value, streamed);
+	// The output buffer starts at the input's size; beyond that only one record's entries are held
+	assert!(
+		streamed_peak < doc.len() + 4096,
+		"{} vs input {}",
+		streamed_peak,
+		doc.len()
+	);
+	assert!(streamed_peak * 3 < tree_peak, "{} vs {}", streamed_peak, tree_peak);
+}
+
+#[test]
+fn decode_reuse_allocations() {
+	let _guard = LOCK.lock().unwrap();
+
+	let message = |i: u32| {
+		Value::map(vec![
+			("sensor", Value::text(format!("sensor-{}", i % 7))),
+			("readings", Value::array(vec![f64::from(i), f64::from(i) * 0.5, 3.25])),
+			("raw", Value::bytes(vec![(i % 256) as u8; 16])),
+			("status", Value::text("ok")),
+		])
+		.encode()
+	};
+	let messages: Vec<Vec<u8>> = (1..=100).map(message).collect();
+
+	let (_, fresh) = allocs_during(|| {
+		for m in &messages {
+			drop(cborg::decode(m).unwrap());
+		}
+	});
+	let mut out = Value::Simple(cborg::Simple::Null);
+	cborg::decode_reuse(&messages[0], &mut out).unwrap();
+	let (_, reused) = allocs_during(|| {
+		for m in &messages {
+			cborg::decode_reuse(m, &mut out).unwrap();
+		}
+	});
+	assert_eq!(cborg::decode(&messages[99]).unwrap(), out);
+	// Once warmed up, every buffer already has room for a message of this shape
+	assert_eq!(0, reused, "plain decode made {} allocations", fresh);
+	assert!(fresh >= 100 * 8);
+}
+
+#[test]
+fn encode_to_slice_allocations() {
+	let _guard = LOCK.lock().unwrap();
+
+	let values = vec![
+		Value::Unsigned(u64::MAX),
+		Value::Negative(-1000),
+		Value::Float(2.5),
+		Value::Simple(cborg::Simple::Null),
+		Value::Tag(1, Box::new(Value::Unsigned(1_700_000_000))),
+		Value::array(vec![Value::from("abc"), Value::bytes(vec![1, 2, 3])]),
+	];
+	let mut buf = [0u8; 1024];
+	for v in &values {
+		let (len, allocs) = allocs_during(|| v.encode_to_slice(&mut buf).unwrap());
+		assert_eq!(0, allocs, "{}", v.to_diagnostic());
+		assert_eq!(v.encode(), buf[..len].to_vec());
+	}
+}
+
+#[test]
+fn value_ord_allocations() {
+	let _guard = LOCK.lock().unwrap();
+
+	let mut keys: Vec<Value> = (0..1000u32)
+		.rev()
+		.map(|i| {
+			Value::array(vec![
+				Value::from(format!("key {}", i % 10)),
+				Value::from(i),
+				Value::map(vec![("n", Value::Float(f64::from(i)))]),
+			])
+		})
+		.collect();
+	let (_, allocs) = allocs_during(|| keys.sort_unstable());
+	assert_eq!(0, allocs);
+	assert!(keys.windows(2).all(|w| w[0].encode_compact() <= w[1].encode_compact()));
+}
@@ -1,8 +1,11 @@
 use core::fmt::Write;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 
+use cborg::ErrorKind;
 use cborg::KeyVal;
+use cborg::Map;
 use cborg::ToValue;
 use cborg::Value;
 use cborg::ValueInto;
@@ -142,10 +145,10 @@ fn encode_test() {
 	let long_key = "long string";
 	let long_val = "This line is greater than 256 characters to test if lengths are encoded correctly after the major. This line is greater than 256 characters to test if lengths are encoded correctly after the major. This line is greater than 256 characters to test if lengths are encoded correctly after the major.";
 
-	let data = Value::Map(vec![
+	let data = Value::Map(Map::from(vec![
 		KeyVal {
 			key: Value::Unsigned(555),
-			val: Value::Map(vec![
+			val: Value::Map(Map::from(vec![
 				KeyVal {
 					key: Value::Utf8String(String::from("float")),
 					val: Value::Float(2.5),
@@ -170,7 +173,7 @@ fn encode_test() {
 					key: Value::Utf8String(String::from("negative")),
 					val: Value::Negative(-4),
 				},
-			]),
+			])),
 		},
 		KeyVal {
 			key: Value::Unsigned(777),
@@ -181,15 +184,10 @@ fn encode_test() {
 				Value::Utf8String(String::from("fourty-four")),
 			]),
 		},
-	]);
+	]));
 
 	let bytes: Vec<u8> = data.encode();
-	for i in 0..125 {
-		if bytes[i] != TEST_DATA_DEFINITE[i] {
-			println!("mismatch at pos {}: {} : {}", i, TEST_DATA_DEFINITE[i], bytes[i]);
-		}
-	}
-
+	cborg::assert_cbor_eq!(&TEST_DATA_DEFINITE, &bytes);
 	assert_eq!(TEST_DATA_DEFINITE.to_vec(), bytes);
 
 	let mut data = HashMap::new();
@@ -339,3 +337,4948 @@ fn decode_to_test() {
 	assert_eq!(11, arr[0]);
 	assert_eq!(-22, arr[1]);
 }
+
+#[test]
+fn btree_value_key_test() {
+	// {"b": "two", 10: "ten", -2: "minus", "a": "one", 300: "big"}
+	let bytes = [
+		0xA5, 0x61, 0x62, 0x63, 0x74, 0x77, 0x6F, 0x0A, 0x63, 0x74, 0x65, 0x6E, 0x21, 0x65, 0x6D, 0x69, 0x6E, 0x75, 0x73,
+		0x61, 0x61, 0x63, 0x6F, 0x6E, 0x65, 0x19, 0x01, 0x2C, 0x63, 0x62, 0x69, 0x67,
+	];
+	// The same map with its keys in canonical order: 10, 300, -2, "a", "b"
+	let canonical = [
+		0xA5, 0x0A, 0x63, 0x74, 0x65, 0x6E, 0x19, 0x01, 0x2C, 0x63, 0x62, 0x69, 0x67, 0x21, 0x65, 0x6D, 0x69, 0x6E, 0x75,
+		0x73, 0x61, 0x61, 0x63, 0x6F, 0x6E, 0x65, 0x61, 0x62, 0x63, 0x74, 0x77, 0x6F,
+	];
+
+	let map: BTreeMap<Value, String> = cborg::decode_to(&bytes).unwrap().unwrap();
+	let keys: Vec<&Value> = map.keys().collect();
+	assert_eq!(
+		keys,
+		vec![
+			&Value::Unsigned(10),
+			&Value::Unsigned(300),
+			&Value::Negative(-2),
+			&Value::Utf8String("a".to_string()),
+			&Value::Utf8String("b".to_string()),
+		]
+	);
+	assert_eq!(map[&Value::Negative(-2)], "minus");
+	assert_eq!(cborg::encode(map).as_slice(), &canonical[..]);
+
+	let map: BTreeMap<Value, Value> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!(5, map.len());
+	assert_eq!(cborg::encode_ref(&map).as_slice(), &canonical[..]);
+
+	// [3, "x", 1, 3]
+	let bytes = [0x84, 0x03, 0x61, 0x78, 0x01, 0x03];
+	let set: BTreeSet<Value> = cborg::decode_to(&bytes).unwrap().unwrap();
+	let items: Vec<Value> = set.iter().cloned().collect();
+	assert_eq!(
+		items,
+		vec![
+			Value::Unsigned(1),
+			Value::Unsigned(3),
+			Value::Utf8String("x".to_string())
+		]
+	);
+	assert_eq!(cborg::encode(set), vec![0x83, 0x01, 0x03, 0x61, 0x78]);
+
+	// Comparing values directly agrees with comparing their encodings
+	let mut values = vec![
+		cborg::decode(&TEST_DATA_DEFINITE).unwrap(),
+		Value::Float(1.5),
+		Value::Float(-0.0),
+		Value::Float(f64::NAN),
+		Value::Simple(cborg::Simple::Null),
+		Value::Simple(cborg::Simple::False),
+		Value::Tag(1, Box::new(Value::Unsigned(2))),
+		Value::Tag(1, Box::new(Value::from("a"))),
+		Value::map(vec![("a", 1), ("b", 2)]),
+		Value::map(vec![("a", 1), ("b", 3)]),
+		Value::map(vec![("b", 1)]),
+		Value::Raw(cborg::RawValue::new(vec![0x18, 0x05]).unwrap()),
+	];
+	for &n in &[0, 23, 24, 255, 256, 65536, u64::MAX] {
+		values.push(Value::Unsigned(n));
+		values.push(Value::Negative(-1 - (n >> 1) as i64));
+		values.push(Value::bytes(vec![0xFF; (n % 300) as usize]));
+		values.push(Value::from("z".repeat((n % 300) as usize)));
+		values.push(Value::Array(vec![Value::Unsigned(n); 2]));
+	}
+	for a in &values {
+		for b in &values {
+			assert_eq!(
+				a.encode_compact().cmp(&b.encode_compact()),
+				a.cmp(b),
+				"{} / {}",
+				a.to_diagnostic(),
+				b.to_diagnostic()
+			);
+		}
+	}
+}
+
+#[test]
+fn take_test() {
+	let big = vec![0xA5; 1024 * 1024];
+	let ptr = big.as_ptr();
+	let mut doc = Value::Map(Map::from(vec![KeyVal {
+		key: Value::from("outer"),
+		val: Value::Map(Map::from(vec![
+			KeyVal {
+				key: Value::from("blob"),
+				val: Value::ByteString(big),
+			},
+			KeyVal {
+				key: Value::from("n"),
+				val: Value::Unsigned(7),
+			},
+		])),
+	}]));
+
+	let inner = match &mut doc {
+		Value::Map(x) => &mut x[0].val,
+		_ => panic!("expected map"),
+	};
+	let blob = match inner {
+		Value::Map(x) => x[0].val.take(),
+		_ => panic!("expected map"),
+	};
+	match &blob {
+		Value::ByteString(x) => {
+			assert_eq!(ptr, x.as_ptr());
+			assert_eq!(1024 * 1024, x.len());
+		}
+		_ => panic!("expected byte string"),
+	}
+	assert_eq!(Value::Simple(cborg::Simple::Null), inner.get_map().unwrap()[0].val);
+
+	let old = inner.replace(Value::Unsigned(1));
+	assert_eq!(2, old.get_map().unwrap().len());
+	assert_eq!(Value::Unsigned(1), *inner);
+
+	let taken = doc.take_entry(&Value::from("outer"));
+	assert_eq!(Some(Value::Unsigned(1)), taken);
+	assert_eq!(0, doc.get_map().unwrap().len());
+	assert_eq!(None, doc.take_entry(&Value::from("outer")));
+	assert_eq!(None, Value::Unsigned(3).take_entry(&Value::from("outer")));
+}
+
+#[test]
+fn f32_exact_test() {
+	assert_eq!(Some(1.5), Value::Float(1.5).as_f32_exact());
+	assert_eq!(None, Value::Float(0.1).as_f32_exact());
+	assert_eq!(None, Value::Float(1e300).as_f32_exact());
+	assert!(Value::Float(f64::NAN).as_f32_exact().unwrap().is_nan());
+	assert_eq!(Some(f32::INFINITY), Value::Float(f64::INFINITY).as_f32_exact());
+	assert_eq!(Some(16_777_216.0), Value::Unsigned(16_777_216).as_f32_exact());
+	assert_eq!(None, Value::Unsigned(16_777_217).as_f32_exact());
+	assert_eq!(None, Value::Unsigned(u64::MAX).as_f32_exact());
+	assert_eq!(Some(-3.0), Value::Negative(-3).as_f32_exact());
+	assert_eq!(None, Value::from("1.5").as_f32_exact());
+
+	// The lossy conversion is still available through FromValue
+	let lossy: f32 = Value::Float(0.1).into_type().unwrap();
+	assert!((lossy - 0.1).abs() < 1e-6);
+	let lossy: f32 = Value::Float(1e300).into_type().unwrap();
+	assert!(lossy.is_infinite());
+}
+
+#[cfg(feature = "tracing")]
+mod trace_capture {
+	use std::fmt::Debug;
+	use std::sync::Arc;
+	use std::sync::Mutex;
+
+	use tracing::field::Field;
+	use tracing::field::Visit;
+	use tracing::span::Attributes;
+	use tracing::span::Id;
+	use tracing::span::Record;
+	use tracing::Event;
+	use tracing::Metadata;
+	use tracing::Subscriber;
+
+	pub type Fields = Vec<(String, String)>;
+
+	#[derive(Default)]
+	pub struct Captured {
+		pub spans: Vec<(String, Fields)>,
+		pub events: Vec<Fields>,
+	}
+
+	impl Captured {
+		pub fn field<'a>(fields: &'a Fields, name: &str) -> Option<&'a str> {
+			fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+		}
+	}
+
+	struct Visitor<'a>(&'a mut Fields);
+	impl Visit for Visitor<'_> {
+		fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+			self.0.push((field.name().to_string(), format!("{:?}", value)));
+		}
+	}
+
+	pub struct Capture(pub Arc<Mutex<Captured>>);
+	impl Subscriber for Capture {
+		fn enabled(&self, _: &Metadata) -> bool { true }
+		fn new_span(&self, attrs: &Attributes) -> Id {
+			let mut c = self.0.lock().unwrap();
+			let mut fields = Fields::new();
+			attrs.record(&mut Visitor(&mut fields));
+			c.spans.push((attrs.metadata().name().to_string(), fields));
+			Id::from_u64(c.spans.len() as u64)
+		}
+		fn record(&self, id: &Id, values: &Record) {
+			let mut c = self.0.lock().unwrap();
+			let span = &mut c.spans[id.into_u64() as usize - 1];
+			values.record(&mut Visitor(&mut span.1));
+		}
+		fn record_follows_from(&self, _: &Id, _: &Id) {}
+		fn event(&self, event: &Event) {
+			let mut fields = Fields::new();
+			event.record(&mut Visitor(&mut fields));
+			self.0.lock().unwrap().events.push(fields);
+		}
+		fn enter(&self, _: &Id) {}
+		fn exit(&self, _: &Id) {}
+	}
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_test() {
+	use std::sync::Arc;
+	use std::sync::Mutex;
+	use trace_capture::*;
+
+	let captured = Arc::new(Mutex::new(Captured::default()));
+	tracing::subscriber::with_default(Capture(captured.clone()), || {
+		cborg::decode_slice(&TEST_DATA_DEFINITE).unwrap();
+	});
+	{
+		let c = captured.lock().unwrap();
+		assert_eq!(1, c.spans.len());
+		let (name, fields) = &c.spans[0];
+		assert_eq!("decode", name);
+		assert_eq!(Some("438"), Captured::field(fields, "input_len"));
+		assert_eq!(Some("21"), Captured::field(fields, "items"));
+		assert_eq!(Some("2"), Captured::field(fields, "max_depth"));
+		assert!(Captured::field(fields, "elapsed_us").is_some());
+		assert!(c.events.is_empty());
+	}
+
+	let captured = Arc::new(Mutex::new(Captured::default()));
+	tracing::subscriber::with_default(Capture(captured.clone()), || {
+		assert!(cborg::decode_slice(&TEST_DATA_DEFINITE[..100]).is_err());
+	});
+	{
+		let c = captured.lock().unwrap();
+		let (_, fields) = &c.spans[0];
+		// Stops at the header of the string that runs past the end
+		assert_eq!(Some("95"), Captured::field(fields, "input_len"));
+		assert_eq!(None, Captured::field(fields, "items"));
+		assert_eq!(1, c.events.len());
+		assert_eq!(Some("95"), Captured::field(&c.events[0], "offset"));
+		assert_eq!(
+			Some(
+				r#"Insufficient bytes: length 296 needs more than the 5 bytes remaining (at value for key 555, value for key "long string")"#
+			),
+			Captured::field(&c.events[0], "error")
+		);
+	}
+
+	let captured = Arc::new(Mutex::new(Captured::default()));
+	tracing::subscriber::with_default(Capture(captured.clone()), || {
+		cborg::encode(vec![1u32, 2, 3]);
+	});
+	let c = captured.lock().unwrap();
+	let (name, fields) = &c.spans[0];
+	assert_eq!("encode", name);
+	assert_eq!(Some("4"), Captured::field(fields, "output_len"));
+	assert_eq!(Some("4"), Captured::field(fields, "items"));
+	assert_eq!(Some("1"), Captured::field(fields, "max_depth"));
+	drop(c);
+
+	// Hitting a nesting limit gets its own event with the limit and where it was hit
+	let opts = cborg::DecodeOptions::new()
+		.max_array_len(2)
+		.max_map_len(1)
+		.max_string_len(4);
+	let limits: &[(Vec<u8>, &str, &str, &str)] = &[
+		(vec![0x81; 200], "depth limit exceeded", "128", "129"),
+		(vec![0xC1; 40], "tag nesting limit exceeded", "16", "17"),
+		// [0, [1, 2, 3]]
+		(
+			vec![0x82, 0x00, 0x83, 0x01, 0x02, 0x03],
+			"array length limit exceeded",
+			"2",
+			"3",
+		),
+		// {1: 2, 3: 4}
+		(
+			vec![0xA2, 0x01, 0x02, 0x03, 0x04],
+			"map length limit exceeded",
+			"1",
+			"1",
+		),
+		// "hello"
+		(
+			vec![0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F],
+			"string length limit exceeded",
+			"4",
+			"1",
+		),
+	];
+	for (bytes, message, limit, offset) in limits {
+		let captured = Arc::new(Mutex::new(Captured::default()));
+		tracing::subscriber::with_default(Capture(captured.clone()), || {
+			assert!(cborg::decode_slice_with(bytes, &opts).is_err());
+		});
+		let c = captured.lock().unwrap();
+		assert_eq!(1, c.events.len());
+		let event = &c.events[0];
+		assert_eq!(
+			Some(*message),
+			Captured::field(event, "message").map(|m| m.trim_matches('"'))
+		);
+		assert_eq!(Some(*limit), Captured::field(event, "limit"));
+		assert_eq!(Some(*offset), Captured::field(event, "offset"));
+	}
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn smallvec_test() {
+	use smallvec::SmallVec;
+
+	// [1, 2, 3]
+	let bytes = [0x83, 0x01, 0x02, 0x03];
+	let small: SmallVec<[u32; 4]> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert!(!small.spilled());
+	assert_eq!(&[1, 2, 3], small.as_slice());
+	assert_eq!(bytes.to_vec(), cborg::encode_ref(&small));
+	assert_eq!(bytes.to_vec(), cborg::encode(small));
+
+	let spilled: SmallVec<[u32; 2]> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert!(spilled.spilled());
+	assert_eq!(&[1, 2, 3], spilled.as_slice());
+	assert_eq!(bytes.to_vec(), cborg::encode(spilled));
+
+	let v = cborg::decode_slice(&TEST_DATA_DEFINITE).unwrap();
+	let dict: BTreeMap<i64, SmallVec<[Value; 8]>> = ValueInto::to_type(&v).unwrap();
+	let arr = &dict[&777];
+	assert_eq!(4, arr.len());
+	assert!(!arr.spilled());
+	assert_eq!(Value::Utf8String(String::from("fourty-four")), arr[3]);
+
+	// 100([1, 2]) converts like its content, as it does for Vec
+	let tagged = [0xD8, 0x64, 0x82, 0x01, 0x02];
+	let small: SmallVec<[u32; 4]> = cborg::decode_to(&tagged).unwrap().unwrap();
+	assert_eq!(&[1, 2], small.as_slice());
+	let v = cborg::decode_slice(&tagged).unwrap();
+	let small: SmallVec<[u32; 4]> = cborg::FromValue::from_ref(&v).unwrap();
+	assert_eq!(&[1, 2], small.as_slice());
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn heapless_test() {
+	// [1, 2, 3]
+	let bytes = [0x83, 0x01, 0x02, 0x03];
+	let arr: heapless::Vec<u32, 4> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!(&[1, 2, 3], arr.as_slice());
+	assert_eq!(bytes.to_vec(), cborg::encode_ref(&arr));
+	let arr: Option<heapless::Vec<u32, 2>> = cborg::decode_to(&bytes).unwrap();
+	assert!(arr.is_none());
+
+	// "hello"
+	let bytes = [0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F];
+	let s: heapless::String<5> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!("hello", s.as_str());
+	assert_eq!(bytes.to_vec(), cborg::encode_ref(&s));
+	let s: Option<heapless::String<4>> = cborg::decode_to(&bytes).unwrap();
+	assert!(s.is_none());
+
+	// h'0102030405'
+	let bytes = [0x45, 0x01, 0x02, 0x03, 0x04, 0x05];
+	let b: heapless::Vec<u8, 8> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!(&[1, 2, 3, 4, 5], b.as_slice());
+	assert_eq!(bytes.to_vec(), cborg::encode_ref(&b));
+	let b: Option<heapless::Vec<u8, 4>> = cborg::decode_to(&bytes).unwrap();
+	assert!(b.is_none());
+
+	let map: heapless::FnvIndexMap<String, Value, 8> = cborg::decode_to(&TEST_DATA_DEFINITE[4..]).unwrap().unwrap();
+	assert_eq!(6, map.len());
+	assert_eq!(Value::Unsigned(8), map["unsigned"]);
+	let map: Option<heapless::FnvIndexMap<String, Value, 4>> = cborg::decode_to(&TEST_DATA_DEFINITE[4..]).unwrap();
+	assert!(map.is_none());
+
+	// 100([1, 2]) converts like its content, as it does for Vec
+	let tagged = [0xD8, 0x64, 0x82, 0x01, 0x02];
+	let v = cborg::decode_slice(&tagged).unwrap();
+	let arr: heapless::Vec<u32, 4> = cborg::FromValue::from_ref(&v).unwrap();
+	assert_eq!(&[1, 2], arr.as_slice());
+	let arr: heapless::Vec<u32, 4> = cborg::decode_to(&tagged).unwrap().unwrap();
+	assert_eq!(&[1, 2], arr.as_slice());
+	// Bytes may also come as an array or a tagged byte string
+	let b: heapless::Vec<u8, 4> = cborg::FromValue::from_ref(&v).unwrap();
+	assert_eq!(&[1, 2], b.as_slice());
+	let b: heapless::Vec<u8, 4> = cborg::decode_to(&[0xD8, 0x64, 0x42, 0x01, 0x02]).unwrap().unwrap();
+	assert_eq!(&[1, 2], b.as_slice());
+	// 100("hi") and 100({1: 2})
+	let s: heapless::String<4> = cborg::decode_to(&[0xD8, 0x64, 0x62, 0x68, 0x69]).unwrap().unwrap();
+	assert_eq!("hi", s.as_str());
+	let map: heapless::FnvIndexMap<u32, u32, 4> = cborg::decode_to(&[0xD8, 0x64, 0xA1, 0x01, 0x02]).unwrap().unwrap();
+	assert_eq!(2, map[&1]);
+}
+
+#[test]
+fn convert_with_test() {
+	use std::time::Duration;
+
+	let secs = |v: &Value| v.get_uint().map(Duration::from_secs);
+
+	// [1, 2, "x", 4]
+	let v = cborg::decode_slice(&[0x84, 0x01, 0x02, 0x61, 0x78, 0x04]).unwrap();
+	let durations = v.convert_array_with(secs).unwrap();
+	assert_eq!(
+		vec![Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(4)],
+		durations
+	);
+	let err = v.try_convert_array_with(secs).unwrap_err();
+	assert!(format!("{:?}", err).contains("index 2"));
+
+	// [1, 2, 4]
+	let v = cborg::decode_slice(&[0x83, 0x01, 0x02, 0x04]).unwrap();
+	assert_eq!(3, v.try_convert_array_with(secs).unwrap().len());
+	assert!(Value::Unsigned(1).convert_array_with(secs).is_none());
+	assert!(Value::Unsigned(1).try_convert_array_with(secs).is_err());
+
+	let v = cborg::decode_slice(&TEST_DATA_DEFINITE[4..]).unwrap();
+	let ints = |k: &Value, v: &Value| Some((k.get_string()?, v.get_uint().or_else(|| v.get_neg().map(|x| x as u64))?));
+	let entries = v.convert_map_with(ints).unwrap();
+	assert_eq!(
+		vec![("unsigned".to_string(), 8), ("negative".to_string(), (-4i64) as u64)],
+		entries
+	);
+	let err = v.try_convert_map_with(ints).unwrap_err();
+	assert!(format!("{:?}", err).contains("key \\\"float\\\""));
+}
+
+#[test]
+fn profile_test() {
+	let definite = cborg::profile(&TEST_DATA_DEFINITE).unwrap();
+	assert_eq!([4, 2, 1, 9, 1, 2, 0, 2], definite.items);
+	assert_eq!([0, 0, 5, 389, 0, 0, 0, 0], definite.payload_bytes);
+	assert_eq!(44, definite.header_bytes);
+	assert_eq!(2, definite.max_depth);
+	assert_eq!(4, definite.max_array_len);
+	assert_eq!(6, definite.max_map_len);
+	assert_eq!(0, definite.indefinite_items);
+
+	let indefinite = cborg::profile(&TEST_DATA_INDEFINITE).unwrap();
+	assert_eq!([4, 2, 1, 7, 1, 2, 0, 2], indefinite.items);
+	assert_eq!([0, 0, 5, 82, 0, 0, 0, 0], indefinite.payload_bytes);
+	assert_eq!(46, indefinite.header_bytes);
+	assert_eq!(2, indefinite.max_depth);
+	assert_eq!(4, indefinite.max_array_len);
+	assert_eq!(5, indefinite.max_map_len);
+	assert_eq!(4, indefinite.indefinite_items);
+
+	let mut merged = definite.clone();
+	merged.merge(&indefinite);
+	assert_eq!([8, 4, 2, 16, 2, 4, 0, 4], merged.items);
+	assert_eq!(90, merged.header_bytes);
+	assert_eq!(6, merged.max_map_len);
+	assert_eq!(4, merged.indefinite_items);
+	assert_eq!(40, merged.total_items());
+
+	let mut both = TEST_DATA_DEFINITE.to_vec();
+	both.extend_from_slice(&TEST_DATA_INDEFINITE);
+	assert_eq!(merged, cborg::profile(&both).unwrap());
+
+	assert!(cborg::profile(&TEST_DATA_DEFINITE[..437]).is_err());
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::profile(&[0x81, 0xFF]).unwrap_err().kind()
+	);
+	assert_eq!(cborg::Profile::default(), cborg::profile(&[]).unwrap());
+}
+
+#[test]
+fn assert_cbor_eq_test() {
+	use std::panic;
+
+	fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+		let err = panic::catch_unwind(f).expect_err("expected a panic");
+		match err.downcast::<String>() {
+			Ok(s) => *s,
+			Err(_) => String::new(),
+		}
+	}
+
+	let decoded = cborg::decode(&TEST_DATA_DEFINITE).unwrap();
+	cborg::assert_cbor_eq!(&TEST_DATA_DEFINITE, decoded);
+	// Same value, different length encoding
+	cborg::assert_cbor_eq!([0x98, 0x02, 0x01, 0x02], [0x82, 0x01, 0x02]);
+
+	let mut changed = TEST_DATA_DEFINITE;
+	changed[436] = b'X'; // "fourty-four" -> "fourty-foXr"
+	let msg = panic_message(move || cborg::assert_cbor_eq!(&TEST_DATA_DEFINITE, &changed));
+	assert!(msg.contains("$.777[3]: \"fourty-four\" != \"fourty-foXr\""), "{}", msg);
+	assert!(
+		msg.contains("left: {555: {\"float\": 2.5, \"bytestring\": h'0102030405'"),
+		"{}",
+		msg
+	);
+
+	let a = Value::Map(Map::from(vec![
+		KeyVal {
+			key: Value::Utf8String("x".into()),
+			val: Value::Unsigned(1),
+		},
+		KeyVal {
+			key: Value::Utf8String("long key".into()),
+			val: Value::Unsigned(2),
+		},
+	]));
+	let b = Value::Map(Map::from(vec![
+		KeyVal {
+			key: Value::Utf8String("long key".into()),
+			val: Value::Unsigned(2),
+		},
+		KeyVal {
+			key: Value::Utf8String("x".into()),
+			val: Value::Unsigned(1),
+		},
+	]));
+	cborg::assert_cbor_eq_unordered!(a, b);
+	let (a2, b2) = (a.clone(), b.clone());
+	let msg = panic_message(move || cborg::assert_cbor_eq!(a2, b2));
+	assert!(msg.contains("$: map keys are in a different order"), "{}", msg);
+
+	let c = Value::Map(Map::from(vec![
+		KeyVal {
+			key: Value::Utf8String("x".into()),
+			val: Value::Array(vec![Value::Float(1.0)]),
+		},
+		KeyVal {
+			key: Value::Utf8String("long key".into()),
+			val: Value::Unsigned(2),
+		},
+	]));
+	let diffs = cborg::diff(&a, &c);
+	assert_eq!(1, diffs.len());
+	assert_eq!("$.x: 1 != [1.0]", diffs[0].to_string());
+	let diffs = cborg::diff(
+		&Value::Array(vec![]),
+		&Value::Array(vec![Value::Simple(cborg::Simple::Null)]),
+	);
+	assert_eq!("$[0]: only in right: null", diffs[0].to_string());
+	let diffs = cborg::diff_unordered(&b, &Value::Map(Map::from(vec![])));
+	assert_eq!(2, diffs.len());
+	assert_eq!("$.\"long key\": only in left: 2", diffs[0].to_string());
+}
+
+#[test]
+fn encode_sorted_by_test() {
+	use core::cmp::Ordering;
+
+	// Numeric keys ascending, then text keys case-insensitively, then everything else
+	fn partner_order(a: &Value, b: &Value) -> Ordering {
+		fn rank(v: &Value) -> (u8, i128, String) {
+			match v {
+				Value::Unsigned(x) => (0, i128::from(*x), String::new()),
+				Value::Negative(x) => (0, i128::from(*x), String::new()),
+				Value::Utf8String(s) => (1, 0, s.to_lowercase()),
+				_ => (2, 0, String::new()),
+			}
+		}
+		rank(a).cmp(&rank(b))
+	}
+	fn text(s: &str) -> Value { Value::Utf8String(s.to_string()) }
+	fn kv(key: Value, val: u64) -> KeyVal {
+		KeyVal {
+			key,
+			val: Value::Unsigned(val),
+		}
+	}
+
+	let inner = Value::Map(Map::from(vec![
+		kv(text("b"), 1),
+		kv(Value::Unsigned(7), 2),
+		kv(text("A"), 3),
+	]));
+	let source = Value::Map(Map::from(vec![
+		kv(text("Zeta"), 5),
+		kv(text("alpha"), 1),
+		KeyVal {
+			key: Value::Unsigned(10),
+			val: Value::Array(vec![inner]),
+		},
+		kv(Value::Negative(-3), 3),
+		kv(text("ALPHA"), 4),
+	]));
+	let copy = source.clone();
+
+	let bytes = source.encode_sorted_by(partner_order);
+	assert_eq!(source, copy);
+	let sorted = Value::Map(Map::from(vec![
+		kv(Value::Negative(-3), 3),
+		KeyVal {
+			key: Value::Unsigned(10),
+			val: Value::Array(vec![Value::Map(Map::from(vec![
+				kv(Value::Unsigned(7), 2),
+				kv(text("A"), 3),
+				kv(text("b"), 1),
+			]))]),
+		},
+		// Ties keep their source order
+		kv(text("alpha"), 1),
+		kv(text("ALPHA"), 4),
+		kv(text("Zeta"), 5),
+	]));
+	cborg::assert_cbor_eq!(bytes, sorted);
+	assert_eq!(bytes, source.try_encode_sorted_by(partner_order).unwrap());
+
+	// Duplicate keys are kept in order by encode_sorted_by and rejected by try_encode_sorted_by
+	let dup = Value::Map(Map::from(vec![
+		kv(text("k"), 1),
+		kv(Value::Unsigned(1), 5),
+		kv(text("k"), 2),
+	]));
+	let bytes = dup.encode_sorted_by(partner_order);
+	assert_eq!(vec![0xA3, 0x01, 0x05, 0x61, b'k', 0x01, 0x61, b'k', 0x02], bytes);
+	let err = dup.try_encode_sorted_by(partner_order).unwrap_err();
+	assert_eq!(&ErrorKind::DuplicateKey, err.kind());
+	assert!(format!("{:?}", err).contains(r#"duplicate map key \"k\""#));
+	let nested = Value::Array(vec![dup]);
+	let err = nested.try_encode_sorted_by(partner_order).unwrap_err();
+	assert_eq!(&ErrorKind::DuplicateKey, err.kind());
+}
+
+#[test]
+fn encode_from_iter_test() {
+	use std::cell::Cell;
+	use std::cell::RefCell;
+	use std::io;
+	use std::rc::Rc;
+
+	// Records how much each write call carries so the test can tell output was not buffered
+	struct Sink {
+		out: Rc<RefCell<Vec<u8>>>,
+		largest_write: usize,
+	}
+	impl io::Write for Sink {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.largest_write = self.largest_write.max(buf.len());
+			self.out.borrow_mut().extend_from_slice(buf);
+			Ok(buf.len())
+		}
+		fn flush(&mut self) -> io::Result<()> { Ok(()) }
+	}
+
+	const N: u64 = 100_000;
+	for &hint in &[Some(N), None] {
+		let out = Rc::new(RefCell::new(Vec::new()));
+		let mut sink = Sink {
+			out: out.clone(),
+			largest_write: 0,
+		};
+		let streamed = Cell::new(true);
+		let records = (1..=N).map(|i| {
+			// By the time record i is produced, the previous records are already in the output
+			if i == N / 2 && out.borrow().len() < (N / 2 - 1) as usize {
+				streamed.set(false);
+			}
+			vec![i, i * 2]
+		});
+		let written = cborg::encode_array_from_iter(&mut sink, records, hint).unwrap();
+		assert!(streamed.get());
+		assert!(sink.largest_write <= 16);
+
+		let out = out.borrow();
+		assert_eq!(written, out.len() as u64);
+		let expected: usize = (1..=N).map(|i| Value::from(vec![i, i * 2]).encode().len()).sum();
+		let framing = if hint.is_some() { 5 } else { 2 };
+		assert_eq!(expected + framing, out.len());
+		let decoded = cborg::decode(out.iter()).unwrap().get_array().unwrap();
+		assert_eq!(N as usize, decoded.len());
+		assert_eq!(Value::from(vec![N, N * 2]), decoded[N as usize - 1]);
+	}
+
+	let mut out = Vec::new();
+	let pairs = vec![("a", 1u32), ("b", 2)];
+	assert_eq!(8, cborg::encode_map_from_iter(&mut out, pairs.clone(), None).unwrap());
+	assert_eq!(vec![0xBF, 0x61, b'a', 0x01, 0x61, b'b', 0x02, 0xFF], out);
+	let mut out = Vec::new();
+	cborg::encode_map_from_iter(&mut out, pairs.clone(), Some(2)).unwrap();
+	let map: HashMap<String, u32> = cborg::decode_to(&out).unwrap().unwrap();
+	assert_eq!(2, map["b"]);
+
+	let mut out = Vec::new();
+	let err = cborg::encode_array_from_iter(&mut out, 1..4u64, Some(4)).unwrap_err();
+	assert!(format!("{:?}", err).contains("produced 3 items but the length hint was 4"));
+	let err = cborg::encode_array_from_iter(&mut Vec::new(), 1..4u64, Some(2)).unwrap_err();
+	assert!(format!("{:?}", err).contains("more than the 2 items"));
+	assert!(cborg::encode_map_from_iter(&mut Vec::new(), pairs, Some(1)).is_err());
+}
+
+#[test]
+fn integer_test() {
+	assert_eq!(Value::Unsigned(0), Value::integer(0).unwrap());
+	assert_eq!(Value::Negative(-1), Value::integer(-1).unwrap());
+	assert_eq!(vec![0x20], Value::integer(-1).unwrap().encode());
+	assert_eq!(Value::Negative(i64::MIN), Value::integer(i128::from(i64::MIN)).unwrap());
+	assert_eq!(Value::Unsigned(u64::MAX), Value::integer(i128::from(u64::MAX)).unwrap());
+	assert_eq!(
+		Value::Unsigned(i64::MAX as u64 + 1),
+		Value::integer(i128::from(i64::MAX) + 1).unwrap()
+	);
+
+	for &i in &[0, -1, i128::from(i64::MIN), i128::from(u64::MAX), 1000, -1000] {
+		assert_eq!(Some(i), Value::integer(i).unwrap().as_integer());
+	}
+	assert_eq!(None, Value::Float(1.0).as_integer());
+
+	// Within CBOR's range but below what Value::Negative can hold
+	let err = Value::integer(-(1i128 << 64)).unwrap_err();
+	assert!(format!("{:?}", err).contains("cannot be held by Value::Negative"));
+	assert!(Value::integer(i128::from(i64::MIN) - 1).is_err());
+
+	let err = Value::integer(-(1i128 << 64) - 1).unwrap_err();
+	assert!(format!("{:?}", err).contains("outside the range of a CBOR integer"));
+	assert!(Value::integer(1i128 << 64).is_err());
+	assert!(Value::integer(i128::MIN).is_err());
+}
+
+#[test]
+fn constructors_test() {
+	// TEST_DATA_DEFINITE, as built field by field in encode_test
+	let data = Value::map(vec![
+		(
+			555u32,
+			Value::map(vec![
+				("float", Value::from(2.5)),
+				("bytestring", Value::bytes(vec![1, 2, 3, 4, 5])),
+				("utf8string", Value::text("你好，世界 - hello, world")),
+				("long string", Value::text(LONG_STRING)),
+				("unsigned", Value::from(8u32)),
+				("negative", Value::from(-4i32)),
+			]),
+		),
+		(
+			777,
+			Value::array(vec![
+				Value::from(11u32),
+				Value::from(-22i32),
+				Value::from(33.3),
+				Value::text("fourty-four"),
+			]),
+		),
+	]);
+	assert_eq!(TEST_DATA_DEFINITE.to_vec(), data.encode());
+
+	assert_eq!(Value::Utf8String("x".to_string()), Value::text(String::from("x")));
+	assert_eq!(Value::ByteString(vec![1]), Value::bytes(&[1u8][..]));
+	assert_eq!(
+		Value::Array(vec![Value::Unsigned(1), Value::Unsigned(2)]),
+		Value::array(vec![1u32, 2])
+	);
+	assert_eq!(Value::Array(vec![]), Value::array(Vec::<Value>::new()));
+	let map = Value::map(vec![("b", 1u32), ("a", 2)]);
+	assert_eq!(
+		Value::Map(Map::from(vec![
+			KeyVal {
+				key: Value::text("b"),
+				val: Value::Unsigned(1)
+			},
+			KeyVal {
+				key: Value::text("a"),
+				val: Value::Unsigned(2)
+			},
+		])),
+		map
+	);
+}
+
+#[test]
+fn decode_lossy_test() {
+	use cborg::Simple;
+
+	fn entry<'a>(v: &'a mut Value, key: &Value) -> &'a mut Value {
+		match v {
+			Value::Map(m) => &mut m.iter_mut().find(|kv| kv.key == *key).unwrap().val,
+			_ => panic!("not a map"),
+		}
+	}
+
+	let original = cborg::decode(&TEST_DATA_DEFINITE).unwrap();
+	let (value, issues) = cborg::decode_lossy(&TEST_DATA_DEFINITE);
+	assert_eq!(original, value);
+	assert!(issues.is_empty());
+
+	// Invalid UTF-8 in one map value: the value is replaced and its siblings survive
+	let mut corrupt = TEST_DATA_DEFINITE;
+	corrupt[50] = 0xFF;
+	let (value, issues) = cborg::decode_lossy(&corrupt);
+	let mut expected = original.clone();
+	*entry(entry(&mut expected, &Value::Unsigned(555)), &Value::text("utf8string")) = Value::Simple(Simple::Undefined);
+	cborg::assert_cbor_eq!(expected, value);
+	assert_eq!(1, issues.len());
+	assert_eq!("$.555.utf8string", issues[0].path.to_string());
+	assert_eq!(48, issues[0].offset);
+	assert!(issues[0].kind == ErrorKind::InvalidUtf8);
+
+	// Truncated inside the last array element: earlier elements and entries survive
+	let (value, issues) = cborg::decode_lossy(&TEST_DATA_DEFINITE[..430]);
+	let mut expected = original.clone();
+	match entry(&mut expected, &Value::Unsigned(777)) {
+		Value::Array(a) => a[3] = Value::Simple(Simple::Undefined),
+		_ => panic!("not an array"),
+	}
+	cborg::assert_cbor_eq!(expected, value);
+	assert_eq!(1, issues.len());
+	assert_eq!("$.777[3]", issues[0].path.to_string());
+	assert_eq!(426, issues[0].offset);
+	assert!(issues[0].kind == ErrorKind::InsufficientBytes);
+
+	// Same data in indefinite-length containers
+	let (value, issues) = cborg::decode_lossy(&TEST_DATA_INDEFINITE[..120]);
+	assert_eq!(1, issues.len());
+	assert_eq!("$.777[3]", issues[0].path.to_string());
+	let arr = value.get_hash_map().unwrap()[&Value::Unsigned(777)]
+		.get_array()
+		.unwrap();
+	assert_eq!(
+		vec![Value::Unsigned(11), Value::Negative(-22), Value::Float(33.3)],
+		arr[..3].to_vec()
+	);
+
+	// Sequences: the damaged record is salvaged, the one before it is untouched
+	let mut seq = vec![0x82, 0x01, 0x02];
+	seq.extend_from_slice(&TEST_DATA_DEFINITE[..430]);
+	let (values, issues) = cborg::decode_lossy_seq(&seq);
+	assert_eq!(2, values.len());
+	assert_eq!(Value::array(vec![1u32, 2]), values[0]);
+	assert_eq!(429, issues[0].offset);
+
+	let (value, issues) = cborg::decode_lossy(&[0x82, 0xFC, 0x01]);
+	assert_eq!(Value::Array(vec![Value::Simple(Simple::Undefined)]), value);
+	assert_eq!("$[0]", issues[0].path.to_string());
+}
+
+#[test]
+fn canonicalize_test() {
+	for data in &[&TEST_DATA_DEFINITE[..], &TEST_DATA_INDEFINITE[..]] {
+		let canonical = cborg::canonicalize(data).unwrap();
+		assert_eq!(cborg::decode(data.iter()).unwrap().encode_canonical(), canonical);
+		let mut written = Vec::new();
+		cborg::canonicalize_to(data, &mut written).unwrap();
+		assert_eq!(canonical, written);
+	}
+
+	// Keys sorted by encoded bytes: shorter text first, 2.5 as a half float, 33.3 kept as a double
+	let canonical = cborg::canonicalize(&TEST_DATA_DEFINITE).unwrap();
+	assert_eq!(
+		vec![0xA2, 0x19, 0x02, 0x2B, 0xA6, 0x65, b'f', b'l', b'o', b'a', b't', 0xF9, 0x41, 0x00],
+		canonical[..14].to_vec()
+	);
+	assert_eq!(TEST_DATA_DEFINITE.len() - 6, canonical.len());
+	let indefinite = cborg::canonicalize(&TEST_DATA_INDEFINITE).unwrap();
+	// Definite lengths throughout: the four breaks are gone and the chunked string's four header
+	// bytes become one two-byte header
+	assert_eq!(&[0xA2, 0x19, 0x02, 0x2B, 0xA5], &indefinite[..5]);
+	assert_eq!(TEST_DATA_INDEFINITE.len() - 6 - 4 - 2, indefinite.len());
+
+	// Floats take the shortest exact width
+	let floats = Value::array(vec![
+		1.5,
+		100000.0,
+		1.0e300,
+		5.960464477539063e-8,
+		f64::INFINITY,
+		f64::NAN,
+		0.1,
+	]);
+	assert_eq!(
+		vec![
+			0x87, 0xF9, 0x3E, 0x00, 0xFA, 0x47, 0xC3, 0x50, 0x00, 0xFB, 0x7E, 0x37, 0xE4, 0x3C, 0x88, 0x00, 0x75, 0x9C,
+			0xF9, 0x00, 0x01, 0xF9, 0x7C, 0x00, 0xF9, 0x7E, 0x00, 0xFB, 0x3F, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A,
+		],
+		floats.encode_canonical()
+	);
+	assert_eq!(
+		floats.encode_canonical(),
+		cborg::canonicalize(&floats.encode()).unwrap()
+	);
+
+	// A larger document mixing definite and indefinite containers
+	let mut doc = vec![0x9F];
+	for i in 1..2000u32 {
+		let record = Value::map(vec![
+			("name", Value::text(format!("record {}", i))),
+			("id", Value::from(i)),
+			("scores", Value::array(vec![f64::from(i) / 4.0, f64::from(i) / 3.0])),
+			("x", Value::bytes(i.to_be_bytes().to_vec())),
+		]);
+		doc.extend_from_slice(&record.encode());
+	}
+	doc.push(0xFF);
+	let canonical = cborg::canonicalize(&doc).unwrap();
+	assert_eq!(cborg::decode(&doc).unwrap().encode_canonical(), canonical);
+	assert_eq!(&[0x99, 0x07, 0xCF, 0xA4, 0x61, b'x'], &canonical[..6]);
+
+	assert!(cborg::canonicalize(&[0x82, 0x01]).is_err());
+	assert!(cborg::canonicalize(&[0x01, 0x01]).is_err());
+	assert!(cborg::canonicalize(&[0x62, 0xFF, 0xFE]).is_err());
+
+	// The same as decode followed by encode_canonical: a leading self-described tag is dropped and
+	// text chunks must be valid UTF-8 on their own
+	let described = [0xD9, 0xD9, 0xF7, 0xD9, 0xD9, 0xF7, 0x81, 0xD9, 0xD9, 0xF7, 0x01];
+	assert_eq!(
+		vec![0x81, 0xD9, 0xD9, 0xF7, 0x01],
+		cborg::canonicalize(&described).unwrap()
+	);
+	assert_eq!(
+		cborg::decode(&described).unwrap().encode_canonical(),
+		cborg::canonicalize(&described).unwrap()
+	);
+	// (_ "\xC3", "\xA9"), "é" split between chunks
+	let split = [0x7F, 0x61, 0xC3, 0x61, 0xA9, 0xFF];
+	assert_eq!(&ErrorKind::InvalidUtf8, cborg::decode(&split).unwrap_err().kind());
+	assert_eq!(&ErrorKind::InvalidUtf8, cborg::canonicalize(&split).unwrap_err().kind());
+
+	// Deep nesting fails with the decoder's limits instead of exhausting the stack
+	for b in &[0x81, 0x9F, 0xA1, 0xBF] {
+		let deep = vec![*b; 200_000];
+		assert_eq!(
+			&ErrorKind::DepthLimitExceeded,
+			cborg::canonicalize(&deep).unwrap_err().kind()
+		);
+	}
+	let chain = vec![0xC1; 200_000];
+	assert_eq!(
+		&ErrorKind::TagNestingTooDeep,
+		cborg::canonicalize(&chain).unwrap_err().kind()
+	);
+	let mut nested = vec![0x81; 128];
+	nested.push(0x01);
+	assert!(cborg::canonicalize(&nested).is_ok());
+	nested.insert(0, 0x81);
+	assert_eq!(
+		&ErrorKind::DepthLimitExceeded,
+		cborg::canonicalize(&nested).unwrap_err().kind()
+	);
+}
+
+#[test]
+fn cwt_test() {
+	use cborg::cwt::Claims;
+	use cborg::cwt::NumericDate;
+	use std::time::Duration;
+	use std::time::UNIX_EPOCH;
+
+	// RFC 8392 appendix A.1
+	let payload: [u8; 80] = [
+		0xA7, 0x01, 0x75, 0x63, 0x6F, 0x61, 0x70, 0x3A, 0x2F, 0x2F, 0x61, 0x73, 0x2E, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C,
+		0x65, 0x2E, 0x63, 0x6F, 0x6D, 0x02, 0x65, 0x65, 0x72, 0x69, 0x6B, 0x77, 0x03, 0x78, 0x18, 0x63, 0x6F, 0x61, 0x70,
+		0x3A, 0x2F, 0x2F, 0x6C, 0x69, 0x67, 0x68, 0x74, 0x2E, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x2E, 0x63, 0x6F,
+		0x6D, 0x04, 0x1A, 0x56, 0x12, 0xAE, 0xB0, 0x05, 0x1A, 0x56, 0x10, 0xD9, 0xF0, 0x06, 0x1A, 0x56, 0x10, 0xD9, 0xF0,
+		0x07, 0x42, 0x0B, 0x71,
+	];
+	let claims = Claims::decode(&payload).unwrap();
+	assert_eq!(Some("coap://as.example.com"), claims.iss.as_deref());
+	assert_eq!(Some("erikw"), claims.sub.as_deref());
+	assert_eq!(Some("coap://light.example.com"), claims.aud.as_deref());
+	assert_eq!(Some(NumericDate::Seconds(1444064944)), claims.exp);
+	assert_eq!(Some(NumericDate::Seconds(1443944944)), claims.nbf);
+	assert_eq!(Some(NumericDate::Seconds(1443944944)), claims.iat);
+	assert_eq!(Some(vec![0x0B, 0x71]), claims.cti);
+	assert!(claims.private.is_empty());
+	assert_eq!(payload.to_vec(), claims.to_value().encode());
+	assert_eq!(
+		UNIX_EPOCH + Duration::from_secs(1444064944),
+		claims.exp.unwrap().to_system_time()
+	);
+
+	// Private claims keep their keys, values and order
+	let mut claims = claims;
+	claims.exp = Some(NumericDate::Fractional(1444064944.5));
+	claims.private.push(KeyVal {
+		key: Value::Negative(-70000),
+		val: Value::text("scope"),
+	});
+	claims.private.push(KeyVal {
+		key: Value::text("role"),
+		val: Value::array(vec!["admin", "ops"]),
+	});
+	claims.private.push(KeyVal {
+		key: Value::Unsigned(8),
+		val: Value::bytes(vec![1, 2]),
+	});
+	let bytes = cborg::encode(claims.clone());
+	let decoded: Claims = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!(claims, decoded);
+	assert_eq!(
+		Some(NumericDate::Fractional(1444064944.5)),
+		Some(NumericDate::from_system_time(decoded.exp.unwrap().to_system_time()))
+	);
+
+	let err = Claims::decode(&[0xA1, 0x04, 0x61, b'x']).unwrap_err();
+	assert!(format!("{:?}", err).contains("claim exp (4) must be a NumericDate"));
+	let err = Claims::decode(&[0xA1, 0x07, 0x61, b'x']).unwrap_err();
+	assert!(format!("{:?}", err).contains("claim cti (7) must be a byte string"));
+	let err = Claims::decode(&[0xA2, 0x02, 0x61, b'x', 0x02, 0x61, b'y']).unwrap_err();
+	assert!(format!("{:?}", err).contains("claim 2 appears more than once"));
+	assert!(Claims::decode(&[0x81, 0x01]).is_err());
+	assert_eq!(None, cborg::decode_to::<Claims, _>(&[0xA1, 0x01, 0x01]).unwrap());
+}
+
+#[test]
+fn key_order_test() {
+	use cborg::KeyOrder;
+
+	let ten = vec![0x0A];
+	let thousand = vec![0x19, 0x03, 0xE8];
+	let a = vec![0x61, b'a'];
+	let arr_thousand = vec![0x81, 0x19, 0x03, 0xE8];
+	let arr_one_one = vec![0x82, 0x01, 0x01];
+	let map = Value::map(vec![
+		(Value::array(vec![1u32, 1]), 1u32),
+		(Value::text("a"), 1),
+		(Value::array(vec![1000u32]), 1),
+		(Value::from(1000u32), 1),
+		(Value::from(10u32), 1),
+	]);
+	let expect = |keys: Vec<&Vec<u8>>| {
+		let mut out = vec![0xA5];
+		for k in keys {
+			out.extend_from_slice(k);
+			out.push(0x01);
+		}
+		out
+	};
+
+	let cases = vec![
+		// Bytewise: the 3-byte 1000 (0x19) sorts before the 2-byte "a" (0x61)
+		(
+			KeyOrder::Rfc8949,
+			expect(vec![&ten, &thousand, &a, &arr_thousand, &arr_one_one]),
+		),
+		// Length first: "a" is shorter than 1000, [1, 1] is shorter than [1000]
+		(
+			KeyOrder::Rfc7049LengthFirst,
+			expect(vec![&ten, &a, &thousand, &arr_one_one, &arr_thousand]),
+		),
+		// Major type first, then length
+		(
+			KeyOrder::Ctap2,
+			expect(vec![&ten, &thousand, &a, &arr_one_one, &arr_thousand]),
+		),
+	];
+	let modes = [KeyOrder::Rfc8949, KeyOrder::Rfc7049LengthFirst, KeyOrder::Ctap2];
+	for (order, expected) in &cases {
+		assert_eq!(*expected, map.encode_canonical_with(*order), "{:?}", order);
+		assert_eq!(
+			*expected,
+			cborg::canonicalize_with(&map.encode(), *order).unwrap(),
+			"{:?}",
+			order
+		);
+		for mode in &modes {
+			assert_eq!(
+				mode == order,
+				cborg::is_canonical_with(expected, *mode),
+				"{:?} checked as {:?}",
+				order,
+				mode
+			);
+		}
+	}
+	assert_eq!(map.encode_canonical(), cases[0].1);
+	assert_eq!(KeyOrder::Rfc8949, KeyOrder::default());
+
+	assert!(cborg::is_canonical(&cborg::canonicalize(&TEST_DATA_DEFINITE).unwrap()));
+	assert!(!cborg::is_canonical(&TEST_DATA_DEFINITE));
+	assert!(!cborg::is_canonical(&TEST_DATA_INDEFINITE));
+	assert!(cborg::is_canonical(&[0xF9, 0x3E, 0x00]));
+	assert!(cborg::is_canonical(&[0xFA, 0x47, 0xC3, 0x50, 0x00]));
+	assert!(!cborg::is_canonical(&[0xFA, 0x3F, 0xC0, 0x00, 0x00])); // 1.5 fits in a half
+	assert!(!cborg::is_canonical(&[0x18, 0x05]));
+	assert!(!cborg::is_canonical(&[0x01, 0x01]));
+	assert!(!cborg::is_canonical(&[0xA2, 0x01, 0x01, 0x01, 0x02]));
+	assert!(!cborg::is_canonical(&[0x62, 0xFF, 0xFE]));
+	assert!(!cborg::is_canonical(&[0xF8, 0x10]));
+	assert!(cborg::is_canonical(&[0xC1, 0x1A, 0x56, 0x12, 0xAE, 0xB0]));
+}
+
+#[test]
+fn decode_reuse_test() {
+	let definite = cborg::decode(&TEST_DATA_DEFINITE).unwrap();
+	let indefinite = cborg::decode(&TEST_DATA_INDEFINITE).unwrap();
+
+	let mut out = Value::Unsigned(3);
+	cborg::decode_reuse(&TEST_DATA_DEFINITE, &mut out).unwrap();
+	assert_eq!(definite, out);
+	// Smaller map, one entry fewer, chunked string
+	cborg::decode_reuse(&TEST_DATA_INDEFINITE, &mut out).unwrap();
+	assert_eq!(indefinite, out);
+	cborg::decode_reuse(&TEST_DATA_DEFINITE, &mut out).unwrap();
+	assert_eq!(definite, out);
+
+	// Every kind of item in the place of every other
+	let shapes = vec![
+		Value::array(vec![
+			Value::text("x"),
+			Value::bytes(vec![1]),
+			Value::map(vec![(1u32, 2u32)]),
+		]),
+		Value::map(vec![
+			(Value::text("k"), Value::array(vec![1u32, 2, 3])),
+			(Value::from(4u32), Value::from(2.5)),
+		]),
+		Value::array(vec![
+			Value::map(vec![("a", "b")]),
+			Value::text("long enough to need a new buffer"),
+		]),
+		Value::text("t"),
+		Value::bytes(vec![9, 8, 7]),
+		Value::from(-7i32),
+		Value::from(true),
+		Value::array(vec![Value::array(vec![5u32])]),
+	];
+	for from in &shapes {
+		for to in &shapes {
+			let mut out = from.clone();
+			cborg::decode_reuse(&to.encode(), &mut out).unwrap();
+			assert_eq!(*to, out);
+		}
+	}
+
+	let mut out = definite.clone();
+	assert!(cborg::decode_reuse(&TEST_DATA_DEFINITE[..100], &mut out).is_err());
+	assert!(cborg::decode_reuse(&[], &mut out).is_err());
+	assert!(cborg::decode_reuse(&[0x62, 0xFF, 0xFE], &mut out).is_err());
+	cborg::decode_reuse(&TEST_DATA_DEFINITE, &mut out).unwrap();
+	assert_eq!(definite, out);
+}
+
+#[test]
+fn lang_string_test() {
+	use cborg::LangString;
+
+	let hello = LangString::new("en-GB", "Hello").unwrap();
+	let bytes = [
+		0xD8, 0x26, 0x82, 0x65, b'e', b'n', b'-', b'G', b'B', 0x65, b'H', b'e', b'l', b'l', b'o',
+	];
+	assert_eq!(bytes.to_vec(), hello.to_value().encode());
+	assert_eq!(bytes.to_vec(), cborg::encode(hello.clone()));
+	let decoded = cborg::decode(&bytes).unwrap();
+	assert_eq!(Value::Tag(38, Box::new(Value::array(vec!["en-GB", "Hello"]))), decoded);
+	assert_eq!(r#"38(["en-GB", "Hello"])"#, decoded.to_diagnostic());
+	assert_eq!(Some(hello.clone()), decoded.as_lang_string());
+	assert_eq!(Some(hello), cborg::decode_to::<LangString, _>(&bytes).unwrap());
+
+	let labels: Vec<LangString> = cborg::decode_to(&cborg::encode(vec![
+		LangString::new("fr", "Bonjour").unwrap().to_value(),
+		LangString::new("zh-Hant-TW", "你好").unwrap().to_value(),
+	]))
+	.unwrap()
+	.unwrap();
+	assert_eq!("zh-Hant-TW", labels[1].lang);
+	assert_eq!("你好", labels[1].text);
+
+	let tag = |v: Value| Value::Tag(38, Box::new(v));
+	assert_eq!(None, tag(Value::text("just a string")).as_lang_string());
+	assert_eq!(None, tag(Value::array(vec!["en"])).as_lang_string());
+	assert_eq!(None, tag(Value::array(vec!["en", "a", "b"])).as_lang_string());
+	assert_eq!(
+		None,
+		tag(Value::array(vec![Value::text("en"), Value::from(5u32)])).as_lang_string()
+	);
+	assert_eq!(None, tag(Value::array(vec!["en_GB", "Hello"])).as_lang_string());
+	assert_eq!(
+		None,
+		Value::Tag(39, Box::new(Value::array(vec!["en", "Hello"]))).as_lang_string()
+	);
+	assert_eq!(None, Value::array(vec!["en", "Hello"]).as_lang_string());
+	assert_eq!(
+		None,
+		cborg::decode_to::<LangString, _>(&tag(Value::text("just a string")).encode()).unwrap()
+	);
+
+	for bad in &["", "-en", "en-", "en--GB", "1en", "en-abcdefghi", "ünd"] {
+		assert!(LangString::new(*bad, "x").is_err(), "{}", bad);
+	}
+	for good in &["en", "i-enochian", "x-klingon", "sgn-BE-FR", "es-419"] {
+		assert!(cborg::value::is_plausible_language_tag(good), "{}", good);
+	}
+}
+
+#[test]
+fn regex_test() {
+	let pattern = r"^\d{3}-[a-z]+$";
+	let v = Value::regex(pattern);
+	let bytes = v.encode();
+	assert_eq!(&[0xD8, 0x23, 0x6E], &bytes[..3]);
+	let decoded = cborg::decode(&bytes).unwrap();
+	assert_eq!(Some(pattern), decoded.as_regex_str());
+
+	assert_eq!(None, Value::text(pattern).as_regex_str());
+	assert_eq!(None, Value::Tag(35, Box::new(Value::from(7u32))).as_regex_str());
+	assert_eq!(
+		None,
+		Value::Tag(35, Box::new(Value::bytes(pattern.as_bytes().to_vec()))).as_regex_str()
+	);
+	assert_eq!(None, Value::Tag(36, Box::new(Value::text(pattern))).as_regex_str());
+
+	#[cfg(feature = "regex")]
+	{
+		use cborg::FromValue;
+		use regex::Regex;
+
+		let re: Regex = cborg::decode_to(&bytes).unwrap().unwrap();
+		assert!(re.is_match("123-abc"));
+		assert_eq!(bytes, cborg::encode(re.clone()));
+		assert_eq!(bytes, re.to_value().encode());
+
+		let broken = Value::regex("(unclosed");
+		assert!(Regex::from_ref(&broken).is_none());
+		let err = broken.to_regex().unwrap_err();
+		assert!(format!("{:?}", err).contains("unclosed group"), "{:?}", err);
+		assert!(Value::Tag(35, Box::new(Value::from(7u32))).to_regex().is_err());
+		assert!(Regex::from_ref(&Value::text(pattern)).is_none());
+	}
+}
+
+#[test]
+fn roundtrip_check_test() {
+	use cborg::DivergenceKind;
+
+	let report = cborg::roundtrip_check(&TEST_DATA_DEFINITE).unwrap();
+	assert!(report.is_faithful());
+	assert_eq!(TEST_DATA_DEFINITE.to_vec(), report.encoded);
+
+	let report = cborg::roundtrip_check(&TEST_DATA_INDEFINITE).unwrap();
+	assert!(!report.is_faithful());
+	let d = report.divergence.unwrap();
+	assert_eq!(DivergenceKind::IndefiniteCollapsed, d.kind);
+	assert_eq!(0, d.offset);
+	assert_eq!("$", d.path.to_string());
+
+	// {"a": (_ "x", "y"), "b": 1}
+	let d = cborg::roundtrip_check(&[0xA2, 0x61, b'a', 0x7F, 0x61, b'x', 0x61, b'y', 0xFF, 0x61, b'b', 0x01])
+		.unwrap()
+		.divergence
+		.unwrap();
+	assert_eq!((DivergenceKind::IndefiniteCollapsed, 3), (d.kind, d.offset));
+	assert_eq!("$.a", d.path.to_string());
+
+	let check = |bytes: &[u8]| cborg::roundtrip_check(bytes).unwrap().divergence.unwrap();
+	// [1, 1.5 as a single]
+	let d = check(&[0x82, 0x01, 0xFA, 0x3F, 0xC0, 0x00, 0x00]);
+	assert_eq!((DivergenceKind::FloatWidthChanged, 2), (d.kind, d.offset));
+	assert_eq!("$[1]", d.path.to_string());
+	// {"k": 7 as a two-byte argument}
+	let d = check(&[0xA1, 0x61, b'k', 0x19, 0x00, 0x07]);
+	assert_eq!((DivergenceKind::IntegerMinimized, 3), (d.kind, d.offset));
+	assert_eq!("$.k", d.path.to_string());
+	// A three-byte string length that fits in one byte
+	let d = check(&[0x79, 0x00, 0x01, b'x']);
+	assert_eq!(DivergenceKind::IntegerMinimized, d.kind);
+	let d = check(&[0x01, 0x02, 0x03]);
+	assert_eq!((DivergenceKind::TrailingData, 1), (d.kind, d.offset));
+
+	assert!(cborg::roundtrip_check(&[0x82, 0x01]).is_err());
+}
+
+#[test]
+fn ct_eq_bytes_test() {
+	let mac = Value::ByteString(vec![0xDE, 0xAD, 0xBE, 0xEF]);
+	assert!(mac.ct_eq_bytes(&[0xDE, 0xAD, 0xBE, 0xEF]));
+	assert!(!mac.ct_eq_bytes(&[0xDE, 0xAD, 0xBE, 0xEE]));
+	assert!(!mac.ct_eq_bytes(&[0x00, 0xAD, 0xBE, 0xEF]));
+	// A matching prefix is not a match, whichever side is longer
+	assert!(!mac.ct_eq_bytes(&[0xDE, 0xAD, 0xBE]));
+	assert!(!mac.ct_eq_bytes(&[0xDE, 0xAD, 0xBE, 0xEF, 0x00]));
+	assert!(!mac.ct_eq_bytes(&[]));
+	assert!(Value::ByteString(vec![]).ct_eq_bytes(&[]));
+	assert!(!Value::Utf8String("\u{DE}".into()).ct_eq_bytes(&[0xC3, 0x9E]));
+	assert!(!Value::Array(vec![]).ct_eq_bytes(&[]));
+
+	assert!(cborg::ct_eq(&mac, &mac.clone()));
+	assert!(!cborg::ct_eq(&mac, &Value::ByteString(vec![0xDE, 0xAD])));
+	assert!(!cborg::ct_eq(
+		&Value::Utf8String("x".into()),
+		&Value::Utf8String("x".into())
+	));
+}
+
+#[test]
+fn index_seq_test() {
+	let mut bytes = Vec::new();
+	for i in 1..=10_000u64 {
+		let v = match i % 3 {
+			0 => Value::Unsigned(i * 1_000),
+			1 => Value::Utf8String(format!("item {}", i)),
+			_ => Value::Array(vec![Value::Unsigned(i), Value::Negative(-(i as i64))]),
+		};
+		bytes.extend(cborg::encode(v));
+	}
+	let mut sequential = Vec::new();
+	let mut iter = bytes.iter();
+	while iter.len() > 0 {
+		sequential.push(cborg::decode_iter(&mut iter).unwrap());
+	}
+	assert_eq!(10_000, sequential.len());
+
+	let dense = cborg::index_seq(&bytes).unwrap();
+	let sparse = cborg::index_seq_sparse(&bytes, 64).unwrap();
+	let cached = cborg::SeqIndex::from_bytes(&sparse.to_bytes(), &bytes).unwrap();
+	assert_eq!(sparse, cached);
+	// A sparse index is roughly 1/64 the size of a dense one
+	assert!(sparse.to_bytes().len() * 32 < dense.to_bytes().len());
+
+	let mut n: usize = 12345;
+	for _ in 0..200 {
+		n = (n * 1_103_515_245 + 12_345) % 10_000;
+		for index in [&dense, &sparse, &cached].iter() {
+			assert_eq!(10_000, index.len());
+			assert_eq!(Some(sequential[n].clone()), index.decode_nth(n).unwrap());
+		}
+		assert_eq!(dense.get(n), sparse.get(n));
+	}
+	assert_eq!(Some(sequential[9_999].clone()), sparse.decode_nth(9_999).unwrap());
+	assert_eq!(None, dense.get(10_000));
+	assert_eq!(None, sparse.decode_nth(10_000).unwrap());
+
+	assert!(cborg::index_seq(&[]).unwrap().is_empty());
+	// The second item is truncated
+	assert!(cborg::index_seq(&[0x01, 0x82, 0x01]).is_err());
+	// An index only loads against data of the length it was built over
+	assert!(cborg::SeqIndex::from_bytes(&dense.to_bytes(), &bytes[..100]).is_err());
+	assert!(cborg::SeqIndex::from_bytes(&[0x84, 0x01], &bytes).is_err());
+}
+
+#[test]
+fn display_colored_test() {
+	fn strip_ansi(s: &str) -> String {
+		let mut out = String::new();
+		let mut chars = s.chars();
+		while let Some(c) = chars.next() {
+			if c == '\x1b' {
+				for c in chars.by_ref() {
+					if c == 'm' {
+						break;
+					}
+				}
+			} else {
+				out.push(c);
+			}
+		}
+		out
+	}
+
+	let v = cborg::decode(TEST_DATA_DEFINITE.iter()).unwrap();
+	let colored = v.display_colored().to_string();
+	assert_ne!(colored, v.to_string());
+	assert_eq!(v.to_string(), strip_ansi(&colored));
+	assert!(!v.to_string().contains('\x1b'));
+
+	let v = Value::map(vec![("name", Value::text("cbor")), ("n", Value::from(-3i32))]);
+	let colored = v.display_colored().to_string();
+	assert!(colored.contains("\x1b[34m\"name\"\x1b[0m"));
+	assert!(colored.contains("\x1b[32m\"cbor\"\x1b[0m"));
+	assert!(colored.contains("\x1b[33m-3\x1b[0m"));
+	assert!(colored.starts_with("\x1b[2m{\x1b[0m\n"));
+	assert_eq!(v.to_string(), strip_ansi(&colored));
+}
+
+#[test]
+fn map_test() {
+	let text = Value::text;
+
+	let mut m = Map::new();
+	assert!(m.is_empty());
+	assert_eq!(None, m.insert(text("b"), Value::Unsigned(1)));
+	assert_eq!(None, m.insert(Value::Unsigned(10), Value::Unsigned(2)));
+	assert_eq!(None, m.insert(text("a"), Value::Unsigned(3)));
+	// Replacing keeps the entry where it was
+	assert_eq!(Some(Value::Unsigned(1)), m.insert(text("b"), Value::Unsigned(4)));
+	assert_eq!(3, m.len());
+	let keys: Vec<&Value> = m.iter().map(|kv| &kv.key).collect();
+	assert_eq!(vec![&text("b"), &Value::Unsigned(10), &text("a")], keys);
+
+	assert_eq!(Some(&Value::Unsigned(4)), m.get(&text("b")));
+	assert_eq!(None, m.get(&text("c")));
+	assert!(m.contains_key(&Value::Unsigned(10)));
+	*m.get_mut(&text("a")).unwrap() = Value::Unsigned(5);
+	assert_eq!(Some(&Value::Unsigned(5)), m.get(&text("a")));
+
+	m.sort_keys();
+	let keys: Vec<Value> = m.entries().iter().map(|kv| kv.key.clone()).collect();
+	assert_eq!(vec![Value::Unsigned(10), text("a"), text("b")], keys);
+
+	assert_eq!(Some(Value::Unsigned(5)), m.remove(&text("a")));
+	assert_eq!(None, m.remove(&text("a")));
+	assert_eq!(2, m.len());
+
+	// Lookups see the first of duplicate keys, as decoded
+	let dup = Map::from(vec![
+		KeyVal {
+			key: text("k"),
+			val: Value::Unsigned(1),
+		},
+		KeyVal {
+			key: text("k"),
+			val: Value::Unsigned(2),
+		},
+	]);
+	assert_eq!(Some(&Value::Unsigned(1)), dup.get(&text("k")));
+
+	// Existing Vec<KeyVal> code keeps working through From and Deref
+	let v = Value::Map(m.clone());
+	assert_eq!(Some(m.to_vec()), v.get_map());
+	let entries: Vec<KeyVal> = m.into();
+	assert_eq!(2, entries.len());
+	let bytes = cborg::encode(v.clone());
+	assert_eq!(v, cborg::decode(bytes.iter()).unwrap());
+}
+
+#[test]
+fn bytes_from_array_test() {
+	use cborg::FromValue;
+	let bytes = |items: Vec<Value>| Vec::<u8>::from_value(Value::Array(items));
+
+	assert_eq!(
+		Some(vec![1, 255, 2]),
+		bytes(vec![Value::Unsigned(1), Value::Unsigned(255), Value::Unsigned(2)])
+	);
+	assert_eq!(
+		None,
+		bytes(vec![Value::Unsigned(1), Value::Unsigned(256), Value::Unsigned(2)])
+	);
+	assert_eq!(None, bytes(vec![Value::Unsigned(1), Value::Negative(-1)]));
+	assert_eq!(None, bytes(vec![Value::Unsigned(1), Value::text("2")]));
+	assert_eq!(Some(vec![]), bytes(vec![]));
+	let arr = Value::Array(vec![Value::Unsigned(500)]);
+	assert_eq!(None, Vec::<u8>::from_ref(&arr));
+
+	let bs = Value::ByteString(vec![0, 1, 0xFF]);
+	assert_eq!(Some(vec![0, 1, 0xFF]), Vec::<u8>::from_ref(&bs));
+	assert_eq!(Some(vec![0, 1, 0xFF]), Vec::<u8>::from_value(bs));
+	// Through decode_to: [1, 500, 2]
+	assert_eq!(
+		None,
+		cborg::decode_to::<Vec<u8>, _>(&[0x83, 0x01, 0x19, 0x01, 0xF4, 0x02]).unwrap()
+	);
+}
+
+#[test]
+fn byte_array_test() {
+	use cborg::FromValue;
+	let iv: [u8; 16] = *b"0123456789abcdef";
+	let hash = [0xABu8; 32];
+
+	assert_eq!(Value::ByteString(iv.to_vec()), Value::from(iv));
+	assert_eq!(Value::ByteString(hash.to_vec()), hash.to_value());
+	assert_eq!(Some(iv), <[u8; 16]>::from_value(Value::from(iv)));
+	assert_eq!(Some(hash), <[u8; 32]>::from_ref(&Value::from(hash)));
+
+	// No padding or truncation
+	assert_eq!(None, <[u8; 16]>::from_value(Value::ByteString(vec![1; 15])));
+	assert_eq!(None, <[u8; 16]>::from_value(Value::ByteString(vec![1; 17])));
+	assert_eq!(None, <[u8; 32]>::from_ref(&Value::ByteString(vec![1; 31])));
+	assert_eq!(None, <[u8; 32]>::from_ref(&Value::ByteString(vec![1; 33])));
+	// An array of small integers is not a byte string
+	let arr = Value::Array((0..16).map(|_| Value::Unsigned(1)).collect());
+	assert_eq!(None, <[u8; 16]>::from_ref(&arr));
+	assert_eq!(None, <[u8; 16]>::from_value(arr));
+
+	let encoded = cborg::encode(hash);
+	assert_eq!(Some(hash), cborg::decode_to::<[u8; 32], _>(&encoded).unwrap());
+}
+
+#[test]
+fn into_value_test() {
+	use cborg::IntoValue;
+
+	// The buffer is moved, not copied
+	let big = vec![0x5Au8; 1 << 20];
+	let ptr = big.as_ptr();
+	match big.into_value() {
+		Value::ByteString(x) => assert_eq!(ptr, x.as_ptr()),
+		x => panic!("expected a byte string, got {:?}", x),
+	}
+	let s = String::from("owned text");
+	let ptr = s.as_ptr();
+	match s.into_value() {
+		Value::Utf8String(x) => assert_eq!(ptr, x.as_ptr()),
+		x => panic!("expected a text string, got {:?}", x),
+	}
+
+	// Same bytes as the borrowing conversions
+	let mut map = BTreeMap::new();
+	map.insert(String::from("a"), vec![String::from("x"), String::from("y")]);
+	map.insert(String::from("b"), vec![]);
+	assert_eq!(map.to_value().encode(), cborg::encode_owned(map.clone()));
+	assert_eq!(map.to_value().encode(), cborg::encode_owned(&map));
+	let bytes = vec![1u8, 2, 3];
+	assert_eq!(cborg::encode(bytes.clone()), cborg::encode_owned(bytes));
+	assert_eq!(cborg::encode(-7i32), cborg::encode_owned(-7i32));
+	assert_eq!(cborg::encode("s"), cborg::encode_owned("s"));
+	let mut hm = HashMap::new();
+	hm.insert(7u32, true);
+	assert_eq!(hm.to_value(), hm.clone().into_value());
+}
+
+#[test]
+fn label_test() {
+	use cborg::FromValue;
+	use cborg::Label;
+
+	// COSE-style protected header: {1: -7, 4: h'6b6964', "note": "x", -2: 1}
+	let header = Value::map(vec![
+		(Value::Unsigned(1), Value::Negative(-7)),
+		(Value::Unsigned(4), Value::bytes(&b"kid"[..])),
+		(Value::text("note"), Value::text("x")),
+		(Value::Negative(-2), Value::Unsigned(1)),
+	]);
+	let bytes = header.encode();
+	let map: BTreeMap<Label, Value> = cborg::decode_to(&bytes).unwrap().unwrap();
+	let keys: Vec<&Label> = map.keys().collect();
+	assert_eq!(
+		vec![&Label::Int(-2), &Label::Int(1), &Label::Int(4), &Label::from("note")],
+		keys
+	);
+	assert_eq!(Value::Negative(-7), map[&Label::from(1)]);
+	assert_eq!(Value::text("x"), map[&Label::from("note")]);
+	assert_eq!(
+		"-2, 1, 4, note",
+		keys.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+	);
+
+	let reencoded = cborg::encode(map.clone());
+	let again: BTreeMap<Label, Value> = cborg::decode_to(&reencoded).unwrap().unwrap();
+	assert_eq!(map, again);
+	assert_eq!(
+		cborg::diff_unordered(&header, &cborg::decode(reencoded.iter()).unwrap()),
+		vec![]
+	);
+
+	let hashed: HashMap<Label, Value> = cborg::decode_to(&bytes).unwrap().unwrap();
+	assert_eq!(4, hashed.len());
+
+	// Other key kinds are rejected
+	assert_eq!(None, Label::from_ref(&Value::bytes(vec![1])));
+	assert_eq!(None, Label::from_ref(&Value::Unsigned(u64::MAX)));
+	assert_eq!(Value::Negative(-3), Label::Int(-3).to_value());
+	// Tags are looked through, as in the other conversions
+	let tagged = Value::tagged(0, Value::text("when"));
+	assert_eq!(Some(Label::from("when")), Label::from_ref(&tagged));
+	assert_eq!(Some(Label::from("when")), Label::from_value(tagged));
+	assert_eq!(
+		Some(Label::Int(-4)),
+		Label::from_ref(&Value::tagged(1, Value::Negative(-4)))
+	);
+	// Map conversions drop entries whose keys do not convert
+	let mixed = Value::map(vec![
+		(Value::Float(1.5), Value::Unsigned(1)),
+		(Value::Unsigned(2), Value::Unsigned(3)),
+	]);
+	let map: BTreeMap<Label, Value> = cborg::decode_to(&mixed.encode()).unwrap().unwrap();
+	assert_eq!(vec![&Label::Int(2)], map.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn decode_seq_to_test() {
+	#[derive(Debug, PartialEq)]
+	struct Record {
+		id: u64,
+		name: String,
+	}
+	impl cborg::FromValue for Record {
+		fn from_value(v: Value) -> Option<Self> { Self::from_ref(&v) }
+		fn from_ref(v: &Value) -> Option<Self> {
+			let map = match v {
+				Value::Map(x) => x,
+				_ => return None,
+			};
+			Some(Record {
+				id: map.get(&Value::text("id"))?.get_uint()?,
+				name: map.get(&Value::text("name"))?.get_string()?,
+			})
+		}
+	}
+	let record = |id: Value, name: &str| Value::map(vec![("id", id), ("name", Value::text(name))]).encode();
+
+	let mut bytes = Vec::new();
+	bytes.extend(record(Value::Unsigned(1), "first"));
+	let second = bytes.len();
+	bytes.extend(record(Value::text("2"), "second"));
+	bytes.extend(record(Value::Unsigned(3), "third"));
+
+	let items: Vec<cborg::Result<Record>> = cborg::decode_seq_to(&bytes).collect();
+	assert_eq!(3, items.len());
+	assert_eq!(
+		&Record {
+			id: 1,
+			name: "first".into()
+		},
+		items[0].as_ref().unwrap()
+	);
+	let err = format!("{:?}", items[1].as_ref().unwrap_err());
+	assert!(
+		err.contains(&format!("could not convert sequence item 1 at offset {}", second)),
+		"{}",
+		err
+	);
+
+	// A malformed item keeps its error and source, with the item and offset as context
+	let malformed = [0x01, 0x62, 0xFF, 0xFE];
+	let values: Vec<cborg::Result<Value>> = cborg::decode_seq(&malformed).collect();
+	assert_eq!(2, values.len());
+	let err = values[1].as_ref().unwrap_err();
+	assert_eq!(&ErrorKind::InvalidUtf8, err.kind());
+	assert!(std::error::Error::source(err).is_some());
+	assert_eq!(Some("sequence item 1 at offset 1"), err.context());
+	assert_eq!(
+		&Record {
+			id: 3,
+			name: "third".into()
+		},
+		items[2].as_ref().unwrap()
+	);
+	assert!(cborg::decode_seq_to_vec::<Record>(&bytes).is_err());
+
+	let mut good = record(Value::Unsigned(1), "a");
+	good.extend(record(Value::Unsigned(2), "b"));
+	let records: Vec<Record> = cborg::decode_seq_to_vec(&good).unwrap();
+	assert_eq!(vec![1, 2], records.iter().map(|r| r.id).collect::<Vec<_>>());
+
+	assert_eq!(0, cborg::decode_seq_to::<Record>(&[]).count());
+	// A truncated item is the last thing yielded
+	let mut truncated = record(Value::Unsigned(1), "a");
+	truncated.extend(&[0x82, 0x01]);
+	let items: Vec<_> = cborg::decode_seq_to::<Value>(&truncated).collect();
+	assert_eq!(2, items.len());
+	assert!(items[1].is_err());
+}
+
+#[test]
+fn query_test() {
+	use cborg::Query;
+
+	let doc = Value::map(vec![
+		(Value::text("name"), Value::text("root")),
+		(
+			Value::text("items"),
+			Value::array(vec![
+				Value::map(vec![
+					("id", Value::Unsigned(1)),
+					("signature", Value::bytes(vec![0xA1])),
+				]),
+				Value::map(vec![("id", Value::Unsigned(2))]),
+			]),
+		),
+		(
+			Value::text("meta"),
+			Value::map(vec![
+				("signature", Value::bytes(vec![0xB2])),
+				("nested", Value::map(vec![("signature", Value::bytes(vec![0xC3]))])),
+			]),
+		),
+		(Value::Unsigned(555), Value::text("numeric")),
+		(
+			Value::text("odd key"),
+			Value::Tag(24, Box::new(Value::map(vec![("id", 9u32)]))),
+		),
+	]);
+	let paths = |q: &str| -> Vec<String> {
+		let query = Query::parse(q).unwrap();
+		query.find(&doc).iter().map(|(p, _)| p.to_string()).collect()
+	};
+
+	assert_eq!(vec!["$"], paths("$"));
+	assert_eq!(vec!["$.name"], paths("$.name"));
+	assert_eq!(vec!["$.name"], paths(".name"));
+	assert_eq!(vec!["$.items[1]"], paths("$.items[1]"));
+	assert_eq!(Vec::<String>::new(), paths("$.items[2]"));
+	assert_eq!(vec!["$.items[0].id", "$.items[1].id"], paths("$.items[*].id"));
+	assert_eq!(vec!["$.555"], paths("$.555"));
+	assert_eq!(vec!["$.\"odd key\".id"], paths("$.\"odd key\".id"));
+	assert_eq!(vec!["$.meta.signature"], paths("$[\"meta\"][\"signature\"]"));
+
+	// A wildcard over a map selects its values
+	assert_eq!(vec!["$.meta.signature", "$.meta.nested"], paths("$.meta.*"));
+	assert_eq!(vec!["$.meta.signature", "$.meta.nested"], paths("$.meta[*]"));
+
+	// Recursive descent finds matches at every depth, looking through tags
+	assert_eq!(
+		vec!["$.items[0].signature", "$.meta.signature", "$.meta.nested.signature"],
+		paths("$..signature")
+	);
+	assert_eq!(
+		vec!["$.items[0].id", "$.items[1].id", "$.\"odd key\".id"],
+		paths("..id")
+	);
+	assert_eq!(vec!["$.items[1]"], paths("$..[1]"));
+	let found = Query::parse("$..signature").unwrap();
+	let found = found.find(&doc);
+	assert_eq!(&Value::bytes(vec![0xC3]), found[2].1);
+
+	for bad in &["$.", "$[", "$[x]", "$[1", "$.\"open", "name", "$.items[*]extra"] {
+		assert!(Query::parse(bad).is_err(), "{} should not parse", bad);
+	}
+}
+
+#[test]
+fn canonical_json_test() {
+	let v = cborg::decode(TEST_DATA_DEFINITE.iter()).unwrap();
+	let expected = format!(
+		concat!(
+			r#"{{"555":{{"bytestring":"AQIDBAU","float":2.5,"long string":"{}","negative":-4,"unsigned":8,"#,
+			r#""utf8string":"你好，世界 - hello, world"}},"777":[11,-22,33.3,"fourty-four"]}}"#
+		),
+		LONG_STRING
+	);
+	assert_eq!(expected, v.to_canonical_json().unwrap());
+
+	// ECMAScript number formatting, with examples from RFC 8785 appendix B
+	let number = |x: f64| Value::Float(x).to_canonical_json().unwrap();
+	assert_eq!("0", number(-0.0));
+	assert_eq!("1", number(1.0));
+	assert_eq!("-4.5", number(-4.5));
+	assert_eq!("0.002", number(2e-3));
+	assert_eq!("0.000001", number(1e-6));
+	assert_eq!("1e-7", number(1e-7));
+	assert_eq!("1.5e-7", number(1.5e-7));
+	assert_eq!("333333333.3333333", number(333333333.3333333));
+	assert_eq!("123456789012345680000", number(123456789012345680000.0));
+	assert_eq!("1e+21", number(1e21));
+	assert_eq!("9007199254740991", number(9007199254740991.0));
+	assert_eq!("5e-324", number(5e-324));
+	assert_eq!("-1.7976931348623157e+308", number(-1.7976931348623157e308));
+
+	let json = |v: Value| v.to_canonical_json().unwrap();
+	assert_eq!(r#""a\"\\\n\u001f/é""#, json(Value::text("a\"\\\n\u{1f}/é")));
+	assert_eq!(
+		r#"[true,false,null,""]"#,
+		json(Value::array(vec![
+			true.to_value(),
+			false.to_value(),
+			Value::Simple(cborg::Simple::Null),
+			Value::bytes(vec![])
+		]))
+	);
+	// Keys sort by UTF-16 code units, so U+10000 (a surrogate pair) sorts before U+FF5E
+	let keys = Value::map(vec![("\u{ff5e}", 1u32), ("\u{10000}", 2u32), ("a", 3u32)]);
+	assert_eq!("{\"a\":3,\"\u{10000}\":2,\"\u{ff5e}\":1}", json(keys));
+	assert_eq!(
+		r#"{"7":"x"}"#,
+		json(Value::map(vec![(Value::Unsigned(7), Value::text("x"))]))
+	);
+
+	// No faithful mapping
+	let dup = Value::map(vec![
+		(Value::Unsigned(1), Value::text("a")),
+		(Value::text("1"), Value::text("b")),
+	]);
+	assert!(dup.to_canonical_json().is_err());
+	assert!(Value::Float(f64::NAN).to_canonical_json().is_err());
+	assert!(Value::Float(f64::INFINITY).to_canonical_json().is_err());
+	assert!(Value::Unsigned(1 << 53).to_canonical_json().is_err());
+	assert!(Value::Negative(-(1 << 53)).to_canonical_json().is_err());
+	assert!(Value::Simple(cborg::Simple::Undefined).to_canonical_json().is_err());
+	assert!(Value::array(vec![Value::Float(f64::NAN)]).to_canonical_json().is_err());
+}
+
+#[test]
+fn approx_eq_test() {
+	fn through_f32(v: &Value) -> Value {
+		match v {
+			Value::Float(x) => Value::Float(*x as f32 as f64),
+			Value::Array(x) => Value::Array(x.iter().map(through_f32).collect()),
+			Value::Map(x) => Value::Map(
+				x.iter()
+					.rev()
+					.map(|kv| KeyVal {
+						key: kv.key.clone(),
+						val: through_f32(&kv.val),
+					})
+					.collect(),
+			),
+			x => x.clone(),
+		}
+	}
+	let original = cborg::decode(TEST_DATA_DEFINITE.iter()).unwrap();
+	// Floats narrowed to f32, map entries reversed
+	let narrowed = through_f32(&original);
+	assert_ne!(original, narrowed);
+	assert!(original.approx_eq(&narrowed, 1e-6, 0.0));
+	assert!(narrowed.approx_eq(&original, 1e-6, 0.0));
+	assert!(!original.approx_eq(&narrowed, 1e-9, 0.0));
+
+	let mut different = narrowed.clone();
+	if let Value::Map(m) = &mut different {
+		*m.get_mut(&Value::Unsigned(777)).unwrap() = Value::array(vec![11u32, 22, 33, 44]);
+	}
+	assert!(!original.approx_eq(&different, 1e-6, 0.0));
+	assert!(!Value::text("a").approx_eq(&Value::text("b"), 1.0, 1.0));
+	assert!(!Value::Float(1.0).approx_eq(&Value::text("1"), 1.0, 1.0));
+
+	// Integers against floats
+	assert!(Value::Unsigned(3).approx_eq(&Value::Float(3.0000001), 1e-6, 0.0));
+	assert!(Value::Float(-2.0).approx_eq(&Value::Negative(-2), 0.0, 0.0));
+	assert!(!Value::Unsigned(3).approx_eq(&Value::Float(3.1), 1e-6, 0.0));
+	assert!(Value::Float(1e-12).approx_eq(&Value::Float(0.0), 0.0, 1e-9));
+
+	// NaN and infinities
+	assert!(Value::Float(f64::NAN).approx_eq(&Value::Float(-f64::NAN), 0.0, 0.0));
+	assert!(!Value::Float(f64::NAN).approx_eq(&Value::Float(1.0), 1.0, 1.0));
+	assert!(Value::Float(f64::INFINITY).approx_eq(&Value::Float(f64::INFINITY), 0.0, 0.0));
+	assert!(!Value::Float(f64::INFINITY).approx_eq(&Value::Float(f64::NEG_INFINITY), 1.0, 1.0));
+}
+
+#[test]
+fn extended_time_test() {
+	use cborg::ExtendedTime;
+	use cborg::FromValue;
+	use std::time::Duration;
+	use std::time::UNIX_EPOCH;
+
+	let decode = |bytes: &[u8]| cborg::decode_to::<ExtendedTime, _>(bytes).unwrap();
+
+	// Nanosecond precision survives the round trip
+	let t = ExtendedTime::new(1_700_000_000, 123_456_789).unwrap();
+	let bytes = cborg::encode(t);
+	assert_eq!(r#"1001({1: 1700000000, -9: 123456789})"#, t.to_value().to_diagnostic());
+	assert_eq!(Some(t), decode(&bytes));
+	assert_eq!(t, ExtendedTime::from_system_time(t.to_system_time()));
+
+	// The coarsest exact unit is used, and no fraction when there is none
+	let ms = ExtendedTime::new(1_700_000_000, 250_000_000).unwrap();
+	assert_eq!(r#"1001({1: 1700000000, -3: 250})"#, ms.to_value().to_diagnostic());
+	let us = ExtendedTime::new(1_700_000_000, 250_001_000).unwrap();
+	assert_eq!(r#"1001({1: 1700000000, -6: 250001})"#, us.to_value().to_diagnostic());
+	let whole = ExtendedTime::new(1_700_000_000, 0).unwrap();
+	assert_eq!(r#"1001({1: 1700000000})"#, whole.to_value().to_diagnostic());
+	assert_eq!(None, ExtendedTime::new(1, 1_000_000_000));
+
+	// 1001({1: 1700000000, -3: 500})
+	let millis_only = [
+		0xD9, 0x03, 0xE9, 0xA2, 0x01, 0x1A, 0x65, 0x53, 0xF1, 0x00, 0x22, 0x19, 0x01, 0xF4,
+	];
+	assert_eq!(
+		Some(ExtendedTime {
+			seconds: 1_700_000_000,
+			nanos: 500_000_000
+		}),
+		decode(&millis_only)
+	);
+
+	// Before 1970 the fraction still counts forward
+	let early = UNIX_EPOCH - Duration::from_millis(86_400_750);
+	let t = ExtendedTime::from_system_time(early);
+	assert_eq!(
+		ExtendedTime {
+			seconds: -86_401,
+			nanos: 250_000_000
+		},
+		t
+	);
+	assert_eq!(early, t.to_system_time());
+	assert_eq!(Some(t), decode(&cborg::encode(t)));
+	let exact = ExtendedTime::from_system_time(UNIX_EPOCH - Duration::from_secs(5));
+	assert_eq!(ExtendedTime { seconds: -5, nanos: 0 }, exact);
+
+	let ext = |entries: Vec<(i64, u64)>| {
+		let map = Value::map(entries.into_iter().map(|(k, v)| (k.to_value(), Value::Unsigned(v))));
+		ExtendedTime::from_value(Value::Tag(1001, Box::new(map)))
+	};
+	// Subsecond keys must agree
+	assert_eq!(None, ext(vec![(1, 5), (-3, 1), (-9, 2)]));
+	assert_eq!(None, ext(vec![(1, 5), (-9, 2), (-9, 3)]));
+	assert_eq!(
+		Some(ExtendedTime {
+			seconds: 5,
+			nanos: 1_000_000
+		}),
+		ext(vec![(1, 5), (-3, 1), (-6, 1_000)])
+	);
+	assert_eq!(None, ext(vec![(1, 5), (-3, 1_000)]));
+	assert_eq!(None, ext(vec![(-3, 1)]));
+	assert_eq!(None, ext(vec![(1, 5), (-7, 1)]));
+	assert_eq!(Some(ExtendedTime { seconds: 5, nanos: 0 }), ext(vec![(1, 5), (8, 1)]));
+
+	// Epoch-based date/time (tag 1) interoperates
+	let tag1 = Value::Tag(1, Box::new(Value::Float(1.5)));
+	assert_eq!(
+		Some(ExtendedTime {
+			seconds: 1,
+			nanos: 500_000_000
+		}),
+		ExtendedTime::from_ref(&tag1)
+	);
+	let tag1 = Value::Tag(1, Box::new(Value::Negative(-10)));
+	assert_eq!(
+		Some(ExtendedTime { seconds: -10, nanos: 0 }),
+		ExtendedTime::from_value(tag1.clone())
+	);
+	assert_eq!(tag1, ExtendedTime { seconds: -10, nanos: 0 }.to_epoch_value());
+	assert_eq!(
+		Value::Tag(1, Box::new(Value::Float(-1.5))),
+		ExtendedTime {
+			seconds: -2,
+			nanos: 500_000_000
+		}
+		.to_epoch_value()
+	);
+	assert_eq!(None, ExtendedTime::from_value(Value::Unsigned(5)));
+}
+
+#[test]
+fn retain_truncate_test() {
+	fn drop_nulls(v: &mut Value) {
+		v.retain_entries(|_, val| *val != Value::Simple(cborg::Simple::Null));
+		match v {
+			Value::Array(x) => x.iter_mut().for_each(drop_nulls),
+			Value::Map(x) => x.iter_mut().for_each(|kv| drop_nulls(&mut kv.val)),
+			_ => (),
+		}
+	}
+	let null = || Value::Simple(cborg::Simple::Null);
+
+	let mut doc = Value::map(vec![
+		(Value::text("a"), null()),
+		(
+			Value::text("b"),
+			Value::map(vec![("x", null()), ("y", Value::Unsigned(1)), ("z", null())]),
+		),
+		(
+			Value::text("c"),
+			Value::array(vec![Value::map(vec![("n", null()), ("m", Value::Unsigned(2))]), null()]),
+		),
+		(Value::text("d"), Value::Unsigned(3)),
+	]);
+	drop_nulls(&mut doc);
+	// Nulls in arrays are elements, not entries, so they stay
+	assert_eq!(r#"{"b": {"y": 1}, "c": [{"m": 2}, null], "d": 3}"#, doc.to_diagnostic());
+	let reencoded = cborg::decode(doc.encode().iter()).unwrap();
+	assert_eq!(doc, reencoded);
+
+	let deny = ["b", "d"];
+	doc.retain_entries(|k, _| !deny.iter().any(|d| *k == Value::text(*d)));
+	assert_eq!(r#"{"c": [{"m": 2}, null]}"#, doc.to_diagnostic());
+
+	let mut long = Value::array((1..=1000u32).map(Value::from));
+	long.truncate(100);
+	assert_eq!(long, Value::array((1..=100u32).map(Value::from)));
+	long.retain_elements(|v| v.get_uint().unwrap() % 10 == 0);
+	assert_eq!(r#"[10, 20, 30, 40, 50, 60, 70, 80, 90, 100]"#, long.to_diagnostic());
+	long.truncate(500);
+	assert_eq!(10, long.get_array().unwrap().len());
+	let bytes = long.encode();
+	assert_eq!(0x8A, bytes[0]);
+
+	let mut map = Value::map(vec![("a", 1u32), ("b", 2u32), ("c", 3u32)]);
+	map.truncate(2);
+	assert_eq!(r#"{"a": 1, "b": 2}"#, map.to_diagnostic());
+	assert_eq!(vec![0xA2], map.encode()[..1].to_vec());
+
+	// Other variants are left alone, or rejected by the try_ forms
+	let mut text = Value::text("abc");
+	text.truncate(1);
+	text.retain_elements(|_| false);
+	text.retain_entries(|_, _| false);
+	assert_eq!(Value::text("abc"), text);
+	assert!(text.try_truncate(1).is_err());
+	assert!(text.try_retain_elements(|_| false).is_err());
+	assert!(text.try_retain_entries(|_, _| false).is_err());
+	assert!(map.try_retain_elements(|_| true).is_err());
+	assert!(long.try_retain_entries(|_, _| true).is_err());
+}
+
+#[test]
+fn stringref_test() {
+	fn text(s: &str) -> Value { Value::text(s) }
+	let sref = |i: u64| Value::Tag(25, Box::new(Value::Unsigned(i)));
+	let namespace = |v: Value| Value::Tag(256, Box::new(v));
+
+	// First example from the stringref specification: only strings long enough for the current
+	// table size are entered, so the second "rrr" is repeated in full
+	let words: Vec<&str> = vec![
+		"1", "222", "333", "4", "555", "666", "777", "888", "999", "aaa", "bbb", "ccc", "ddd", "eee", "fff", "ggg",
+		"hhh", "iii", "jjj", "kkk", "lll", "mmm", "nnn", "ooo", "ppp", "qqq", "rrr", "333", "ssss", "qqq", "rrr", "ssss",
+	];
+	let plain = Value::array(words.iter().cloned());
+	let mut compressed: Vec<Value> = words[..27].iter().map(|w| text(w)).collect();
+	compressed.extend(vec![sref(1), text("ssss"), sref(23), text("rrr"), sref(24)]);
+	let compressed = namespace(Value::Array(compressed)).encode();
+	assert_eq!(plain, cborg::decode_with_stringrefs(&compressed).unwrap());
+	assert_eq!(compressed, cborg::encode_with_stringrefs(&plain));
+
+	// Second example: map keys are strings like any other
+	let record = |name: &str, count: u32| {
+		vec![
+			("name", text(name)),
+			("count", Value::from(count)),
+			("rank", Value::Unsigned(4)),
+		]
+	};
+	let plain = Value::array(vec![
+		Value::map(record("Cocktail", 417)),
+		Value::map(vec![
+			("rank", Value::Unsigned(4)),
+			("count", Value::Unsigned(312)),
+			("name", text("Bath")),
+		]),
+		Value::map(vec![
+			("count", Value::Unsigned(691)),
+			("name", text("Food")),
+			("rank", Value::Unsigned(4)),
+		]),
+	]);
+	let compressed = namespace(Value::array(vec![
+		Value::map(record("Cocktail", 417)),
+		Value::map(vec![
+			(sref(3), Value::Unsigned(4)),
+			(sref(2), Value::Unsigned(312)),
+			(sref(0), text("Bath")),
+		]),
+		Value::map(vec![
+			(sref(2), Value::Unsigned(691)),
+			(sref(0), text("Food")),
+			(sref(3), Value::Unsigned(4)),
+		]),
+	]))
+	.encode();
+	assert_eq!(compressed, cborg::encode_with_stringrefs(&plain));
+	let expanded = cborg::decode_with_stringrefs(&compressed).unwrap();
+	assert_eq!(cborg::decode(plain.encode().iter()).unwrap(), expanded);
+
+	// Byte and text strings share a table but keep their types; nested namespaces start afresh
+	let plain = Value::array(vec![
+		Value::bytes(&b"abc"[..]),
+		text("abc"),
+		Value::bytes(&b"abc"[..]),
+		namespace(Value::array(vec!["abc", "abc"])),
+		text("abc"),
+	]);
+	let compressed = cborg::encode_with_stringrefs(&plain);
+	let expanded = cborg::decode_with_stringrefs(&compressed).unwrap();
+	let plain_expanded = Value::array(vec![
+		Value::bytes(&b"abc"[..]),
+		text("abc"),
+		Value::bytes(&b"abc"[..]),
+		Value::array(vec!["abc", "abc"]),
+		text("abc"),
+	]);
+	assert_eq!(plain_expanded, expanded);
+	assert!(compressed.len() < plain.encode().len() + 3);
+
+	// Round trip over the fixture
+	let fixture = cborg::decode(TEST_DATA_DEFINITE.iter()).unwrap();
+	let doubled = Value::array(vec![fixture.clone(), fixture.clone()]);
+	let compressed = cborg::encode_with_stringrefs(&doubled);
+	assert!(compressed.len() < doubled.encode().len() - 300);
+	assert_eq!(doubled, cborg::decode_with_stringrefs(&compressed).unwrap());
+
+	// Without expansion the tags are kept
+	let compressed = cborg::encode_with_stringrefs(&Value::array(vec!["abc", "defg", "defg"]));
+	let v = cborg::decode(compressed.iter()).unwrap();
+	assert_eq!(r#"256(["abc", "defg", 25(1)])"#, v.to_diagnostic());
+
+	// Out of range and out of namespace references
+	assert!(cborg::decode_with_stringrefs(&namespace(Value::array(vec![text("abc"), sref(1)])).encode()).is_err());
+	assert!(cborg::decode_with_stringrefs(&namespace(Value::array(vec![text("ab"), sref(0)])).encode()).is_err());
+	assert!(cborg::decode_with_stringrefs(&Value::array(vec![text("abc"), sref(0)]).encode()).is_err());
+}
+
+#[cfg(feature = "unicode-normalization")]
+#[test]
+fn nfc_test() {
+	let composed = "caf\u{e9}";
+	let decomposed = "cafe\u{301}";
+	assert_ne!(composed, decomposed);
+
+	let doc = Value::map(vec![
+		(Value::text("name"), Value::text(composed)),
+		(Value::text(decomposed), Value::Unsigned(1)),
+		(
+			Value::text("tags"),
+			Value::array(vec![
+				Value::text("ok"),
+				Value::Tag(32, Box::new(Value::text(decomposed))),
+			]),
+		),
+	]);
+	let violations: Vec<String> = doc.nfc_violations().iter().map(|p| p.to_string()).collect();
+	assert_eq!(vec!["$.\"cafe\u{301}\"", "$.tags[1]"], violations);
+	assert!(doc.check_nfc().is_err());
+	let err = format!("{:?}", doc.check_nfc().unwrap_err());
+	assert!(err.contains("cafe"), "{}", err);
+
+	assert!(Value::text(composed).check_nfc().is_ok());
+	assert!(Value::text(decomposed).check_nfc().is_err());
+
+	let ascii = cborg::decode(TEST_DATA_INDEFINITE.iter()).unwrap();
+	let before = ascii.clone();
+	assert!(ascii.check_nfc().is_ok());
+	assert_eq!(before, ascii);
+	assert!(cborg::decode(TEST_DATA_DEFINITE.iter()).unwrap().check_nfc().is_ok());
+}
+
+#[test]
+fn ensure_path_test() {
+	use cborg::path::PathSegment;
+
+	let key = |s: &str| PathSegment::Key(Value::text(s));
+	let mut doc = Value::Map(Map::new());
+	*doc.ensure_path(&[key("a"), key("b"), key("c")]).unwrap() = Value::Unsigned(1);
+	assert_eq!(r#"{"a": {"b": {"c": 1}}}"#, doc.to_diagnostic());
+
+	// Existing nodes are reused and an index equal to the length appends
+	*doc
+		.ensure_path(&[key("a"), key("list"), PathSegment::Index(0)])
+		.unwrap() = Value::Unsigned(2);
+	*doc
+		.ensure_path(&[key("a"), key("list"), PathSegment::Index(1)])
+		.unwrap() = Value::Unsigned(3);
+	*doc.ensure_pointer("$.a.list[0]").unwrap() = Value::Unsigned(4);
+	*doc.ensure_pointer("$.a.b.d").unwrap() = Value::text("x");
+	assert_eq!(
+		r#"{"a": {"b": {"c": 1, "d": "x"}, "list": [4, 3]}}"#,
+		doc.to_diagnostic()
+	);
+
+	let err = doc
+		.ensure_path(&[key("a"), key("list"), PathSegment::Index(5)])
+		.unwrap_err();
+	assert!(format!("{:?}", err).contains("[5] at $.a.list"), "{:?}", err);
+
+	let err = doc.ensure_pointer("$.a.b.c.e").unwrap_err();
+	let msg = format!("{:?}", err);
+	assert!(msg.contains(".e at $.a.b.c"), "{}", msg);
+	assert!(msg.contains("expected a map but found an integer"), "{}", msg);
+	assert!(doc.ensure_pointer("$.a[0]").is_err());
+	assert!(doc.ensure_pointer("$.a[*]").is_err());
+	assert_eq!(
+		r#"{"a": {"b": {"c": 1, "d": "x"}, "list": [4, 3]}}"#,
+		doc.to_diagnostic()
+	);
+}
+
+#[test]
+fn dyn_collection_test() {
+	use cborg::FromValue;
+
+	let mut prices = HashMap::new();
+	prices.insert("apple".to_string(), 3u32);
+	let row: Vec<Box<dyn ToValue>> = vec![
+		Box::new(42u64),
+		Box::new("cell"),
+		Box::new(2.5f64),
+		Box::new(prices.clone()),
+	];
+	let bytes = cborg::encode_ref(&row);
+	let decoded = cborg::decode(bytes.iter()).unwrap();
+	let items = decoded.get_array().unwrap();
+	assert_eq!(4, items.len());
+	assert_eq!(Value::Unsigned(42), items[0]);
+	assert_eq!(Value::text("cell"), items[1]);
+	assert_eq!(Value::Float(2.5), items[2]);
+	assert_eq!(Some(prices), HashMap::<String, u32>::from_ref(&items[3]));
+
+	let borrowed: [&dyn ToValue; 3] = [&42u64, &"cell", &2.5f64];
+	assert_eq!(
+		decoded.get_array().unwrap()[..3].to_vec(),
+		Value::array_dyn(&borrowed).get_array().unwrap()
+	);
+	assert_eq!(Value::array_dyn(&borrowed), borrowed[..].to_value());
+	assert_eq!(cborg::encode_dyn(&row), bytes);
+}
+
+#[test]
+fn mime_test() {
+	let msg = "From: alice@example.com\r\nSubject: hi\r\n\r\nHello\r\n";
+	let v = Value::mime(msg);
+	let bytes = v.encode();
+	assert_eq!(&[0xD8, 0x24], &bytes[..2]);
+	let decoded = cborg::decode_strict(&bytes).unwrap();
+	assert_eq!(Some(msg), decoded.as_mime_str());
+	assert_eq!(v, decoded);
+	assert_eq!(None, Value::text(msg).as_mime_str());
+
+	// Tag 36 over a byte string is only rejected by the strict decoder
+	let mut bad = vec![0x82, 0x01, 0xD8, 0x24, 0x42];
+	bad.extend_from_slice(b"hi");
+	let lenient = cborg::decode_slice(&bad).unwrap();
+	assert_eq!(None, lenient.get_array().unwrap()[1].as_mime_str());
+	let err = format!("{:?}", cborg::decode_strict(&bad).unwrap_err());
+	assert!(err.contains("tag 36 at $[1] must contain a text string"), "{}", err);
+}
+
+#[cfg(feature = "mime")]
+#[test]
+fn mime_headers_test() {
+	let msg = concat!(
+		"Received: from mail.example.com\r\n",
+		"\tby mx.example.org;\r\n",
+		"    Tue, 1 Jul 2003 10:52:37 +0200\r\n",
+		"Subject: Quarterly\r\n",
+		"  report\r\n",
+		"Content-Type: text/plain; charset=utf-8\r\n",
+		"\r\n",
+		"X-Not-A-Header: body\r\n",
+	);
+	let pair = |n: &str, v: &str| (n.to_string(), v.to_string());
+	let expected = vec![
+		pair(
+			"Received",
+			"from mail.example.com by mx.example.org; Tue, 1 Jul 2003 10:52:37 +0200",
+		),
+		pair("Subject", "Quarterly report"),
+		pair("Content-Type", "text/plain; charset=utf-8"),
+	];
+	assert_eq!(expected, cborg::mime_headers(msg));
+	assert_eq!(Some(expected), Value::mime(msg).mime_headers());
+	assert_eq!(None, Value::text(msg).mime_headers());
+	assert!(cborg::mime_headers("\r\nSubject: late\r\n").is_empty());
+}
+
+#[test]
+fn tag_test() {
+	use cborg::FromValue;
+
+	let mut bytes = vec![0xC0, 0x74];
+	bytes.extend_from_slice(b"2013-03-21T20:04:00Z");
+	let v = cborg::decode(bytes.iter()).unwrap();
+	assert_eq!(Value::Tag(0, Box::new(Value::text("2013-03-21T20:04:00Z"))), v);
+	assert_eq!(6, v.major());
+	assert_eq!(bytes, v.encode());
+	let s: String = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!("2013-03-21T20:04:00Z", s);
+
+	// Conversions look through tags at any depth
+	let bytes = [0x82, 0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0, 0xC1, 0xC1, 0x01];
+	let times: Vec<u64> = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!(vec![1_363_896_240, 1], times);
+	let v = cborg::decode(bytes.iter()).unwrap();
+	assert_eq!(Some(vec![1_363_896_240u64, 1]), Vec::<u64>::from_ref(&v));
+	assert_eq!(bytes.to_vec(), v.encode());
+	assert_ne!(v, Value::array(vec![1_363_896_240u64, 1]));
+}
+
+#[test]
+fn multi_byte_tag_test() {
+	let cases: [(&[u8], u64); 4] = [
+		(&[0xD8, 0x20], 32),
+		(&[0xD9, 0x03, 0xE9], 1001),
+		(&[0xDA, 0x00, 0x01, 0x00, 0x00], 65_536),
+		(
+			&[0xDB, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+			0x0102_0304_0506_0708,
+		),
+	];
+	for (header, tag) in cases.iter() {
+		let mut bytes = header.to_vec();
+		bytes.extend_from_slice(&[0x82, 0x61, b'a', 0x07]);
+		let v = cborg::decode(bytes.iter()).unwrap();
+		assert_eq!(
+			Value::Tag(*tag, Box::new(Value::array(vec![Value::text("a"), Value::Unsigned(7)]))),
+			v
+		);
+		assert_eq!(bytes, v.encode());
+
+		// A truncated tag argument is reported as running out of bytes, not misread as content
+		for end in 1..header.len() {
+			let err = cborg::decode(header[..end].iter()).unwrap_err();
+			assert_eq!(&ErrorKind::InsufficientBytes, err.kind(), "{:02X?}", &header[..end]);
+		}
+		let err = cborg::decode(header.iter()).unwrap_err();
+		assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	}
+}
+
+#[test]
+fn datetime_string_test() {
+	use cborg::ExtendedTime;
+	use cborg::FromValue;
+
+	let t = |s: &str| ExtendedTime::from_rfc3339(s).unwrap();
+	let utc = ExtendedTime::new(1_363_896_240, 0).unwrap();
+	assert_eq!(utc, t("2013-03-21T20:04:00Z"));
+	assert_eq!(utc, t("2013-03-21T22:04:00+02:00"));
+	assert_eq!(utc, t("2013-03-21T14:34:00-05:30"));
+	assert_eq!(utc, t("2013-03-21t20:04:00z"));
+	assert_eq!(
+		ExtendedTime::new(1_363_896_240, 500_000_000).unwrap(),
+		t("2013-03-21T20:04:00.5Z")
+	);
+	assert_eq!(
+		ExtendedTime::new(1_363_896_240, 123_456_789).unwrap(),
+		t("2013-03-21T21:04:00.1234567891+01:00")
+	);
+	assert_eq!(
+		ExtendedTime::new(-1, 999_000_000).unwrap(),
+		t("1969-12-31T23:59:59.999Z")
+	);
+	assert_eq!(t("1998-12-31T23:59:60Z"), t("1999-01-01T00:00:00Z"));
+	assert_eq!(t("2000-02-29T00:00:00Z").seconds, 951_782_400);
+	assert_eq!(
+		"1969-12-31T23:59:59.999Z",
+		t("1969-12-31T23:59:59.999Z").to_rfc3339().unwrap()
+	);
+	assert_eq!(
+		"2013-03-21T20:04:00Z",
+		t("2013-03-21T15:04:00-05:00").to_rfc3339().unwrap()
+	);
+	for bad in &[
+		"2013-03-21",
+		"2013-03-21T20:04:00",
+		"2013-02-29T00:00:00Z",
+		"2013-13-01T00:00:00Z",
+		"2013-03-21T24:00:00Z",
+		"2013-03-21T20:04:00.Z",
+		"2013-03-21T20:04:00+0200",
+		"2013-03-21T20:04:00Z ",
+	] {
+		assert!(ExtendedTime::from_rfc3339(bad).is_err(), "{}", bad);
+		assert!(Value::from_rfc3339(bad).is_err(), "{}", bad);
+	}
+
+	let v = Value::from_rfc3339("2013-03-21T20:04:00.5+01:00").unwrap();
+	let bytes = v.encode();
+	assert_eq!(&[0xC0, 0x78, 0x1B], &bytes[..3]);
+	let decoded = cborg::decode_strict(&bytes).unwrap();
+	assert_eq!(
+		ExtendedTime::new(1_363_892_640, 500_000_000).unwrap(),
+		decoded.get_datetime().unwrap()
+	);
+	assert_eq!(decoded.get_datetime().ok(), ExtendedTime::from_ref(&decoded));
+	assert!(Value::text("2013-03-21T20:04:00Z").get_datetime().is_err());
+
+	// An invalid date under tag 0 is passed through by the lenient decoder only
+	let mut bytes = vec![0xC0, 0x6A];
+	bytes.extend_from_slice(b"yesterday!");
+	let lenient = cborg::decode_slice(&bytes).unwrap();
+	assert!(lenient.get_datetime().is_err());
+	assert_eq!(None, ExtendedTime::from_ref(&lenient));
+	let err = format!("{:?}", cborg::decode_strict(&bytes).unwrap_err());
+	assert!(
+		err.contains("tag 0 at $ must contain an RFC 3339 date/time string"),
+		"{}",
+		err
+	);
+}
+
+#[test]
+fn system_time_test() {
+	use std::time::Duration;
+	use std::time::SystemTime;
+	use std::time::UNIX_EPOCH;
+
+	let now = SystemTime::now();
+	let bytes = cborg::encode(now);
+	assert_eq!(0xC1, bytes[0]);
+	let decoded: SystemTime = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	let since = now.duration_since(UNIX_EPOCH).unwrap();
+	assert_eq!(UNIX_EPOCH + Duration::from_secs(since.as_secs()), decoded);
+
+	// Before 1970, the fraction is dropped towards the past
+	let before = UNIX_EPOCH - Duration::from_millis(1500);
+	assert_eq!(Value::Tag(1, Box::new(Value::Negative(-2))), before.to_value());
+	let decoded: SystemTime = cborg::decode_to(cborg::encode(before).iter()).unwrap().unwrap();
+	assert_eq!(UNIX_EPOCH - Duration::from_secs(2), decoded);
+
+	// Float payloads keep their fraction
+	let v = Value::Tag(1, Box::new(Value::Float(-1.5)));
+	assert_eq!(Some(before), v.to_type());
+	let v = Value::Tag(1, Box::new(Value::Float(1_363_896_240.5)));
+	assert_eq!(Some(UNIX_EPOCH + Duration::from_millis(1_363_896_240_500)), v.to_type());
+
+	// Out of range or not a time
+	let v: Option<SystemTime> = Value::Tag(1, Box::new(Value::Unsigned(u64::MAX))).to_type();
+	assert_eq!(None, v);
+	let v: Option<SystemTime> = Value::Tag(1, Box::new(Value::Float(f64::INFINITY))).to_type();
+	assert_eq!(None, v);
+	let v: Option<SystemTime> = Value::Unsigned(5).to_type();
+	assert_eq!(None, v);
+}
+
+#[test]
+fn bignum_decode_test() {
+	use cborg::FromValue;
+
+	// u64::MAX + 1
+	let bytes = [0xC2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+	let n: u128 = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!(u128::from(u64::MAX) + 1, n);
+	let n: i128 = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!(i128::from(u64::MAX) + 1, n);
+
+	// -(2^80) is -1 - (2^80 - 1)
+	let mut bytes = vec![0xC3, 0x4A];
+	bytes.extend_from_slice(&[0xFF; 10]);
+	let n: i128 = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!(-(1i128 << 80), n);
+	assert_eq!(None, cborg::decode_to::<u128, _>(bytes.iter()).unwrap());
+
+	// Leading zeros are tolerated, even past 16 bytes
+	let mut padded = vec![0u8; 20];
+	padded[19] = 7;
+	assert_eq!(
+		Some(7u128),
+		u128::from_ref(&Value::Tag(2, Box::new(Value::ByteString(padded))))
+	);
+	assert_eq!(
+		Some(0i128),
+		i128::from_ref(&Value::Tag(2, Box::new(Value::ByteString(vec![]))))
+	);
+	assert_eq!(
+		Some(-1i128),
+		i128::from_ref(&Value::Tag(3, Box::new(Value::ByteString(vec![0]))))
+	);
+
+	// Plain integers
+	assert_eq!(Some(5u128), u128::from_ref(&Value::Unsigned(5)));
+	assert_eq!(None, u128::from_ref(&Value::Negative(-5)));
+	assert_eq!(Some(-5i128), i128::from_ref(&Value::Negative(-5)));
+
+	// Overflow
+	assert_eq!(
+		None,
+		u128::from_ref(&Value::Tag(2, Box::new(Value::ByteString(vec![1; 17]))))
+	);
+	assert_eq!(
+		Some(u128::MAX),
+		u128::from_ref(&Value::Tag(2, Box::new(Value::ByteString(vec![0xFF; 16]))))
+	);
+	assert_eq!(
+		None,
+		i128::from_ref(&Value::Tag(2, Box::new(Value::ByteString(vec![0xFF; 16]))))
+	);
+	assert_eq!(
+		Some(i128::MIN),
+		i128::from_ref(&Value::Tag(
+			3,
+			Box::new(Value::ByteString(i128::MAX.to_be_bytes().to_vec()))
+		))
+	);
+	assert_eq!(None, i128::from_ref(&Value::Tag(2, Box::new(Value::text("1")))));
+	assert!(cborg::decode_strict(&[0xC2, 0x61, b'1']).is_err());
+}
+
+#[test]
+fn bignum_encode_test() {
+	let bytes = cborg::encode(u128::MAX);
+	let mut expected = vec![0xC2, 0x50];
+	expected.extend_from_slice(&[0xFF; 16]);
+	assert_eq!(expected, bytes);
+	assert_eq!(Some(u128::MAX), cborg::decode_to(bytes.iter()).unwrap());
+
+	// Values that fit the integer variants stay compact
+	assert_eq!(vec![0x18, 0x64], cborg::encode(100u128));
+	assert_eq!(vec![0x38, 0x63], cborg::encode(-100i128));
+	assert_eq!(Value::Unsigned(u64::MAX), Value::from(u128::from(u64::MAX)));
+	assert_eq!(Value::Negative(i64::MIN), Value::from(i128::from(i64::MIN)));
+
+	let big = u128::from(u64::MAX) + 1;
+	assert_eq!(vec![0xC2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0], cborg::encode(big));
+	assert_eq!(cborg::encode(big), cborg::encode(big as i128));
+	assert_eq!(
+		vec![0xC3, 0x48, 0x80, 0, 0, 0, 0, 0, 0, 0],
+		cborg::encode(i128::from(i64::MIN) - 1)
+	);
+
+	for n in [
+		i128::MIN,
+		i128::MIN + 1,
+		-(1 << 80),
+		i128::from(i64::MIN) - 1,
+		-2,
+		-1,
+		0,
+		1,
+		1 << 100,
+		i128::MAX,
+	]
+	.iter()
+	{
+		assert_eq!(Some(*n), cborg::decode_to(cborg::encode(*n).iter()).unwrap(), "{}", n);
+	}
+	assert_eq!(Some(big), cborg::decode_to(cborg::encode_owned(big).iter()).unwrap());
+}
+
+#[test]
+fn decimal_test() {
+	use cborg::Decimal;
+	use cborg::FromValue;
+
+	// RFC 8949 example: 273.15
+	let bytes = [0xC4, 0x82, 0x21, 0x19, 0x6A, 0xB3];
+	let v = cborg::decode_strict(&bytes).unwrap();
+	let d = Decimal {
+		exponent: -2,
+		mantissa: 27315,
+	};
+	assert_eq!(Some(d), v.get_decimal());
+	assert_eq!(Some(d), cborg::decode_to(bytes.iter()).unwrap());
+	assert_eq!(bytes.to_vec(), cborg::encode(d));
+	assert_eq!(273.15, d.to_f64());
+
+	// Large mantissas are bignums
+	let big = Decimal {
+		exponent: 3,
+		mantissa: -(1 << 100),
+	};
+	let bytes = cborg::encode(big);
+	assert_eq!(&[0xC4, 0x82, 0x03, 0xC3], &bytes[..4]);
+	assert_eq!(Some(big), cborg::decode_to(bytes.iter()).unwrap());
+	assert_eq!(-1.2676506002282294e33, big.to_f64());
+	assert_eq!(
+		f64::INFINITY,
+		Decimal {
+			exponent: 400,
+			mantissa: 1
+		}
+		.to_f64()
+	);
+	assert_eq!(
+		0.0,
+		Decimal {
+			exponent: -400,
+			mantissa: 1
+		}
+		.to_f64()
+	);
+
+	// Malformed payloads
+	let tagged = |content: Value| Value::Tag(4, Box::new(content));
+	let malformed = [
+		tagged(Value::array(vec![Value::Negative(-2)])),
+		tagged(Value::array(vec![
+			Value::Negative(-2),
+			Value::Unsigned(1),
+			Value::Unsigned(1),
+		])),
+		tagged(Value::array(vec![Value::Float(-2.0), Value::Unsigned(1)])),
+		tagged(Value::array(vec![Value::Negative(-2), Value::text("1")])),
+		tagged(Value::text("2.73")),
+	];
+	for v in malformed.iter() {
+		assert_eq!(None, v.get_decimal(), "{}", v.to_diagnostic());
+		assert!(cborg::decode_strict(&v.encode()).is_err(), "{}", v.to_diagnostic());
+	}
+	assert_eq!(
+		None,
+		Decimal::from_ref(&Value::array(vec![Value::Negative(-2), Value::Unsigned(1)]))
+	);
+
+	// Valid, but too big for Decimal
+	let huge = tagged(Value::array(vec![
+		Value::Unsigned(1),
+		Value::Tag(2, Box::new(Value::ByteString(vec![1; 17]))),
+	]));
+	assert_eq!(None, huge.get_decimal());
+	assert!(cborg::decode_strict(&huge.encode()).is_ok());
+}
+
+#[test]
+fn bigfloat_test() {
+	use cborg::Bigfloat;
+
+	// RFC 8949 example: 1.5 is [-1, 3]
+	let b = Bigfloat {
+		exponent: -1,
+		mantissa: 3,
+	};
+	let bytes = cborg::encode(b);
+	assert_eq!(vec![0xC5, 0x82, 0x20, 0x03], bytes);
+	let v = Value::Tag(5, Box::new(Value::array(vec![Value::Negative(-1), Value::Unsigned(3)])));
+	assert_eq!(Some(b), v.get_bigfloat());
+	assert_eq!(1.5, b.to_f64());
+	assert_eq!(None, v.get_decimal());
+
+	let big = Bigfloat {
+		exponent: 2,
+		mantissa: i128::MIN,
+	};
+	let bytes = cborg::encode(big);
+	assert_eq!(Some(big), cborg::decode_to(bytes.iter()).unwrap());
+	assert_eq!(-(2f64.powi(129)), big.to_f64());
+
+	let f = |exponent: i64, mantissa: i128| Bigfloat { exponent, mantissa }.to_f64();
+	assert_eq!(f64::MAX, f(971, (1 << 53) - 1));
+	assert_eq!(f64::INFINITY, f(972, (1 << 53) - 1));
+	assert_eq!(f64::NEG_INFINITY, f(i64::MAX, -1));
+	assert_eq!(f64::MIN_POSITIVE, f(-1022, 1));
+	assert_eq!(5e-324, f(-1074, 1));
+	assert_eq!(0.0, f(-1076, 1));
+	assert_eq!(0.0, f(i64::MIN, i128::MAX));
+	assert_eq!(0.0, f(i64::MAX, 0));
+	assert_eq!(2f64.powi(-1000), f(-1100, 1 << 100));
+
+	let malformed = Value::Tag(5, Box::new(Value::array(vec![Value::Unsigned(1)])));
+	assert_eq!(None, malformed.get_bigfloat());
+	assert!(cborg::decode_strict(&malformed.encode()).is_err());
+}
+
+#[test]
+fn text_hint_test() {
+	use cborg::TextHint;
+
+	let bytes = Value::bytes(vec![0xFB, 0xFF, 0x01]);
+	let tagged = |tag: u64, v: Value| Value::Tag(tag, Box::new(v));
+
+	let url = tagged(21, bytes.clone());
+	let b64 = tagged(22, Value::array(vec![bytes.clone(), Value::bytes(b"hi".to_vec())]));
+	let hex = tagged(23, bytes.clone());
+	assert_eq!(Some(TextHint::Base64Url), url.to_text_hint());
+	assert_eq!(Some(TextHint::Base64), b64.to_text_hint());
+	assert_eq!(Some(TextHint::Base16), hex.to_text_hint());
+	assert_eq!(None, bytes.to_text_hint());
+	assert_eq!(None, tagged(24, bytes.clone()).to_text_hint());
+	assert_eq!(Some(TextHint::Base16), TextHint::from_tag(23));
+	assert_eq!(22, TextHint::Base64.tag());
+
+	// Diagnostic notation
+	assert_eq!("h'fbff01'", bytes.to_diagnostic());
+	assert_eq!("21(b64'-_8B')", url.to_diagnostic());
+	assert_eq!("22([b64'+/8B', b64'aGk='])", b64.to_diagnostic());
+	assert_eq!("23(h'fbff01')", hex.to_diagnostic());
+
+	// The innermost hint applies
+	let nested = tagged(23, Value::array(vec![bytes.clone(), tagged(22, bytes.clone())]));
+	assert_eq!("23([h'fbff01', 22(b64'+/8B')])", nested.to_diagnostic());
+
+	// JSON
+	assert_eq!(r#""-_8B""#, bytes.to_canonical_json().unwrap());
+	assert_eq!(r#""-_8B""#, url.to_canonical_json().unwrap());
+	assert_eq!(r#"["+/8B","aGk="]"#, b64.to_canonical_json().unwrap());
+	assert_eq!(r#""fbff01""#, hex.to_canonical_json().unwrap());
+	assert_eq!(r#"["fbff01","+/8B"]"#, nested.to_canonical_json().unwrap());
+	let keyed = tagged(23, Value::map(vec![(bytes.clone(), Value::Unsigned(1))]));
+	assert_eq!(r#"{"fbff01":1}"#, keyed.to_canonical_json().unwrap());
+
+	// Display
+	let mut out = Vec::new();
+	cborg::value::types::print_cbor(&hex, &mut out).unwrap();
+	assert_eq!("23(fbff01)", String::from_utf8(out).unwrap());
+	let mut out = Vec::new();
+	cborg::value::types::print_cbor(&bytes, &mut out).unwrap();
+	assert_eq!("[251, 255, 1]", String::from_utf8(out).unwrap());
+	let colored = b64.display_colored().to_string();
+	assert!(colored.contains("+/8B") && colored.contains("aGk="), "{}", colored);
+}
+
+#[test]
+fn embedded_test() {
+	let inner = Value::map(vec![
+		(Value::text("sig"), Value::bytes(vec![1, 2, 3])),
+		(
+			Value::text("claims"),
+			Value::map(vec![(
+				Value::Unsigned(1),
+				Value::array(vec![Value::text("a"), Value::Negative(-5)]),
+			)]),
+		),
+	]);
+	let wrapped = Value::encode_embedded(&inner);
+	let outer = Value::map(vec![(Value::text("payload"), wrapped.clone())]);
+	let bytes = outer.encode();
+	let decoded = cborg::decode_strict(&bytes).unwrap();
+	assert_eq!(outer, decoded);
+	let payload = &decoded.get_map().unwrap()[0].val;
+	assert_eq!(inner, payload.decode_embedded(false).unwrap());
+
+	// Nested embedding
+	let twice = Value::encode_embedded(&wrapped);
+	assert_eq!(
+		inner,
+		twice.decode_embedded(false).unwrap().decode_embedded(false).unwrap()
+	);
+
+	// Untagged byte strings only with the flag
+	let bare = Value::bytes(inner.encode());
+	assert!(bare.decode_embedded(false).is_err());
+	assert_eq!(inner, bare.decode_embedded(true).unwrap());
+
+	// Errors name the embedded item
+	let truncated = Value::Tag(24, Box::new(Value::bytes(vec![0x82, 0x01])));
+	let err = truncated.decode_embedded(false).unwrap_err();
+	assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	assert_eq!(Some("embedded data item (tag 24)"), err.context());
+	// The inner error is kept as is, source included
+	let bad_utf8 = Value::Tag(24, Box::new(Value::bytes(vec![0x62, 0xFF, 0xFE])));
+	let err = bad_utf8.decode_embedded(false).unwrap_err();
+	assert_eq!(&ErrorKind::InvalidUtf8, err.kind());
+	assert!(std::error::Error::source(&err).is_some());
+	assert_eq!(Some("embedded data item (tag 24)"), err.context());
+	let trailing = Value::Tag(24, Box::new(Value::bytes(vec![0x01, 0x02])));
+	assert!(format!("{:?}", trailing.decode_embedded(false).unwrap_err()).contains("followed by 1 more bytes"));
+	assert!(Value::Tag(24, Box::new(Value::text("x")))
+		.decode_embedded(true)
+		.is_err());
+	assert!(cborg::decode_strict(&Value::Tag(24, Box::new(Value::text("x"))).encode()).is_err());
+}
+
+#[test]
+fn uri_test() {
+	let v = Value::uri("https://example.com/a%20b?q=1#top");
+	let bytes = v.encode();
+	assert_eq!(&[0xD8, 0x20], &bytes[..2]);
+	let decoded = cborg::decode_strict(&bytes).unwrap();
+	assert_eq!(Some("https://example.com/a%20b?q=1#top"), decoded.get_uri());
+	assert_eq!(None, Value::text("https://example.com").get_uri());
+
+	for good in &[
+		"urn:isbn:0451450523",
+		"mailto:a@example.com",
+		"tag:example.com,2005:x",
+		"a+b-c.d:",
+	] {
+		assert!(cborg::decode_strict(&Value::uri(good).encode()).is_ok(), "{}", good);
+	}
+	for bad in &[
+		"not a uri",
+		"/relative/path",
+		"1http://x",
+		"http://x/%zz",
+		"http://x/#a#b",
+		"http://x/\u{e9}",
+	] {
+		let bytes = Value::uri(bad).encode();
+		assert!(cborg::decode_slice(&bytes).is_ok(), "{}", bad);
+		let err = format!("{:?}", cborg::decode_strict(&bytes).unwrap_err());
+		assert!(err.contains("tag 32 at $ must contain a URI"), "{}: {}", bad, err);
+	}
+	assert!(cborg::decode_strict(&Value::Tag(32, Box::new(Value::Unsigned(1))).encode()).is_err());
+}
+
+#[cfg(feature = "url")]
+#[test]
+fn url_test() {
+	use cborg::FromValue;
+
+	let url = url::Url::parse("https://example.com/path?x=1").unwrap();
+	let v = url.to_value();
+	assert_eq!(Some("https://example.com/path?x=1"), v.get_uri());
+	assert_eq!(Some(url.clone()), url::Url::from_ref(&v));
+	let decoded: url::Url = cborg::decode_to(cborg::encode(url.clone()).iter()).unwrap().unwrap();
+	assert_eq!(url, decoded);
+	assert!(Value::uri("http://[::1").to_url().is_err());
+	assert!(Value::text("https://example.com").to_url().is_err());
+}
+
+#[test]
+fn uuid_tag_test() {
+	let mut bytes = vec![0xD8, 0x25, 0x50];
+	bytes.extend_from_slice(&[0xAB; 16]);
+	assert!(cborg::decode_strict(&bytes).is_ok());
+	let short = Value::Tag(37, Box::new(Value::bytes(vec![0xAB; 15])));
+	assert!(cborg::decode_strict(&short.encode()).is_err());
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_test() {
+	use cborg::FromValue;
+	use uuid::Uuid;
+
+	let id = Uuid::parse_str("8c9f2d3e-1a2b-4c5d-9e8f-0a1b2c3d4e5f").unwrap();
+	let bytes = cborg::encode(id);
+	assert_eq!(&[0xD8, 0x25, 0x50], &bytes[..3]);
+	assert_eq!(id.as_bytes(), &bytes[3..]);
+	let decoded: Uuid = cborg::decode_to(bytes.iter()).unwrap().unwrap();
+	assert_eq!(id, decoded);
+
+	// Untagged forms
+	assert_eq!(Some(id), Uuid::from_ref(&Value::bytes(id.as_bytes().to_vec())));
+	assert_eq!(
+		Some(id),
+		Uuid::from_ref(&Value::text("8c9f2d3e-1a2b-4c5d-9e8f-0a1b2c3d4e5f"))
+	);
+	assert_eq!(None, Uuid::from_ref(&Value::text("8c9f2d3e1a2b4c5d9e8f0a1b2c3d4e5f")));
+	assert_eq!(
+		None,
+		Uuid::from_ref(&Value::text("8c9f2d3e-1a2b-4c5d-9e8f-0a1b2c3d4e5g"))
+	);
+
+	// Wrong lengths fail
+	assert_eq!(None, Uuid::from_ref(&Value::bytes(vec![1; 15])));
+	assert_eq!(
+		None,
+		Uuid::from_ref(&Value::Tag(37, Box::new(Value::bytes(vec![1; 17]))))
+	);
+	assert_eq!(
+		None,
+		Uuid::from_ref(&Value::Tag(
+			37,
+			Box::new(Value::text("8c9f2d3e-1a2b-4c5d-9e8f-0a1b2c3d4e5f"))
+		))
+	);
+
+	// A map keyed by UUIDs, mixing tagged and untagged keys
+	let other = Uuid::from_bytes([7; 16]);
+	let v = Value::map(vec![
+		(id.to_value(), Value::text("first")),
+		(Value::bytes(other.as_bytes().to_vec()), Value::text("second")),
+	]);
+	let map: HashMap<Uuid, String> = cborg::decode_to(v.encode().iter()).unwrap().unwrap();
+	assert_eq!(2, map.len());
+	assert_eq!("first", map[&id]);
+	assert_eq!("second", map[&other]);
+	let mut sorted: BTreeMap<Uuid, u32> = BTreeMap::new();
+	sorted.insert(id, 1);
+	sorted.insert(other, 2);
+	assert_eq!(
+		Some(sorted.clone()),
+		cborg::decode_to(cborg::encode_ref(&sorted).iter()).unwrap()
+	);
+}
+
+#[test]
+fn self_described_test() {
+	let map: HashMap<String, u32> = [("a".to_string(), 1u32)].iter().cloned().collect();
+	let plain = cborg::encode_ref(&map);
+	let described = cborg::encode_self_described(map.clone());
+	assert_eq!(&[0xD9, 0xD9, 0xF7], &described[..3]);
+	assert_eq!(plain, described[3..].to_vec());
+
+	// Decoding is the same with or without the tag
+	assert_eq!(Some(map.clone()), cborg::decode_to(plain.iter()).unwrap());
+	assert_eq!(Some(map.clone()), cborg::decode_to(described.iter()).unwrap());
+	assert_eq!(
+		cborg::decode_slice(&plain).unwrap(),
+		cborg::decode_slice(&described).unwrap()
+	);
+	let mut reused = Value::Simple(cborg::Simple::Null);
+	cborg::decode_reuse(&described, &mut reused).unwrap();
+	assert_eq!(cborg::decode_slice(&plain).unwrap(), reused);
+	let (lossy, issues) = cborg::decode_lossy(&described);
+	assert!(issues.is_empty());
+	assert_eq!(reused, lossy);
+
+	// Repeated leading tags are all skipped; nested ones are kept but convert through
+	let bytes = [0xD9, 0xD9, 0xF7, 0xD9, 0xD9, 0xF7, 0x82, 0xD9, 0xD9, 0xF7, 0x01, 0x02];
+	let v = cborg::decode_slice(&bytes).unwrap();
+	assert!(!v.is_self_described());
+	assert_eq!("[55799(1), 2]", v.to_diagnostic());
+	assert!(v.get_array().unwrap()[0].is_self_described());
+	assert_eq!(Some(vec![1u32, 2]), cborg::decode_to(bytes.iter()).unwrap());
+
+	let tagged = Value::Tag(55799, Box::new(Value::Tag(55799, Box::new(Value::Unsigned(3)))));
+	assert!(tagged.is_self_described());
+	assert_eq!(Value::Unsigned(3), tagged.strip_self_described());
+	assert_eq!(
+		Value::Tag(1, Box::new(Value::Unsigned(3))),
+		Value::Tag(1, Box::new(Value::Unsigned(3))).strip_self_described()
+	);
+}
+
+#[test]
+fn typed_array_test() {
+	use cborg::Endian;
+	use cborg::FromValue;
+
+	// uint16 big-endian (tag 65) and little-endian (tag 69)
+	let be = cborg::decode_slice(&[0xD8, 0x41, 0x44, 0x01, 0x02, 0x00, 0x03]).unwrap();
+	let le = cborg::decode_slice(&[0xD8, 0x45, 0x44, 0x02, 0x01, 0x03, 0x00]).unwrap();
+	assert_eq!("[258, 3]", be.expand_typed_array().unwrap().to_diagnostic());
+	assert_eq!("[258, 3]", le.expand_typed_array().unwrap().to_diagnostic());
+	assert_eq!(Some(vec![258u16, 3]), Vec::<u16>::from_ref(&le));
+	assert_eq!(
+		Some(vec![258u32, 3]),
+		cborg::decode_to(&[0xD8, 0x45, 0x44, 0x02, 0x01, 0x03, 0x00]).unwrap()
+	);
+
+	// Signed elements are sign-extended
+	let v = Value::Tag(77, Box::new(Value::ByteString(vec![0xFE, 0xFF, 0x05, 0x00])));
+	assert_eq!("[-2, 5]", v.expand_typed_array().unwrap().to_diagnostic());
+	let v = Value::Tag(72, Box::new(Value::ByteString(vec![0x80, 0x7F])));
+	assert_eq!("[-128, 127]", v.expand_typed_array().unwrap().to_diagnostic());
+
+	// Floats: f16 big-endian (80), f32 little-endian (85), f64 big-endian (82)
+	let v = Value::Tag(80, Box::new(Value::ByteString(vec![0x3C, 0x00, 0xC0, 0x00])));
+	assert_eq!(Some(vec![1.0f64, -2.0]), Vec::<f64>::from_ref(&v));
+	let v = Value::typed_array(&[1.5f32, -0.25], Endian::Little);
+	assert!(matches!(v, Value::Tag(85, _)));
+	assert_eq!(Some(vec![1.5f32, -0.25]), Vec::<f32>::from_ref(&v));
+	let v = Value::typed_array(&[0.1f64], Endian::Big);
+	assert_eq!(Some(vec![0.1f64]), Vec::<f64>::from_value(v));
+
+	// Encoding round-trips for every element type
+	for &endian in &[Endian::Big, Endian::Little] {
+		let v = Value::typed_array(&[1u16, 65535], endian);
+		assert_eq!(Some(vec![1u16, 65535]), Vec::<u16>::from_ref(&v));
+		let v = Value::typed_array(&[u64::MAX, 7], endian);
+		assert_eq!(Some(vec![u64::MAX, 7]), Vec::<u64>::from_ref(&v));
+		let v = Value::typed_array(&[i32::MIN, -1, 1], endian);
+		assert_eq!(Some(vec![i32::MIN, -1, 1]), Vec::<i32>::from_ref(&v));
+		let bytes = cborg::encode(v.clone());
+		assert_eq!(v, cborg::decode_slice(&bytes).unwrap());
+	}
+	// Single bytes have no byte order
+	assert_eq!(
+		Value::Tag(64, Box::new(Value::ByteString(vec![1, 2]))),
+		Value::typed_array(&[1u8, 2], Endian::Little)
+	);
+	let wide = Value::typed_array(&[1u16, 300], Endian::Big);
+	assert_eq!(None, Vec::<u8>::from_ref(&wide));
+	assert_eq!(
+		Some(vec![1u8, 2]),
+		Vec::<u8>::from_ref(&Value::typed_array(&[1u16, 2], Endian::Big))
+	);
+
+	// Lengths that are not a whole number of elements are rejected
+	let odd = Value::Tag(66, Box::new(Value::ByteString(vec![0, 0, 0, 1, 2, 3])));
+	let err = odd.expand_typed_array().unwrap_err();
+	assert!(format!("{:?}", err).contains("typed array (tag 66) of 6 bytes is not a whole number of 4-byte elements"));
+	assert_eq!(None, Vec::<u32>::from_ref(&odd));
+	assert!(cborg::decode_strict(&cborg::encode(odd.clone())).is_err());
+	assert!(cborg::decode_strict(&cborg::encode(Value::typed_array(&[1u32], Endian::Big))).is_ok());
+
+	// Reserved tag 76, 128-bit floats and non-typed-arrays
+	assert!(Value::Tag(76, Box::new(Value::ByteString(vec![1])))
+		.expand_typed_array()
+		.is_err());
+	assert!(Value::Tag(83, Box::new(Value::ByteString(vec![0; 16])))
+		.expand_typed_array()
+		.is_err());
+	assert!(Value::Tag(66, Box::new(Value::Utf8String("x".into())))
+		.expand_typed_array()
+		.is_err());
+	assert!(Value::ByteString(vec![1]).expand_typed_array().is_err());
+}
+
+#[test]
+fn tags_test() {
+	use cborg::tags;
+
+	let v = Value::tagged(tags::EPOCH, 1_600_000_000u64);
+	assert_eq!(Value::Tag(1, Box::new(Value::Unsigned(1_600_000_000))), v);
+	assert_eq!(Some((tags::EPOCH, &Value::Unsigned(1_600_000_000))), v.tag());
+	assert_eq!(&Value::Unsigned(1_600_000_000), v.untagged());
+
+	// Only one layer is peeled
+	let nested = Value::tagged(tags::SELF_DESCRIBED, Value::tagged(tags::URI, "a:b"));
+	assert_eq!(Some(tags::URI), nested.untagged().tag().map(|(t, _)| t));
+	assert_eq!(&Value::Utf8String("a:b".into()), nested.untagged().untagged());
+
+	// Untagged values have no tag and are their own content
+	let plain = Value::Unsigned(7);
+	assert_eq!(None, plain.tag());
+	assert_eq!(&plain, plain.untagged());
+
+	// The constructors for tagged types use the named tags
+	assert_eq!(Some(tags::REGEX), Value::regex("a+").tag().map(|(t, _)| t));
+	assert_eq!(Some(tags::MIME), Value::mime("x").tag().map(|(t, _)| t));
+	assert_eq!(Some(tags::URI), Value::uri("a:b").tag().map(|(t, _)| t));
+	assert_eq!(
+		Some(tags::EMBEDDED),
+		Value::encode_embedded(&plain).tag().map(|(t, _)| t)
+	);
+	assert!(tags::TYPED_ARRAYS.contains(&70));
+	assert_eq!(
+		[0xD9, 0xD9, 0xF7],
+		cborg::encode(Value::tagged(tags::SELF_DESCRIBED, 1u32))[..3]
+	);
+}
+
+#[test]
+fn unknown_tag_policy_test() {
+	use cborg::{DecodeOptions, TagPolicy};
+
+	// {"a": [100("x"), 32("a:b")], 1(1): 200(2)}
+	let bytes = [
+		0xA2, 0x61, 0x61, 0x82, 0xD8, 0x64, 0x61, 0x78, 0xD8, 0x20, 0x63, 0x61, 0x3A, 0x62, 0xC1, 0x01, 0xD8, 0xC8, 0x02,
+	];
+	let keep = cborg::decode_slice_with(&bytes, &DecodeOptions::new()).unwrap();
+	assert_eq!(r#"{"a": [100("x"), 32("a:b")], 1(1): 200(2)}"#, keep.to_diagnostic());
+	assert_eq!(cborg::decode_slice(&bytes).unwrap(), keep);
+	let opts = DecodeOptions::new().unknown_tags(TagPolicy::Keep);
+	assert_eq!(keep, cborg::decode_slice_with(&bytes, &opts).unwrap());
+
+	// Only unknown tags are stripped, at any depth
+	let opts = DecodeOptions::new().unknown_tags(TagPolicy::Strip);
+	let stripped = cborg::decode_slice_with(&bytes, &opts).unwrap();
+	assert_eq!(r#"{"a": ["x", 32("a:b")], 1(1): 2}"#, stripped.to_diagnostic());
+
+	let opts = DecodeOptions::new().unknown_tags(TagPolicy::Error);
+	let err = cborg::decode_slice_with(&bytes, &opts).unwrap_err();
+	assert_eq!(&ErrorKind::UnknownTag(100), err.kind());
+
+	// Registered tags are kept under any policy
+	let opts = DecodeOptions::new()
+		.unknown_tags(TagPolicy::Error)
+		.known_tag(100)
+		.known_tag(200);
+	assert_eq!(keep, cborg::decode_slice_with(&bytes, &opts).unwrap());
+	let opts = DecodeOptions::new().unknown_tags(TagPolicy::Strip).known_tag(200);
+	assert_eq!(
+		r#"{"a": ["x", 32("a:b")], 1(1): 200(2)}"#,
+		cborg::decode_slice_with(&bytes, &opts).unwrap().to_diagnostic()
+	);
+}
+
+#[test]
+fn duration_test() {
+	use cborg::FromValue;
+	use std::time::Duration;
+
+	// Floats hold short durations to the nanosecond
+	let d = Duration::new(1, 500_000_001);
+	assert_eq!(Value::Float(1.500_000_001), d.to_value());
+	assert_eq!(Some(d), Duration::from_value(Value::from(d)));
+	let bytes = cborg::encode(Duration::from_millis(1500));
+	assert_eq!(Some(Duration::from_millis(1500)), cborg::decode_to(&bytes).unwrap());
+
+	// Long durations lose nanoseconds as a float but not as an array
+	let long = Duration::new(u64::MAX / 2, 999_999_999);
+	assert_ne!(Some(long), Duration::from_ref(&long.to_value()));
+	let exact = Value::duration_exact(long);
+	assert_eq!(Some(long), Duration::from_ref(&exact));
+	let bytes = cborg::encode(exact);
+	assert_eq!(Some(long), cborg::decode_to(&bytes).unwrap());
+	assert_eq!(
+		Some(Duration::new(3, 1)),
+		Duration::from_ref(&Value::duration_exact(Duration::new(3, 1)))
+	);
+
+	// Integers are whole seconds
+	assert_eq!(Some(Duration::from_secs(42)), Duration::from_ref(&Value::Unsigned(42)));
+
+	// Negative, non-finite and malformed values are rejected
+	assert_eq!(None, Duration::from_ref(&Value::Negative(-1)));
+	assert_eq!(None, Duration::from_ref(&Value::Float(-0.5)));
+	assert_eq!(None, Duration::from_ref(&Value::Float(f64::NAN)));
+	assert_eq!(None, Duration::from_ref(&Value::Float(f64::INFINITY)));
+	assert_eq!(None, Duration::from_ref(&Value::array(vec![1u64, 1_000_000_000])));
+	assert_eq!(None, Duration::from_ref(&Value::array(vec![1u64])));
+	assert_eq!(None, Duration::from_ref(&Value::Utf8String("1s".into())));
+}
+
+#[test]
+fn strip_tags_test() {
+	use cborg::tags;
+
+	let deep = (0..1000).fold(Value::Unsigned(1), |v, i| Value::tagged(i, v));
+	let v = Value::map(vec![
+		(
+			Value::tagged(tags::URI, "a:b"),
+			Value::array(vec![deep.clone(), Value::Unsigned(2)]),
+		),
+		(
+			Value::text("k"),
+			Value::tagged(7, Value::map(vec![(Value::tagged(8, "x"), deep)])),
+		),
+	]);
+	let plain = r#"{"a:b": [1, 2], "k": {"x": 1}}"#;
+	assert_eq!(plain, v.strip_tags().to_diagnostic());
+
+	let mut w = v.clone();
+	w.strip_tags_mut();
+	assert_eq!(plain, w.to_diagnostic());
+	assert_eq!(v.strip_tags(), w);
+
+	// Values without tags are unchanged
+	let mut same = w.clone();
+	same.strip_tags_mut();
+	assert_eq!(w, same);
+	assert_eq!(w, w.strip_tags());
+}
+
+#[test]
+fn tag_handler_test() {
+	use cborg::{CborError, DecodeOptions, FromValue};
+
+	#[derive(Debug, PartialEq)]
+	struct Timestamp {
+		seconds: u64,
+	}
+	impl FromValue for Timestamp {
+		fn from_value(v: Value) -> Option<Self> { Timestamp::from_ref(&v) }
+		fn from_ref(v: &Value) -> Option<Self> {
+			let seconds = v.get_hash_map()?.get(&Value::text("seconds"))?.get_uint()?;
+			Some(Timestamp { seconds })
+		}
+	}
+	fn timestamp(tag: u64, v: Value) -> cborg::Result<Value> {
+		match v {
+			Value::Unsigned(seconds) => Ok(Value::map(vec![("seconds", seconds)])),
+			_ => Err(CborError::from(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("tag {} needs an unsigned integer", tag),
+			))),
+		}
+	}
+
+	// [1(1600000000), 1(5)]
+	let bytes = [0x82, 0xC1, 0x1A, 0x5F, 0x5E, 0x10, 0x00, 0xC1, 0x05];
+	let opts = DecodeOptions::new().tag_handler(1, timestamp);
+	let v = cborg::decode_slice_with(&bytes, &opts).unwrap();
+	assert_eq!(r#"[{"seconds": 1600000000}, {"seconds": 5}]"#, v.to_diagnostic());
+	let stamps: Vec<Timestamp> = FromValue::from_value(v).unwrap();
+	assert_eq!(
+		vec![Timestamp { seconds: 1_600_000_000 }, Timestamp { seconds: 5 }],
+		stamps
+	);
+
+	// Handlers compose: the inner tag is handled before the outer one sees its content
+	fn wrap(tag: u64, v: Value) -> cborg::Result<Value> { Ok(Value::map(vec![(Value::Unsigned(tag), v)])) }
+	let opts = DecodeOptions::new().tag_handler(1, timestamp).tag_handler(100, wrap);
+	let v = cborg::decode_slice_with(&[0xD8, 0x64, 0xC1, 0x05], &opts).unwrap();
+	assert_eq!(r#"{100: {"seconds": 5}}"#, v.to_diagnostic());
+
+	// A later registration replaces an earlier one
+	let opts = DecodeOptions::new().tag_handler(1, wrap).tag_handler(1, timestamp);
+	assert_eq!(
+		r#"{"seconds": 5}"#,
+		cborg::decode_slice_with(&[0xC1, 0x05], &opts).unwrap().to_diagnostic()
+	);
+
+	// Handler errors fail the decode as they are, with the tag in their context
+	let err = cborg::decode_slice_with(&[0x81, 0xC1, 0x61, 0x78], &opts).unwrap_err();
+	assert_eq!(&ErrorKind::Io, err.kind());
+	assert_eq!(Some("array index 0, handler for tag 1"), err.context());
+	assert_eq!(
+		"I/O error: tag 1 needs an unsigned integer (at array index 0, handler for tag 1)",
+		err.to_string()
+	);
+	let io = err.get_ref().downcast_ref::<std::io::Error>().unwrap();
+	assert_eq!(std::io::ErrorKind::InvalidData, io.kind());
+
+	// Without a handler the tag is kept
+	assert_eq!("1(5)", cborg::decode_slice(&[0xC1, 0x05]).unwrap().to_diagnostic());
+}
+
+#[test]
+fn tag_depth_test() {
+	use cborg::DecodeOptions;
+
+	let chain = |n: usize| {
+		let mut bytes = vec![0xC1; n];
+		bytes.push(0x01);
+		bytes
+	};
+
+	// 16 nested tags are allowed by default, 17 are not
+	let v = cborg::decode_slice(&chain(16)).unwrap();
+	assert_eq!(Value::Unsigned(1), v.strip_tags());
+	let err = cborg::decode_slice(&chain(17)).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+	assert!(format!("{:?}", err).contains("more than 16 nested tags"));
+
+	// Only tags directly inside each other count towards the limit
+	let mut bytes = Vec::new();
+	for _ in 0..10 {
+		bytes.extend_from_slice(&[0x81, 0xC1, 0xC1, 0xC1]);
+	}
+	bytes.push(0x01);
+	assert!(cborg::decode_slice(&bytes).is_ok());
+
+	let opts = DecodeOptions::new().max_tag_depth(2);
+	assert!(cborg::decode_slice_with(&chain(2), &opts).is_ok());
+	assert!(cborg::decode_slice_with(&chain(3), &opts).is_err());
+	let opts = DecodeOptions::new().max_tag_depth(1000);
+	assert!(cborg::decode_slice_with(&chain(1000), &opts).is_ok());
+
+	// Long chains fail at the limit without exhausting the stack
+	let long = vec![0xC1; 1_000_000];
+	let err = cborg::decode_slice(&long).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+
+	// An unterminated chain within the limit reports how deep it got
+	let opts = DecodeOptions::new().max_tag_depth(usize::MAX);
+	let err = cborg::decode_slice_with(&long, &opts).unwrap_err();
+	assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	assert!(format!("{:?}", err).contains("input ended inside a chain of 1000000 tags"));
+	let err = cborg::decode_slice(&[0xC1, 0xD8, 0x64]).unwrap_err();
+	assert!(format!("{:?}", err).contains("input ended inside a chain of 2 tags"));
+
+	// The other decoders apply the default limit too
+	let mut out = Value::Unsigned(0);
+	assert!(cborg::decode_reuse(&chain(16), &mut out).is_ok());
+	assert_eq!(Value::Unsigned(1), out.strip_tags());
+	let err = cborg::decode_reuse(&chain(17), &mut out).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+	assert!(cborg::decode_with_stringrefs(&chain(16)).is_ok());
+	let err = cborg::decode_with_stringrefs(&chain(17)).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+	assert!(cborg::decode_lossy(&chain(16)).1.is_empty());
+	assert_eq!(ErrorKind::TagNestingTooDeep, cborg::decode_lossy(&chain(17)).1[0].kind);
+	assert_eq!(
+		&ErrorKind::TagNestingTooDeep,
+		cborg::decode_lenient(&chain(17)).1[0].kind()
+	);
+	assert!(cborg::decode_reuse(&bytes, &mut out).is_ok());
+	assert!(cborg::decode_with_stringrefs(&bytes).is_ok());
+	assert!(cborg::decode_lossy(&bytes).1.is_empty());
+
+	// and fail on long chains rather than overflowing the stack
+	let err = cborg::decode_reuse(&long, &mut out).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+	let err = cborg::decode_with_stringrefs(&long).unwrap_err();
+	assert_eq!(&ErrorKind::TagNestingTooDeep, err.kind());
+	let (_, issues) = cborg::decode_lossy(&long);
+	assert_eq!(
+		vec![ErrorKind::TagNestingTooDeep],
+		issues.iter().map(|i| i.kind).collect::<Vec<_>>()
+	);
+	let (_, errors) = cborg::decode_lenient(&long);
+	assert_eq!(&ErrorKind::TagNestingTooDeep, errors[0].kind());
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn base64_transport_test() {
+	let v = cborg::decode_slice(&TEST_DATA_DEFINITE).unwrap();
+
+	let b64 = v.encode_base64url();
+	assert!(!b64.contains('=') && !b64.contains('+') && !b64.contains('/'));
+	assert_eq!(v, cborg::decode_base64(&b64).unwrap());
+	let hex = v.encode_hex();
+	assert_eq!(TEST_DATA_DEFINITE.len() * 2, hex.len());
+	assert_eq!(v, cborg::decode_hex(&hex).unwrap());
+	assert_eq!(v, cborg::decode_hex(&hex.to_uppercase()).unwrap());
+
+	// Padding and the standard alphabet are accepted too
+	let bytes = [0x43, 0xFB, 0xFF, 0xBF]; // h'fbffbf'
+	assert_eq!("Q_v_vw", Value::ByteString(vec![0xFB, 0xFF, 0xBF]).encode_base64url());
+	let expected = cborg::decode_slice(&bytes).unwrap();
+	for text in &["Q_v_vw", "Q_v_vw==", "Q/v/vw", "Q/v/vw=="] {
+		assert_eq!(expected, cborg::decode_base64(text).unwrap());
+	}
+
+	// Malformed text fails with its own error kind
+	for text in &["Q_v_v", "Q_v_vw=", "Q_v!vw", "Q_v_vx"] {
+		let err = cborg::decode_base64(text).unwrap_err();
+		assert_eq!(&ErrorKind::TextEncoding, err.kind(), "{}", text);
+	}
+	assert!(format!("{:?}", cborg::decode_base64("Q_v!vw").unwrap_err()).contains("invalid base64 character '!' at 3"));
+	assert!(cborg::decode_hex("4").is_err());
+	assert!(format!("{:?}", cborg::decode_hex("4g").unwrap_err()).contains("invalid hex character 'g' at 1"));
+
+	// Valid text that is not valid CBOR fails as CBOR
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_hex("82").unwrap_err().kind()
+	);
+}
+
+#[test]
+fn raw_value_test() {
+	use cborg::RawValue;
+
+	// A non-minimal encoding shows the bytes are spliced rather than re-encoded
+	let body_bytes = vec![0x82, 0x18, 0x05, 0x63, b'a', b'b', b'c']; // [5, "abc"]
+	let body = RawValue::new(body_bytes.clone()).unwrap();
+	let envelope = Value::map(vec![("hdr", Value::Unsigned(1)), ("body", Value::from(body.clone()))]);
+	let bytes = envelope.encode();
+	let mut expected = vec![0xA2, 0x63, b'h', b'd', b'r', 0x01, 0x64, b'b', b'o', b'd', b'y'];
+	expected.extend_from_slice(&body_bytes);
+	assert_eq!(expected, bytes);
+	assert_eq!(body_bytes.as_slice(), body.as_bytes());
+
+	// Decoding the envelope gives the body as ordinary values, which a raw value never equals
+	let decoded = cborg::decode_slice(&bytes).unwrap();
+	assert_eq!(r#"{"hdr": 1, "body": [5, "abc"]}"#, decoded.to_diagnostic());
+	assert_ne!(decoded, envelope);
+	assert_eq!(envelope.to_diagnostic(), decoded.to_diagnostic());
+	let decoded_body = match &decoded {
+		Value::Map(m) => m.get(&Value::from("body")).unwrap(),
+		_ => panic!("not a map"),
+	};
+	assert_eq!(&body.decode(), decoded_body);
+	assert_eq!(Value::Raw(body.clone()), body.to_value());
+
+	// Equality, ordering and hashing all go by the raw bytes
+	let five = Value::Raw(RawValue::new(vec![0x18, 0x05]).unwrap());
+	assert_ne!(Value::Unsigned(5), five);
+	assert_eq!(core::cmp::Ordering::Greater, five.cmp(&Value::Unsigned(5)));
+	assert_eq!(core::cmp::Ordering::Less, five.cmp(&Value::Unsigned(24)));
+	let same_encoding = Value::Raw(RawValue::new(vec![0x05]).unwrap());
+	assert_eq!(core::cmp::Ordering::Greater, same_encoding.cmp(&Value::Unsigned(5)));
+	assert_eq!(core::cmp::Ordering::Less, Value::Unsigned(5).cmp(&same_encoding));
+	let set: std::collections::HashSet<Value> = vec![five.clone(), Value::Unsigned(5), five.clone()]
+		.into_iter()
+		.collect();
+	assert_eq!(2, set.len());
+	let set: BTreeSet<Value> = vec![same_encoding.clone(), Value::Unsigned(5), same_encoding]
+		.into_iter()
+		.collect();
+	assert_eq!(2, set.len());
+	assert_eq!(decoded.encode_canonical(), envelope.encode_canonical());
+	assert_eq!(
+		vec![0x82, 0x05, 0x63, b'a', b'b', b'c'],
+		Value::Raw(body.clone()).encode_canonical()
+	);
+
+	// Only a single well-formed item is accepted
+	assert!(RawValue::new(vec![0x82, 0x01]).is_err());
+	assert!(RawValue::new(vec![0x01, 0x02]).is_err());
+	assert!(RawValue::new(vec![]).is_err());
+	assert_eq!(vec![0x01], RawValue::new(vec![0x01]).unwrap().into_bytes());
+}
+
+#[test]
+fn tag_display_test() {
+	use cborg::tags;
+
+	let epoch = Value::tagged(tags::EPOCH, 1_363_896_240u64);
+	assert_eq!("1(1363896240)", epoch.to_string());
+	let uri = Value::uri("https://example.com");
+	assert_eq!(r#"32("https://example.com")"#, uri.to_string());
+	assert_eq!(
+		r#"55799(32("https://example.com"))"#,
+		Value::tagged(tags::SELF_DESCRIBED, uri.clone()).to_string()
+	);
+
+	// Tags nest inside containers with the usual indentation, and around them
+	let v = Value::map(vec![(Value::text("t"), Value::array(vec![epoch.clone(), uri]))]);
+	assert_eq!(
+		"{\n   \"t\": [\n      1(1363896240),\n      32(\"https://example.com\"),\n   ],\n}",
+		v.to_string()
+	);
+	let v = Value::tagged(100, Value::array(vec![epoch]));
+	assert_eq!("100([\n   1(1363896240),\n])", v.to_string());
+	assert_eq!("100([1(1363896240)])", v.to_diagnostic());
+
+	// Debug uses the same rendering
+	assert_eq!("100([\n   1(1363896240),\n])", format!("{:?}", v));
+}
+
+#[test]
+fn zero_argument_test() {
+	assert_eq!(Value::Unsigned(0), cborg::decode_slice(&[0x00]).unwrap());
+	assert_eq!(Value::Negative(-1), cborg::decode_slice(&[0x20]).unwrap());
+	assert_eq!(Value::Array(vec![]), cborg::decode_slice(&[0x80]).unwrap());
+	assert_eq!(Value::Map(Map::new()), cborg::decode_slice(&[0xA0]).unwrap());
+	assert_eq!(Value::ByteString(vec![]), cborg::decode_slice(&[0x40]).unwrap());
+	assert_eq!(Value::Utf8String(String::new()), cborg::decode_slice(&[0x60]).unwrap());
+	assert_eq!(Some(0u32), cborg::decode_to(&[0x00]).unwrap());
+	assert_eq!(Some(-1i32), cborg::decode_to(&[0x20]).unwrap());
+
+	// Empty items inside containers and tags
+	let v = cborg::decode_slice(&[0x84, 0x00, 0x80, 0xA0, 0xC0, 0x60]).unwrap();
+	assert_eq!(r#"[0, [], {}, 0("")]"#, v.to_diagnostic());
+	assert_eq!(Some(vec![0u32, 0]), cborg::decode_to(&[0x82, 0x00, 0x00]).unwrap());
+	for v in &[
+		Value::Unsigned(0),
+		Value::Negative(-1),
+		Value::Array(vec![]),
+		Value::Utf8String(String::new()),
+	] {
+		assert_eq!(*v, cborg::decode_slice(&v.encode()).unwrap());
+	}
+}
+
+#[test]
+fn float_width_test() {
+	let f = |bytes: &[u8]| cborg::decode_slice(bytes).unwrap().get_float().unwrap();
+
+	// Half precision
+	assert_eq!(1.5, f(&[0xF9, 0x3E, 0x00]));
+	assert_eq!(65504.0, f(&[0xF9, 0x7B, 0xFF]));
+	assert_eq!(5.960_464_477_539_063e-8, f(&[0xF9, 0x00, 0x01]));
+	assert_eq!(f64::INFINITY, f(&[0xF9, 0x7C, 0x00]));
+	assert!(f(&[0xF9, 0x7E, 0x00]).is_nan());
+	let z = f(&[0xF9, 0x80, 0x00]);
+	assert!(z == 0.0 && z.is_sign_negative());
+
+	// Single precision
+	assert_eq!(100_000.0, f(&[0xFA, 0x47, 0xC3, 0x50, 0x00]));
+	assert_eq!(f64::from(f32::MAX), f(&[0xFA, 0x7F, 0x7F, 0xFF, 0xFF]));
+	assert_eq!(f64::INFINITY, f(&[0xFA, 0x7F, 0x80, 0x00, 0x00]));
+	assert_eq!(f64::NEG_INFINITY, f(&[0xFA, 0xFF, 0x80, 0x00, 0x00]));
+	let z = f(&[0xFA, 0x80, 0x00, 0x00, 0x00]);
+	assert!(z == 0.0 && z.is_sign_negative());
+
+	// Double precision
+	assert_eq!(1.1, f(&[0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]));
+	assert_eq!(-4.1, f(&[0xFB, 0xC0, 0x10, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66]));
+	let z = f(&[0xFB, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+	assert!(z == 0.0 && z.is_sign_negative());
+
+	assert_eq!(
+		Some(100_000f32),
+		cborg::decode_to(&[0xFA, 0x47, 0xC3, 0x50, 0x00]).unwrap()
+	);
+}
+
+#[test]
+fn invalid_utf8_test() {
+	let cases: [&[u8]; 5] = [
+		&[0x61, 0xC3],                         // truncated 2-byte sequence
+		&[0x62, 0xE2, 0x82],                   // truncated 3-byte sequence
+		&[0x63, 0xED, 0xA0, 0x80],             // lone high surrogate U+D800 as WTF-8
+		&[0x63, 0xED, 0xBF, 0xBF],             // lone low surrogate U+DFFF as WTF-8
+		&[0x7F, 0x61, 0xF0, 0x61, 0x90, 0xFF], // sequence split across chunks
+	];
+	for bytes in cases.iter() {
+		let err = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(&ErrorKind::InvalidUtf8, err.kind(), "{:02x?}", bytes);
+		assert!(format!("{:?}", err).contains("Utf8Error"));
+
+		// Inside containers, as map keys and through the other entry points
+		let mut arr = vec![0x81];
+		arr.extend_from_slice(bytes);
+		assert!(cborg::decode_slice(&arr).is_err());
+		let mut map = vec![0xA1];
+		map.extend_from_slice(bytes);
+		map.push(0x01);
+		assert_eq!(&ErrorKind::InvalidUtf8, cborg::decode_slice(&map).unwrap_err().kind());
+		assert!(cborg::decode_to::<std::collections::HashMap<String, u32>, _>(&map).is_err());
+		assert!(cborg::decode_to::<String, _>(*bytes).is_err());
+		let mut out = Value::Simple(cborg::Simple::Null);
+		assert_eq!(
+			&ErrorKind::InvalidUtf8,
+			cborg::decode_reuse(bytes, &mut out).unwrap_err().kind()
+		);
+		assert!(!cborg::decode_lossy(&arr).1.is_empty());
+		assert!(cborg::decode_with_stringrefs(&arr).is_err());
+	}
+}
+
+#[test]
+fn parse_float_minor_test() {
+	let bytes = [0x3F, 0xF0, 0, 0, 0, 0, 0, 0];
+	for minor in &[0u8, 24, 28, 31] {
+		let err = cborg::parse_float(*minor, &mut bytes.iter()).unwrap_err();
+		assert_eq!(&ErrorKind::UnexpectedValue, err.kind());
+		assert!(format!("{:?}", err).contains(&format!("additional information {} is not a float width", minor)));
+	}
+	assert_eq!(1.0, cborg::parse_float(27, &mut bytes.iter()).unwrap());
+
+	// A break or reserved value where an item is expected errors rather than panicking
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::decode_slice(&[0x81, 0xFF]).unwrap_err().kind()
+	);
+	assert!(cborg::decode_slice(&[0xFC]).is_err());
+}
+
+#[test]
+fn reserved_additional_info_test() {
+	for major in 0..8u8 {
+		for minor in 28..=30u8 {
+			let b = major << 5 | minor;
+			for bytes in &[vec![b], vec![0x81, b], vec![0xA1, 0x01, b], vec![0xC1, b]] {
+				let err = cborg::decode_slice(bytes).unwrap_err();
+				assert_eq!(&ErrorKind::ReservedAdditionalInfo, err.kind(), "{:02x?}", bytes);
+				assert!(format!("{:?}", err).contains(&format!("initial byte 0x{:02X}", b)));
+				let mut out = Value::Simple(cborg::Simple::Null);
+				assert!(cborg::decode_reuse(bytes, &mut out).is_err());
+				let (_, issues) = cborg::decode_lossy(bytes);
+				assert!(
+					issues.iter().any(|i| i.kind == ErrorKind::ReservedAdditionalInfo),
+					"{:02x?}",
+					bytes
+				);
+				assert!(!cborg::is_canonical(bytes));
+			}
+		}
+	}
+	// Chunks of indefinite-length strings are checked too
+	let err = cborg::decode_slice(&[0x5F, 0x5C, 0xFF]).unwrap_err();
+	assert_eq!(&ErrorKind::ReservedAdditionalInfo, err.kind());
+	assert!(format!("{:?}", err).contains("0x5C"));
+	for b in &[0x1C, 0x3D, 0x5E, 0x7C, 0x9D, 0xBE, 0xDC, 0xFE] {
+		assert!(cborg::decode_slice(&[*b]).is_err());
+	}
+}
+
+#[test]
+fn indefinite_chunk_test() {
+	// Valid chunks concatenate
+	let v = cborg::decode_slice(&[0x7F, 0x61, 0x61, 0x62, 0x62, 0x63, 0xFF]).unwrap();
+	assert_eq!(Value::text("abc"), v);
+	let v = cborg::decode_slice(&[0x5F, 0x41, 0x01, 0x42, 0x02, 0x03, 0xFF]).unwrap();
+	assert_eq!(Value::bytes(vec![1, 2, 3]), v);
+
+	let bad: [&[u8]; 5] = [
+		&[0x7F, 0x41, 0x61, 0xFF],             // byte string chunk in a text string
+		&[0x5F, 0x61, 0x61, 0xFF],             // text string chunk in a byte string
+		&[0x5F, 0x82, 0x01, 0x02, 0xFF],       // array header as a chunk
+		&[0x5F, 0x5F, 0x41, 0x01, 0xFF, 0xFF], // nested indefinite byte string
+		&[0x7F, 0x7F, 0x61, 0x61, 0xFF, 0xFF], // nested indefinite text string
+	];
+	for bytes in bad.iter() {
+		let err = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(&ErrorKind::UnexpectedValue, err.kind(), "{:02x?}", bytes);
+		assert!(format!("{:?}", err).contains("in an indefinite-length"));
+		let mut out = Value::Simple(cborg::Simple::Null);
+		assert!(cborg::decode_reuse(bytes, &mut out).is_err());
+	}
+	assert!(format!("{:?}", cborg::decode_slice(bad[2]).unwrap_err()).contains("chunk with initial byte 0x82"));
+}
+
+#[test]
+fn utf8_chunk_test() {
+	// "é" (C3 A9) split across two chunks of an indefinite-length text string
+	let split = [0x7F, 0x61, 0xC3, 0x61, 0xA9, 0xFF];
+	let err = cborg::decode_slice(&split).unwrap_err();
+	assert_eq!(&ErrorKind::InvalidUtf8, err.kind());
+	let mut out = Value::Simple(cborg::Simple::Null);
+	assert!(cborg::decode_reuse(&split, &mut out).is_err());
+	assert!(cborg::decode_with_stringrefs(&split).is_err());
+	assert_eq!(1, cborg::decode_lossy(&split).1.len());
+
+	let opts = cborg::DecodeOptions::new().allow_split_utf8(true);
+	assert_eq!(Value::text("é"), cborg::decode_slice_with(&split, &opts).unwrap());
+	// The joined text must still be valid
+	assert!(cborg::decode_slice_with(&[0x7F, 0x61, 0xC3, 0xFF], &opts).is_err());
+
+	// Whole characters in each chunk are fine either way
+	let whole = [0x7F, 0x62, 0xC3, 0xA9, 0x61, 0x61, 0xFF];
+	assert_eq!(Value::text("éa"), cborg::decode_slice(&whole).unwrap());
+	assert_eq!(Value::text("éa"), cborg::decode_slice_with(&whole, &opts).unwrap());
+}
+
+#[test]
+fn length_bomb_test() {
+	let huge = [0xFF; 8];
+	for major in &[0x9B, 0xBB, 0x5B, 0x7B] {
+		let mut bytes = vec![*major];
+		bytes.extend_from_slice(&huge);
+		let err = cborg::decode_slice(&bytes).unwrap_err();
+		assert_eq!(&ErrorKind::InsufficientBytes, err.kind(), "{:02x?}", bytes);
+		assert!(format!("{:?}", err).contains("bytes remaining"));
+		let mut out = Value::Simple(cborg::Simple::Null);
+		assert_eq!(
+			&ErrorKind::InsufficientBytes,
+			cborg::decode_reuse(&bytes, &mut out).unwrap_err().kind()
+		);
+
+		// Inside an array and as a chunk of an indefinite-length string
+		let mut nested = vec![0x82, 0x01];
+		nested.extend_from_slice(&bytes);
+		assert_eq!(
+			&ErrorKind::InsufficientBytes,
+			cborg::decode_slice(&nested).unwrap_err().kind()
+		);
+
+		// An input of unknown length reads until it runs out instead
+		let mut it = bytes.iter();
+		let mut unsized_iter = std::iter::from_fn(|| it.next());
+		assert_eq!(
+			&ErrorKind::InsufficientBytes,
+			cborg::decode_iter(&mut unsized_iter).unwrap_err().kind()
+		);
+	}
+	let chunk = [0x5F, 0x5A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_slice(&chunk).unwrap_err().kind()
+	);
+
+	// Lengths that fit the input still decode, with or without a reservation
+	let bytes = [0x83, 0x01, 0x02, 0x03];
+	let opts = cborg::DecodeOptions::new().max_prealloc(0);
+	assert_eq!(
+		cborg::decode_slice(&bytes).unwrap(),
+		cborg::decode_slice_with(&bytes, &opts).unwrap()
+	);
+	// A map needs two bytes per entry
+	assert!(cborg::decode_slice(&[0xA2, 0x01, 0x02, 0x03]).is_err());
+}
+
+#[test]
+fn max_depth_test() {
+	// Arrays are [...] and maps are {0: ...}, around a 0
+	fn nested(open: u8, depth: usize) -> Vec<u8> {
+		let level: &[u8] = if open == 0xA1 { &[0xA1, 0x00] } else { &[open] };
+		let mut bytes = level.repeat(depth);
+		bytes.push(0x00);
+		bytes
+	}
+
+	// Ten thousand nested arrays fail instead of overflowing the stack
+	let deep = nested(0x81, 10_000);
+	let err = cborg::decode_slice(&deep).unwrap_err();
+	assert_eq!(&ErrorKind::DepthLimitExceeded, err.kind());
+	assert!(format!("{:?}", err).contains("more than 128 nested"));
+	assert!(cborg::decode_slice(&nested(0xA1, 10_000)).is_err());
+	let mut indefinite = vec![0x9F; 10_000];
+	indefinite.extend(vec![0xFF; 10_000]);
+	assert!(cborg::decode_slice(&indefinite).is_err());
+	let mut out = Value::Simple(cborg::Simple::Null);
+	assert_eq!(
+		&ErrorKind::DepthLimitExceeded,
+		cborg::decode_reuse(&deep, &mut out).unwrap_err().kind()
+	);
+	assert_eq!(
+		&ErrorKind::DepthLimitExceeded,
+		cborg::decode_with_stringrefs(&deep).unwrap_err().kind()
+	);
+	let (_, issues) = cborg::decode_lossy(&deep);
+	assert_eq!(cborg::ErrorKind::DepthLimitExceeded, issues[0].kind);
+
+	// The default limit is 128
+	assert!(cborg::decode_slice(&nested(0x81, 128)).is_ok());
+	assert!(cborg::decode_slice(&nested(0x81, 129)).is_err());
+	assert!(cborg::decode_slice(&nested(0xA1, 128)).is_ok());
+	assert!(cborg::decode_slice(&nested(0xA1, 129)).is_err());
+
+	let opts = cborg::DecodeOptions::new().max_depth(2);
+	assert!(cborg::decode_slice_with(&nested(0x81, 2), &opts).is_ok());
+	assert!(cborg::decode_slice_with(&nested(0x81, 3), &opts).is_err());
+	// Tags do not count towards the limit
+	let tagged = [0x81, 0xC1, 0x81, 0xC1, 0x00];
+	assert!(cborg::decode_slice_with(&tagged, &opts).is_ok());
+}
+
+#[test]
+fn deep_nesting_test() {
+	const DEPTH: usize = 100_000;
+	// Decoded on a 128 KiB stack, far too small for one call frame per level
+	let decode = std::thread::Builder::new().stack_size(128 * 1024).spawn(|| {
+		let opts = cborg::DecodeOptions::new().max_depth(DEPTH);
+		let mut bytes = vec![0x81; DEPTH];
+		bytes.push(0x00);
+		let mut v = cborg::decode_slice_with(&bytes, &opts).unwrap();
+		// Taken apart a level at a time, as dropping the whole tree would recurse
+		let mut levels = 0;
+		while let Value::Array(mut arr) = v {
+			assert_eq!(1, arr.len());
+			v = arr.pop().unwrap();
+			levels += 1;
+		}
+		assert_eq!(DEPTH, levels);
+		assert_eq!(Value::Unsigned(0), v);
+
+		// Indefinite-length maps, each {0: ...}, closed by their break codes
+		let mut bytes = [0xBF, 0x00].repeat(DEPTH);
+		bytes.push(0x00);
+		bytes.extend(vec![0xFF; DEPTH]);
+		let mut v = cborg::decode_slice_with(&bytes, &opts).unwrap();
+		let mut levels = 0;
+		while let Value::Map(mut map) = v {
+			v = map.pop().unwrap().val;
+			levels += 1;
+		}
+		assert_eq!(DEPTH, levels);
+
+		// One level more than allowed
+		let mut bytes = vec![0x81; DEPTH + 1];
+		bytes.push(0x00);
+		assert!(cborg::decode_slice_with(&bytes, &opts).is_err());
+	});
+	decode.unwrap().join().unwrap();
+}
+
+#[test]
+fn decode_exact_test() {
+	// A single item decodes as with decode_slice
+	let bytes = [0x82, 0x01, 0x61, b'a'];
+	assert_eq!(
+		cborg::decode_slice(&bytes).unwrap(),
+		cborg::decode_exact(&bytes).unwrap()
+	);
+	let v: Vec<u8> = cborg::decode_to_exact(&[0x82, 0x01, 0x02]).unwrap().unwrap();
+	assert_eq!(vec![1, 2], v);
+
+	// Followed by garbage
+	let err = cborg::decode_exact(&[0x01, 0xFF, 0xFF]).unwrap_err();
+	assert_eq!(&ErrorKind::TrailingBytes { remaining: 2 }, err.kind());
+	assert!(format!("{:?}", err).contains("ends at offset 1"));
+	assert!(cborg::decode_slice(&[0x01, 0xFF, 0xFF]).is_ok());
+
+	// Followed by a second valid item, as when two messages are concatenated
+	let two = [0x82, 0x01, 0x02, 0x82, 0x03, 0x04];
+	assert_eq!(
+		&ErrorKind::TrailingBytes { remaining: 3 },
+		cborg::decode_exact(&two).unwrap_err().kind()
+	);
+	assert!(cborg::decode_to_exact::<Vec<u8>>(&two).is_err());
+
+	// Errors in the item itself come first
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_exact(&[0x82, 0x01]).unwrap_err().kind()
+	);
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_exact(&[]).unwrap_err().kind()
+	);
+}
+
+#[test]
+fn decode_partial_test() {
+	let mut buf = TEST_DATA_DEFINITE.to_vec();
+	buf.extend_from_slice(&TEST_DATA_INDEFINITE);
+
+	let (first, len) = cborg::decode_partial(&buf).unwrap();
+	assert_eq!(TEST_DATA_DEFINITE.len(), len);
+	assert_eq!(cborg::decode_slice(&TEST_DATA_DEFINITE).unwrap(), first);
+	let (second, rest) = cborg::decode_partial(&buf[len..]).unwrap();
+	assert_eq!(TEST_DATA_INDEFINITE.len(), rest);
+	assert_eq!(cborg::decode_slice(&TEST_DATA_INDEFINITE).unwrap(), second);
+
+	// The other way round, so the indefinite-length item comes first
+	let mut buf = TEST_DATA_INDEFINITE.to_vec();
+	buf.extend_from_slice(&TEST_DATA_DEFINITE);
+	let (_, len) = cborg::decode_partial(&buf).unwrap();
+	assert_eq!(TEST_DATA_INDEFINITE.len(), len);
+
+	// Every item of a sequence, with nothing re-scanned
+	let seq = [
+		0x01, 0x9F, 0x9F, 0xFF, 0xA1, 0x01, 0x5F, 0x41, 0x00, 0xFF, 0xFF, 0xC1, 0x02,
+	];
+	let mut pos = 0;
+	let mut items = Vec::new();
+	while pos < seq.len() {
+		let (v, len) = cborg::decode_partial(&seq[pos..]).unwrap();
+		items.push(v.to_diagnostic());
+		pos += len;
+	}
+	assert_eq!(vec!["1", "[[], {1: h'00'}]", "1(2)"], items);
+
+	assert!(cborg::decode_partial(&TEST_DATA_INDEFINITE[..50]).is_err());
+}
+
+#[test]
+fn decode_all_test() {
+	let mut log = TEST_DATA_DEFINITE.to_vec();
+	log.extend_from_slice(&TEST_DATA_INDEFINITE);
+	log.push(0xF6);
+	let items = cborg::decode_all(&log).unwrap();
+	assert_eq!(3, items.len());
+	assert_eq!(cborg::decode_slice(&TEST_DATA_INDEFINITE).unwrap(), items[1]);
+	assert_eq!(Value::Simple(cborg::Simple::Null), items[2]);
+
+	// An empty input is an empty sequence
+	assert_eq!(0, cborg::decode_all(&[]).unwrap().len());
+	assert_eq!(0, cborg::decode_seq(&[]).count());
+
+	// Truncation inside an item is reported after the items before it
+	let cut = &log[..TEST_DATA_DEFINITE.len() + 10];
+	let mut seq = cborg::decode_seq(cut);
+	assert!(seq.next().unwrap().is_ok());
+	let err = seq.next().unwrap().unwrap_err();
+	assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	assert!(seq.next().is_none());
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_all(cut).unwrap_err().kind()
+	);
+
+	// encode_seq writes what decode_all reads
+	let values = vec![
+		Value::Unsigned(1),
+		Value::text("two"),
+		Value::Array(vec![Value::Unsigned(3)]),
+	];
+	let bytes = cborg::encode_seq(&values);
+	assert_eq!(values, cborg::decode_all(&bytes).unwrap());
+	assert_eq!(Vec::<u8>::new(), cborg::encode_seq(Vec::<Value>::new()));
+}
+
+#[test]
+fn error_kind_test() {
+	// A break code where an item belongs, through each decoder
+	let stray = [0x81, 0xFF];
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::decode_slice(&stray).unwrap_err().kind()
+	);
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::decode_with_stringrefs(&stray).unwrap_err().kind()
+	);
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::canonicalize(&stray).unwrap_err().kind()
+	);
+	assert_eq!(ErrorKind::InvalidBreak, cborg::decode_lossy(&stray).1[0].kind);
+
+	// -2^64 does not fit an i64
+	let below_min = [0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+	assert_eq!(
+		&ErrorKind::LengthOverflow,
+		cborg::decode_with_stringrefs(&below_min).unwrap_err().kind()
+	);
+	assert_eq!(
+		&ErrorKind::LengthOverflow,
+		cborg::canonicalize(&below_min).unwrap_err().kind()
+	);
+	assert_eq!(ErrorKind::LengthOverflow, cborg::decode_lossy(&below_min).1[0].kind);
+
+	// Every kind has its own text
+	let kinds = [
+		ErrorKind::UnexpectedValue,
+		ErrorKind::InsufficientBytes,
+		ErrorKind::Io,
+		ErrorKind::UnknownTag(7),
+		ErrorKind::TagNestingTooDeep,
+		ErrorKind::TextEncoding,
+		ErrorKind::InvalidUtf8,
+		ErrorKind::ReservedAdditionalInfo,
+		ErrorKind::DepthLimitExceeded,
+		ErrorKind::TrailingBytes { remaining: 1 },
+		ErrorKind::DuplicateKey,
+		ErrorKind::LengthOverflow,
+		ErrorKind::InvalidBreak,
+	];
+	let texts: BTreeSet<String> = kinds.iter().map(|k| k.to_string()).collect();
+	assert_eq!(kinds.len(), texts.len());
+	for k in kinds.iter() {
+		assert_eq!(k.to_string(), format!("{:?}", k));
+	}
+}
+
+#[test]
+fn error_source_test() {
+	use std::error::Error;
+
+	// The inner error is reachable as the source
+	let err = cborg::decode_slice(&[0x62, 0xC3, 0x28]).unwrap_err();
+	assert_eq!(&ErrorKind::InvalidUtf8, err.kind());
+	let source = err.source().unwrap();
+	assert!(source.downcast_ref::<std::string::FromUtf8Error>().is_some());
+	assert_eq!(source.to_string(), err.message());
+	assert_eq!(format!("Invalid UTF-8: {}", err.message()), err.to_string());
+	assert!(err.get_ref().is::<std::string::FromUtf8Error>());
+	assert!(err.into_inner().downcast::<std::string::FromUtf8Error>().is_ok());
+
+	// A message is shown after the kind
+	let err = cborg::decode_slice(&[0x3C]).unwrap_err();
+	assert_eq!(&ErrorKind::ReservedAdditionalInfo, err.kind());
+	assert_eq!(
+		"initial byte 0x3C has reserved additional information 28",
+		err.message()
+	);
+	assert_eq!(
+		"Reserved additional information: initial byte 0x3C has reserved additional information 28",
+		err.to_string()
+	);
+	// A message alone is not a source, so chains do not repeat it
+	assert!(err.source().is_none());
+
+	// Without details there is no source and only the kind is shown
+	let err = cborg::decode_slice(&[]).unwrap_err();
+	assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	assert_eq!("", err.message());
+	assert!(err.source().is_none());
+	assert_eq!("Insufficient bytes", err.to_string());
+
+	// I/O errors keep the io::Error, even one with an empty message
+	let err = cborg::CborError::from(std::io::Error::other("disk full"));
+	assert_eq!(&ErrorKind::Io, err.kind());
+	assert!(err.source().unwrap().downcast_ref::<std::io::Error>().is_some());
+	let err = cborg::CborError::from(std::io::Error::other(""));
+	assert_eq!("", err.message());
+	assert!(err.source().unwrap().downcast_ref::<std::io::Error>().is_some());
+
+	// The chain can be walked like any other error's
+	let err: Box<dyn Error> = Box::new(cborg::decode_slice(&[0x62, 0xC3, 0x28]).unwrap_err());
+	let mut chain = vec![err.to_string()];
+	let mut next = err.source();
+	while let Some(e) = next {
+		chain.push(e.to_string());
+		next = e.source();
+	}
+	assert_eq!(2, chain.len());
+}
+
+#[test]
+fn duplicate_key_test() {
+	use cborg::DecodeOptions;
+	use cborg::DuplicateKeyPolicy;
+
+	// {1: "a", "k": 1, 1: "b", "k": 2, 2: "c"}
+	let bytes = [
+		0xA5, 0x01, 0x61, b'a', 0x61, b'k', 0x01, 0x01, 0x61, b'b', 0x61, b'k', 0x02, 0x02, 0x61, b'c',
+	];
+	let decode = |policy| cborg::decode_slice_with(&bytes, &DecodeOptions::new().duplicate_keys(policy));
+
+	let all = decode(DuplicateKeyPolicy::Allow).unwrap();
+	assert_eq!(cborg::decode_slice(&bytes).unwrap(), all);
+	assert_eq!(5, all.get_map().unwrap().len());
+
+	let first = decode(DuplicateKeyPolicy::FirstWins).unwrap();
+	assert_eq!(r#"{1: "a", "k": 1, 2: "c"}"#, first.to_diagnostic());
+	let last = decode(DuplicateKeyPolicy::LastWins).unwrap();
+	assert_eq!(r#"{1: "b", "k": 2, 2: "c"}"#, last.to_diagnostic());
+
+	let err = decode(DuplicateKeyPolicy::Error).unwrap_err();
+	assert_eq!(&ErrorKind::DuplicateKey, err.kind());
+	assert!(err.message().contains("duplicate map key 1"));
+	// The string key is caught too, and in nested and indefinite-length maps
+	let strings = [0x81, 0xBF, 0x61, b'k', 0x01, 0x61, b'k', 0x02, 0xFF];
+	let opts = DecodeOptions::new().duplicate_keys(DuplicateKeyPolicy::Error);
+	let err = cborg::decode_slice_with(&strings, &opts).unwrap_err();
+	assert!(err.message().contains(r#"duplicate map key "k""#));
+
+	// HashMap and BTreeMap see what the policy left
+	let first_k = DecodeOptions::new().duplicate_keys(DuplicateKeyPolicy::FirstWins);
+	let h: Vec<HashMap<String, u32>> =
+		cborg::FromValue::from_value(cborg::decode_slice_with(&strings, &first_k).unwrap()).unwrap();
+	assert_eq!(1, h[0]["k"]);
+	let b: BTreeMap<u32, String> = cborg::FromValue::from_value(first).unwrap();
+	assert_eq!("a", b[&1]);
+	let b: BTreeMap<u32, String> = cborg::FromValue::from_value(last).unwrap();
+	assert_eq!("b", b[&1]);
+	// Without a policy the conversion keeps the last entry
+	let b: BTreeMap<u32, String> = cborg::FromValue::from_value(all.clone()).unwrap();
+	assert_eq!("b", b[&1]);
+
+	// The same policies apply to a Map directly
+	let mut m = Map::from(all.get_map().unwrap());
+	assert!(m.clone().dedup_keys(DuplicateKeyPolicy::Error).is_err());
+	m.dedup_keys(DuplicateKeyPolicy::LastWins).unwrap();
+	assert_eq!(3, m.len());
+}
+
+#[test]
+fn stray_break_test() {
+	let cases: [(&[u8], &str); 7] = [
+		(&[0xFF], "outside any indefinite-length item"),
+		(&[0x82, 0x01, 0xFF], "inside a definite-length array"),
+		(&[0xA1, 0xFF, 0x01], "inside a definite-length map"),
+		(&[0xA1, 0x01, 0xFF], "in place of a map value"),
+		(&[0xBF, 0x01, 0xFF], "in place of a map value"),
+		(&[0xC1, 0xFF], "in place of a tag's content"),
+		(&[0x9F, 0x82, 0xFF, 0xFF], "inside a definite-length array"),
+	];
+	for (bytes, context) in cases.iter() {
+		let err = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(&ErrorKind::InvalidBreak, err.kind(), "{:02x?}", bytes);
+		assert_eq!(format!("break code {}", context), err.message());
+		let mut out = Value::Simple(cborg::Simple::Null);
+		assert_eq!(
+			&ErrorKind::InvalidBreak,
+			cborg::decode_reuse(bytes, &mut out).unwrap_err().kind()
+		);
+	}
+
+	// Breaks that end indefinite-length items are fine, including as the key position of a map
+	let ok = [0x9F, 0xBF, 0xFF, 0x9F, 0xFF, 0xFF];
+	assert_eq!("[{}, []]", cborg::decode_slice(&ok).unwrap().to_diagnostic());
+}
+
+#[test]
+fn simple_value_test() {
+	use cborg::Simple;
+	use core::convert::TryFrom;
+
+	// Two-byte simple values must be 32 or more
+	let err = cborg::decode_slice(&[0xF8, 0x10]).unwrap_err();
+	assert_eq!(&ErrorKind::UnexpectedValue, err.kind());
+	assert_eq!(
+		"simple value 16 in two-byte form, which needs 32 or more",
+		err.message()
+	);
+	assert!(cborg::decode_slice(&[0xF8, 0x1F]).is_err());
+	assert_eq!(
+		Value::Simple(Simple::try_from(32).unwrap()),
+		cborg::decode_slice(&[0xF8, 0x20]).unwrap()
+	);
+	assert_eq!(
+		Value::Simple(Simple::try_from(255).unwrap()),
+		cborg::decode_slice(&[0xF8, 0xFF]).unwrap()
+	);
+	assert_eq!(
+		Value::Simple(Simple::try_from(16).unwrap()),
+		cborg::decode_slice(&[0xF0]).unwrap()
+	);
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_slice(&[0xF8]).unwrap_err().kind()
+	);
+
+	// Each initial byte of major type 7 that is not a simple value or float
+	for b in 0xFCu8..=0xFE {
+		let err = cborg::decode_slice(&[b]).unwrap_err();
+		assert_eq!(&ErrorKind::ReservedAdditionalInfo, err.kind(), "{:02X}", b);
+		assert_eq!(
+			&ErrorKind::ReservedAdditionalInfo,
+			cborg::decode_with_stringrefs(&[b]).unwrap_err().kind()
+		);
+	}
+	assert_eq!(
+		&ErrorKind::InvalidBreak,
+		cborg::decode_slice(&[0xFF]).unwrap_err().kind()
+	);
+
+	// The other decoders agree on the two-byte form
+	assert!(cborg::decode_with_stringrefs(&[0xF8, 0x10]).is_err());
+	assert_eq!(ErrorKind::UnexpectedValue, cborg::decode_lossy(&[0xF8, 0x10]).1[0].kind);
+	assert!(cborg::canonicalize(&[0xF8, 0x10]).is_err());
+}
+
+#[test]
+fn fuzz_regression_test() {
+	// Inputs found by the fuzz targets. Each must give an error rather than panic.
+	let crashers: &[&[u8]] = &[
+		// Negative integers below i64::MIN overflowed
+		&[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+		&[0x3B, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+		&[0x81, 0x3B, 0xD9, 0x01, 0x00, 0x82, 0x63, 0x61, 0x61, 0x61],
+		&[0xA1, 0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00],
+	];
+	for bytes in crashers {
+		let err = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(&ErrorKind::LengthOverflow, err.kind(), "{:02X?}", bytes);
+		assert!(cborg::decode_to::<HashMap<Value, Value>, _>(*bytes).is_err());
+		assert!(cborg::decode_with_stringrefs(bytes).is_err());
+		assert!(cborg::canonicalize(bytes).is_err());
+		let mut v = Value::Simple(cborg::Simple::Null);
+		assert!(cborg::decode_reuse(bytes, &mut v).is_err());
+	}
+	assert_eq!(
+		Value::Negative(i64::MIN),
+		cborg::decode_slice(&[0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap()
+	);
+
+	// Truncated data, huge lengths, invalid text and deep nesting
+	let malformed: &[&[u8]] = &[
+		&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+		&[0xBB, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00],
+		&[0x7B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+		&[0x62, 0xC3, 0x28],
+		&[0x7F, 0x61, 0xC3, 0x61, 0xA9, 0xFF],
+		&[0xF8, 0x10],
+		&[0xC2, 0xFF],
+		&[0xBF, 0x01, 0xFF],
+		&[0x5F, 0x61, 0x61, 0xFF],
+		&[0x1B, 0x00],
+		&[0xFB, 0x00, 0x00],
+	];
+	let canonical = cborg::DecodeOptions::new().require_canonical(true);
+	for bytes in malformed {
+		assert!(cborg::decode_slice(bytes).is_err(), "{:02X?}", bytes);
+		assert!(cborg::decode_to::<HashMap<Value, Value>, _>(*bytes).is_err());
+		assert!(cborg::decode_with_stringrefs(bytes).is_err(), "{:02X?}", bytes);
+		assert!(!cborg::decode_lossy(bytes).1.is_empty(), "{:02X?}", bytes);
+		assert!(!cborg::decode_lenient(bytes).1.is_empty(), "{:02X?}", bytes);
+		assert!(cborg::canonicalize(bytes).is_err(), "{:02X?}", bytes);
+		assert!(cborg::decode_slice_with(bytes, &canonical).is_err(), "{:02X?}", bytes);
+		let mut v = Value::Simple(cborg::Simple::Null);
+		assert!(cborg::decode_reuse(bytes, &mut v).is_err(), "{:02X?}", bytes);
+	}
+
+	// Deep arrays, maps and tag chains reach the limits in every entry point
+	let deep: &[(u8, ErrorKind)] = &[
+		(0x81, ErrorKind::DepthLimitExceeded),
+		(0x9F, ErrorKind::DepthLimitExceeded),
+		(0xA1, ErrorKind::DepthLimitExceeded),
+		(0xC1, ErrorKind::TagNestingTooDeep),
+	];
+	for (b, kind) in deep {
+		let bytes = vec![*b; 100_000];
+		assert_eq!(kind, cborg::decode_slice(&bytes).unwrap_err().kind(), "{:02X}", b);
+		assert_eq!(
+			kind,
+			cborg::decode_with_stringrefs(&bytes).unwrap_err().kind(),
+			"{:02X}",
+			b
+		);
+		assert_eq!(kind, &cborg::decode_lossy(&bytes).1[0].kind, "{:02X}", b);
+		assert_eq!(kind, cborg::decode_lenient(&bytes).1[0].kind(), "{:02X}", b);
+		assert_eq!(kind, cborg::canonicalize(&bytes).unwrap_err().kind(), "{:02X}", b);
+		assert_eq!(kind, cborg::decode_strict(&bytes).unwrap_err().kind(), "{:02X}", b);
+		let mut v = Value::Simple(cborg::Simple::Null);
+		assert_eq!(
+			kind,
+			cborg::decode_reuse(&bytes, &mut v).unwrap_err().kind(),
+			"{:02X}",
+			b
+		);
+		// The deterministic-form check finds indefinite lengths before the depth limit
+		let err = cborg::decode_slice_with(&bytes, &canonical).unwrap_err();
+		if *b == 0x9F {
+			assert_eq!(&ErrorKind::NotCanonical, err.kind());
+		} else {
+			assert_eq!(kind, err.kind(), "{:02X}", b);
+		}
+	}
+}
+
+#[test]
+fn strict_mode_test() {
+	use cborg::DecodeOptions;
+
+	let lenient = DecodeOptions::new();
+	let strict = DecodeOptions::new().require_canonical(true);
+
+	// 5 with a one-byte argument is only rejected when canonical form is required
+	assert_eq!(
+		Value::Unsigned(5),
+		cborg::decode_slice_with(&[0x18, 0x05], &lenient).unwrap()
+	);
+	let err = cborg::decode_slice_with(&[0x18, 0x05], &strict).unwrap_err();
+	assert_eq!(&ErrorKind::NotCanonical, err.kind());
+	assert_eq!(
+		"argument 5 of major type 0 not in shortest form at offset 0",
+		err.message()
+	);
+
+	let rejected: &[(&[u8], &str)] = &[
+		(
+			&[0x82, 0x01, 0x59, 0x00, 0x01, 0x00],
+			"argument 1 of major type 2 not in shortest form at offset 2",
+		),
+		(&[0x9F, 0x01, 0xFF], "indefinite-length item at offset 0"),
+		(&[0x81, 0x7F, 0xFF], "indefinite-length item at offset 1"),
+		(
+			&[0xFA, 0x3F, 0xC0, 0x00, 0x00],
+			"float 1.5 not in its shortest form at offset 0",
+		),
+		(&[0xA2, 0x02, 0x00, 0x01, 0x00], "map key out of order at offset 3"),
+		(&[0xA2, 0x01, 0x00, 0x01, 0x00], "duplicate map key at offset 3"),
+		(
+			&[0xD8, 0x01, 0x00],
+			"argument 1 of major type 6 not in shortest form at offset 0",
+		),
+	];
+	for (bytes, msg) in rejected {
+		assert!(cborg::decode_slice_with(bytes, &lenient).is_ok(), "{:02X?}", bytes);
+		let err = cborg::decode_slice_with(bytes, &strict).unwrap_err();
+		assert_eq!(&ErrorKind::NotCanonical, err.kind(), "{:02X?}", bytes);
+		assert_eq!(*msg, err.message());
+	}
+
+	// Canonical data decodes the same either way
+	let canonical = cborg::canonicalize(&TEST_DATA_DEFINITE).unwrap();
+	assert_eq!(
+		cborg::decode_slice_with(&canonical, &lenient).unwrap(),
+		cborg::decode_slice_with(&canonical, &strict).unwrap()
+	);
+	assert!(cborg::decode_slice_with(&[0xC1, 0xF9, 0x3E, 0x00], &strict).is_ok());
+
+	// Malformed input keeps its own error kind
+	assert_eq!(
+		&ErrorKind::InvalidUtf8,
+		cborg::decode_slice_with(&[0x62, 0xC3, 0x28], &strict)
+			.unwrap_err()
+			.kind()
+	);
+	assert_eq!(
+		&ErrorKind::InsufficientBytes,
+		cborg::decode_slice_with(&[0x82, 0x01], &strict).unwrap_err().kind()
+	);
+	let deep = [0x81; 1000];
+	assert_eq!(
+		&ErrorKind::DepthLimitExceeded,
+		cborg::decode_slice_with(&deep, &strict).unwrap_err().kind()
+	);
+
+	// The check does not recurse, so a raised depth limit cannot overflow the stack. The input
+	// ends inside its innermost container.
+	let unlimited = DecodeOptions::new().require_canonical(true).max_depth(1_000_000);
+	for b in &[0x81, 0xA1] {
+		let deep = vec![*b; 200_000];
+		let err = cborg::decode_slice_with(&deep, &unlimited).unwrap_err();
+		assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+	}
+}
+
+#[test]
+fn validate_test() {
+	// Good vectors validate to their full length, or to the first item's length
+	assert_eq!(TEST_DATA_DEFINITE.len(), cborg::validate(&TEST_DATA_DEFINITE).unwrap());
+	assert_eq!(
+		TEST_DATA_INDEFINITE.len(),
+		cborg::validate(&TEST_DATA_INDEFINITE).unwrap()
+	);
+	let mut two = TEST_DATA_INDEFINITE.to_vec();
+	two.extend_from_slice(&TEST_DATA_DEFINITE);
+	assert_eq!(TEST_DATA_INDEFINITE.len(), cborg::validate(&two).unwrap());
+	assert_eq!(1, cborg::validate(&[0x80, 0xFF]).unwrap());
+	assert_eq!(4, cborg::validate(&[0xBF, 0xA0, 0x80, 0xFF]).unwrap());
+	assert_eq!(4, cborg::validate(&[0xC1, 0xD8, 0x20, 0x00]).unwrap());
+
+	// Malformed inputs fail with the same kind as the decoder
+	let malformed: &[(&[u8], ErrorKind)] = &[
+		(&[], ErrorKind::InsufficientBytes),
+		(&[0x82, 0x01], ErrorKind::InsufficientBytes),
+		(
+			&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			ErrorKind::InsufficientBytes,
+		),
+		(
+			&[0x7B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			ErrorKind::InsufficientBytes,
+		),
+		(&[0x62, 0xC3, 0x28], ErrorKind::InvalidUtf8),
+		(&[0x7F, 0x61, 0xC3, 0x61, 0xA9, 0xFF], ErrorKind::InvalidUtf8),
+		(&[0x5F, 0x61, 0x61, 0xFF], ErrorKind::UnexpectedValue),
+		(&[0xF8, 0x10], ErrorKind::UnexpectedValue),
+		(&[0x1C], ErrorKind::ReservedAdditionalInfo),
+		(
+			&[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			ErrorKind::LengthOverflow,
+		),
+		(&[0xFF], ErrorKind::InvalidBreak),
+		(&[0x81, 0xFF], ErrorKind::InvalidBreak),
+		(&[0xBF, 0x01, 0xFF], ErrorKind::InvalidBreak),
+		(&[0x9F, 0xC2, 0xFF], ErrorKind::InvalidBreak),
+		(&[0x81; 200], ErrorKind::DepthLimitExceeded),
+		(&[0xC1; 20], ErrorKind::TagNestingTooDeep),
+	];
+	for (bytes, kind) in malformed {
+		let err = cborg::validate(bytes).unwrap_err();
+		assert_eq!(kind, err.kind(), "{:02X?}", bytes);
+		let decoded = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(decoded.kind(), err.kind(), "{:02X?}", bytes);
+		assert_eq!(decoded.message(), err.message(), "{:02X?}", bytes);
+	}
+}
+
+#[test]
+fn decode_with_test() {
+	use cborg::DecodeOptions;
+	use cborg::DuplicateKeyPolicy;
+	use cborg::TagPolicy;
+
+	// The default options decode like the functions without them
+	let default = DecodeOptions::default();
+	for bytes in &[&TEST_DATA_DEFINITE[..], &TEST_DATA_INDEFINITE[..]] {
+		let v = cborg::decode(*bytes).unwrap();
+		assert_eq!(v, cborg::decode_with(*bytes, &default).unwrap());
+		assert_eq!(v, cborg::decode_slice_with(bytes, &default).unwrap());
+		assert_eq!(
+			cborg::decode_to::<Value, _>(*bytes).unwrap(),
+			cborg::decode_to_with::<Value, _>(*bytes, &default).unwrap()
+		);
+	}
+
+	// Options apply through every entry point, including to iterators that are not slices
+	let opts = DecodeOptions::new()
+		.unknown_tags(TagPolicy::Strip)
+		.duplicate_keys(DuplicateKeyPolicy::Error)
+		.require_canonical(true);
+	let bytes = [0xA1, 0x61, 0x61, 0xD8, 0x64, 0x01]; // {"a": 100(1)}
+	let chained = || bytes[..3].iter().chain(bytes[3..].iter());
+	assert_eq!(
+		r#"{"a": 1}"#,
+		cborg::decode_with(chained(), &opts).unwrap().to_diagnostic()
+	);
+	let map: HashMap<String, u32> = cborg::decode_to_with(chained(), &opts).unwrap().unwrap();
+	assert_eq!(1, map["a"]);
+
+	let duplicate = [0xA2, 0x01, 0x02, 0x01, 0x03];
+	let opts = DecodeOptions::new().duplicate_keys(DuplicateKeyPolicy::Error);
+	assert_eq!(
+		&ErrorKind::DuplicateKey,
+		cborg::decode_with(&duplicate, &opts).unwrap_err().kind()
+	);
+	assert!(cborg::decode_to_with::<HashMap<u32, u32>, _>(&duplicate, &opts).is_err());
+	assert!(cborg::decode_to::<HashMap<u32, u32>, _>(&duplicate).is_ok());
+
+	let non_minimal = [0x81, 0x18, 0x05];
+	let opts = DecodeOptions::new().require_canonical(true);
+	let err = cborg::decode_with(non_minimal[..1].iter().chain(&non_minimal[1..]), &opts).unwrap_err();
+	assert_eq!(&ErrorKind::NotCanonical, err.kind());
+	assert!(cborg::decode_with(&non_minimal, &DecodeOptions::new()).is_ok());
+}
+
+#[test]
+fn negative_overflow_test() {
+	use cborg::DecodeOptions;
+
+	// -2^63 is the lowest negative integer a Value holds
+	let min = [0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+	assert_eq!(Value::Negative(i64::MIN), cborg::decode_slice(&min).unwrap());
+	assert_eq!(min.to_vec(), Value::Negative(i64::MIN).encode());
+	assert_eq!(Some(i64::MIN), cborg::decode_to::<i64, _>(&min).unwrap());
+	assert_eq!(min.to_vec(), cborg::canonicalize(&min).unwrap());
+
+	// -2^63 - 1 and -2^64 do not fit
+	let below: &[&[u8]] = &[
+		&[0x3B, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+		&[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+	];
+	for bytes in below {
+		let err = cborg::decode_slice(bytes).unwrap_err();
+		assert_eq!(&ErrorKind::LengthOverflow, err.kind());
+		assert_eq!("negative integer below i64::MIN", err.message());
+		assert_eq!(
+			&ErrorKind::LengthOverflow,
+			cborg::decode_with(*bytes, &DecodeOptions::new().require_canonical(true))
+				.unwrap_err()
+				.kind()
+		);
+		assert_eq!(&ErrorKind::LengthOverflow, cborg::validate(bytes).unwrap_err().kind());
+		assert_eq!(
+			&ErrorKind::LengthOverflow,
+			cborg::decode_with_stringrefs(bytes).unwrap_err().kind()
+		);
+		assert_eq!(
+			&ErrorKind::LengthOverflow,
+			cborg::canonicalize(bytes).unwrap_err().kind()
+		);
+		assert_eq!(ErrorKind::LengthOverflow, cborg::decode_lossy(bytes).1[0].kind);
+		let mut v = Value::Simple(cborg::Simple::Null);
+		assert_eq!(
+			&ErrorKind::LengthOverflow,
+			cborg::decode_reuse(bytes, &mut v).unwrap_err().kind()
+		);
+		assert!(cborg::decode_to::<i64, _>(*bytes).is_err());
+	}
+}
+
+#[test]
+fn length_header_test() {
+	// Lengths on either side of each change in argument width
+	let cases: &[(usize, &[u8])] = &[
+		(23, &[0x57]),
+		(24, &[0x58, 0x18]),
+		(255, &[0x58, 0xFF]),
+		(256, &[0x59, 0x01, 0x00]),
+		(65535, &[0x59, 0xFF, 0xFF]),
+		(65536, &[0x5A, 0x00, 0x01, 0x00, 0x00]),
+	];
+	for (len, header) in cases {
+		let bytes = Value::ByteString(vec![0; *len]).encode();
+		assert_eq!(*header, &bytes[..header.len()], "{}", len);
+		assert_eq!(header.len() + len, bytes.len());
+		let text = Value::Utf8String("a".repeat(*len)).encode();
+		assert_eq!(header[0] + 0x20, text[0]);
+		assert_eq!(&header[1..], &text[1..header.len()]);
+		let array = Value::Array(vec![Value::Unsigned(0); *len]).encode();
+		assert_eq!(header[0] + 0x40, array[0]);
+		assert_eq!(&header[1..], &array[1..header.len()]);
+		let sorted = Value::Array(vec![Value::Unsigned(0); *len]).encode_sorted_by(|a, b| a.cmp(b));
+		assert_eq!(array, sorted);
+		assert_eq!(Value::ByteString(vec![0; *len]), cborg::decode_slice(&bytes).unwrap());
+	}
+
+	// Headers of lengths too large to build, written ahead of an empty iterator
+	let cases: &[(u64, &[u8])] = &[
+		(0xFFFF_FFFF, &[0x9A, 0xFF, 0xFF, 0xFF, 0xFF]),
+		(0x1_0000_0000, &[0x9B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]),
+		(
+			0x1234_5678_9ABC_DEF0,
+			&[0x9B, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0],
+		),
+		(u64::MAX, &[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+	];
+	for (len, header) in cases {
+		let mut out = Vec::new();
+		assert!(cborg::encode_array_from_iter(&mut out, Vec::<u32>::new(), Some(*len)).is_err());
+		assert_eq!(*header, &out[..]);
+	}
+}
+
+#[test]
+fn integer_width_test() {
+	let cases: &[(u64, &[u8])] = &[
+		(23, &[0x17]),
+		(24, &[0x18, 0x18]),
+		(255, &[0x18, 0xFF]),
+		(256, &[0x19, 0x01, 0x00]),
+		(65535, &[0x19, 0xFF, 0xFF]),
+		(65536, &[0x1A, 0x00, 0x01, 0x00, 0x00]),
+		(4294967295, &[0x1A, 0xFF, 0xFF, 0xFF, 0xFF]),
+		(4294967296, &[0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]),
+	];
+	for (x, bytes) in cases {
+		let v = Value::Unsigned(*x);
+		assert_eq!(bytes.to_vec(), v.encode(), "{}", x);
+		assert_eq!(v, cborg::decode_slice(bytes).unwrap());
+		assert!(cborg::is_canonical(bytes));
+
+		// -1 - x has the same argument under major type 1
+		let neg = Value::Negative(-1 - *x as i64);
+		let mut expected = bytes.to_vec();
+		expected[0] += 0x20;
+		assert_eq!(expected, neg.encode(), "-1 - {}", x);
+		assert_eq!(neg, cborg::decode_slice(&expected).unwrap());
+
+		// and so does tag x
+		let tag = Value::Tag(*x, Box::new(Value::Unsigned(0)));
+		let mut expected = bytes.to_vec();
+		expected[0] += 0xC0;
+		expected.push(0x00);
+		assert_eq!(expected, tag.encode(), "{}(0)", x);
+		assert_eq!(expected, tag.encode_sorted_by(|a, b| a.cmp(b)));
+	}
+}
+
+#[test]
+fn simple_encode_test() {
+	use cborg::Simple;
+	use core::convert::TryFrom;
+
+	let cases: &[(u8, &[u8])] = &[(0, &[0xE0]), (19, &[0xF3]), (32, &[0xF8, 0x20]), (255, &[0xF8, 0xFF])];
+	for (x, bytes) in cases {
+		let simple = Simple::try_from(*x).unwrap();
+		match &simple {
+			Simple::Unassigned(n) => assert_eq!(*x, n.get()),
+			_ => panic!("simple({}) is not unassigned", x),
+		}
+		let v = Value::Simple(simple.clone());
+		assert_eq!(bytes.to_vec(), v.encode(), "simple({})", x);
+		assert_eq!(v, cborg::decode_slice(bytes).unwrap());
+		assert_eq!(v, cborg::decode_slice(&v.encode_canonical()).unwrap());
+
+		// Inside a map the entries around it still decode
+		let map = Value::map(vec![(Value::Simple(simple), "a"), (Value::Unsigned(1), "b")]);
+		let encoded = map.encode();
+		assert_eq!(map, cborg::decode_exact(&encoded).unwrap());
+		assert_eq!(map, cborg::decode_with_stringrefs(&encoded).unwrap());
+	}
+
+	// 20 to 23 are only ever the named values, so equality agrees with ordering, and 24 to 31
+	// have no encoding and cannot be built
+	assert!(Simple::try_from(20) == Ok(Simple::False));
+	assert!(Simple::try_from(23) == Ok(Simple::Undefined));
+	assert_eq!(vec![0xF6], Simple::try_from(22).unwrap().encode());
+	for x in 24..=31 {
+		assert!(Simple::try_from(x).is_err());
+	}
+}
+
+#[test]
+fn float_key_test() {
+	use core::cmp::Ordering;
+
+	let nan = Value::Float(f64::NAN);
+	let zero = Value::Float(0.0);
+	let neg_zero = Value::Float(-0.0);
+	assert_eq!(nan, Value::Float(f64::NAN));
+	assert_ne!(zero, neg_zero);
+	assert_ne!(nan, Value::Float(-f64::NAN));
+	assert_eq!(Ordering::Equal, nan.cmp(&Value::Float(f64::NAN)));
+	assert_ne!(Ordering::Equal, zero.cmp(&neg_zero));
+
+	let mut map = HashMap::<Value, Value>::new();
+	map.insert(nan.clone(), Value::from("nan"));
+	map.insert(zero.clone(), Value::from("zero"));
+	map.insert(neg_zero.clone(), Value::from("negative zero"));
+	assert_eq!(3, map.len());
+	assert_eq!(Some(&Value::from("nan")), map.get(&Value::Float(f64::NAN)));
+	assert_eq!(Some(&Value::from("zero")), map.get(&Value::Float(0.0)));
+	assert_eq!(Some(&Value::from("negative zero")), map.get(&Value::Float(-0.0)));
+	map.insert(Value::Float(f64::NAN), Value::from("nan again"));
+	assert_eq!(3, map.len());
+
+	// Keys decoded from any float width
+	let bytes = [
+		0xA3, 0xF9, 0x7E, 0x00, 0x01, 0xF9, 0x00, 0x00, 0x02, 0xF9, 0x80, 0x00, 0x03,
+	];
+	let decoded = cborg::decode_slice(&bytes).unwrap();
+	let map = decoded.get_hash_map().unwrap();
+	assert_eq!(3, map.len());
+	assert_eq!(Some(&Value::Unsigned(1)), map.get(&nan));
+	assert_eq!(Some(&Value::Unsigned(2)), map.get(&zero));
+	assert_eq!(Some(&Value::Unsigned(3)), map.get(&neg_zero));
+	assert_eq!(decoded, cborg::decode_slice(&decoded.encode()).unwrap());
+}
+
+#[test]
+fn error_context_test() {
+	// TEST_DATA_DEFINITE cut short at several depths
+	let cases: &[(usize, Option<&str>)] = &[
+		(1, None),
+		(5, Some("value for key 555")),
+		(20, Some("value for key 555, map key")),
+		(60, Some(r#"value for key 555, value for key "utf8string""#)),
+		(100, Some(r#"value for key 555, value for key "long string""#)),
+		(400, Some(r#"value for key 555, value for key "unsigned""#)),
+		(425, Some("value for key 777, array index 2")),
+		(437, Some("value for key 777, array index 3")),
+	];
+	for (len, context) in cases {
+		let err = cborg::decode_slice(&TEST_DATA_DEFINITE[..*len]).unwrap_err();
+		assert_eq!(&ErrorKind::InsufficientBytes, err.kind());
+		assert_eq!(*context, err.context(), "{}", len);
+	}
+
+	// Display adds the context after the message
+	let err = cborg::decode_slice(&TEST_DATA_DEFINITE[..100]).unwrap_err();
+	assert_eq!(
+		r#"Insufficient bytes: length 296 needs more than the 5 bytes remaining (at value for key 555, value for key "long string")"#,
+		err.to_string()
+	);
+	let err = cborg::decode_slice(&[0x82, 0x01, 0x18]).unwrap_err();
+	assert_eq!("Insufficient bytes (at array index 1)", err.to_string());
+	// A length that runs past the end fails at the array's own position
+	let err = cborg::decode_slice(&[0x82, 0x01]).unwrap_err();
+	assert_eq!(None, err.context());
+	let err = cborg::decode_slice(&[0x1C]).unwrap_err();
+	assert_eq!(None, err.context());
+
+	// Tags and indefinite-length items
+	let err = cborg::decode_slice(&[0xBF, 0x61, 0x61, 0xC1, 0x9F, 0x01, 0x62, 0xC3, 0x28]).unwrap_err();
+	assert_eq!(&ErrorKind::InvalidUtf8, err.kind());
+	assert_eq!(
+		Some(r#"value for key "a", content of tag 1, array index 1"#),
+		err.context()
+	);
+	let err = cborg::decode_slice(&[0xA1, 0x82, 0x01, 0x18]).unwrap_err();
+	assert_eq!(Some("map key, array index 1"), err.context());
+	let err = cborg::decode_with(
+		&[0x81, 0xD8, 0x64, 0x00],
+		&cborg::DecodeOptions::new().unknown_tags(cborg::TagPolicy::Error),
+	)
+	.unwrap_err();
+	assert_eq!(&ErrorKind::UnknownTag(100), err.kind());
+	assert_eq!(Some("array index 0"), err.context());
+}
+
+#[test]
+fn decode_owned_test() {
+	// Yields one owned byte at a time, with no length known up front
+	struct OneByOne(Vec<u8>, usize);
+	impl Iterator for OneByOne {
+		type Item = u8;
+		fn next(&mut self) -> Option<u8> {
+			let b = self.0.get(self.1).copied();
+			self.1 += 1;
+			b
+		}
+	}
+
+	for bytes in &[&TEST_DATA_DEFINITE[..], &TEST_DATA_INDEFINITE[..]] {
+		let expected = cborg::decode(*bytes).unwrap();
+		assert_eq!(expected, cborg::decode_owned(OneByOne(bytes.to_vec(), 0)).unwrap());
+		assert_eq!(expected, cborg::decode_owned(bytes.to_vec()).unwrap());
+	}
+
+	// From a reader
+	let reader = std::io::Cursor::new(TEST_DATA_DEFINITE.to_vec());
+	let bytes = std::io::Read::bytes(reader).map(|b| b.unwrap());
+	assert_eq!(
+		cborg::decode(&TEST_DATA_DEFINITE).unwrap(),
+		cborg::decode_owned(bytes).unwrap()
+	);
+
+	// Errors are of the same kind as for borrowed bytes, though without a known length a string
+	// that runs past the end fails when its content runs out rather than at its header
+	for len in &[1, 20, 100, 437] {
+		let owned = cborg::decode_owned(OneByOne(TEST_DATA_DEFINITE[..*len].to_vec(), 0)).unwrap_err();
+		let borrowed = cborg::decode(&TEST_DATA_DEFINITE[..*len]).unwrap_err();
+		assert_eq!(borrowed.kind(), owned.kind());
+	}
+	let owned = cborg::decode_owned(OneByOne(TEST_DATA_DEFINITE[..100].to_vec(), 0)).unwrap_err();
+	assert_eq!(
+		Some(r#"value for key 555, value for key "long string""#),
+		owned.context()
+	);
+	let mut iter = OneByOne(vec![0x81, 0x62, 0xC3, 0x28], 0);
+	assert_eq!(
+		&ErrorKind::InvalidUtf8,
+		cborg::decode_owned(&mut iter).unwrap_err().kind()
+	);
+}
+
+#[test]
+fn decode_lenient_test() {
+	fn keys(v: &Value) -> Vec<String> {
+		match v {
+			Value::Map(m) => m.iter().map(|kv| kv.key.to_diagnostic()).collect(),
+			_ => panic!("not a map"),
+		}
+	}
+	fn inner(v: &Value) -> &Value {
+		match v {
+			Value::Map(m) => &m[0].val,
+			_ => panic!("not a map"),
+		}
+	}
+
+	// Undamaged input decodes as usual
+	let (value, errors) = cborg::decode_lenient(&TEST_DATA_DEFINITE);
+	assert_eq!(cborg::decode(&TEST_DATA_DEFINITE).unwrap(), value.unwrap());
+	assert!(errors.is_empty());
+
+	// Invalid UTF-8 in "utf8string": only that entry is left out
+	let mut bytes = TEST_DATA_DEFINITE;
+	bytes[50] = 0xFF;
+	let (value, errors) = cborg::decode_lenient(&bytes);
+	let value = value.unwrap();
+	assert_eq!(vec!["555", "777"], keys(&value));
+	assert_eq!(
+		vec![
+			r#""float""#,
+			r#""bytestring""#,
+			r#""long string""#,
+			r#""unsigned""#,
+			r#""negative""#
+		],
+		keys(inner(&value))
+	);
+	assert_eq!(1, errors.len());
+	assert_eq!(&ErrorKind::InvalidUtf8, errors[0].kind());
+	assert_eq!(
+		Some(r#"value for key 555, value for key "utf8string""#),
+		errors[0].context()
+	);
+
+	// A reserved header in place of the "utf8string" value: the keys before it survive and the
+	// parse ends there
+	let mut bytes = TEST_DATA_DEFINITE;
+	bytes[48] = 0x7C;
+	let (value, errors) = cborg::decode_lenient(&bytes);
+	let value = value.unwrap();
+	assert_eq!(vec!["555"], keys(&value));
+	assert_eq!(vec![r#""float""#, r#""bytestring""#], keys(inner(&value)));
+	assert_eq!(1, errors.len());
+	assert_eq!(&ErrorKind::ReservedAdditionalInfo, errors[0].kind());
+
+	// Truncated strings keep what was read, up to the last whole character of text
+	let (value, errors) = cborg::decode_lenient(&TEST_DATA_DEFINITE[..55]);
+	let value = value.unwrap();
+	assert_eq!(
+		r#"{555: {"float": 2.5, "bytestring": h'0102030405', "utf8string": "你"}}"#,
+		value.to_diagnostic()
+	);
+	assert_eq!(&ErrorKind::InsufficientBytes, errors[0].kind());
+	let (value, _) = cborg::decode_lenient(&TEST_DATA_DEFINITE[..36]);
+	assert_eq!(
+		r#"{555: {"float": 2.5, "bytestring": h'01020304'}}"#,
+		value.unwrap().to_diagnostic()
+	);
+
+	// Nothing is left when the first header is unreadable
+	let (value, errors) = cborg::decode_lenient(&[0xBC, 0x01]);
+	assert!(value.is_none());
+	assert_eq!(&ErrorKind::ReservedAdditionalInfo, errors[0].kind());
+	assert_eq!(None, errors[0].context());
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn decode_bytes_test() {
+	use bytes::Bytes;
+
+	// {"payload": h'...' (1 MB), "n": 1}
+	let payload: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+	let v = Value::map(vec![
+		("payload", Value::bytes(payload.clone())),
+		("n", Value::Unsigned(1)),
+	]);
+	let encoded = v.encode();
+	let buf = Bytes::from(encoded.clone());
+	let start = encoded.len() - payload.len() - 3;
+
+	let decoded = cborg::decode_bytes(buf.clone()).unwrap();
+	assert_eq!(v, decoded);
+	assert_eq!(cborg::decode_slice(&encoded).unwrap(), decoded);
+	let shared = match &decoded {
+		Value::Map(m) => match &m[0].val {
+			Value::SharedBytes(b) => b.clone(),
+			_ => panic!("not shared"),
+		},
+		_ => panic!("not a map"),
+	};
+	assert_eq!(buf[start..].as_ptr(), shared.as_ptr());
+	assert_eq!(payload.len(), shared.len());
+
+	// Converting to Bytes shares the buffer too, and encoding writes the content back as it was
+	let b: Bytes = cborg::decode_bytes(buf.slice(start - 5..start + payload.len()))
+		.unwrap()
+		.into_type()
+		.unwrap();
+	assert_eq!(buf[start..].as_ptr(), b.as_ptr());
+	assert_eq!(encoded, decoded.encode());
+	assert_eq!(
+		encoded,
+		Value::map(vec![("payload", Value::from(shared)), ("n", Value::Unsigned(1))]).encode()
+	);
+
+	// Everything else decodes as decode_slice has it
+	for bytes in &[&TEST_DATA_DEFINITE[..], &TEST_DATA_INDEFINITE[..]] {
+		let v = cborg::decode_bytes(Bytes::copy_from_slice(bytes)).unwrap();
+		assert_eq!(cborg::decode_slice(bytes).unwrap(), v);
+		assert_eq!(cborg::decode_slice(bytes).unwrap().to_diagnostic(), v.to_diagnostic());
+	}
+	for len in &[0, 1, 20, 100, 437] {
+		let err = cborg::decode_bytes(Bytes::copy_from_slice(&TEST_DATA_DEFINITE[..*len])).unwrap_err();
+		assert_eq!(
+			cborg::decode_slice(&TEST_DATA_DEFINITE[..*len]).unwrap_err().kind(),
+			err.kind()
+		);
+	}
+
+	// Tags over byte strings read their content from shared bytes as from copied ones
+	let shared = |bytes: &[u8]| cborg::decode_bytes(Bytes::copy_from_slice(bytes)).unwrap();
+	// 2(h'010000000000000000') and 3(h'010000000000000000')
+	let big = [0xC2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+	assert_eq!(Some(1u128 << 64), cborg::FromValue::from_ref(&shared(&big)));
+	assert_eq!(Some(1i128 << 64), cborg::FromValue::from_ref(&shared(&big)));
+	let mut neg = big;
+	neg[0] = 0xC3;
+	assert_eq!(Some(-1 - (1i128 << 64)), cborg::FromValue::from_ref(&shared(&neg)));
+	// 4([-2, 2(h'010000000000000000')])
+	let decimal = [0xC4, 0x82, 0x21, 0xC2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+	let v = shared(&decimal);
+	assert_eq!(
+		Some((1i128 << 64, -2)),
+		v.get_decimal().map(|d| (d.mantissa, d.exponent))
+	);
+	// 24(h'820102'), also read without the tag
+	let embedded = [0xD8, 0x18, 0x43, 0x82, 0x01, 0x02];
+	let v = shared(&embedded);
+	assert_eq!("[1, 2]", v.decode_embedded(false).unwrap().to_diagnostic());
+	assert_eq!(
+		"[1, 2]",
+		shared(&embedded[2..]).decode_embedded(true).unwrap().to_diagnostic()
+	);
+	// 37(h'00112233445566778899aabbccddeeff')
+	let mut uuid = vec![0xD8, 0x25, 0x50];
+	uuid.extend((0..16u8).map(|i| i * 0x11));
+	// 64(h'0102') and 72(h'ff02'), uint8 and sint8 typed arrays
+	let typed: &[&[u8]] = &[&[0xD8, 0x40, 0x42, 0x01, 0x02], &[0xD8, 0x48, 0x42, 0xFF, 0x02]];
+	assert_eq!("[1, 2]", shared(typed[0]).expand_typed_array().unwrap().to_diagnostic());
+	assert_eq!(
+		"[-1, 2]",
+		shared(typed[1]).expand_typed_array().unwrap().to_diagnostic()
+	);
+	for bytes in [&big[..], &neg, &decimal, &embedded, &uuid].iter().chain(typed) {
+		let v = shared(bytes);
+		assert!(v.check_tags().is_ok(), "{}", v.to_diagnostic());
+		assert_eq!(cborg::decode_slice(bytes).unwrap(), v);
+	}
+	#[cfg(feature = "uuid")]
+	{
+		let id: uuid::Uuid = cborg::FromValue::from_ref(&shared(&uuid)).unwrap();
+		assert_eq!(&uuid[3..], id.as_bytes());
+		let id: Option<uuid::Uuid> = cborg::FromValue::from_ref(&shared(&uuid[2..]));
+		assert!(id.is_some());
+	}
+}
+
+#[test]
+fn size_limits_test() {
+	use cborg::DecodeOptions;
+
+	let kind = |bytes: &[u8], opts: &DecodeOptions| *cborg::decode_slice_with(bytes, opts).unwrap_err().kind();
+
+	// [1, 2, 3] and [_ 1, 2, 3]
+	let definite = [0x83, 0x01, 0x02, 0x03];
+	let indefinite = [0x9F, 0x01, 0x02, 0x03, 0xFF];
+	let opts = DecodeOptions::new().max_array_len(3);
+	assert!(cborg::decode_slice_with(&definite, &opts).is_ok());
+	assert!(cborg::decode_slice_with(&indefinite, &opts).is_ok());
+	let opts = DecodeOptions::new().max_array_len(2);
+	assert_eq!(ErrorKind::ArrayTooLong { len: 3 }, kind(&definite, &opts));
+	assert_eq!(ErrorKind::ArrayTooLong { len: 3 }, kind(&indefinite, &opts));
+	// The declared length fails before the missing elements are noticed
+	assert_eq!(
+		ErrorKind::ArrayTooLong { len: u64::MAX },
+		kind(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &opts)
+	);
+
+	// {1: 2, 3: 4} and {_ 1: 2, 3: 4}
+	let definite = [0xA2, 0x01, 0x02, 0x03, 0x04];
+	let indefinite = [0xBF, 0x01, 0x02, 0x03, 0x04, 0xFF];
+	let opts = DecodeOptions::new().max_map_len(2);
+	assert!(cborg::decode_slice_with(&definite, &opts).is_ok());
+	assert!(cborg::decode_slice_with(&indefinite, &opts).is_ok());
+	let opts = DecodeOptions::new().max_map_len(1);
+	assert_eq!(ErrorKind::MapTooLong { len: 2 }, kind(&definite, &opts));
+	assert_eq!(ErrorKind::MapTooLong { len: 2 }, kind(&indefinite, &opts));
+	// The array limit does not apply to maps, nor the map limit to arrays
+	assert!(cborg::decode_slice_with(&definite, &DecodeOptions::new().max_array_len(0)).is_ok());
+	assert!(cborg::decode_slice_with(&[0x82, 0x01, 0x02], &opts).is_ok());
+
+	// "hello", (_ "hel", "lo") and h'0102030405', (_ h'0102', h'030405')
+	let strings: [&[u8]; 4] = [
+		&[0x65, b'h', b'e', b'l', b'l', b'o'],
+		&[0x7F, 0x63, b'h', b'e', b'l', 0x62, b'l', b'o', 0xFF],
+		&[0x45, 0x01, 0x02, 0x03, 0x04, 0x05],
+		&[0x5F, 0x42, 0x01, 0x02, 0x43, 0x03, 0x04, 0x05, 0xFF],
+	];
+	for bytes in &strings {
+		assert!(cborg::decode_slice_with(bytes, &DecodeOptions::new().max_string_len(5)).is_ok());
+		assert_eq!(
+			ErrorKind::StringTooLong { len: 5 },
+			kind(bytes, &DecodeOptions::new().max_string_len(4))
+		);
+	}
+	// Nested items fail with their position
+	let opts = DecodeOptions::new().max_string_len(3);
+	let err = cborg::decode_slice_with(&TEST_DATA_DEFINITE, &opts).unwrap_err();
+	assert_eq!(&ErrorKind::StringTooLong { len: 5 }, err.kind());
+	assert_eq!(Some("value for key 555, map key"), err.context());
+
+	// The defaults are unlimited
+	assert_eq!(
+		cborg::decode(&TEST_DATA_INDEFINITE).unwrap(),
+		cborg::decode_slice_with(&TEST_DATA_INDEFINITE, &DecodeOptions::new()).unwrap()
+	);
+}
+
+#[test]
+fn encode_to_writer_test() {
+	use std::io;
+
+	// Accepts at most `max` bytes per write, and fails once `limit` bytes have been written
+	struct Short {
+		out: Vec<u8>,
+		max: usize,
+		limit: usize,
+	}
+	impl io::Write for Short {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			if self.out.len() >= self.limit {
+				return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+			}
+			let n = buf.len().min(self.max).min(self.limit - self.out.len());
+			self.out.extend_from_slice(&buf[..n]);
+			Ok(n)
+		}
+		fn flush(&mut self) -> io::Result<()> { Ok(()) }
+	}
+
+	let long = Value::Array(vec![
+		Value::bytes(vec![7; 20000]),
+		Value::from("x".repeat(9000)),
+		Value::Unsigned(1),
+	]);
+	let values = vec![
+		cborg::decode(&TEST_DATA_DEFINITE).unwrap(),
+		cborg::decode(&TEST_DATA_INDEFINITE).unwrap(),
+		Value::Array((0..5000u64).map(Value::Unsigned).collect()),
+		long,
+	];
+	for v in &values {
+		let mut out = Vec::new();
+		v.encode_to(&mut out).unwrap();
+		assert_eq!(v.encode(), out);
+
+		let mut out = Vec::new();
+		cborg::encode_to_writer(v, &mut out).unwrap();
+		assert_eq!(v.encode(), out);
+
+		let mut short = Short {
+			out: Vec::new(),
+			max: 3,
+			limit: usize::MAX,
+		};
+		v.encode_to(&mut short).unwrap();
+		assert_eq!(v.encode(), short.out);
+	}
+	assert_eq!(TEST_DATA_DEFINITE.to_vec(), {
+		let mut out = Vec::new();
+		values[0].encode_to(&mut out).unwrap();
+		out
+	});
+
+	// Errors from the writer are returned, with what was written before them left in place
+	let mut failing = Short {
+		out: Vec::new(),
+		max: 100,
+		limit: 10,
+	};
+	let err = values[3].encode_to(&mut failing).unwrap_err();
+	assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+	assert_eq!(values[3].encode()[..10], failing.out[..]);
+}
+
+#[test]
+fn encode_into_test() {
+	let first = cborg::decode(&TEST_DATA_DEFINITE).unwrap();
+	let second = cborg::decode(&TEST_DATA_INDEFINITE).unwrap();
+
+	let mut buf = Vec::new();
+	first.encode_into(&mut buf);
+	second.encode_into(&mut buf);
+	assert_eq!([first.encode(), second.encode()].concat(), buf);
+
+	// Existing contents are kept, and the buffer can be cleared and reused
+	let mut buf = vec![0xAA, 0xBB];
+	cborg::encode_into(&vec![1u32, 2], &mut buf);
+	cborg::encode_into("x", &mut buf);
+	assert_eq!(vec![0xAA, 0xBB, 0x82, 0x01, 0x02, 0x61, b'x'], buf);
+	buf.clear();
+	first.encode_into(&mut buf);
+	assert_eq!(TEST_DATA_DEFINITE.to_vec(), buf);
+}
+
+#[test]
+fn encoded_size_test() {
+	use core::convert::TryFrom;
+
+	use cborg::RawValue;
+	use cborg::Simple;
+
+	let mut values = vec![
+		cborg::decode(&TEST_DATA_DEFINITE).unwrap(),
+		cborg::decode(&TEST_DATA_INDEFINITE).unwrap(),
+		Value::Float(1.5),
+		Value::Float(f64::NAN),
+		Value::Raw(RawValue::new(vec![0x9F, 0x01, 0xFF]).unwrap()),
+		Value::map(vec![("a", Value::Array(vec![])), ("b", Value::Simple(Simple::Null))]),
+	];
+	// Every header width: 1, 2, 3, 5 and 9 bytes
+	for &n in &[0, 23, 24, 255, 256, 65535, 65536, 0xFFFF_FFFF, 0x1_0000_0000, u64::MAX] {
+		values.push(Value::Unsigned(n));
+		values.push(Value::Negative(-1 - (n >> 1) as i64));
+		values.push(Value::Tag(n, Box::new(Value::Unsigned(n))));
+	}
+	for &len in &[0, 23, 24, 255, 256, 65535, 65536] {
+		values.push(Value::bytes(vec![1; len]));
+		values.push(Value::from("a".repeat(len)));
+		values.push(Value::Array(vec![Value::Unsigned(1); len]));
+		values.push(Value::Map(
+			(0..len as u64)
+				.map(|i| KeyVal {
+					key: Value::Unsigned(i),
+					val: Value::Float(0.0),
+				})
+				.collect::<Vec<_>>()
+				.into(),
+		));
+	}
+	for n in 0..=255u8 {
+		if let Ok(s) = Simple::try_from(n) {
+			values.push(Value::Simple(s));
+		}
+	}
+	values.push(Value::Array(values.clone()));
+
+	for v in &values {
+		assert_eq!(
+			v.encode().len(),
+			v.encoded_size(),
+			"{}",
+			v.to_diagnostic().chars().take(40).collect::<String>()
+		);
+	}
+}
+
+#[test]
+fn encode_to_slice_test() {
+	let nested = cborg::decode(&TEST_DATA_DEFINITE).unwrap();
+	let values = vec![
+		Value::Unsigned(500),
+		Value::Simple(cborg::Simple::True),
+		nested.clone(),
+		cborg::decode(&TEST_DATA_INDEFINITE).unwrap(),
+		Value::Array(vec![
+			nested.clone(),
+			Value::Tag(24, Box::new(Value::Array(vec![Value::Float(1.0)]))),
+		]),
+	];
+	for v in &values {
+		let expected = v.encode();
+		let n = expected.len();
+
+		// Exactly fitting, and with room to spare
+		let mut buf = vec![0xEE; n + 4];
+		assert_eq!(n, v.encode_to_slice(&mut buf[..n]).unwrap());
+		assert_eq!(expected[..], buf[..n]);
+		assert_eq!(n, v.encode_to_slice(&mut buf).unwrap());
+		assert_eq!(expected[..], buf[..n]);
+		assert_eq!([0xEE; 4], buf[n..]);
+
+		// One byte short fails with the size needed and writes nothing
+		let mut buf = vec![0xEE; n - 1];
+		let err = v.encode_to_slice(&mut buf).unwrap_err();
+		assert_eq!(&ErrorKind::BufferTooSmall { required: n }, err.kind());
+		assert!(buf.iter().all(|b| *b == 0xEE));
+	}
+	let err = Value::Unsigned(0).encode_to_slice(&mut []).unwrap_err();
+	assert_eq!(&ErrorKind::BufferTooSmall { required: 1 }, err.kind());
+}